@@ -1,20 +1,154 @@
+use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
 
-fn main() -> io::Result<()> {
+const USAGE: &str = "usage: compiler-cli [--allow|--warn|--deny <lint>]...
+       compiler-cli compile <file> [-o <output>] [-O1|-O2] [--allow|--warn|--deny <lint>]...
+       compiler-cli run <module>
+       compiler-cli build <file> [-o <output>] [-O1|-O2] [--allow|--warn|--deny <lint>]...
+       compiler-cli disassemble <file> [--function <name>] [-O1|-O2]
+       compiler-cli emit c|wat|wasm|llvm|ir <file> [-o <output>] [-O1|-O2] [--allow|--warn|--deny <lint>]...";
+
+enum Command {
+    Repl,
+    // Compiles a source file to a module, which can be run later without the source
+    Compile {
+        source: PathBuf,
+        output: PathBuf,
+        opt_level: Option<compiler::OptLevel>,
+    },
+    Run {
+        module: PathBuf,
+    },
+    // Compiles a source file to a native executable, using the system's C compiler to assemble
+    // and link it
+    Build {
+        source: PathBuf,
+        output: PathBuf,
+        opt_level: Option<compiler::OptLevel>,
+    },
+    // Lists the bytecode of a compiled module, or of a source file once it has been compiled
+    Disassemble {
+        file: PathBuf,
+        func: Option<String>,
+        opt_level: Option<compiler::OptLevel>,
+    },
+    // Translates a source file into another language, to be compiled by something else
+    Emit {
+        format: Format,
+        source: PathBuf,
+        output: PathBuf,
+        opt_level: Option<compiler::OptLevel>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    C,
+    Wat,
+    Wasm,
+    Llvm,
+    Ir,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(Self::C),
+            "wat" => Some(Self::Wat),
+            "wasm" => Some(Self::Wasm),
+            "llvm" => Some(Self::Llvm),
+            "ir" => Some(Self::Ir),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Wat => "wat",
+            Self::Wasm => "wasm",
+            Self::Llvm => "ll",
+            Self::Ir => "ir",
+        }
+    }
+}
+
+fn main() -> io::Result<ExitCode> {
+    let (command, lints) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            eprintln!("{}", USAGE);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let result = match command {
+        Command::Repl => return repl(&lints),
+        Command::Compile {
+            source,
+            output,
+            opt_level,
+        } => compile_file(&source, &output, opt_level, &lints),
+        Command::Run { module } => run_file(&module),
+        Command::Build {
+            source,
+            output,
+            opt_level,
+        } => build_file(&source, &output, opt_level, &lints),
+        Command::Disassemble {
+            file,
+            func,
+            opt_level,
+        } => disassemble_file(&file, func.as_deref(), opt_level, &lints),
+        Command::Emit {
+            format,
+            source,
+            output,
+            opt_level,
+        } => emit_file(format, &source, &output, opt_level, &lints),
+    };
+
+    match result {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn repl(lints: &compiler::Lints) -> io::Result<ExitCode> {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut stderr = io::stderr();
 
     let mut input = String::new();
     let mut env = compiler::Env::default();
+    let mut type_env = compiler::TypeEnv::default();
 
     loop {
         write!(stdout, "→ ")?;
         stdout.flush()?;
 
-        stdin.read_line(&mut input)?;
+        if stdin.read_line(&mut input)? == 0 {
+            writeln!(stdout)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        if let Some(name) = input.trim().strip_prefix(":type ") {
+            match type_env.type_of(name.trim()) {
+                Some(ty) => writeln!(stdout, "{} : {}", name.trim(), ty)?,
+                None => writeln!(stderr, "‘{}’ is not defined", name.trim())?,
+            }
+
+            input.clear();
+            continue;
+        }
 
-        match run(input.trim(), &mut env) {
+        match run(input.trim(), &mut env, &mut type_env, lints) {
             Ok(Some(val)) => writeln!(stdout, "{}", val)?,
             Ok(None) => {}
             Err(msg) => writeln!(stderr, "{}", msg)?,
@@ -24,16 +158,342 @@ fn main() -> io::Result<()> {
     }
 }
 
-fn run(input: &str, env: &mut compiler::Env) -> Result<Option<compiler::Val>, String> {
-    let parse = compiler::parse(input).map_err(|msg| format!("Parse error: {}", msg))?;
+// Lints are configured with `--allow`, `--warn` or `--deny` followed by the name of a lint, or by
+// `warnings` to configure every lint at once. Later flags take precedence over earlier ones.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<(Command, compiler::Lints), String> {
+    let mut args = args.peekable();
+
+    let mut command = match args.peek().map(String::as_str) {
+        Some(name @ ("compile" | "run" | "build" | "disassemble" | "emit")) => {
+            let name = name.to_string();
+            args.next();
+
+            let format = match name.as_str() {
+                "emit" => {
+                    let format = args
+                        .next()
+                        .ok_or_else(|| "expected a format to emit".to_string())?;
+                    Some(
+                        Format::from_name(&format)
+                            .ok_or_else(|| format!("unknown format ‘{}’", format))?,
+                    )
+                }
+                _ => None,
+            };
+
+            let path = args
+                .next()
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("expected a file to {}", name))?;
+
+            match name.as_str() {
+                "compile" => Command::Compile {
+                    output: path.with_extension("cbc"),
+                    source: path,
+                    opt_level: None,
+                },
+                "run" => Command::Run { module: path },
+                "emit" => {
+                    let format = format.unwrap();
+                    Command::Emit {
+                        format,
+                        output: path.with_extension(format.extension()),
+                        source: path,
+                        opt_level: None,
+                    }
+                }
+                "build" => Command::Build {
+                    output: path.with_extension(""),
+                    source: path,
+                    opt_level: None,
+                },
+                _ => Command::Disassemble {
+                    file: path,
+                    func: None,
+                    opt_level: None,
+                },
+            }
+        }
+        _ => Command::Repl,
+    };
+
+    let mut lints = compiler::Lints::default();
+
+    while let Some(flag) = args.next() {
+        let level = match flag.as_str() {
+            "--allow" | "-A" => compiler::Level::Allow,
+            "--warn" | "-W" => compiler::Level::Warn,
+            "--deny" | "-D" => compiler::Level::Deny,
+            "--output" | "-o" => match &mut command {
+                Command::Compile { output, .. }
+                | Command::Build { output, .. }
+                | Command::Emit { output, .. } => {
+                    *output = args
+                        .next()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| format!("expected a file after ‘{}’", flag))?;
+                    continue;
+                }
+                _ => {
+                    return Err(format!(
+                        "‘{}’ can only be used with ‘compile’, ‘build’ or ‘emit’",
+                        flag,
+                    ));
+                }
+            },
+            "--function" | "-f" => match &mut command {
+                Command::Disassemble { func, .. } => {
+                    *func = Some(
+                        args.next()
+                            .ok_or_else(|| format!("expected a function after ‘{}’", flag))?,
+                    );
+                    continue;
+                }
+                _ => return Err(format!("‘{}’ can only be used with ‘disassemble’", flag)),
+            },
+            _ if flag.starts_with("-O") => {
+                let level = compiler::OptLevel::from_name(&flag[2..])
+                    .ok_or_else(|| format!("unknown optimization level ‘{}’", &flag[2..]))?;
+
+                match &mut command {
+                    Command::Compile { opt_level, .. }
+                    | Command::Build { opt_level, .. }
+                    | Command::Disassemble { opt_level, .. }
+                    | Command::Emit { opt_level, .. } => {
+                        *opt_level = Some(level);
+                        continue;
+                    }
+                    _ => {
+                        return Err(format!(
+                            "‘{}’ can only be used with ‘compile’, ‘build’, ‘disassemble’ or \
+                             ‘emit’",
+                            flag,
+                        ));
+                    }
+                }
+            }
+            _ => return Err(format!("unknown argument ‘{}’", flag)),
+        };
+
+        let name = args
+            .next()
+            .ok_or_else(|| format!("expected the name of a lint after ‘{}’", flag))?;
+
+        if name == "warnings" {
+            lints.set_all(level);
+        } else {
+            let lint = compiler::Lint::from_name(&name)
+                .ok_or_else(|| format!("unknown lint ‘{}’", name))?;
+            lints.set(lint, level);
+        }
+    }
+
+    Ok((command, lints))
+}
+
+fn compile_file(
+    source: &Path,
+    output: &Path,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<(), String> {
+    let module = compile_source(source, opt_level, lints)?;
+
+    fs::write(output, module.to_bytes())
+        .map_err(|err| format!("could not write ‘{}’: {}", output.display(), err))
+}
+
+fn compile_source(
+    source: &Path,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<compiler::Module, String> {
+    let (input, program) = lower_source(source, opt_level, lints)?;
+
+    Ok(compiler::Module::compile(&program, &input))
+}
+
+// Checks and lowers a source file, optimizing it if asked to and reporting what that changed,
+// and returns the program along with the source it was parsed from
+fn lower_source(
+    source: &Path,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<(String, compiler::Program), String> {
+    let (input, parse) = check_source(source, lints)?;
+    let mut program = parse
+        .lower()
+        .map_err(|msg| format!("Compile error: {}", msg))?;
+
+    if let Some(level) = opt_level {
+        eprint!("{}", program.optimize(level));
+    }
+
+    Ok((input, program))
+}
+
+// Reads, parses and checks a source file, returning the parsed program along with the source it
+// was parsed from
+fn check_source(
+    source: &Path,
+    lints: &compiler::Lints,
+) -> Result<(String, compiler::Parse), String> {
+    let input = fs::read_to_string(source)
+        .map_err(|err| format!("could not read ‘{}’: {}", source.display(), err))?;
+    // Files usually end with a newline, which the parser would otherwise reject
+    let input = input.trim_end();
+
+    let mut parse = compiler::parse(input).map_err(|msg| format!("Parse error: {}", msg))?;
+    let diagnostics = parse.check(&mut compiler::TypeEnv::default(), lints);
+
+    let rendered: String = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(input))
+        .collect();
+
+    if diagnostics.iter().any(compiler::Diagnostic::is_error) {
+        return Err(rendered.trim_end().to_string());
+    }
+
+    eprint!("{}", rendered);
+
+    Ok((input.to_string(), parse))
+}
+
+fn build_file(
+    source: &Path,
+    output: &Path,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<(), String> {
+    let (_, program) = lower_source(source, opt_level, lints)?;
+
+    let asm = program
+        .to_x86_64()
+        .map_err(|msg| format!("Compile error: {}", msg))?;
+
+    let asm_path = std::env::temp_dir().join(format!("compiler-cli-{}.s", process::id()));
+    fs::write(&asm_path, asm)
+        .map_err(|err| format!("could not write ‘{}’: {}", asm_path.display(), err))?;
+
+    let status = process::Command::new("cc")
+        .arg(&asm_path)
+        .arg("-o")
+        .arg(output)
+        .status();
+    let _ = fs::remove_file(&asm_path);
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("assembling and linking failed with {}", status)),
+        Err(err) => Err(format!("could not run ‘cc’: {}", err)),
+    }
+}
+
+fn emit_file(
+    format: Format,
+    source: &Path,
+    output: &Path,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<(), String> {
+    let (input, program) = lower_source(source, opt_level, lints)?;
+
+    let emitted = match format {
+        Format::C => Ok(program
+            .to_c(&input, &source.display().to_string())
+            .into_bytes()),
+        Format::Wat => program.to_wat().map(String::into_bytes),
+        Format::Wasm => program.to_wasm(),
+        Format::Llvm => program.to_llvm_ir().map(String::into_bytes),
+        Format::Ir => program.dump_ir().map(String::into_bytes),
+    }
+    .map_err(|msg| format!("Compile error: {}", msg))?;
+
+    fs::write(output, emitted)
+        .map_err(|err| format!("could not write ‘{}’: {}", output.display(), err))
+}
+
+fn load_module(path: &Path) -> Result<compiler::Module, String> {
+    let bytes =
+        fs::read(path).map_err(|err| format!("could not read ‘{}’: {}", path.display(), err))?;
+
+    compiler::Module::from_bytes(&bytes)
+        .map_err(|msg| format!("could not load ‘{}’: {}", path.display(), msg))
+}
+
+fn run_file(path: &Path) -> Result<(), String> {
+    let module = load_module(path)?;
+
+    match module.run() {
+        Ok(compiler::Val::Unit) => Ok(()),
+        Ok(val) => {
+            println!("{}", val);
+            Ok(())
+        }
+        Err(msg) => Err(format!("Evaluation error: {}", msg)),
+    }
+}
+
+fn run(
+    input: &str,
+    env: &mut compiler::Env,
+    type_env: &mut compiler::TypeEnv,
+    lints: &compiler::Lints,
+) -> Result<Option<compiler::Val>, String> {
+    let mut parse = compiler::parse(input).map_err(|msg| format!("Parse error: {}", msg))?;
+
+    // The line is checked against a copy of the types seen so far, so that nothing it defines is
+    // remembered unless it also evaluates successfully
+    let mut new_type_env = type_env.clone();
+    let diagnostics = parse.check(&mut new_type_env, lints);
+
+    let rendered: String = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(input))
+        .collect();
+
+    if diagnostics.iter().any(compiler::Diagnostic::is_error) {
+        return Err(rendered.trim_end().to_string());
+    }
+
+    eprint!("{}", rendered);
 
     let evaluated = parse
         .eval(env)
         .map_err(|msg| format!("Evaluation error: {}", msg))?;
 
+    *type_env = new_type_env;
+
     if evaluated == compiler::Val::Unit {
         Ok(None)
     } else {
         Ok(Some(evaluated))
     }
 }
+
+// Files with the extension `compile` gives modules by default are loaded as they are, and anything
+// else is compiled first
+fn disassemble_file(
+    path: &Path,
+    func: Option<&str>,
+    opt_level: Option<compiler::OptLevel>,
+    lints: &compiler::Lints,
+) -> Result<(), String> {
+    let module = match path.extension() {
+        Some(extension) if extension == "cbc" => load_module(path)?,
+        _ => compile_source(path, opt_level, lints)?,
+    };
+
+    match func {
+        Some(name) => {
+            let listing = module
+                .disassemble_func(name)
+                .ok_or_else(|| format!("no function named ‘{}’", name))?;
+            print!("{}", listing);
+        }
+        None => print!("{}", module.disassemble()),
+    }
+
+    Ok(())
+}
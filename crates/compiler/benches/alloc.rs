@@ -0,0 +1,100 @@
+// Counts the allocations made while parsing, checking and evaluating a program. Run with
+// `cargo bench`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use compiler::{Env, Lints, TypeEnv, Val};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const PROGRAM: &str = "{
+    enum Tree { Leaf, Node(left, value, right) }
+    fn insertInto tree value => match tree {
+        Leaf => Node Leaf value Leaf,
+        Node(left, current, right) => match value < current {
+            true => {
+                let left = insertInto left value
+                Node left current right
+            },
+            false => {
+                let right = insertInto right value
+                Node left current right
+            },
+        },
+    }
+    fn sum tree => match tree {
+        Leaf => 0,
+        Node(left, value, right) => {
+            let total = sum left + value
+            total + sum right
+        },
+    }
+    fn build tree n => match n {
+        0 => tree,
+        _ => {
+            let scaled = n * 37
+            let quotient = scaled / 101
+            let rounded = quotient * 101
+            let value = scaled - rounded
+            let rest = n - 1
+            let grown = insertInto tree value
+            build grown rest
+        },
+    }
+    let tree = build Leaf 200
+    sum tree
+}";
+
+fn main() {
+    let (parse_allocations, parse_bytes, mut parse) = count(|| compiler::parse(PROGRAM).unwrap());
+    let (check_allocations, check_bytes, _) =
+        count(|| parse.check(&mut TypeEnv::default(), &Lints::default()));
+    let (eval_allocations, eval_bytes, result) = count(|| parse.eval(&mut Env::default()));
+
+    assert_eq!(result, Ok(Val::Number(10036)));
+
+    println!(
+        "   parse: {} allocations, {} bytes",
+        parse_allocations, parse_bytes
+    );
+    println!(
+        "   check: {} allocations, {} bytes",
+        check_allocations, check_bytes
+    );
+    println!(
+        "    eval: {} allocations, {} bytes",
+        eval_allocations, eval_bytes
+    );
+}
+
+fn count<T>(f: impl FnOnce() -> T) -> (usize, usize, T) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+
+    let result = f();
+
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        BYTES.load(Ordering::Relaxed) - bytes,
+        result,
+    )
+}
@@ -0,0 +1,55 @@
+// Compares evaluating a recursive program by looking names up in an `Env` with evaluating it once
+// it has been lowered to use slot-indexed frames, and with running it as bytecode. Run with
+// `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use compiler::{Env, Lints, TypeEnv, Val};
+
+const PROGRAM: &str = "{
+    fn fib n => match n {
+        0 => 0,
+        1 => 1,
+        _ => {
+            let a = n - 1
+            let b = n - 2
+            fib a + fib b
+        },
+    }
+    fib 20
+}";
+
+const RUNS: u32 = 10;
+
+fn main() {
+    let mut parse = compiler::parse(PROGRAM).unwrap();
+    let diagnostics = parse.check(&mut TypeEnv::default(), &Lints::default());
+    assert!(!diagnostics.iter().any(|diagnostic| diagnostic.is_error()));
+
+    let program = parse.lower().unwrap();
+    let module = parse.compile(PROGRAM).unwrap();
+
+    let env = time("env", || parse.eval(&mut Env::default()));
+    let frames = time("frames", || program.eval());
+    let bytecode = time("bytecode", || module.run());
+
+    println!(
+        "frames are {:.1}x faster, bytecode is {:.1}x faster",
+        env.as_secs_f64() / frames.as_secs_f64(),
+        env.as_secs_f64() / bytecode.as_secs_f64(),
+    );
+}
+
+// Returns the average time taken by `f` over several runs
+fn time(name: &str, mut f: impl FnMut() -> Result<Val, String>) -> Duration {
+    assert_eq!(f(), Ok(Val::Number(6765)));
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f().unwrap();
+    }
+    let average = start.elapsed() / RUNS;
+
+    println!("{:>8}: {:?} per run", name, average);
+    average
+}
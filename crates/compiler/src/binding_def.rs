@@ -1,19 +1,36 @@
+use crate::checker::Checker;
 use crate::env::Env;
 use crate::expr::Expr;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::type_expr::TypeExpr;
 use crate::utils;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct BindingDef {
-    pub name: String,
+    pub name: Symbol,
+    pub ty: Option<TypeExpr>,
     pub val: Expr,
+    pub span: Span,
 }
 
 impl BindingDef {
     pub fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("let", s)?;
+        let before = s;
+
+        let s = utils::tag("let", before)?;
         let (s, _) = utils::extract_whitespace_non_empty(s)?;
 
         let (s, name) = utils::extract_ident(s)?;
+
+        let (s, ty) = match TypeExpr::new_annotation(s) {
+            Ok((s, ty)) => (s, Some(ty)),
+            Err(_) => (s, None),
+        };
         let (s, _) = utils::extract_whitespace(s);
 
         let s = utils::tag("=", s)?;
@@ -24,22 +41,59 @@ impl BindingDef {
         Ok((
             s,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
+                ty,
                 val,
+                span: Span::new(before, s),
             },
         ))
     }
 
-    pub fn eval(&self, env: &mut Env) -> Result<(), String> {
-        env.store_binding(self.name.clone(), self.val.eval(env)?);
+    pub fn eval(&self, env: &mut Env) -> Result<(), Interrupt> {
+        env.store_binding(self.name, self.val.eval(env)?);
         Ok(())
     }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        // The binding is only in scope after its value, which may refer to an earlier binding of
+        // the same name
+        self.val.resolve(resolver);
+        resolver.define_binding(self.name, self.span);
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let val = self.val.lower(lowerer)?;
+        let slot = lowerer.define_binding(self.name);
+
+        Ok(Node::Define {
+            slot,
+            val: Box::new(val),
+        })
+    }
+
+    pub(crate) fn check(&self, env: &mut TypeEnv, checker: &mut Checker) {
+        let mut ty = self.val.check(env, checker);
+
+        if let Some(annotation) = &self.ty {
+            let annotated_ty = annotation.to_type(env, checker);
+            let what = format!("‘{}’", self.name);
+            checker.expect(&annotated_ty, &ty, self.span, &what);
+
+            ty = annotated_ty;
+        }
+
+        let ty = checker.generalize(&ty, env);
+
+        env.store_binding(self.name, ty);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::expr::{Number, Op};
+    use crate::expr::{BindingUsage, Number, Op};
+    use crate::span::Span;
+    use crate::types::Type;
 
     #[test]
     fn parse_binding_def() {
@@ -48,12 +102,15 @@ mod tests {
             Ok((
                 "",
                 BindingDef {
-                    name: "a".to_string(),
+                    name: Symbol::intern("a"),
+                    ty: None,
                     val: Expr::Operation {
                         lhs: Box::new(Expr::Number(Number(10))),
                         rhs: Box::new(Expr::Number(Number(2))),
                         op: Op::Div,
+                        span: Span::new("10 / 2", ""),
                     },
+                    span: Span::new("let a = 10 / 2", ""),
                 },
             )),
         );
@@ -65,4 +122,47 @@ mod tests {
             Err("Expected whitespace".to_string()),
         );
     }
+
+    #[test]
+    fn parse_binding_def_with_annotation() {
+        assert_eq!(
+            BindingDef::new("let xs: List<Int> = ys"),
+            Ok((
+                "",
+                BindingDef {
+                    name: Symbol::intern("xs"),
+                    ty: Some(TypeExpr {
+                        name: Symbol::intern("List"),
+                        args: vec![TypeExpr {
+                            name: Symbol::intern("Int"),
+                            args: Vec::new(),
+                            span: Span::new("Int> = ys", "> = ys"),
+                        }],
+                        span: Span::new("List<Int> = ys", " = ys"),
+                    }),
+                    val: Expr::BindingUsage(BindingUsage {
+                        name: Symbol::intern("ys"),
+                        kind: None,
+                        span: Span::new("ys", "")
+                    }),
+                    span: Span::new("let xs: List<Int> = ys", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn check_binding_def_against_annotation() {
+        let (_, binding_def) = BindingDef::new("let a: Bool = 1").unwrap();
+        let mut env = TypeEnv::default();
+        let mut checker = Checker::default();
+
+        binding_def.check(&mut env, &mut checker);
+
+        assert_eq!(
+            checker.diagnostics[0].message,
+            "expected ‘a’ to be ‘Bool’, got ‘Int’",
+        );
+        assert_eq!(env.type_of("a"), Some(Type::Bool));
+    }
 }
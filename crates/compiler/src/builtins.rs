@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use crate::types::Type;
+use crate::val::{MapKey, Val};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Builtin {
+    pub(crate) name: &'static str,
+    pub(crate) arity: usize,
+    pub(crate) func: fn(Vec<Val>) -> Result<Val, String>,
+}
+
+pub(crate) const NAMES: [&str; 3] = ["insert", "has", "keys"];
+
+/// Builtins are only consulted once no user-defined function of the same name is in scope.
+pub(crate) fn get(name: &str) -> Option<Builtin> {
+    let (name, arity, func): (_, _, fn(_) -> _) = match name {
+        "insert" => ("insert", 3, insert),
+        "has" => ("has", 2, has),
+        "keys" => ("keys", 1, keys),
+        _ => return None,
+    };
+
+    Some(Builtin { name, arity, func })
+}
+
+// The arity of every builtin is checked before it is called, so the conversions from `args` to
+// fixed-size arrays below cannot fail.
+
+fn insert(args: Vec<Val>) -> Result<Val, String> {
+    let [map, key, val]: [Val; 3] = args.try_into().unwrap();
+
+    let mut map = into_map(map)?;
+    map.insert(MapKey::try_from(key)?, val);
+
+    Ok(Val::Map(map))
+}
+
+fn has(args: Vec<Val>) -> Result<Val, String> {
+    let [map, key]: [Val; 2] = args.try_into().unwrap();
+
+    let map = into_map(map)?;
+    Ok(Val::Bool(map.contains_key(&MapKey::try_from(key)?)))
+}
+
+fn keys(args: Vec<Val>) -> Result<Val, String> {
+    let [map]: [Val; 1] = args.try_into().unwrap();
+
+    let map = into_map(map)?;
+    Ok(Val::List(map.into_keys().map(Val::from).collect()))
+}
+
+/// Returns the type of the builtin `name`, whose generics are inferred separately for each call.
+pub(crate) fn type_of(name: &str) -> Option<Type> {
+    let map = Type::Map(Box::new(Type::Generic(0)), Box::new(Type::Generic(1)));
+
+    let (params, ret) = match name {
+        "insert" => (vec![map.clone(), Type::Generic(0), Type::Generic(1)], map),
+        "has" => (vec![map, Type::Generic(0)], Type::Bool),
+        "keys" => (vec![map], Type::List(Box::new(Type::Generic(0)))),
+        _ => return None,
+    };
+
+    Some(Type::Func {
+        params,
+        ret: Box::new(ret),
+    })
+}
+
+fn into_map(val: Val) -> Result<BTreeMap<MapKey, Val>, String> {
+    match val {
+        Val::Map(map) => Ok(map),
+        _ => Err(format!("expected a map, got ‘{}’", val)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, i32)]) -> Val {
+        Val::Map(
+            entries
+                .iter()
+                .map(|(key, val)| (MapKey::Str(key.to_string()), Val::Number(*val)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn insert_returns_new_map() {
+        assert_eq!(
+            insert(vec![
+                map(&[("a", 1)]),
+                Val::Str("b".to_string()),
+                Val::Number(2)
+            ]),
+            Ok(map(&[("a", 1), ("b", 2)])),
+        );
+    }
+
+    #[test]
+    fn insert_overwrites_existing_key() {
+        assert_eq!(
+            insert(vec![
+                map(&[("a", 1)]),
+                Val::Str("a".to_string()),
+                Val::Number(2)
+            ]),
+            Ok(map(&[("a", 2)])),
+        );
+    }
+
+    #[test]
+    fn has_existing_key() {
+        assert_eq!(
+            has(vec![map(&[("a", 1)]), Val::Str("a".to_string())]),
+            Ok(Val::Bool(true)),
+        );
+    }
+
+    #[test]
+    fn keys_are_sorted() {
+        assert_eq!(
+            keys(vec![map(&[("b", 1), ("a", 2)])]),
+            Ok(Val::List(vec![
+                Val::Str("a".to_string()),
+                Val::Str("b".to_string()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn keys_of_non_map() {
+        assert_eq!(
+            keys(vec![Val::Number(1)]),
+            Err("expected a map, got ‘1’".to_string()),
+        );
+    }
+}
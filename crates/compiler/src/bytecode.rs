@@ -0,0 +1,518 @@
+mod disassemble;
+mod file;
+
+use crate::builtins;
+use crate::expr::Op;
+use crate::interrupt::Interrupt;
+use crate::lower::{Func, Local, Node, Program, SlotPattern};
+use crate::span::{Lines, Span};
+use crate::symbol::Symbol;
+use crate::val::Val;
+
+/// A program compiled to bytecode for the virtual machine in `vm`. Every value the program uses
+/// directly is kept once in the module's constant pool, and instructions refer to it by index.
+#[derive(Debug)]
+pub struct Module {
+    pub(crate) constants: Vec<Val>,
+    pub(crate) funcs: Vec<Function>,
+    pub(crate) main: Function,
+}
+
+#[derive(Debug)]
+pub(crate) struct Function {
+    pub(crate) name: Option<Symbol>,
+    pub(crate) num_params: usize,
+    pub(crate) num_slots: usize,
+    pub(crate) code: Vec<Instr>,
+    // The patterns of the function's match arms, which are tested as a whole by `Instr::Match`
+    pub(crate) patterns: Vec<SlotPattern>,
+    // Pairs of the offset of an instruction and the source line it and the instructions after it
+    // came from, in order of offset
+    pub(crate) lines: Vec<(usize, usize)>,
+}
+
+impl Function {
+    /// Returns the source line the instruction at `offset` was compiled from, if it is known.
+    pub(crate) fn line_at(&self, offset: usize) -> Option<usize> {
+        let idx = self.lines.partition_point(|(start, _)| *start <= offset);
+        idx.checked_sub(1).map(|idx| self.lines[idx].1)
+    }
+}
+
+/// A single instruction for the virtual machine, which evaluates expressions by pushing their
+/// values onto a stack. Every jump target is the index of an instruction in the same function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Instr {
+    Const(u32),
+    Load(u32),
+    LoadOuter {
+        depth: u32,
+        slot: u32,
+    },
+    Store(u32),
+    Pop,
+    Binary(Op),
+    Index,
+    Range {
+        inclusive: bool,
+    },
+    List(u32),
+    Map(u32),
+    // The constant is a variant without fields, which the arguments are filled into
+    Construct {
+        variant: u32,
+        num_args: u32,
+    },
+    CallBuiltin {
+        builtin: u32,
+        num_args: u32,
+    },
+    Call {
+        func: u32,
+        depth: u32,
+        num_args: u32,
+    },
+    Return,
+    Jump(u32),
+    // Pops the value of a match guard, jumping if it is false
+    Guard(u32),
+    // Matches the value in the slot against a pattern, binding it or jumping if it does not match
+    Match {
+        pattern: u32,
+        slot: u32,
+        otherwise: u32,
+    },
+    NoMatch(u32),
+    // Loops keep the iterator they were started with, along with the height of the stack, so that
+    // `break` and `continue` can discard anything left on it by the expressions they are in
+    IterStart,
+    IterNext {
+        slot: u32,
+        exit: u32,
+    },
+    LoopExit(u32),
+    LoopContinue(u32),
+    // Fails with the message in the constant
+    Fail(u32),
+}
+
+impl Module {
+    /// Compiles a program that has already been lowered, such as one that has been optimized
+    /// since. `source` is the input the program was parsed from, which its spans are relative to.
+    pub fn compile(program: &Program, source: &str) -> Self {
+        let lines = Lines::new(source);
+
+        let mut constants = Vec::new();
+
+        let funcs = program
+            .funcs
+            .iter()
+            .map(|func| Compiler::compile(func, &mut constants, &lines, false))
+            .collect();
+        let main = Compiler::compile(&program.main, &mut constants, &lines, true);
+
+        Self {
+            constants,
+            funcs,
+            main,
+        }
+    }
+}
+
+struct Loop {
+    start: usize,
+    // Jumps to the end of the loop, which is only known once its body has been compiled
+    exits: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    constants: &'a mut Vec<Val>,
+    source_lines: &'a Lines,
+    code: Vec<Instr>,
+    patterns: Vec<SlotPattern>,
+    lines: Vec<(usize, usize)>,
+    // The spans of the statements being compiled, innermost last
+    spans: Vec<Span>,
+    num_slots: usize,
+    loops: Vec<Loop>,
+    is_main: bool,
+}
+
+impl<'a> Compiler<'a> {
+    fn compile(
+        func: &Func,
+        constants: &'a mut Vec<Val>,
+        source_lines: &'a Lines,
+        is_main: bool,
+    ) -> Function {
+        let mut compiler = Self {
+            constants,
+            source_lines,
+            code: Vec::new(),
+            patterns: Vec::new(),
+            lines: Vec::new(),
+            spans: Vec::new(),
+            num_slots: func.num_slots,
+            loops: Vec::new(),
+            is_main,
+        };
+
+        compiler.node(&func.body);
+        compiler.emit(Instr::Return);
+
+        Function {
+            name: func.name,
+            num_params: func.num_params,
+            num_slots: compiler.num_slots,
+            code: compiler.code,
+            patterns: compiler.patterns,
+            lines: compiler.lines,
+        }
+    }
+
+    // Records that the instructions emitted from now on came from the line `span` starts on
+    fn mark_line(&mut self, span: Span) {
+        let offset = self.code.len();
+        let line = self.source_lines.line_of(span);
+
+        // A line recorded with nothing emitted for it since is replaced rather than kept
+        if self
+            .lines
+            .last()
+            .is_some_and(|(last_offset, _)| *last_offset == offset)
+        {
+            self.lines.pop();
+        }
+        if self
+            .lines
+            .last()
+            .is_none_or(|(_, last_line)| *last_line != line)
+        {
+            self.lines.push((offset, line));
+        }
+    }
+
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.code.push(instr);
+        self.code.len() - 1
+    }
+
+    // Points the jump at `at` to the next instruction to be emitted
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len() as u32;
+
+        match &mut self.code[at] {
+            Instr::Jump(to) | Instr::Guard(to) | Instr::LoopExit(to) => *to = target,
+            Instr::Match { otherwise, .. } => *otherwise = target,
+            Instr::IterNext { exit, .. } => *exit = target,
+            instr => unreachable!("cannot patch {:?}", instr),
+        }
+    }
+
+    fn constant(&mut self, val: Val) -> u32 {
+        let index = match self.constants.iter().position(|constant| *constant == val) {
+            Some(index) => index,
+            None => {
+                self.constants.push(val);
+                self.constants.len() - 1
+            }
+        };
+
+        index as u32
+    }
+
+    fn fail(&mut self, interrupt: Interrupt) {
+        let msg = self.constant(Val::Str(interrupt.into_error()));
+        self.emit(Instr::Fail(msg));
+    }
+
+    fn all(&mut self, nodes: &[Node]) -> u32 {
+        for node in nodes {
+            self.node(node);
+        }
+
+        nodes.len() as u32
+    }
+
+    // Emits code for `node` that leaves nothing on the stack, for statements whose values are
+    // thrown away
+    fn discard(&mut self, node: &Node) {
+        match node {
+            // Definitions and loops always evaluate to `()`, and constants to themselves, so there
+            // is no need to push their values only to pop them again
+            Node::Const(_) => {}
+            Node::Define { slot, val } => {
+                self.node(val);
+                self.emit(Instr::Store(*slot as u32));
+            }
+            Node::For {
+                slot,
+                iterable,
+                body,
+            } => self.for_loop(*slot, iterable, body),
+            Node::Spanned { span, node } => self.in_span(*span, |compiler| compiler.discard(node)),
+            _ => {
+                self.node(node);
+                self.emit(Instr::Pop);
+            }
+        }
+    }
+
+    fn for_loop(&mut self, slot: usize, iterable: &Node, body: &Node) {
+        self.node(iterable);
+        self.emit(Instr::IterStart);
+
+        let start = self.emit(Instr::IterNext {
+            slot: slot as u32,
+            exit: 0,
+        });
+        self.loops.push(Loop {
+            start,
+            exits: vec![start],
+        });
+
+        self.discard(body);
+        self.emit(Instr::Jump(start as u32));
+
+        for at in self.loops.pop().unwrap().exits {
+            self.patch(at);
+        }
+    }
+
+    fn in_span(&mut self, span: Span, emit: impl FnOnce(&mut Self)) {
+        self.mark_line(span);
+        self.spans.push(span);
+        emit(self);
+        self.spans.pop();
+
+        // Whatever comes after a nested statement belongs to the one enclosing it
+        if let Some(enclosing) = self.spans.last() {
+            self.mark_line(*enclosing);
+        }
+    }
+
+    // Emits code that leaves the value of `node` on top of the stack
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Const(val) => {
+                let constant = self.constant(val.clone());
+                self.emit(Instr::Const(constant));
+            }
+            Node::Local(Local { depth: 0, slot }) => {
+                self.emit(Instr::Load(*slot as u32));
+            }
+            Node::Local(Local { depth, slot }) => {
+                self.emit(Instr::LoadOuter {
+                    depth: *depth as u32,
+                    slot: *slot as u32,
+                });
+            }
+            Node::Define { slot, val } => {
+                self.node(val);
+                self.emit(Instr::Store(*slot as u32));
+                self.node(&Node::Const(Val::Unit));
+            }
+            Node::Operation { lhs, rhs, op } => {
+                self.node(lhs);
+                self.node(rhs);
+                self.emit(Instr::Binary(*op));
+            }
+            Node::Call { func, depth, args } => {
+                let num_args = self.all(args);
+                self.emit(Instr::Call {
+                    func: *func as u32,
+                    depth: *depth as u32,
+                    num_args,
+                });
+            }
+            Node::Construct {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let num_args = self.all(args);
+                let variant = self.constant(Val::Variant {
+                    enum_name: *enum_name,
+                    variant: *variant,
+                    fields: Vec::new(),
+                });
+                self.emit(Instr::Construct { variant, num_args });
+            }
+            Node::Builtin { builtin, args } => {
+                let num_args = self.all(args);
+                let builtin = builtins::NAMES
+                    .iter()
+                    .position(|name| *name == builtin.name)
+                    .unwrap();
+                self.emit(Instr::CallBuiltin {
+                    builtin: builtin as u32,
+                    num_args,
+                });
+            }
+            Node::Block(nodes) => match nodes.split_last() {
+                Some((last, rest)) => {
+                    for node in rest {
+                        self.discard(node);
+                    }
+                    self.node(last);
+                }
+                None => self.node(&Node::Const(Val::Unit)),
+            },
+            Node::Match { scrutinee, arms } => {
+                // The value being matched is kept in a slot of its own, so that every arm can
+                // test it without it getting in the way on the stack
+                let slot = self.num_slots as u32;
+                self.num_slots += 1;
+
+                self.node(scrutinee);
+                self.emit(Instr::Store(slot));
+
+                let mut ends = Vec::new();
+
+                for arm in arms {
+                    let pattern = self.patterns.len() as u32;
+                    self.patterns.push(arm.pattern.clone());
+
+                    let mut next_arm = vec![self.emit(Instr::Match {
+                        pattern,
+                        slot,
+                        otherwise: 0,
+                    })];
+
+                    if let Some(guard) = &arm.guard {
+                        self.node(guard);
+                        next_arm.push(self.emit(Instr::Guard(0)));
+                    }
+
+                    self.node(&arm.body);
+                    ends.push(self.emit(Instr::Jump(0)));
+
+                    for at in next_arm {
+                        self.patch(at);
+                    }
+                }
+
+                self.emit(Instr::NoMatch(slot));
+
+                for at in ends {
+                    self.patch(at);
+                }
+            }
+            Node::List(items) => {
+                let len = self.all(items);
+                self.emit(Instr::List(len));
+            }
+            Node::Map(entries) => {
+                for (key, val) in entries {
+                    self.node(key);
+                    self.node(val);
+                }
+                self.emit(Instr::Map(entries.len() as u32));
+            }
+            Node::Index { target, index } => {
+                self.node(target);
+                self.node(index);
+                self.emit(Instr::Index);
+            }
+            Node::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.node(start);
+                self.node(end);
+                self.emit(Instr::Range {
+                    inclusive: *inclusive,
+                });
+            }
+            Node::For {
+                slot,
+                iterable,
+                body,
+            } => {
+                self.for_loop(*slot, iterable, body);
+                self.node(&Node::Const(Val::Unit));
+            }
+            Node::Break => match self.loops.last() {
+                Some(_) => {
+                    let at = self.emit(Instr::LoopExit(0));
+                    self.loops.last_mut().unwrap().exits.push(at);
+                }
+                None => self.fail(Interrupt::Break),
+            },
+            Node::Continue => match self.loops.last() {
+                Some(innermost) => {
+                    let start = innermost.start as u32;
+                    self.emit(Instr::LoopContinue(start));
+                }
+                None => self.fail(Interrupt::Continue),
+            },
+            Node::Spanned { span, node } => self.in_span(*span, |compiler| compiler.node(node)),
+            Node::Return(val) => {
+                self.node(val);
+
+                if self.is_main {
+                    self.fail(Interrupt::Return(Val::Unit));
+                } else {
+                    self.emit(Instr::Return);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lints, TypeEnv};
+
+    fn compile(s: &str) -> Module {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.compile(s).unwrap()
+    }
+
+    #[test]
+    fn record_line_of_each_statement() {
+        let module = compile(
+            "{
+    fn f x => {
+        let y = {
+            let z = x * 2
+            z + 1
+        }
+        x / y
+        y
+    }
+    f 1
+}",
+        );
+        let func = &module.funcs[0];
+
+        let lines: Vec<_> = (0..func.code.len())
+            .map(|offset| func.line_at(offset).unwrap())
+            .collect();
+
+        // The binding of `y` is stored after the block it is bound to, so it goes back to line 3
+        let store = func
+            .code
+            .iter()
+            .rposition(|instr| matches!(instr, Instr::Store(_)));
+        assert_eq!(lines[store.unwrap()], 3);
+        assert!(lines.contains(&4) && lines.contains(&5) && lines.contains(&8));
+
+        // The value of a statement is thrown away by the statement itself
+        let pop = func
+            .code
+            .iter()
+            .position(|instr| matches!(instr, Instr::Pop));
+        assert_eq!(lines[pop.unwrap()], 7);
+
+        let call = module
+            .main
+            .code
+            .iter()
+            .position(|instr| matches!(instr, Instr::Call { .. }));
+        assert_eq!(module.main.line_at(call.unwrap()), Some(10));
+    }
+}
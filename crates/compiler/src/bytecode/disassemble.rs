@@ -0,0 +1,279 @@
+use std::fmt::Write;
+
+use super::{Function, Instr, Module};
+use crate::builtins;
+use crate::expr::Op;
+use crate::lower::SlotPattern;
+use crate::val::Val;
+
+impl Module {
+    /// Lists every instruction of every function in the module, along with the source line each
+    /// came from and what their operands refer to.
+    pub fn disassemble(&self) -> String {
+        let mut listing = self.disassemble_function(&self.main, None);
+
+        for id in 0..self.funcs.len() {
+            listing.push('\n');
+            listing.push_str(&self.disassemble_function(&self.funcs[id], Some(id)));
+        }
+
+        listing
+    }
+
+    /// Like `disassemble`, but only lists the functions defined with the name `name`, of which
+    /// there may be several in different scopes. Returns `None` if there are none.
+    pub fn disassemble_func(&self, name: &str) -> Option<String> {
+        let listings: Vec<_> = (0..self.funcs.len())
+            .filter(|id| {
+                self.funcs[*id]
+                    .name
+                    .is_some_and(|func| func.as_str() == name)
+            })
+            .map(|id| self.disassemble_function(&self.funcs[id], Some(id)))
+            .collect();
+
+        (!listings.is_empty()).then(|| listings.join("\n"))
+    }
+
+    fn disassemble_function(&self, func: &Function, id: Option<usize>) -> String {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {}", what),
+            _ => format!("{} {}s", n, what),
+        };
+
+        let mut listing = match (func.name, id) {
+            (Some(name), Some(id)) => format!(
+                "fn #{} {} ({}, {})\n",
+                id,
+                name,
+                plural(func.num_params, "parameter"),
+                plural(func.num_slots, "slot"),
+            ),
+            _ => format!("main ({})\n", plural(func.num_slots, "slot")),
+        };
+
+        let line_width = func
+            .lines
+            .iter()
+            .map(|(_, line)| line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let mut lines = func.lines.iter().peekable();
+
+        for (offset, instr) in func.code.iter().enumerate() {
+            // Lines are only shown where they change, like in a listing produced by an assembler
+            let line = match lines.next_if(|(start, _)| *start == offset) {
+                Some((_, line)) => line.to_string(),
+                None => "|".to_string(),
+            };
+
+            let (text, comment) = self.describe(func, *instr);
+
+            let _ = write!(
+                listing,
+                "{:04}  {:>width$}  ",
+                offset,
+                line,
+                width = line_width
+            );
+            match comment {
+                Some(comment) => {
+                    let _ = writeln!(listing, "{:<24}; {}", text, comment);
+                }
+                None => {
+                    let _ = writeln!(listing, "{}", text);
+                }
+            }
+        }
+
+        listing
+    }
+
+    // Returns the instruction as text, along with what its operands refer to if that is not
+    // obvious from the operands themselves
+    fn describe(&self, func: &Function, instr: Instr) -> (String, Option<String>) {
+        let constant = |idx: u32| &self.constants[idx as usize];
+
+        match instr {
+            Instr::Const(idx) => (format!("const {}", idx), Some(constant(idx).to_string())),
+            Instr::Load(slot) => (format!("load {}", slot), None),
+            Instr::LoadOuter { depth, slot } => (format!("load_outer {}, {}", depth, slot), None),
+            Instr::Store(slot) => (format!("store {}", slot), None),
+            Instr::Pop => ("pop".to_string(), None),
+            Instr::Binary(op) => {
+                let mnemonic = match op {
+                    Op::Add => "add",
+                    Op::Sub => "sub",
+                    Op::Mul => "mul",
+                    Op::Div => "div",
+                    Op::Eq => "eq",
+                    Op::NotEq => "not_eq",
+                    Op::Lt => "lt",
+                    Op::LtEq => "lt_eq",
+                    Op::Gt => "gt",
+                    Op::GtEq => "gt_eq",
+                };
+                (mnemonic.to_string(), None)
+            }
+            Instr::Index => ("index".to_string(), None),
+            Instr::Range { inclusive: false } => ("range".to_string(), None),
+            Instr::Range { inclusive: true } => ("range_inclusive".to_string(), None),
+            Instr::List(len) => (format!("list {}", len), None),
+            Instr::Map(len) => (format!("map {}", len), None),
+            Instr::Construct { variant, num_args } => {
+                let comment = match constant(variant) {
+                    Val::Variant {
+                        enum_name, variant, ..
+                    } => format!("{}::{}", enum_name, variant),
+                    val => val.to_string(),
+                };
+                (
+                    format!("construct {}, {}", variant, num_args),
+                    Some(comment),
+                )
+            }
+            Instr::CallBuiltin { builtin, num_args } => (
+                format!("call_builtin {}, {}", builtin, num_args),
+                Some(builtins::NAMES[builtin as usize].to_string()),
+            ),
+            Instr::Call {
+                func,
+                depth,
+                num_args,
+            } => (
+                format!("call {}, {}, {}", func, depth, num_args),
+                self.funcs[func as usize].name.map(|name| name.to_string()),
+            ),
+            Instr::Return => ("return".to_string(), None),
+            Instr::Jump(target) => (format!("jump {}", target), None),
+            Instr::Guard(target) => (format!("guard {}", target), None),
+            Instr::Match {
+                pattern,
+                slot,
+                otherwise,
+            } => (
+                format!("match {}, {}, {}", pattern, slot, otherwise),
+                Some(describe_pattern(&func.patterns[pattern as usize])),
+            ),
+            Instr::NoMatch(slot) => (format!("no_match {}", slot), None),
+            Instr::IterStart => ("iter_start".to_string(), None),
+            Instr::IterNext { slot, exit } => (format!("iter_next {}, {}", slot, exit), None),
+            Instr::LoopExit(target) => (format!("loop_exit {}", target), None),
+            Instr::LoopContinue(target) => (format!("loop_continue {}", target), None),
+            Instr::Fail(msg) => (format!("fail {}", msg), Some(constant(msg).to_string())),
+        }
+    }
+}
+
+// Bindings are shown as the slot they are stored in, since their names are not kept
+fn describe_pattern(pattern: &SlotPattern) -> String {
+    match pattern {
+        SlotPattern::Wildcard => "_".to_string(),
+        SlotPattern::Number(n) => n.to_string(),
+        SlotPattern::Bool(b) => b.to_string(),
+        SlotPattern::Binding(slot) => format!("${}", slot),
+        SlotPattern::Variant {
+            variant, fields, ..
+        } if fields.is_empty() => variant.to_string(),
+        SlotPattern::Variant {
+            variant, fields, ..
+        } => {
+            let fields: Vec<_> = fields.iter().map(describe_pattern).collect();
+            format!("{}({})", variant, fields.join(", "))
+        }
+        SlotPattern::List { items, rest } => {
+            let mut items: Vec<_> = items.iter().map(describe_pattern).collect();
+            match rest.as_deref() {
+                Some(SlotPattern::Wildcard) => items.push("..".to_string()),
+                Some(rest) => items.push(format!("..{}", describe_pattern(rest))),
+                None => {}
+            }
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lints, TypeEnv};
+
+    fn compile(s: &str) -> Module {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.compile(s).unwrap()
+    }
+
+    #[test]
+    fn disassemble_func() {
+        let module = compile(
+            "{
+    fn half n => match n {
+        0 => 0,
+        m => m / 2,
+    }
+    half 4
+}",
+        );
+
+        assert_eq!(
+            module.disassemble_func("half"),
+            Some(
+                "fn #0 half (1 parameter, 3 slots)
+0000  2  load 0
+0001  |  store 2
+0002  |  match 0, 2, 5           ; 0
+0003  |  const 0                 ; 0
+0004  |  jump 11
+0005  |  match 1, 2, 10          ; $1
+0006  |  load 1
+0007  |  const 1                 ; 2
+0008  |  div
+0009  |  jump 11
+0010  |  no_match 2
+0011  |  return
+"
+                .to_string()
+            ),
+        );
+    }
+
+    #[test]
+    fn disassemble_whole_module() {
+        let module = compile(
+            "{
+    enum Option { Some(value), None }
+    let x = Some 1
+    match x {
+        Some(y) => y,
+        None => { return 0 },
+    }
+}",
+        );
+
+        assert_eq!(
+            module.disassemble(),
+            "main (3 slots)
+0000  3  const 0                 ; 1
+0001  |  construct 1, 1          ; Option::Some
+0002  |  store 0
+0003  4  load 0
+0004  |  store 2
+0005  |  match 0, 2, 8           ; Some($1)
+0006  |  load 1
+0007  |  jump 13
+0008  |  match 1, 2, 12          ; None
+0009  6  const 2                 ; 0
+0010  |  fail 3                  ; \"cannot return outside of a function\"
+0011  4  jump 13
+0012  |  no_match 2
+0013  1  return
+"
+        );
+    }
+
+    #[test]
+    fn disassemble_missing_func() {
+        assert_eq!(compile("1").disassemble_func("f"), None);
+    }
+}
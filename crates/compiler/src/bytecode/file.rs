@@ -0,0 +1,805 @@
+//! The binary format compiled modules are saved in, so that they can be run later without the
+//! source they were compiled from.
+//!
+//! Every number is stored in little-endian byte order. Counts, indices and offsets are `u32`, and
+//! strings are a `u32` length followed by that many bytes of UTF-8. A module is laid out as:
+//!
+//! ```text
+//! magic       b"\0cbc"
+//! version     u16, currently `VERSION`
+//! constants   count, then each constant as a value
+//! main        function
+//! functions   count, then each function, in the order calls refer to them
+//! ```
+//!
+//! A function is its name, which is a byte saying whether it has one followed by the name if it
+//! does, then its number of parameters and of slots, then its instructions, match patterns and
+//! debug line table, each as a count followed by that many entries. A line table entry is the
+//! offset of an instruction followed by the source line it and the instructions after it came
+//! from.
+//!
+//! An instruction is an opcode byte followed by its operands, which are all `u32` apart from the
+//! operator of `Binary` and whether a `Range` is inclusive, which are single bytes. Values and
+//! patterns begin with a tag byte saying what kind they are, followed by what they contain.
+//!
+//! The version is increased whenever the layout or the meaning of an opcode changes, and modules
+//! of any other version are refused rather than run.
+
+use std::collections::BTreeMap;
+
+use super::{Function, Instr, Module};
+use crate::builtins;
+use crate::expr::Op;
+use crate::lower::SlotPattern;
+use crate::symbol::Symbol;
+use crate::val::{MapKey, Val};
+
+const MAGIC: [u8; 4] = *b"\0cbc";
+pub(crate) const VERSION: u16 = 3;
+
+// Every call sets aside room for all of its function's slots, so a module asking for more than any
+// program needs could run out of memory before its first instruction
+const MAX_SLOTS: usize = u16::MAX as usize;
+
+impl Module {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+
+        writer.bytes.extend(MAGIC);
+        writer.bytes.extend(VERSION.to_le_bytes());
+
+        writer.len(self.constants.len());
+        for constant in &self.constants {
+            writer.val(constant);
+        }
+
+        writer.function(&self.main);
+        writer.len(self.funcs.len());
+        for func in &self.funcs {
+            writer.function(func);
+        }
+
+        writer.bytes
+    }
+
+    /// Loads a module saved with `to_bytes`. Anything the instructions refer to by index is
+    /// checked to exist, including the frames of enclosing functions, but not whether they use
+    /// the stack correctly, so only modules written by this library should be run.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(4).ok() != Some(&MAGIC[..]) {
+            return Err("not a compiled module".to_string());
+        }
+
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            return Err(format!(
+                "module was compiled for version {} of the format, but only version {} is supported",
+                version, VERSION,
+            ));
+        }
+
+        let constants = reader.list(Reader::val)?;
+        let main = reader.function()?;
+        let funcs = reader.list(Reader::function)?;
+
+        if !reader.bytes.is_empty() {
+            return Err("unexpected data after end of module".to_string());
+        }
+
+        let module = Self {
+            constants,
+            funcs,
+            main,
+        };
+        module.validate()?;
+
+        Ok(module)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for func in std::iter::once(&self.main).chain(&self.funcs) {
+            self.validate_function(func)?;
+        }
+
+        self.validate_nesting()
+    }
+
+    // Works out which function each function is defined in from the calls made to it, checking
+    // that the enclosing functions calls and loads refer to are always there when they are run.
+    // Functions that are never called are never run, so nothing is checked about them.
+    fn validate_nesting(&self) -> Result<(), String> {
+        // Here `main` is function zero and the others are numbered from one
+        let func = |idx: usize| match idx {
+            0 => &self.main,
+            idx => &self.funcs[idx - 1],
+        };
+        let missing = || "module refers to an enclosing function that does not exist".to_string();
+
+        let mut parents = vec![None; self.funcs.len() + 1];
+        let mut reached = vec![false; self.funcs.len() + 1];
+        reached[0] = true;
+        let mut unchecked = vec![0];
+
+        while let Some(idx) = unchecked.pop() {
+            // Every enclosing function of a function that has been reached has been reached too
+            let ancestor = |parents: &[Option<usize>], depth: u32| {
+                (0..depth).try_fold(idx, |idx, _| parents[idx])
+            };
+
+            for instr in &func(idx).code {
+                match *instr {
+                    Instr::LoadOuter { depth, slot } => {
+                        let outer = ancestor(&parents, depth).ok_or_else(missing)?;
+                        if slot as usize >= func(outer).num_slots {
+                            return Err("module refers to a slot that does not exist".to_string());
+                        }
+                    }
+                    Instr::Call {
+                        func: callee,
+                        depth,
+                        ..
+                    } => {
+                        let parent = ancestor(&parents, depth).ok_or_else(missing)?;
+                        let callee = callee as usize + 1;
+
+                        if !reached[callee] {
+                            reached[callee] = true;
+                            parents[callee] = Some(parent);
+                            unchecked.push(callee);
+                        } else if parents[callee] != Some(parent) {
+                            return Err(
+                                "module calls a function from outside the one it is defined in"
+                                    .to_string(),
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_function(&self, func: &Function) -> Result<(), String> {
+        let check = |valid: bool, what: &str| match valid {
+            true => Ok(()),
+            false => Err(format!("module refers to a {} that does not exist", what)),
+        };
+        let slot = |slot: u32| check((slot as usize) < func.num_slots, "slot");
+        let target = |target: u32| check((target as usize) < func.code.len(), "jump target");
+        let constant = |constant: u32| self.constants.get(constant as usize);
+
+        check(func.num_params <= func.num_slots, "slot")?;
+        if func.num_slots > MAX_SLOTS {
+            return Err(format!(
+                "function has {} slots, but at most {} are supported",
+                func.num_slots, MAX_SLOTS,
+            ));
+        }
+
+        for instr in &func.code {
+            match *instr {
+                Instr::Const(idx) => check(constant(idx).is_some(), "constant")?,
+                Instr::Load(idx) | Instr::Store(idx) | Instr::NoMatch(idx) => slot(idx)?,
+                Instr::Jump(to)
+                | Instr::Guard(to)
+                | Instr::LoopExit(to)
+                | Instr::LoopContinue(to) => target(to)?,
+                Instr::IterNext { slot: idx, exit } => {
+                    slot(idx)?;
+                    target(exit)?;
+                }
+                Instr::Match {
+                    pattern,
+                    slot: idx,
+                    otherwise,
+                } => {
+                    let pattern = func.patterns.get(pattern as usize);
+                    check(pattern.is_some(), "pattern")?;
+                    check(pattern.unwrap().max_slot() <= func.num_slots, "slot")?;
+                    slot(idx)?;
+                    target(otherwise)?;
+                }
+                Instr::Construct { variant, .. } => check(
+                    matches!(constant(variant), Some(Val::Variant { .. })),
+                    "variant",
+                )?,
+                Instr::Fail(msg) => check(matches!(constant(msg), Some(Val::Str(_))), "message")?,
+                Instr::CallBuiltin { builtin, num_args } => check(
+                    builtins::NAMES
+                        .get(builtin as usize)
+                        .and_then(|name| builtins::get(name))
+                        .is_some_and(|builtin| builtin.arity == num_args as usize),
+                    "builtin",
+                )?,
+                Instr::Call { func, num_args, .. } => check(
+                    self.funcs
+                        .get(func as usize)
+                        .is_some_and(|func| func.num_params == num_args as usize),
+                    "function",
+                )?,
+                Instr::LoadOuter { .. }
+                | Instr::Pop
+                | Instr::Binary(_)
+                | Instr::Index
+                | Instr::Range { .. }
+                | Instr::List(_)
+                | Instr::Map(_)
+                | Instr::Return
+                | Instr::IterStart => {}
+            }
+        }
+
+        // Running off the end of a function would leave the virtual machine with nothing to do
+        match func.code.last() {
+            Some(Instr::Return | Instr::Jump(_) | Instr::Fail(_) | Instr::NoMatch(_)) => Ok(()),
+            _ => Err("function does not end with a return".to_string()),
+        }
+    }
+}
+
+impl SlotPattern {
+    // One more than the highest slot the pattern binds, or zero if it does not bind any
+    fn max_slot(&self) -> usize {
+        match self {
+            Self::Wildcard | Self::Number(_) | Self::Bool(_) => 0,
+            Self::Binding(slot) => slot + 1,
+            Self::Variant { fields, .. } => fields.iter().map(Self::max_slot).max().unwrap_or(0),
+            Self::List { items, rest } => items
+                .iter()
+                .chain(rest.as_deref())
+                .map(Self::max_slot)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+
+const OPS: [Op; 10] = [
+    Op::Add,
+    Op::Sub,
+    Op::Mul,
+    Op::Div,
+    Op::Eq,
+    Op::NotEq,
+    Op::Lt,
+    Op::LtEq,
+    Op::Gt,
+    Op::GtEq,
+];
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes.extend(n.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+
+    fn function(&mut self, func: &Function) {
+        match func.name {
+            Some(name) => {
+                self.u8(1);
+                self.str(name.as_str());
+            }
+            None => self.u8(0),
+        }
+
+        self.len(func.num_params);
+        self.len(func.num_slots);
+
+        self.len(func.code.len());
+        for instr in &func.code {
+            self.instr(*instr);
+        }
+
+        self.len(func.patterns.len());
+        for pattern in &func.patterns {
+            self.pattern(pattern);
+        }
+
+        self.len(func.lines.len());
+        for (offset, line) in &func.lines {
+            self.len(*offset);
+            self.len(*line);
+        }
+    }
+
+    fn instr(&mut self, instr: Instr) {
+        let (opcode, operands): (u8, &[u32]) = match instr {
+            Instr::Const(constant) => (0, &[constant]),
+            Instr::Load(slot) => (1, &[slot]),
+            Instr::LoadOuter { depth, slot } => (2, &[depth, slot]),
+            Instr::Store(slot) => (3, &[slot]),
+            Instr::Pop => (4, &[]),
+            Instr::Binary(op) => {
+                self.u8(5);
+                self.u8(OPS.iter().position(|o| *o == op).unwrap() as u8);
+                return;
+            }
+            Instr::Index => (6, &[]),
+            Instr::Range { inclusive } => {
+                self.u8(7);
+                self.u8(inclusive as u8);
+                return;
+            }
+            Instr::List(len) => (8, &[len]),
+            Instr::Map(len) => (9, &[len]),
+            Instr::Construct { variant, num_args } => (10, &[variant, num_args]),
+            Instr::CallBuiltin { builtin, num_args } => (11, &[builtin, num_args]),
+            Instr::Call {
+                func,
+                depth,
+                num_args,
+            } => (12, &[func, depth, num_args]),
+            Instr::Return => (13, &[]),
+            Instr::Jump(target) => (14, &[target]),
+            Instr::Guard(target) => (15, &[target]),
+            Instr::Match {
+                pattern,
+                slot,
+                otherwise,
+            } => (16, &[pattern, slot, otherwise]),
+            Instr::NoMatch(slot) => (17, &[slot]),
+            Instr::IterStart => (18, &[]),
+            Instr::IterNext { slot, exit } => (19, &[slot, exit]),
+            Instr::LoopExit(target) => (20, &[target]),
+            Instr::LoopContinue(target) => (21, &[target]),
+            Instr::Fail(msg) => (22, &[msg]),
+        };
+
+        self.u8(opcode);
+        for operand in operands {
+            self.u32(*operand);
+        }
+    }
+
+    fn val(&mut self, val: &Val) {
+        match val {
+            Val::Unit => self.u8(0),
+            Val::Number(n) => {
+                self.u8(1);
+                self.bytes.extend(n.to_le_bytes());
+            }
+            Val::Bool(b) => {
+                self.u8(2);
+                self.u8(*b as u8);
+            }
+            Val::Str(s) => {
+                self.u8(3);
+                self.str(s);
+            }
+            Val::List(items) => {
+                self.u8(4);
+                self.len(items.len());
+                for item in items {
+                    self.val(item);
+                }
+            }
+            Val::Map(map) => {
+                self.u8(5);
+                self.len(map.len());
+                for (key, val) in map {
+                    self.val(&Val::from(key.clone()));
+                    self.val(val);
+                }
+            }
+            Val::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.u8(6);
+                self.bytes.extend(start.to_le_bytes());
+                self.bytes.extend(end.to_le_bytes());
+                self.u8(*inclusive as u8);
+            }
+            Val::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                self.u8(7);
+                self.str(enum_name.as_str());
+                self.str(variant.as_str());
+                self.len(fields.len());
+                for field in fields {
+                    self.val(field);
+                }
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &SlotPattern) {
+        match pattern {
+            SlotPattern::Wildcard => self.u8(0),
+            SlotPattern::Number(n) => {
+                self.u8(1);
+                self.bytes.extend(n.to_le_bytes());
+            }
+            SlotPattern::Bool(b) => {
+                self.u8(2);
+                self.u8(*b as u8);
+            }
+            SlotPattern::Binding(slot) => {
+                self.u8(3);
+                self.len(*slot);
+            }
+            SlotPattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                self.u8(4);
+                self.str(enum_name.as_str());
+                self.str(variant.as_str());
+                self.len(fields.len());
+                for field in fields {
+                    self.pattern(field);
+                }
+            }
+            SlotPattern::List { items, rest } => {
+                self.u8(5);
+                self.len(items.len());
+                for item in items {
+                    self.pattern(item);
+                }
+                match rest {
+                    Some(rest) => {
+                        self.u8(1);
+                        self.pattern(rest);
+                    }
+                    None => self.u8(0),
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() {
+            return Err("module ended unexpectedly".to_string());
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            n => Err(format!("invalid boolean {} in module", n)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn str(&mut self) -> Result<&'a str, String> {
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| "invalid string in module".to_string())
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, String> {
+        Ok(Symbol::intern(self.str()?))
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let len = self.len()?;
+
+        // The count is not trusted to reserve space up front, since it may not be accurate
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+
+        Ok(items)
+    }
+
+    fn function(&mut self) -> Result<Function, String> {
+        Ok(Function {
+            name: match self.bool()? {
+                true => Some(self.symbol()?),
+                false => None,
+            },
+            num_params: self.len()?,
+            num_slots: self.len()?,
+            code: self.list(Self::instr)?,
+            patterns: self.list(Self::pattern)?,
+            lines: self.list(|reader| Ok((reader.len()?, reader.len()?)))?,
+        })
+    }
+
+    fn instr(&mut self) -> Result<Instr, String> {
+        let instr = match self.u8()? {
+            0 => Instr::Const(self.u32()?),
+            1 => Instr::Load(self.u32()?),
+            2 => Instr::LoadOuter {
+                depth: self.u32()?,
+                slot: self.u32()?,
+            },
+            3 => Instr::Store(self.u32()?),
+            4 => Instr::Pop,
+            5 => {
+                let op = self.u8()?;
+                Instr::Binary(
+                    *OPS.get(op as usize)
+                        .ok_or_else(|| format!("unknown operator {} in module", op))?,
+                )
+            }
+            6 => Instr::Index,
+            7 => Instr::Range {
+                inclusive: self.bool()?,
+            },
+            8 => Instr::List(self.u32()?),
+            9 => Instr::Map(self.u32()?),
+            10 => Instr::Construct {
+                variant: self.u32()?,
+                num_args: self.u32()?,
+            },
+            11 => Instr::CallBuiltin {
+                builtin: self.u32()?,
+                num_args: self.u32()?,
+            },
+            12 => Instr::Call {
+                func: self.u32()?,
+                depth: self.u32()?,
+                num_args: self.u32()?,
+            },
+            13 => Instr::Return,
+            14 => Instr::Jump(self.u32()?),
+            15 => Instr::Guard(self.u32()?),
+            16 => Instr::Match {
+                pattern: self.u32()?,
+                slot: self.u32()?,
+                otherwise: self.u32()?,
+            },
+            17 => Instr::NoMatch(self.u32()?),
+            18 => Instr::IterStart,
+            19 => Instr::IterNext {
+                slot: self.u32()?,
+                exit: self.u32()?,
+            },
+            20 => Instr::LoopExit(self.u32()?),
+            21 => Instr::LoopContinue(self.u32()?),
+            22 => Instr::Fail(self.u32()?),
+            opcode => return Err(format!("unknown opcode {} in module", opcode)),
+        };
+
+        Ok(instr)
+    }
+
+    fn val(&mut self) -> Result<Val, String> {
+        let val = match self.u8()? {
+            0 => Val::Unit,
+            1 => Val::Number(self.i32()?),
+            2 => Val::Bool(self.bool()?),
+            3 => Val::Str(self.str()?.to_string()),
+            4 => Val::List(self.list(Self::val)?),
+            5 => {
+                let entries =
+                    self.list(|reader| Ok((MapKey::try_from(reader.val()?)?, reader.val()?)))?;
+                Val::Map(entries.into_iter().collect::<BTreeMap<_, _>>())
+            }
+            6 => Val::Range {
+                start: self.i32()?,
+                end: self.i32()?,
+                inclusive: self.bool()?,
+            },
+            7 => Val::Variant {
+                enum_name: self.symbol()?,
+                variant: self.symbol()?,
+                fields: self.list(Self::val)?,
+            },
+            tag => return Err(format!("unknown kind of constant {} in module", tag)),
+        };
+
+        Ok(val)
+    }
+
+    fn pattern(&mut self) -> Result<SlotPattern, String> {
+        let pattern = match self.u8()? {
+            0 => SlotPattern::Wildcard,
+            1 => SlotPattern::Number(self.i32()?),
+            2 => SlotPattern::Bool(self.bool()?),
+            3 => SlotPattern::Binding(self.len()?),
+            4 => SlotPattern::Variant {
+                enum_name: self.symbol()?,
+                variant: self.symbol()?,
+                fields: self.list(Self::pattern)?,
+            },
+            5 => SlotPattern::List {
+                items: self.list(Self::pattern)?,
+                rest: match self.bool()? {
+                    true => Some(Box::new(self.pattern()?)),
+                    false => None,
+                },
+            },
+            tag => return Err(format!("unknown kind of pattern {} in module", tag)),
+        };
+
+        Ok(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lints, TypeEnv};
+
+    fn compile(s: &str) -> Module {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.compile(s).unwrap()
+    }
+
+    const PROGRAM: &str = "{
+    enum Option { Some(value), None }
+    fn find xs limit => {
+        for x in xs {
+            match x {
+                Some(n) if n > limit => { return n },
+                _ => { continue },
+            }
+        }
+        0
+    }
+    let m = #{ \"a\": 1..=3 }
+    match [None, Some 2, Some 7] {
+        [] => 0,
+        [_, ..xs] => find xs 5,
+    }
+}";
+
+    #[test]
+    fn run_module_read_back_from_bytes() {
+        let module = compile(PROGRAM);
+        let read = Module::from_bytes(&module.to_bytes()).unwrap();
+
+        assert_eq!(read.run(), Ok(Val::Number(7)));
+        assert_eq!(read.to_bytes(), module.to_bytes());
+        assert_eq!(read.funcs[0].lines, module.funcs[0].lines);
+    }
+
+    #[test]
+    fn refuse_bytes_that_are_not_module() {
+        assert_eq!(
+            Module::from_bytes(b"fn f => 1").map(|_| ()),
+            Err("not a compiled module".to_string()),
+        );
+    }
+
+    #[test]
+    fn refuse_module_of_other_version() {
+        let mut bytes = compile("1").to_bytes();
+        bytes[4] = 1;
+
+        assert_eq!(
+            Module::from_bytes(&bytes).map(|_| ()),
+            Err(
+                "module was compiled for version 1 of the format, but only version 3 is supported"
+                    .to_string()
+            ),
+        );
+    }
+
+    #[test]
+    fn refuse_truncated_module() {
+        let bytes = compile(PROGRAM).to_bytes();
+
+        assert_eq!(
+            Module::from_bytes(&bytes[..bytes.len() - 1]).map(|_| ()),
+            Err("module ended unexpectedly".to_string()),
+        );
+    }
+
+    #[test]
+    fn refuse_module_referring_to_missing_constant() {
+        let mut module = compile("1");
+        module.constants.clear();
+
+        assert_eq!(
+            Module::from_bytes(&module.to_bytes()).map(|_| ()),
+            Err("module refers to a constant that does not exist".to_string()),
+        );
+    }
+
+    #[test]
+    fn refuse_module_with_too_many_slots() {
+        let mut module = compile("1");
+        module.main.num_slots = 1 << 31;
+
+        assert_eq!(
+            Module::from_bytes(&module.to_bytes()).map(|_| ()),
+            Err("function has 2147483648 slots, but at most 65535 are supported".to_string()),
+        );
+    }
+
+    #[test]
+    fn refuse_module_referring_to_missing_enclosing_function() {
+        let mut module = compile(PROGRAM);
+        let Some(Instr::Call { depth, .. }) = module
+            .main
+            .code
+            .iter_mut()
+            .find(|instr| matches!(instr, Instr::Call { .. }))
+        else {
+            panic!("expected a call");
+        };
+        *depth = 1;
+
+        assert_eq!(
+            Module::from_bytes(&module.to_bytes()).map(|_| ()),
+            Err("module refers to an enclosing function that does not exist".to_string()),
+        );
+    }
+
+    #[test]
+    fn refuse_module_loading_from_missing_slot_of_enclosing_function() {
+        let mut module = compile(
+            "{
+    let a = 1
+    fn f => a
+    f
+}",
+        );
+        let Some(Instr::LoadOuter { slot, .. }) = module.funcs[0]
+            .code
+            .iter_mut()
+            .find(|instr| matches!(instr, Instr::LoadOuter { .. }))
+        else {
+            panic!("expected a load from the enclosing function");
+        };
+        *slot = 1;
+
+        assert_eq!(
+            Module::from_bytes(&module.to_bytes()).map(|_| ()),
+            Err("module refers to a slot that does not exist".to_string()),
+        );
+    }
+}
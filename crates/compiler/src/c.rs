@@ -0,0 +1,814 @@
+use std::fmt::Write;
+
+use crate::expr::Op;
+use crate::interrupt::Interrupt;
+use crate::lower::{Local, Node, Program, SlotPattern};
+use crate::span::{Lines, Span};
+use crate::val::Val;
+
+const RUNTIME: &str = include_str!("c/runtime.c");
+
+impl Program {
+    /// Translates the program into a single C99 source file, whose `main` prints the value the
+    /// program evaluates to unless it is `()`, or reports the error it fails with. `source` is the
+    /// input the program was parsed from, which along with `file_name` is used to add `#line`
+    /// directives pointing back to it.
+    ///
+    /// Every function becomes a C function, and every value a tagged `rt_val` struct whose
+    /// behaviour is provided by a runtime included at the top of the file.
+    ///
+    /// To embed the program in something else, compile the file with `RT_NO_MAIN` defined and
+    /// call `const char *program(rt_val *result)`. It returns `NULL` after storing the value the
+    /// program evaluates to in `result`, or the message of the error the program fails with.
+    /// Nothing is freed until `rt_free_all` is called.
+    pub fn to_c(&self, source: &str, file_name: &str) -> String {
+        let mut translator = Translator {
+            program: self,
+            lines: Lines::new(source),
+            file_name: c_string(file_name),
+            queue: Vec::new(),
+            queued: vec![false; self.funcs.len()],
+        };
+
+        let main = translator.function(None);
+
+        // Functions are only translated once something calls them, so that the file does not
+        // contain any that are unused
+        let mut funcs = Vec::new();
+        while let Some(id) = translator.queue.pop() {
+            funcs.push((id, translator.function(Some(id))));
+        }
+        funcs.sort_by_key(|(id, _)| *id);
+
+        let mut c = format!("/* Generated from {} */\n\n{}\n", file_name, RUNTIME);
+
+        for (id, _) in &funcs {
+            let _ = writeln!(c, "{};", self.signature(*id));
+        }
+        for (_, func) in funcs {
+            c.push('\n');
+            c.push_str(&func);
+        }
+
+        c.push('\n');
+        c.push_str(&main);
+        c.push_str(
+            "
+const char *program(rt_val *result) {
+    if (setjmp(rt_failed)) {
+        return rt_error;
+    }
+    *result = fn_main();
+    return NULL;
+}
+
+#ifndef RT_NO_MAIN
+int main(void) {
+    rt_val val;
+    const char *error = program(&val);
+    if (error) {
+        fprintf(stderr, \"Evaluation error: %s\\n\", error);
+        rt_free_all();
+        return 1;
+    }
+    if (val.tag != RT_UNIT) {
+        puts(rt_to_string(val));
+    }
+    rt_free_all();
+    return 0;
+}
+#endif
+",
+        );
+
+        c
+    }
+
+    fn signature(&self, id: usize) -> String {
+        let params: String = (0..self.funcs[id].num_params)
+            .map(|param| format!(", rt_val a{}", param))
+            .collect();
+
+        format!(
+            "static rt_val {}(rt_frame *parent{})",
+            self.c_name(id),
+            params
+        )
+    }
+
+    fn c_name(&self, id: usize) -> String {
+        match self.funcs[id].name {
+            Some(name) => format!("fn{}_{}", id, name),
+            None => format!("fn{}", id),
+        }
+    }
+}
+
+struct Translator<'p> {
+    program: &'p Program,
+    lines: Lines,
+    file_name: String,
+    queue: Vec<usize>,
+    queued: Vec<bool>,
+}
+
+impl Translator<'_> {
+    // Translates the function with the given id, or the program itself
+    fn function(&mut self, id: Option<usize>) -> String {
+        let func = match id {
+            Some(id) => &self.program.funcs[id],
+            None => &self.program.main,
+        };
+
+        let mut body = Body {
+            translator: self,
+            code: String::new(),
+            indent: 1,
+            temps: 0,
+            iters: 0,
+            labels: 0,
+            loops: Vec::new(),
+            spans: Vec::new(),
+            source_line: None,
+            uses_frame: false,
+            uses_parent: false,
+            is_main: id.is_none(),
+        };
+        let result = body.node(&func.body);
+        body.line(format!("return {};", result));
+        let (code, temps, iters) = (body.code, body.temps, body.iters);
+        let (uses_frame, uses_parent) = (body.uses_frame, body.uses_parent);
+
+        let mut c = match id {
+            Some(id) => format!("{} {{\n", self.program.signature(id)),
+            None => "static rt_val fn_main(void) {\n".to_string(),
+        };
+
+        // Everything is declared up front, so that no jump can skip over a declaration. The frame is
+        // only needed by the functions defined inside this one, so it is left out when they are
+        // never called from here.
+        if func.num_slots > 0 || uses_frame {
+            let _ = writeln!(c, "    rt_val slots[{}];", func.num_slots.max(1));
+        }
+        if uses_frame {
+            c.push_str("    rt_frame frame;\n");
+        }
+        for temp in 0..temps {
+            let _ = writeln!(c, "    rt_val t{};", temp);
+        }
+        for iter in 0..iters {
+            let _ = writeln!(c, "    rt_iter i{};", iter);
+        }
+
+        if uses_frame {
+            c.push_str("    frame.slots = slots;\n");
+            match id {
+                Some(_) => c.push_str("    frame.parent = parent;\n"),
+                None => c.push_str("    frame.parent = NULL;\n"),
+            }
+        }
+        // Every function is passed the frame it was defined in, which it may have no use for
+        if id.is_some() && !uses_frame && !uses_parent {
+            c.push_str("    (void)parent;\n");
+        }
+        for param in 0..func.num_params {
+            let _ = writeln!(c, "    slots[{}] = a{};", param, param);
+        }
+
+        c.push_str(&code);
+        c.push_str("}\n");
+        c
+    }
+}
+
+struct Loop {
+    label: usize,
+    broken: bool,
+    continued: bool,
+}
+
+// The body of a single C function, which every node in a function is translated into
+struct Body<'t, 'p> {
+    translator: &'t mut Translator<'p>,
+    code: String,
+    indent: usize,
+    temps: usize,
+    iters: usize,
+    labels: usize,
+    loops: Vec<Loop>,
+    // The spans of the statements being translated, innermost last, and the line of the source
+    // the compiler takes the next line of code to come from, once a `#line` directive has been
+    // written
+    spans: Vec<Span>,
+    source_line: Option<usize>,
+    uses_frame: bool,
+    uses_parent: bool,
+    is_main: bool,
+}
+
+impl Body<'_, '_> {
+    fn line(&mut self, line: impl AsRef<str>) {
+        // Directives are only written once there is code for them to apply to, so that leaving a
+        // nested statement straight after entering another does not need one
+        if let Some(span) = self.spans.last() {
+            let line = self.translator.lines.line_of(*span);
+            if self.source_line != Some(line) {
+                self.source_line = Some(line);
+                let _ = writeln!(self.code, "#line {} {}", line, self.translator.file_name);
+            }
+        }
+
+        let indent = "    ".repeat(self.indent);
+        self.write(format!("{}{}", indent, line.as_ref()));
+    }
+
+    // Writes a line of code without a directive, keeping track of the line the compiler counts it
+    // as, since every line after a directive is taken to come from the one after the last
+    fn write(&mut self, code: String) {
+        let _ = writeln!(self.code, "{}", code);
+        self.source_line = self.source_line.map(|line| line + 1);
+    }
+
+    fn frame_at(&mut self, depth: usize) -> String {
+        match depth {
+            0 => {
+                self.uses_frame = true;
+                "(&frame)".to_string()
+            }
+            _ => {
+                self.uses_parent = true;
+                format!("parent{}", "->parent".repeat(depth - 1))
+            }
+        }
+    }
+
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("t{}", self.temps - 1)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    // Stores `expr` in a new temporary, returning its name
+    fn assign(&mut self, expr: String) -> String {
+        let temp = self.temp();
+        self.line(format!("{} = {};", temp, expr));
+        temp
+    }
+
+    // Marks a value as deliberately unused, since compilers warn about temporaries that are set
+    // but never read
+    fn discard(&mut self, val: String) {
+        if val
+            .strip_prefix('t')
+            .is_some_and(|temp| temp.parse::<usize>().is_ok())
+        {
+            self.line(format!("(void){};", val));
+        }
+    }
+
+    fn fail(&mut self, interrupt: Interrupt) {
+        self.line(format!(
+            "rt_fail(\"%s\", {});",
+            c_string(&interrupt.into_error())
+        ));
+    }
+
+    fn all(&mut self, nodes: &[Node]) -> Vec<String> {
+        nodes.iter().map(|node| self.node(node)).collect()
+    }
+
+    // Translates `node` into statements, returning an expression for its value that has no side
+    // effects
+    fn node(&mut self, node: &Node) -> String {
+        match node {
+            Node::Const(val) => constant(val),
+            Node::Local(Local { depth: 0, slot }) => self.assign(format!("slots[{}]", slot)),
+            Node::Local(Local { depth, slot }) => {
+                let frame = self.frame_at(*depth);
+                self.assign(format!("{}->slots[{}]", frame, slot))
+            }
+            Node::Define { slot, val } => {
+                let val = self.node(val);
+                self.line(format!("slots[{}] = {};", slot, val));
+                "rt_unit()".to_string()
+            }
+            Node::Operation { lhs, rhs, op } => {
+                let func = match op {
+                    Op::Add => "rt_add",
+                    Op::Sub => "rt_sub",
+                    Op::Mul => "rt_mul",
+                    Op::Div => "rt_div",
+                    Op::Eq => "rt_eq",
+                    Op::NotEq => "rt_not_eq",
+                    Op::Lt => "rt_lt",
+                    Op::LtEq => "rt_lt_eq",
+                    Op::Gt => "rt_gt",
+                    Op::GtEq => "rt_gt_eq",
+                };
+                let lhs = self.node(lhs);
+                let rhs = self.node(rhs);
+                self.assign(format!("{}({}, {})", func, lhs, rhs))
+            }
+            Node::Call { func, depth, args } => {
+                if !self.translator.queued[*func] {
+                    self.translator.queued[*func] = true;
+                    self.translator.queue.push(*func);
+                }
+
+                let args: String = self
+                    .all(args)
+                    .into_iter()
+                    .map(|arg| format!(", {}", arg))
+                    .collect();
+                let name = self.translator.program.c_name(*func);
+                let frame = self.frame_at(*depth);
+                self.assign(format!("{}({}{})", name, frame, args))
+            }
+            Node::Construct {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let fields = self.all(args);
+                self.assign(format!(
+                    "rt_variant(\"{}\", \"{}\", {}, {})",
+                    enum_name,
+                    variant,
+                    fields.len(),
+                    array(&fields),
+                ))
+            }
+            Node::Builtin { builtin, args } => {
+                let args = self.all(args);
+                self.assign(format!("rt_builtin_{}({})", builtin.name, args.join(", ")))
+            }
+            Node::Block(nodes) => {
+                let mut result = "rt_unit()".to_string();
+                for node in nodes {
+                    let discarded = std::mem::replace(&mut result, self.node(node));
+                    self.discard(discarded);
+                }
+                result
+            }
+            Node::Match { scrutinee, arms } => {
+                let scrutinee = self.node(scrutinee);
+                let result = self.temp();
+                let end = self.label();
+
+                for arm in arms {
+                    let next = self.label();
+                    let mut skips = self.pattern(&arm.pattern, &scrutinee, next);
+
+                    if let Some(guard) = &arm.guard {
+                        let guard = self.node(guard);
+                        self.line(format!("if (!rt_guard({})) goto next{};", guard, next));
+                        skips = true;
+                    }
+
+                    let body = self.node(&arm.body);
+                    self.line(format!("{} = {};", result, body));
+                    self.line(format!("goto end{};", end));
+
+                    if skips {
+                        self.write(format!("next{}:;", next));
+                    }
+                }
+
+                self.line(format!("rt_no_match({});", scrutinee));
+                if !arms.is_empty() {
+                    self.write(format!("end{}:;", end));
+                }
+                result
+            }
+            Node::List(items) => {
+                let items = self.all(items);
+                self.assign(format!("rt_list({}, {})", items.len(), array(&items)))
+            }
+            Node::Map(entries) => {
+                let map = self.assign("rt_map()".to_string());
+                for (key, val) in entries {
+                    let key = self.node(key);
+                    let val = self.node(val);
+                    self.line(format!(
+                        "{} = rt_map_insert({}, {}, {});",
+                        map, map, key, val
+                    ));
+                }
+                map
+            }
+            Node::Index { target, index } => {
+                let target = self.node(target);
+                let index = self.node(index);
+                self.assign(format!("rt_index({}, {})", target, index))
+            }
+            Node::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.node(start);
+                let end = self.node(end);
+                self.assign(format!(
+                    "rt_range({}, {}, {})",
+                    start, end, *inclusive as u8
+                ))
+            }
+            Node::For {
+                slot,
+                iterable,
+                body,
+            } => {
+                let iterable = self.node(iterable);
+                let iter = format!("i{}", self.iters);
+                self.iters += 1;
+                let label = self.label();
+
+                self.line(format!("{} = rt_iter_start({});", iter, iterable));
+                self.line(format!(
+                    "while (rt_iter_next(&{}, &slots[{}])) {{",
+                    iter, slot,
+                ));
+
+                self.indent += 1;
+                self.loops.push(Loop {
+                    label,
+                    broken: false,
+                    continued: false,
+                });
+                let val = self.node(body);
+                self.discard(val);
+                let innermost = self.loops.pop().unwrap();
+                if innermost.continued {
+                    self.write(format!("continue{}:;", label));
+                }
+                self.indent -= 1;
+
+                self.line("}");
+                if innermost.broken {
+                    self.write(format!("break{}:;", label));
+                }
+                "rt_unit()".to_string()
+            }
+            Node::Break => {
+                match self.loops.last_mut() {
+                    Some(innermost) => {
+                        innermost.broken = true;
+                        let label = innermost.label;
+                        self.line(format!("goto break{};", label));
+                    }
+                    None => self.fail(Interrupt::Break),
+                }
+                "rt_unit()".to_string()
+            }
+            Node::Continue => {
+                match self.loops.last_mut() {
+                    Some(innermost) => {
+                        innermost.continued = true;
+                        let label = innermost.label;
+                        self.line(format!("goto continue{};", label));
+                    }
+                    None => self.fail(Interrupt::Continue),
+                }
+                "rt_unit()".to_string()
+            }
+            Node::Return(val) => {
+                let val = self.node(val);
+                if self.is_main {
+                    self.discard(val);
+                    self.fail(Interrupt::Return(Val::Unit));
+                } else {
+                    self.line(format!("return {};", val));
+                }
+                "rt_unit()".to_string()
+            }
+            Node::Spanned { span, node } => {
+                self.spans.push(*span);
+                let result = self.node(node);
+                self.spans.pop();
+                result
+            }
+        }
+    }
+
+    // Stores whatever `pattern` binds in its slots if `val` matches it, and jumps to the label
+    // otherwise. Returns whether there is anything that can make it jump.
+    fn pattern(&mut self, pattern: &SlotPattern, val: &str, next: usize) -> bool {
+        match pattern {
+            SlotPattern::Wildcard => false,
+            SlotPattern::Number(_) | SlotPattern::Bool(_) => {
+                let expected = match pattern {
+                    SlotPattern::Number(n) => constant(&Val::Number(*n)),
+                    _ => constant(&Val::Bool(matches!(pattern, SlotPattern::Bool(true)))),
+                };
+                self.line(format!(
+                    "if (!rt_equal({}, {})) goto next{};",
+                    val, expected, next,
+                ));
+                true
+            }
+            SlotPattern::Binding(slot) => {
+                self.line(format!("slots[{}] = {};", slot, val));
+                false
+            }
+            SlotPattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                self.line(format!(
+                    "if (!rt_is_variant({}, \"{}\", \"{}\")) goto next{};",
+                    val, enum_name, variant, next,
+                ));
+
+                for (idx, field) in fields.iter().enumerate() {
+                    self.pattern(field, &format!("{}.items[{}]", val, idx), next);
+                }
+                true
+            }
+            SlotPattern::List { items, rest } => {
+                self.line(format!(
+                    "if (!rt_is_list({}, {}, {})) goto next{};",
+                    val,
+                    items.len(),
+                    rest.is_some() as u8,
+                    next,
+                ));
+
+                for (idx, item) in items.iter().enumerate() {
+                    self.pattern(item, &format!("{}.items[{}]", val, idx), next);
+                }
+                if let Some(rest) = rest {
+                    let len = items.len();
+                    let rest_val = format!("rt_list({0}.len - {1}, {0}.items + {1})", val, len);
+                    self.pattern(rest, &rest_val, next);
+                }
+                true
+            }
+        }
+    }
+}
+
+fn constant(val: &Val) -> String {
+    match val {
+        Val::Unit => "rt_unit()".to_string(),
+        // The most negative number cannot be written as a literal, since it is parsed as the
+        // negation of a number that does not fit
+        Val::Number(i32::MIN) => "rt_number(INT32_MIN)".to_string(),
+        Val::Number(n) => format!("rt_number({})", n),
+        Val::Bool(b) => format!("rt_bool({})", *b as u8),
+        Val::Str(s) => format!("rt_str({})", c_string(s)),
+        // Only literals are ever constants
+        _ => unreachable!("cannot translate constant {:?}", val),
+    }
+}
+
+// A compound literal for an array of values, which C does not allow to be empty
+fn array(items: &[String]) -> String {
+    match items {
+        [] => "NULL".to_string(),
+        _ => format!("(rt_val[]){{{}}}", items.join(", ")),
+    }
+}
+
+// Quotes `s` as a C string literal. Anything outside printable ASCII is written as an octal escape,
+// which unlike a hex escape cannot run into the characters after it.
+fn c_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for byte in s.bytes() {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\t' => quoted.push_str("\\t"),
+            // Question marks could otherwise start a trigraph
+            b'?' => quoted.push_str("\\?"),
+            b' '..=b'~' => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\{:03o}", byte);
+            }
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Command, Output};
+
+    use super::*;
+    use crate::{Env, Lints, TypeEnv};
+
+    // Compiles C with the system's C compiler in strict C99 mode, treating warnings as errors, and
+    // runs it. Returns `None` if there is no compiler.
+    fn build_and_run(c: &str, defines: &[&str]) -> Option<Output> {
+        let dir = crate::codegen::scratch_path("c-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, exe) = (dir.join("prog.c"), dir.join("prog"));
+        std::fs::write(&source, c).unwrap();
+
+        let built = Command::new("cc")
+            .args([
+                "-std=c99",
+                "-pedantic-errors",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+            ])
+            .args(defines)
+            .arg("-o")
+            .arg(&exe)
+            .arg(&source)
+            .output();
+        match built {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => panic!("{}", String::from_utf8_lossy(&output.stderr)),
+            Err(_) => return None,
+        }
+
+        let output = Command::new(&exe).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        Some(output)
+    }
+
+    // Compiles and runs the C the program is translated into, checking that it behaves like the
+    // interpreter. Returns what it printed, or `None` without checking anything if there is no
+    // compiler.
+    fn compile_and_run(s: &str) -> Option<String> {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        let c = parse.lower().unwrap().to_c(s, "test.src");
+        let output = build_and_run(&c, &[])?;
+
+        let printed = match parse.eval(&mut Env::default()) {
+            Ok(Val::Unit) => String::from_utf8(output.stdout).unwrap(),
+            Ok(val) => {
+                let stdout = String::from_utf8(output.stdout).unwrap();
+                assert_eq!(stdout, format!("{}\n", val));
+                stdout
+            }
+            Err(msg) => {
+                let stderr = String::from_utf8(output.stderr).unwrap();
+                assert_eq!(stderr, format!("Evaluation error: {}\n", msg));
+                assert!(!output.status.success());
+                stderr
+            }
+        };
+
+        Some(printed)
+    }
+
+    #[test]
+    fn translate_recursive_func() {
+        compile_and_run(
+            "{
+    fn fib n => match n {
+        0 => 0,
+        1 => 1,
+        _ => {
+            let a = n - 1
+            let b = n - 2
+            fib a + fib b
+        },
+    }
+    fib 15
+}",
+        );
+    }
+
+    #[test]
+    fn translate_list_patterns() {
+        compile_and_run(
+            "{
+    fn sum items => match items {
+        [] => 0,
+        [x, ..rest] => {
+            let total = sum rest
+            x + total
+        },
+    }
+    let a = sum [1, 2, 3]
+    match [a, 4] { [x] => [x], [_, ..rest] => rest }
+}",
+        );
+    }
+
+    #[test]
+    fn translate_nested_funcs_and_loops() {
+        compile_and_run(
+            "{
+    let base = 10
+    fn find xs => {
+        fn over x => x > base
+        for x in xs {
+            match over x {
+                true => { return x },
+                false => { continue },
+            }
+        }
+        0
+    }
+    let a = find [3, 12, 40]
+    let b = [1, { for x in 0..3 { 1 + { break } } }, a]
+    [b, 1..=3, \"say \\\"hi\\\"\\n\"]
+}",
+        );
+    }
+
+    #[test]
+    fn translate_enums_maps_and_builtins() {
+        compile_and_run(
+            "{
+    enum Option { Some(value), None }
+    fn lookup map key => match has map key {
+        true => Some map[key],
+        false => None,
+    }
+    let map = insert #{\"b\": 1, 3: 2} \"a\" 2
+    let total = 0
+    for k in map {
+        let total = total + 1
+    }
+    [lookup map \"b\", lookup map \"c\", keys map, map]
+}",
+        );
+    }
+
+    #[test]
+    fn translate_runtime_errors() {
+        compile_and_run("[1, 2][5]");
+        compile_and_run("match 3 { 1 => 1 }");
+        compile_and_run("{ return 1 }");
+        compile_and_run(
+            "for c in \"héllo\" { match c == \"l\" { true => { break }, false => [c][3] } }",
+        );
+    }
+
+    #[test]
+    fn embed_program() {
+        let s = "{ fn f x => { 10 / x }  [f 5, f 0] }";
+        let c = crate::parse(s)
+            .unwrap()
+            .lower()
+            .unwrap()
+            .to_c(s, "test.src");
+
+        // Failing does not stop whatever the program is embedded in, which can run it again
+        let harness = "
+int main(void) {
+    int run;
+    for (run = 0; run < 2; run++) {
+        rt_val val;
+        const char *error = program(&val);
+        puts(error ? error : rt_to_string(val));
+        rt_free_all();
+    }
+    return 0;
+}
+";
+        if let Some(output) = build_and_run(&(c + harness), &["-DRT_NO_MAIN"]) {
+            assert!(output.status.success());
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "attempt to divide by zero\nattempt to divide by zero\n",
+            );
+        }
+    }
+
+    #[test]
+    fn point_line_directives_at_source() {
+        let s = "{
+    let a = 1
+
+    a + 2
+}";
+        let c = crate::parse(s)
+            .unwrap()
+            .lower()
+            .unwrap()
+            .to_c(s, "dir/prog.src");
+
+        assert!(c.contains("#line 2 \"dir/prog.src\"\n    slots[0] = rt_number(1);\n"));
+        // Every line after a directive counts as coming from the next line of the source, so
+        // lines of code from the same statement each need one
+        assert!(c.contains(
+            "#line 4 \"dir/prog.src\"
+    t0 = slots[0];
+#line 4 \"dir/prog.src\"
+    t1 = rt_add(t0, rt_number(2));
+"
+        ));
+    }
+
+    #[test]
+    fn quote_c_string() {
+        assert_eq!(c_string("a\"b\\c\n"), r#""a\"b\\c\n""#);
+        assert_eq!(c_string("é?"), r#""\303\251\?""#);
+    }
+}
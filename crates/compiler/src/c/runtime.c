@@ -0,0 +1,556 @@
+/* Support code included at the start of every program compiled to C. Values behave exactly like
+   they do in the interpreter, including how they are displayed and the errors they cause. Errors
+   jump straight back to `program`, and everything allocated while running it is kept until
+   `rt_free_all` frees it all at once, since values share their items freely. Every function
+   that is not part of the interface for embedding is `static inline`, so that compilers do not
+   warn about the ones a program never uses. */
+
+#include <setjmp.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef enum { RT_UNIT, RT_NUMBER, RT_BOOL, RT_STR, RT_LIST, RT_MAP, RT_RANGE, RT_VARIANT } rt_tag;
+
+typedef struct rt_val {
+    rt_tag tag;
+    /* Numbers, and the start of ranges */
+    int32_t number;
+    int32_t end;
+    /* Bools, and whether ranges are inclusive */
+    int flag;
+    /* Strings, and the variant of enum values */
+    const char *str;
+    const char *enum_name;
+    /* Lists, the fields of enum values, and maps as keys alternating with their values, which
+       are kept ordered by key */
+    struct rt_val *items;
+    size_t len;
+} rt_val;
+
+/* The slots of a call, linked to the frame of the function the called function was defined in */
+typedef struct rt_frame {
+    rt_val *slots;
+    struct rt_frame *parent;
+} rt_frame;
+
+/* Every allocation starts with a link to the one made before it, padded so that what follows is
+   aligned for anything */
+typedef union rt_block {
+    union rt_block *prev;
+    long double align_float;
+    long long align_int;
+    void *align_ptr;
+} rt_block;
+
+static rt_block *rt_allocated = NULL;
+
+/* Where `program` goes back to when the program fails, and the message it failed with */
+static jmp_buf rt_failed;
+static const char *rt_error = NULL;
+
+static inline void *rt_alloc(size_t size) {
+    rt_block *block = malloc(sizeof(rt_block) + size);
+    if (!block) {
+        abort();
+    }
+    block->prev = rt_allocated;
+    rt_allocated = block;
+    return block + 1;
+}
+
+/* Frees everything allocated since it was last called, including the values `program` returned */
+void rt_free_all(void) {
+    while (rt_allocated) {
+        rt_block *prev = rt_allocated->prev;
+        free(rt_allocated);
+        rt_allocated = prev;
+    }
+}
+
+static inline void rt_fail(const char *fmt, ...) {
+    va_list args;
+    int len;
+    char *msg;
+
+    va_start(args, fmt);
+    len = vsnprintf(NULL, 0, fmt, args);
+    va_end(args);
+
+    msg = rt_alloc((size_t) len + 1);
+    va_start(args, fmt);
+    vsnprintf(msg, (size_t) len + 1, fmt, args);
+    va_end(args);
+
+    rt_error = msg;
+    longjmp(rt_failed, 1);
+}
+
+static inline rt_val rt_unit(void) {
+    rt_val val = {.tag = RT_UNIT};
+    return val;
+}
+
+static inline rt_val rt_number(int32_t n) {
+    rt_val val = {.tag = RT_NUMBER};
+    val.number = n;
+    return val;
+}
+
+static inline rt_val rt_bool(int b) {
+    rt_val val = {.tag = RT_BOOL};
+    val.flag = b;
+    return val;
+}
+
+static inline rt_val rt_str(const char *s) {
+    rt_val val = {.tag = RT_STR};
+    val.str = s;
+    return val;
+}
+
+static inline rt_val *rt_copy(size_t len, const rt_val *items) {
+    rt_val *copy = rt_alloc(len * sizeof(rt_val));
+    if (len) {
+        memcpy(copy, items, len * sizeof(rt_val));
+    }
+    return copy;
+}
+
+static inline rt_val rt_list(size_t len, const rt_val *items) {
+    rt_val val = {.tag = RT_LIST};
+    val.items = rt_copy(len, items);
+    val.len = len;
+    return val;
+}
+
+static inline rt_val rt_map(void) {
+    rt_val val = {.tag = RT_MAP};
+    return val;
+}
+
+static inline rt_val rt_variant(const char *enum_name, const char *variant, size_t len,
+                                const rt_val *fields) {
+    rt_val val = {.tag = RT_VARIANT};
+    val.enum_name = enum_name;
+    val.str = variant;
+    val.items = rt_copy(len, fields);
+    val.len = len;
+    return val;
+}
+
+typedef struct {
+    char *data;
+    size_t len, cap;
+} rt_buf;
+
+static inline void rt_push(rt_buf *buf, const char *s, size_t len) {
+    if (buf->len + len + 1 > buf->cap) {
+        char *data = rt_alloc((buf->len + len + 1) * 2);
+        if (buf->len) {
+            memcpy(data, buf->data, buf->len);
+        }
+        buf->data = data;
+        buf->cap = (buf->len + len + 1) * 2;
+    }
+    memcpy(buf->data + buf->len, s, len);
+    buf->len += len;
+    buf->data[buf->len] = '\0';
+}
+
+static inline void rt_push_str(rt_buf *buf, const char *s) {
+    rt_push(buf, s, strlen(s));
+}
+
+/* Strings are displayed quoted, with the same escapes as Rust's `Debug` */
+static inline void rt_display_str(rt_buf *buf, const char *s) {
+    char escape[16];
+
+    rt_push_str(buf, "\"");
+    for (; *s; s++) {
+        unsigned char c = (unsigned char) *s;
+
+        switch (c) {
+        case '"': rt_push_str(buf, "\\\""); break;
+        case '\\': rt_push_str(buf, "\\\\"); break;
+        case '\n': rt_push_str(buf, "\\n"); break;
+        case '\r': rt_push_str(buf, "\\r"); break;
+        case '\t': rt_push_str(buf, "\\t"); break;
+        default:
+            if (c < 0x20 || c == 0x7f) {
+                sprintf(escape, "\\u{%x}", c);
+                rt_push_str(buf, escape);
+            } else {
+                rt_push(buf, s, 1);
+            }
+        }
+    }
+    rt_push_str(buf, "\"");
+}
+
+static inline void rt_display(rt_buf *buf, rt_val val) {
+    char number[32];
+    size_t i;
+
+    switch (val.tag) {
+    case RT_UNIT:
+        rt_push_str(buf, "Unit");
+        break;
+    case RT_NUMBER:
+        sprintf(number, "%ld", (long) val.number);
+        rt_push_str(buf, number);
+        break;
+    case RT_BOOL:
+        rt_push_str(buf, val.flag ? "true" : "false");
+        break;
+    case RT_STR:
+        rt_display_str(buf, val.str);
+        break;
+    case RT_LIST:
+        rt_push_str(buf, "[");
+        for (i = 0; i < val.len; i++) {
+            rt_push_str(buf, i ? ", " : "");
+            rt_display(buf, val.items[i]);
+        }
+        rt_push_str(buf, "]");
+        break;
+    case RT_MAP:
+        rt_push_str(buf, "#{");
+        for (i = 0; i < val.len; i += 2) {
+            rt_push_str(buf, i ? ", " : "");
+            rt_display(buf, val.items[i]);
+            rt_push_str(buf, ": ");
+            rt_display(buf, val.items[i + 1]);
+        }
+        rt_push_str(buf, "}");
+        break;
+    case RT_RANGE:
+        sprintf(number, "%ld..%s%ld", (long) val.number, val.flag ? "=" : "", (long) val.end);
+        rt_push_str(buf, number);
+        break;
+    case RT_VARIANT:
+        rt_push_str(buf, val.str);
+        if (val.len) {
+            rt_push_str(buf, "(");
+            for (i = 0; i < val.len; i++) {
+                rt_push_str(buf, i ? ", " : "");
+                rt_display(buf, val.items[i]);
+            }
+            rt_push_str(buf, ")");
+        }
+        break;
+    }
+}
+
+static inline const char *rt_to_string(rt_val val) {
+    rt_buf buf = {NULL, 0, 0};
+    rt_push_str(&buf, "");
+    rt_display(&buf, val);
+    return buf.data;
+}
+
+static inline int rt_equal(rt_val a, rt_val b) {
+    size_t i;
+
+    if (a.tag != b.tag) {
+        return 0;
+    }
+
+    switch (a.tag) {
+    case RT_UNIT: return 1;
+    case RT_NUMBER: return a.number == b.number;
+    case RT_BOOL: return a.flag == b.flag;
+    case RT_STR: return strcmp(a.str, b.str) == 0;
+    case RT_RANGE: return a.number == b.number && a.end == b.end && a.flag == b.flag;
+    case RT_VARIANT:
+        if (strcmp(a.enum_name, b.enum_name) != 0 || strcmp(a.str, b.str) != 0) {
+            return 0;
+        }
+        /* fall through */
+    case RT_LIST:
+    case RT_MAP:
+        if (a.len != b.len) {
+            return 0;
+        }
+        for (i = 0; i < a.len; i++) {
+            if (!rt_equal(a.items[i], b.items[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+
+    return 0;
+}
+
+static inline void rt_expect_numbers(rt_val lhs, rt_val rhs) {
+    if (lhs.tag != RT_NUMBER || rhs.tag != RT_NUMBER) {
+        rt_fail("Cannot evaluate operation whose left-hand side and right-hand side are not both "
+                "numbers");
+    }
+}
+
+/* Arithmetic wraps around on overflow, rather than being undefined like it is for C's signed
+   integers */
+static inline rt_val rt_add(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_number((int32_t) ((uint32_t) lhs.number + (uint32_t) rhs.number));
+}
+
+static inline rt_val rt_sub(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_number((int32_t) ((uint32_t) lhs.number - (uint32_t) rhs.number));
+}
+
+static inline rt_val rt_mul(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_number((int32_t) ((uint32_t) lhs.number * (uint32_t) rhs.number));
+}
+
+static inline rt_val rt_div(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    if (rhs.number == 0) {
+        rt_fail("attempt to divide by zero");
+    }
+    if (lhs.number == INT32_MIN && rhs.number == -1) {
+        rt_fail("attempt to divide with overflow");
+    }
+    return rt_number(lhs.number / rhs.number);
+}
+
+static inline rt_val rt_eq(rt_val lhs, rt_val rhs) { return rt_bool(rt_equal(lhs, rhs)); }
+static inline rt_val rt_not_eq(rt_val lhs, rt_val rhs) { return rt_bool(!rt_equal(lhs, rhs)); }
+
+static inline rt_val rt_lt(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_bool(lhs.number < rhs.number);
+}
+
+static inline rt_val rt_lt_eq(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_bool(lhs.number <= rhs.number);
+}
+
+static inline rt_val rt_gt(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_bool(lhs.number > rhs.number);
+}
+
+static inline rt_val rt_gt_eq(rt_val lhs, rt_val rhs) {
+    rt_expect_numbers(lhs, rhs);
+    return rt_bool(lhs.number >= rhs.number);
+}
+
+static inline void rt_expect_key(rt_val key) {
+    if (key.tag != RT_NUMBER && key.tag != RT_STR) {
+        rt_fail("map keys must be numbers or strings, but ‘%s’ is neither", rt_to_string(key));
+    }
+}
+
+/* Numbers come before strings, like they do in the interpreter */
+static inline int rt_compare_keys(rt_val a, rt_val b) {
+    if (a.tag != b.tag) {
+        return a.tag == RT_NUMBER ? -1 : 1;
+    }
+    if (a.tag == RT_NUMBER) {
+        return (a.number > b.number) - (a.number < b.number);
+    }
+    return strcmp(a.str, b.str);
+}
+
+/* Returns the index of the key in `map`, or of where it would be inserted if it is not there */
+static inline size_t rt_find_key(rt_val map, rt_val key, int *found) {
+    size_t i;
+
+    for (i = 0; i < map.len; i += 2) {
+        int order = rt_compare_keys(map.items[i], key);
+        if (order >= 0) {
+            *found = order == 0;
+            return i;
+        }
+    }
+
+    *found = 0;
+    return map.len;
+}
+
+static inline rt_val rt_map_insert(rt_val map, rt_val key, rt_val val) {
+    int found;
+    size_t idx;
+    rt_val inserted = map;
+
+    rt_expect_key(key);
+    idx = rt_find_key(map, key, &found);
+
+    if (found) {
+        inserted.items = rt_copy(map.len, map.items);
+    } else {
+        inserted.len = map.len + 2;
+        inserted.items = rt_alloc(inserted.len * sizeof(rt_val));
+        memcpy(inserted.items, map.items, idx * sizeof(rt_val));
+        memcpy(inserted.items + idx + 2, map.items + idx, (map.len - idx) * sizeof(rt_val));
+    }
+
+    inserted.items[idx] = key;
+    inserted.items[idx + 1] = val;
+    return inserted;
+}
+
+static inline rt_val rt_index(rt_val target, rt_val index) {
+    if (target.tag == RT_MAP) {
+        int found;
+        size_t idx;
+
+        rt_expect_key(index);
+        idx = rt_find_key(target, index, &found);
+        if (!found) {
+            rt_fail("map has no key ‘%s’", rt_to_string(index));
+        }
+        return target.items[idx + 1];
+    }
+
+    if (target.tag == RT_LIST && index.tag == RT_NUMBER) {
+        if (index.number < 0 || (size_t) index.number >= target.len) {
+            rt_fail("index %ld is out of bounds for a list of length %lu", (long) index.number,
+                    (unsigned long) target.len);
+        }
+        return target.items[index.number];
+    }
+
+    rt_fail("cannot index into ‘%s’", rt_to_string(target));
+    return rt_unit();
+}
+
+static inline rt_val rt_range(rt_val start, rt_val end, int inclusive) {
+    rt_val val = {.tag = RT_RANGE};
+
+    if (start.tag != RT_NUMBER || end.tag != RT_NUMBER) {
+        rt_fail("Cannot evaluate range whose start and end are not both numbers");
+    }
+
+    val.number = start.number;
+    val.end = end.number;
+    val.flag = inclusive;
+    return val;
+}
+
+typedef struct {
+    rt_val source;
+    size_t pos;
+    int64_t next;
+} rt_iter;
+
+static inline rt_iter rt_iter_start(rt_val source) {
+    rt_iter iter;
+
+    if (source.tag != RT_RANGE && source.tag != RT_LIST && source.tag != RT_STR &&
+        source.tag != RT_MAP) {
+        rt_fail("cannot iterate over ‘%s’", rt_to_string(source));
+    }
+
+    iter.source = source;
+    iter.pos = 0;
+    iter.next = source.number;
+    return iter;
+}
+
+static inline int rt_iter_next(rt_iter *iter, rt_val *item) {
+    rt_val source = iter->source;
+
+    switch (source.tag) {
+    case RT_RANGE:
+        if (iter->next > source.end || (iter->next == source.end && !source.flag)) {
+            return 0;
+        }
+        *item = rt_number((int32_t) iter->next++);
+        return 1;
+    case RT_LIST:
+        if (iter->pos >= source.len) {
+            return 0;
+        }
+        *item = source.items[iter->pos++];
+        return 1;
+    case RT_MAP:
+        if (iter->pos >= source.len) {
+            return 0;
+        }
+        *item = source.items[iter->pos];
+        iter->pos += 2;
+        return 1;
+    case RT_STR: {
+        /* Strings are iterated over a character at a time, each of which is UTF-8 encoded */
+        const char *rest = source.str + iter->pos;
+        unsigned char lead = (unsigned char) *rest;
+        size_t len = lead < 0x80 ? 1 : lead < 0xe0 ? 2 : lead < 0xf0 ? 3 : 4;
+        char *c;
+
+        if (!lead) {
+            return 0;
+        }
+
+        c = rt_alloc(len + 1);
+        memcpy(c, rest, len);
+        c[len] = '\0';
+        iter->pos += len;
+        *item = rt_str(c);
+        return 1;
+    }
+    default:
+        return 0;
+    }
+}
+
+static inline int rt_is_variant(rt_val val, const char *enum_name, const char *variant) {
+    return val.tag == RT_VARIANT && strcmp(val.enum_name, enum_name) == 0 &&
+           strcmp(val.str, variant) == 0;
+}
+
+static inline int rt_is_list(rt_val val, size_t len, int rest) {
+    return val.tag == RT_LIST && (val.len == len || (rest && val.len > len));
+}
+
+static inline int rt_guard(rt_val val) {
+    if (val.tag != RT_BOOL) {
+        rt_fail("match guard must evaluate to a boolean");
+    }
+    return val.flag;
+}
+
+static inline void rt_no_match(rt_val val) {
+    rt_fail("no arm of match expression matched ‘%s’", rt_to_string(val));
+}
+
+static inline void rt_expect_map(rt_val val) {
+    if (val.tag != RT_MAP) {
+        rt_fail("expected a map, got ‘%s’", rt_to_string(val));
+    }
+}
+
+static inline rt_val rt_builtin_insert(rt_val map, rt_val key, rt_val val) {
+    rt_expect_map(map);
+    return rt_map_insert(map, key, val);
+}
+
+static inline rt_val rt_builtin_has(rt_val map, rt_val key) {
+    int found;
+
+    rt_expect_map(map);
+    rt_expect_key(key);
+    rt_find_key(map, key, &found);
+    return rt_bool(found);
+}
+
+static inline rt_val rt_builtin_keys(rt_val map) {
+    rt_val keys = {.tag = RT_LIST};
+    size_t i;
+
+    rt_expect_map(map);
+    keys.len = map.len / 2;
+    keys.items = rt_alloc(keys.len * sizeof(rt_val));
+    for (i = 0; i < keys.len; i++) {
+        keys.items[i] = map.items[2 * i];
+    }
+    return keys;
+}
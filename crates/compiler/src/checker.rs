@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+
+/// Collects the diagnostics found while checking a program, along with what is needed to check
+/// statements whose meaning depends on the constructs enclosing them.
+#[derive(Debug, Default)]
+pub(crate) struct Checker {
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    // The type each type variable has been inferred to be, along with where that was inferred
+    substitution: Vec<Option<(Type, Span)>>,
+    // The return type of each function whose body is being checked, innermost last
+    return_types: Vec<Type>,
+    loop_depth: usize,
+}
+
+/// The type variables that were followed while unifying two types that turned out to conflict,
+/// which point to where the conflicting types were inferred.
+#[derive(Debug, Default)]
+pub(crate) struct Conflict(Vec<u32>);
+
+impl Checker {
+    pub(crate) fn error(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic::error(message, span));
+    }
+
+    pub(crate) fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports an error at `span` that is caused by `conflict`, with a note pointing to each of
+    /// the other places that contributed to it.
+    pub(crate) fn conflict(&mut self, message: String, span: Span, conflict: Conflict) {
+        let mut diagnostic = Diagnostic::error(message, span);
+        let mut seen = vec![span];
+
+        for var in conflict.0 {
+            let Some((ty, origin)) = &self.substitution[var as usize] else {
+                continue;
+            };
+
+            if seen.contains(origin) {
+                continue;
+            }
+
+            let [ty] = self.display([ty]);
+            diagnostic =
+                diagnostic.with_note(format!("inferred to be ‘{}’ here", ty), Some(*origin));
+            seen.push(*origin);
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports an error unless `actual` can be unified with `expected`.
+    pub(crate) fn expect(&mut self, expected: &Type, actual: &Type, span: Span, what: &str) {
+        if let Err(conflict) = self.unify(expected, actual, span) {
+            let [expected, actual] = self.display([expected, actual]);
+            self.conflict(
+                format!("expected {} to be ‘{}’, got ‘{}’", what, expected, actual),
+                span,
+                conflict,
+            );
+        }
+    }
+
+    pub(crate) fn fresh_var(&mut self) -> Type {
+        self.substitution.push(None);
+        Type::Var(self.substitution.len() as u32 - 1)
+    }
+
+    /// Replaces every type variable in `ty` whose type has been inferred with that type.
+    pub(crate) fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match &self.substitution[*var as usize] {
+                Some((ty, _)) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            _ => ty.map_children(|ty| self.resolve(ty)),
+        }
+    }
+
+    /// Infers whatever types are needed to make `a` and `b` the same, recording that they were
+    /// inferred at `span`. Types are compatible with `Unknown` and `Never` without inferring
+    /// anything, since neither constrains what the other type can be.
+    pub(crate) fn unify(&mut self, a: &Type, b: &Type, span: Span) -> Result<(), Conflict> {
+        let mut conflict = Conflict::default();
+
+        if self.unify_inner(a, b, span, &mut conflict) {
+            Ok(())
+        } else {
+            Err(conflict)
+        }
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type, span: Span, conflict: &mut Conflict) -> bool {
+        let a = self.follow(a, conflict);
+        let b = self.follow(b, conflict);
+
+        match (&a, &b) {
+            (Type::Var(a), Type::Var(b)) if a == b => true,
+            (Type::Var(_), Type::Never) | (Type::Never, Type::Var(_)) => true,
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                let mut vars = Vec::new();
+                self.resolve(ty).vars(&mut vars);
+
+                // A type cannot contain itself
+                if vars.contains(var) {
+                    return false;
+                }
+
+                self.substitution[*var as usize] = Some((ty.clone(), span));
+                true
+            }
+            (Type::Never | Type::Unknown, _) | (_, Type::Never | Type::Unknown) => true,
+            (Type::List(a), Type::List(b)) => self.unify_inner(a, b, span, conflict),
+            (Type::Map(a_key, a_val), Type::Map(b_key, b_val)) => {
+                self.unify_inner(a_key, b_key, span, conflict)
+                    && self.unify_inner(a_val, b_val, span, conflict)
+            }
+            (Type::Enum(a_name, a_args), Type::Enum(b_name, b_args))
+                if a_name == b_name && a_args.len() == b_args.len() =>
+            {
+                a_args
+                    .iter()
+                    .zip(b_args)
+                    .all(|(a, b)| self.unify_inner(a, b, span, conflict))
+            }
+            (
+                Type::Func {
+                    params: a_params,
+                    ret: a_ret,
+                },
+                Type::Func {
+                    params: b_params,
+                    ret: b_ret,
+                },
+            ) if a_params.len() == b_params.len() => {
+                a_params
+                    .iter()
+                    .zip(b_params)
+                    .all(|(a, b)| self.unify_inner(a, b, span, conflict))
+                    && self.unify_inner(a_ret, b_ret, span, conflict)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    // Follows type variables until reaching one that has not been inferred or a type that is not a
+    // variable, remembering which variables were followed
+    fn follow(&self, ty: &Type, conflict: &mut Conflict) -> Type {
+        match ty {
+            Type::Var(var) => match &self.substitution[*var as usize] {
+                Some((ty, _)) => {
+                    conflict.0.push(*var);
+                    self.follow(ty, conflict)
+                }
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// Turns the type variables in `ty` that do not occur anywhere in `env` into generics, so that
+    /// each use of whatever has the type can infer them differently.
+    pub(crate) fn generalize(&self, ty: &Type, env: &TypeEnv) -> Type {
+        let mut env_vars = Vec::new();
+        for env_ty in env.types() {
+            self.resolve(env_ty).vars(&mut env_vars);
+        }
+
+        let ty = self.resolve(ty);
+
+        let mut vars = Vec::new();
+        ty.vars(&mut vars);
+        vars.retain(|var| !env_vars.contains(var));
+
+        replace_vars(&ty, &|var| {
+            vars.iter()
+                .position(|generic_var| *generic_var == var)
+                .map(|idx| Type::Generic(idx as u32))
+        })
+    }
+
+    /// Replaces every generic in `ty` with a fresh type variable.
+    pub(crate) fn instantiate(&mut self, ty: &Type) -> Type {
+        let mut vars = HashMap::new();
+        self.instantiate_inner(ty, &mut vars)
+    }
+
+    fn instantiate_inner(&mut self, ty: &Type, vars: &mut HashMap<u32, Type>) -> Type {
+        match ty {
+            Type::Generic(idx) => match vars.get(idx) {
+                Some(var) => var.clone(),
+                None => {
+                    let var = self.fresh_var();
+                    vars.insert(*idx, var.clone());
+                    var
+                }
+            },
+            _ => ty.map_children(|child| self.instantiate_inner(child, vars)),
+        }
+    }
+
+    /// Formats types for showing in a diagnostic, naming the type variables that are yet to be
+    /// inferred `a`, `b` and so on in the order they appear.
+    pub(crate) fn display<const N: usize>(&self, tys: [&Type; N]) -> [String; N] {
+        let tys = tys.map(|ty| self.resolve(ty));
+
+        let mut vars = Vec::new();
+        for ty in &tys {
+            ty.vars(&mut vars);
+        }
+
+        tys.map(|ty| {
+            replace_vars(&ty, &|var| {
+                vars.iter()
+                    .position(|other| *other == var)
+                    .map(|idx| Type::Generic(idx as u32))
+            })
+            .to_string()
+        })
+    }
+
+    /// Checks the body of a function with `check_body`, requiring both the type of the body and
+    /// the types of any values it returns early to be `ret`.
+    pub(crate) fn check_func_body(
+        &mut self,
+        ret: &Type,
+        span: Span,
+        check_body: impl FnOnce(&mut Self) -> Type,
+    ) {
+        // Loop control flow cannot cross function boundaries
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.return_types.push(ret.clone());
+
+        let body_ty = check_body(self);
+
+        self.return_types.pop();
+        self.loop_depth = loop_depth;
+
+        if let Err(conflict) = self.unify(ret, &body_ty, span) {
+            let [ret, body_ty] = self.display([ret, &body_ty]);
+            self.conflict(
+                format!(
+                    "function returns ‘{}’ but its body evaluates to ‘{}’",
+                    ret, body_ty,
+                ),
+                span,
+                conflict,
+            );
+        }
+    }
+
+    pub(crate) fn check_loop_body(&mut self, check_body: impl FnOnce(&mut Self)) {
+        self.loop_depth += 1;
+        check_body(self);
+        self.loop_depth -= 1;
+    }
+
+    pub(crate) fn check_loop_control(&mut self, keyword: &str, span: Span) {
+        if self.loop_depth == 0 {
+            self.error(format!("cannot {} outside of a loop", keyword), span);
+        }
+    }
+
+    pub(crate) fn check_return(&mut self, ty: Type, span: Span) {
+        let Some(ret) = self.return_types.last().cloned() else {
+            self.error("cannot return outside of a function".to_string(), span);
+            return;
+        };
+
+        if let Err(conflict) = self.unify(&ret, &ty, span) {
+            let [ty, ret] = self.display([&ty, &ret]);
+            self.conflict(
+                format!(
+                    "cannot return ‘{}’ from a function that already returns ‘{}’",
+                    ty, ret,
+                ),
+                span,
+                conflict,
+            );
+        }
+    }
+}
+
+fn replace_vars(ty: &Type, replacement: &impl Fn(u32) -> Option<Type>) -> Type {
+    match ty {
+        Type::Var(var) => replacement(*var).unwrap_or_else(|| ty.clone()),
+        _ => ty.map_children(|ty| replace_vars(ty, replacement)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Severity;
+    use crate::lint::{Level, Lints};
+    use crate::type_env::TypeEnv;
+    use crate::types::Type;
+
+    // Lints are tested on their own, so that they do not get in the way of the diagnostics here
+    fn allow_lints() -> Lints {
+        let mut lints = Lints::default();
+        lints.set_all(Level::Allow);
+        lints
+    }
+
+    fn check(s: &str) -> Vec<(Severity, String)> {
+        crate::parse(s)
+            .unwrap()
+            .check(&mut TypeEnv::default(), &allow_lints())
+            .into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn check_well_typed_program() {
+        assert_eq!(
+            check("{ let xs = [1, 2] let m = #{ \"a\": xs } m[\"a\"][0] * 2 }"),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn check_operation_on_non_numbers() {
+        assert_eq!(
+            check("1 + {}"),
+            vec![(
+                Severity::Error,
+                "‘+’ requires both sides to be ‘Int’, got ‘Int’ and ‘Unit’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_reports_every_error() {
+        assert_eq!(
+            check("[1 < true, 2..\"b\"]"),
+            vec![
+                (
+                    Severity::Error,
+                    "‘<’ requires both sides to be ‘Int’, got ‘Int’ and ‘Bool’".to_string(),
+                ),
+                (
+                    Severity::Error,
+                    "expected end of range to be ‘Int’, got ‘Str’".to_string(),
+                ),
+                (
+                    Severity::Error,
+                    "expected list item to be ‘Bool’, got ‘Range’".to_string(),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn check_mixed_list() {
+        assert_eq!(
+            check("[1, true]"),
+            vec![(
+                Severity::Error,
+                "expected list item to be ‘Int’, got ‘Bool’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_break_outside_of_loop() {
+        assert_eq!(
+            check("{ for x in 0..3 { break } continue }"),
+            vec![(
+                Severity::Error,
+                "cannot continue outside of a loop".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_loop_control_does_not_cross_function_boundary() {
+        assert_eq!(
+            check("for x in [1] { fn f => break }"),
+            vec![(
+                Severity::Error,
+                "cannot break outside of a loop".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_conflicting_returns() {
+        assert_eq!(
+            check("fn f x => { return 1 return \"a\" }"),
+            vec![(
+                Severity::Error,
+                "cannot return ‘Str’ from a function that already returns ‘Int’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_funcs_that_call_each_other() {
+        assert_eq!(
+            check(
+                "{ fn even n => match n { 0 => true, _ => odd { n - 1 } } \
+                   fn odd n => match n { 0 => false, _ => even { n - 1 } } \
+                   even 4 }"
+            ),
+            Vec::new(),
+        );
+        assert_eq!(
+            check(
+                "{ fn even n => match n { 0 => true, _ => odd [n] } \
+                   fn odd n => match n { 0 => false, _ => even { n - 1 } } \
+                   even 4 }"
+            ),
+            vec![
+                (
+                    Severity::Error,
+                    "expected pattern ‘0’ to be ‘List<Int>’, got ‘Int’".to_string(),
+                ),
+                (
+                    Severity::Error,
+                    "‘-’ requires both sides to be ‘Int’, got ‘List<Int>’ and ‘Int’".to_string(),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn check_stores_return_type_of_func() {
+        let mut env = TypeEnv::default();
+        let diagnostics = crate::parse("fn f x => { return [x] [] }")
+            .unwrap()
+            .check(&mut env, &allow_lints());
+
+        assert_eq!(diagnostics, Vec::new());
+        assert_eq!(
+            env.type_of("f"),
+            Some(Type::Func {
+                params: vec![Type::Generic(0)],
+                ret: Box::new(Type::List(Box::new(Type::Generic(0)))),
+            }),
+        );
+    }
+
+    fn type_of(s: &str, name: &str) -> String {
+        let mut env = TypeEnv::default();
+        let diagnostics = crate::parse(s).unwrap().check(&mut env, &allow_lints());
+
+        assert_eq!(diagnostics, Vec::new());
+        env.type_of(name).unwrap().to_string()
+    }
+
+    #[test]
+    fn infer_identity() {
+        assert_eq!(type_of("fn id x => x", "id"), "a -> a");
+    }
+
+    #[test]
+    fn infer_arithmetic() {
+        assert_eq!(type_of("fn add x y => x + y", "add"), "Int -> Int -> Int");
+    }
+
+    #[test]
+    fn infer_unused_param() {
+        assert_eq!(type_of("fn const x y => x", "const"), "a -> b -> a");
+    }
+
+    #[test]
+    fn infer_recursive_func() {
+        assert_eq!(
+            type_of(
+                "fn fact n => match n { 0 => 1, n => { let m = n - 1 n * fact m } }",
+                "fact",
+            ),
+            "Int -> Int",
+        );
+    }
+
+    #[test]
+    fn infer_builtin_call() {
+        assert_eq!(
+            type_of("fn set m => insert m \"a\" true", "set"),
+            "Map<Str, Bool> -> Map<Str, Bool>",
+        );
+    }
+
+    #[test]
+    fn generalize_nested_func() {
+        assert_eq!(
+            type_of(
+                "fn f x => {
+    fn id y => y
+    let a = id 1
+    let b = id true
+    [id x]
+}",
+                "f",
+            ),
+            "a -> List<a>",
+        );
+    }
+
+    #[test]
+    fn generalize_let_binding() {
+        assert_eq!(
+            check(
+                "{
+    let empty = []
+    let a = [1] == empty
+    [true] == empty
+}",
+            ),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn do_not_generalize_types_of_params() {
+        assert_eq!(
+            check(
+                "fn f x => {
+    fn g y => x
+    let a = g 1 + 1
+    g 2 == true
+}",
+            ),
+            vec![(
+                Severity::Error,
+                "cannot compare ‘Int’ with ‘Bool’ using ‘==’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn report_where_conflicting_type_was_inferred() {
+        let source = "fn f x => { let a = x + 1 x == true }";
+        let diagnostics = crate::parse(source)
+            .unwrap()
+            .check(&mut TypeEnv::default(), &allow_lints());
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.render(source))
+                .collect::<String>(),
+            "error: cannot compare ‘Int’ with ‘Bool’ using ‘==’
+  --> 1:27
+  |
+1 | fn f x => { let a = x + 1 x == true }
+  |                           ^^^^^^^^^
+note: inferred to be ‘Int’ here
+  --> 1:21
+  |
+1 | fn f x => { let a = x + 1 x == true }
+  |                     ^^^^^
+",
+        );
+    }
+
+    #[test]
+    fn report_infinite_type() {
+        assert_eq!(
+            check("fn f x => [x] == x"),
+            vec![(
+                Severity::Error,
+                "cannot compare ‘List<a>’ with ‘a’ using ‘==’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_iterating_over_bool() {
+        assert_eq!(
+            check("for x in true { x }"),
+            vec![(Severity::Error, "cannot iterate over ‘Bool’".to_string())],
+        );
+    }
+
+    #[test]
+    fn infer_generic_func() {
+        assert_eq!(
+            type_of("fn first<T> xs: List<T> -> T => xs[0]", "first"),
+            "List<a> -> a",
+        );
+    }
+
+    // Checks each line in turn against the same environment, as the REPL does
+    fn check_lines(lines: &[&str]) -> (TypeEnv<'static>, Vec<(Severity, String)>) {
+        let mut env = TypeEnv::default();
+        let diagnostics = lines
+            .iter()
+            .flat_map(|line| crate::parse(line).unwrap().check(&mut env, &allow_lints()))
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+            .collect();
+
+        (env, diagnostics)
+    }
+
+    #[test]
+    fn infer_generic_enum() {
+        let (env, diagnostics) = check_lines(&[
+            "enum Option<T> { Some(value: T), None }",
+            "let a = Some 1",
+            "let b = None",
+            "enum Pair<A, B> { Pair(first: A, second: B) }",
+        ]);
+
+        assert_eq!(diagnostics, Vec::new());
+        assert_eq!(env.type_of("a").unwrap().to_string(), "Option<Int>");
+        assert_eq!(env.type_of("b").unwrap().to_string(), "Option<a>");
+        assert_eq!(
+            env.type_of("Pair").unwrap().to_string(),
+            "a -> b -> Pair<a, b>",
+        );
+    }
+
+    #[test]
+    fn check_generic_enum_field() {
+        let (_, diagnostics) = check_lines(&[
+            "enum Option<T> { Some(value: T), None }",
+            "fn f o: Option<Int> => match o { Some(x) => x == true, None => false }",
+        ]);
+
+        assert_eq!(
+            diagnostics,
+            vec![(
+                Severity::Error,
+                "cannot compare ‘Int’ with ‘Bool’ using ‘==’".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_type_param_inferred_to_be_concrete() {
+        assert_eq!(
+            check("fn f<T> x: T => x + 1"),
+            vec![(
+                Severity::Error,
+                "type parameter ‘T’ was inferred to be ‘Int’".to_string(),
+            )],
+        );
+        assert_eq!(
+            check("fn f<T, U> x: T y: U => x == y"),
+            vec![(
+                Severity::Error,
+                "type parameters ‘T’ and ‘U’ were inferred to be the same type".to_string(),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_type_param_declared_more_than_once() {
+        let duplicate = vec![(
+            Severity::Error,
+            "type parameter ‘T’ is declared more than once".to_string(),
+        )];
+
+        assert_eq!(check("fn f<T, T> x: T => x"), duplicate);
+        assert_eq!(check("enum Pair<T, T> { Pair(first: T) }"), duplicate);
+    }
+
+    #[test]
+    fn instantiate_generic_func_per_call() {
+        let (_, diagnostics) = check_lines(&[
+            "fn wrap<T> x: T -> List<T> => [x]",
+            "let a = wrap 1",
+            "let b = wrap true",
+        ]);
+
+        assert_eq!(diagnostics, Vec::new());
+    }
+}
@@ -0,0 +1,119 @@
+/// What a node evaluates to, which is all the type information the backends that only support
+/// numbers need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    Number,
+    Unit,
+}
+
+/// The code a backend has generated for each function of a program. Functions are only generated
+/// once something calls them, at which point what they evaluate to is worked out too.
+pub(crate) struct Funcs<T> {
+    states: Vec<State<T>>,
+}
+
+enum State<T> {
+    NotGenerated,
+    // Any call made while a function is being generated is a recursive one
+    InProgress { assumed_number: bool },
+    Generated { code: T, kind: Kind },
+}
+
+impl<T> Funcs<T> {
+    pub(crate) fn new(num_funcs: usize) -> Self {
+        Self {
+            states: (0..num_funcs).map(|_| State::NotGenerated).collect(),
+        }
+    }
+
+    /// Returns what a function evaluates to if it has already been generated, or is being
+    /// generated by one of the calls the current one is nested in. Otherwise the caller has to
+    /// generate the function and pass it to `finish`.
+    pub(crate) fn start(&mut self, id: usize) -> Option<Kind> {
+        match &mut self.states[id] {
+            State::Generated { kind, .. } => Some(*kind),
+            // A recursive call cannot be what ends the recursion, so the function's value comes
+            // from somewhere else
+            State::InProgress { assumed_number } => {
+                *assumed_number = true;
+                Some(Kind::Number)
+            }
+            State::NotGenerated => {
+                self.states[id] = State::InProgress {
+                    assumed_number: false,
+                };
+                None
+            }
+        }
+    }
+
+    /// Records the code generated for a function, failing with what is not supported if a
+    /// recursive call took it to evaluate to a number when it does not.
+    pub(crate) fn finish(&mut self, id: usize, code: T, kind: Kind) -> Result<(), &'static str> {
+        if let State::InProgress {
+            assumed_number: true,
+        } = self.states[id]
+            && kind == Kind::Unit
+        {
+            return Err("recursive functions evaluating to ‘()’");
+        }
+
+        self.states[id] = State::Generated { code, kind };
+        Ok(())
+    }
+
+    /// The code of every function that has been generated, along with its index in the program.
+    pub(crate) fn into_generated(self) -> impl Iterator<Item = (usize, T)> {
+        self.states
+            .into_iter()
+            .enumerate()
+            .filter_map(|(id, state)| match state {
+                State::Generated { code, .. } => Some((id, code)),
+                State::NotGenerated | State::InProgress { .. } => None,
+            })
+    }
+}
+
+/// A path in the system's temporary directory that no other test uses, including those running
+/// at the same time in the same process.
+#[cfg(test)]
+pub(crate) fn scratch_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("{}-{}-{}", name, std::process::id(), id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_each_func_once() {
+        let mut funcs = Funcs::new(3);
+
+        assert_eq!(funcs.start(2), None);
+        assert_eq!(funcs.finish(2, "two", Kind::Unit), Ok(()));
+        assert_eq!(funcs.start(2), Some(Kind::Unit));
+
+        assert_eq!(funcs.into_generated().collect::<Vec<_>>(), [(2, "two")]);
+    }
+
+    #[test]
+    fn assume_recursive_calls_evaluate_to_numbers() {
+        let mut funcs = Funcs::new(2);
+
+        assert_eq!(funcs.start(0), None);
+        assert_eq!(funcs.start(0), Some(Kind::Number));
+        assert_eq!(funcs.finish(0, "zero", Kind::Number), Ok(()));
+
+        assert_eq!(funcs.start(1), None);
+        assert_eq!(funcs.start(1), Some(Kind::Number));
+        assert_eq!(
+            funcs.finish(1, "one", Kind::Unit),
+            Err("recursive functions evaluating to ‘()’"),
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a program before it is run.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<Note>,
+}
+
+/// Additional information attached to a diagnostic, optionally pointing at another location.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(message: String, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn warning(message: String, span: Span) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message, span)
+        }
+    }
+
+    pub(crate) fn with_note(mut self, message: String, span: Option<Span>) -> Self {
+        self.notes.push(Note { message, span });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Renders the diagnostic together with the lines of `source` it refers to, where `source` is
+    /// the complete input the program was parsed from.
+    pub fn render(&self, source: &str) -> String {
+        let mut rendered = format!("{}: {}\n", self.severity, self.message);
+        render_snippet(&mut rendered, source, self.span);
+
+        for note in &self.notes {
+            rendered.push_str(&format!("note: {}\n", note.message));
+
+            if let Some(span) = note.span {
+                render_snippet(&mut rendered, source, span);
+            }
+        }
+
+        rendered
+    }
+}
+
+fn render_snippet(rendered: &mut String, source: &str, span: Span) {
+    let range = span.range(source);
+
+    let line_start = source[..range.start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[range.start..]
+        .find('\n')
+        .map_or(source.len(), |idx| range.start + idx);
+
+    let line_number = source[..range.start].matches('\n').count() + 1;
+    let column = source[line_start..range.start].chars().count() + 1;
+
+    // Spans covering several lines are only underlined up to the end of their first line
+    let underline_len = source[range.start..range.end.min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    rendered.push_str(&format!("{} --> {}:{}\n", gutter, line_number, column));
+    rendered.push_str(&format!("{} |\n", gutter));
+    rendered.push_str(&format!(
+        "{} | {}\n",
+        line_number,
+        &source[line_start..line_end],
+    ));
+    rendered.push_str(&format!(
+        "{} | {}{}\n",
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(underline_len),
+    ));
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_error() {
+        let source = "let a = 1\nlet b = a + {}";
+        let span = Span::new(&source[18..], "");
+
+        assert_eq!(
+            Diagnostic::error("something went wrong".to_string(), span).render(source),
+            "error: something went wrong
+  --> 2:9
+  |
+2 | let b = a + {}
+  |         ^^^^^^
+",
+        );
+    }
+
+    #[test]
+    fn render_warning_with_notes() {
+        let source = "fn f => 1\nf";
+        let def_span = Span::new(source, &source[9..]);
+        let usage_span = Span::new(&source[10..], "");
+
+        assert_eq!(
+            Diagnostic::warning("look here".to_string(), usage_span)
+                .with_note("and here".to_string(), Some(def_span))
+                .with_note("and nowhere".to_string(), None)
+                .render(source),
+            "warning: look here
+  --> 2:1
+  |
+2 | f
+  | ^
+note: and here
+  --> 1:1
+  |
+1 | fn f => 1
+  | ^^^^^^^^^
+note: and nowhere
+",
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use crate::checker::Checker;
+use crate::lower::Lowerer;
+use crate::resolver::{Definition, NameKind, Resolver};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::type_expr::TypeExpr;
+use crate::types::Type;
+use crate::{Env, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct EnumDef {
+    pub(crate) name: Symbol,
+    pub(crate) type_params: Vec<Symbol>,
+    pub(crate) variants: Vec<Variant>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Variant {
+    pub(crate) name: Symbol,
+    pub(crate) fields: Vec<Field>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Field {
+    pub(crate) name: Symbol,
+    pub(crate) ty: Option<TypeExpr>,
+}
+
+impl EnumDef {
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("enum", before)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let (s, name) = utils::extract_ident(s)?;
+        let (s, type_params) = TypeExpr::new_params(s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let s = utils::tag("{", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, variants) = utils::sequence(Variant::new, utils::extract_comma, s)?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag("}", s)?;
+
+        Ok((
+            s,
+            Self {
+                name: Symbol::intern(name),
+                type_params,
+                variants,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), String> {
+        for variant in &self.variants {
+            env.store_constructor(
+                variant.name,
+                self.name,
+                variant.fields.iter().map(|field| field.name).collect(),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self, resolver: &mut Resolver) {
+        for variant in &self.variants {
+            resolver.define(
+                variant.name,
+                Definition {
+                    kind: NameKind::Constructor,
+                    arity: variant.fields.len(),
+                    span: Some(variant.span),
+                },
+            );
+        }
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) {
+        for variant in &self.variants {
+            lowerer.define_constructor(variant.name, self.name, variant.fields.len());
+        }
+    }
+
+    pub(crate) fn check(&self, env: &mut TypeEnv, checker: &mut Checker) {
+        TypeExpr::check_params(&self.type_params, self.span, checker);
+
+        // The enum is stored first so that its variants can hold values of the enum itself
+        env.store_enum(
+            self.name,
+            self.type_params.len(),
+            self.variants.iter().map(|variant| variant.name).collect(),
+        );
+
+        let generics: Vec<_> = (0..self.type_params.len())
+            .map(|idx| Type::Generic(idx as u32))
+            .collect();
+
+        let mut params_env = env.create_child();
+        for (name, generic) in self.type_params.iter().zip(&generics) {
+            params_env.store_type_param(*name, generic.clone());
+        }
+
+        let constructors: Vec<_> = self
+            .variants
+            .iter()
+            .map(|variant| {
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|field| match &field.ty {
+                        Some(ty) => ty.to_type(&params_env, checker),
+                        // Unannotated fields can hold values of any type
+                        None => Type::Unknown,
+                    })
+                    .collect();
+
+                (variant.name, fields)
+            })
+            .collect();
+
+        for (name, fields) in constructors {
+            env.store_constructor(name, fields, Type::Enum(self.name, generics.clone()));
+        }
+    }
+}
+
+impl Variant {
+    fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let (s, name) = utils::extract_ident(s)?;
+
+        // Variants without a payload may leave off the parentheses entirely
+        let (s, fields) = match utils::tag("(", s) {
+            Ok(s) => {
+                let (s, _) = utils::extract_whitespace(s);
+
+                let (s, fields) = utils::sequence(Field::new, utils::extract_comma, s)?;
+
+                let (s, _) = utils::extract_whitespace(s);
+                let s = utils::tag(")", s)?;
+                (s, fields)
+            }
+            Err(_) => (s, Vec::new()),
+        };
+
+        Ok((
+            s,
+            Self {
+                name: Symbol::intern(name),
+                fields,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+}
+
+impl Field {
+    fn new(s: &str) -> Result<(&str, Self), String> {
+        let (s, name) = utils::extract_ident(s)?;
+
+        let (s, ty) = match TypeExpr::new_annotation(s) {
+            Ok((s, ty)) => (s, Some(ty)),
+            Err(_) => (s, None),
+        };
+
+        Ok((
+            s,
+            Self {
+                name: Symbol::intern(name),
+                ty,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str) -> Field {
+        Field {
+            name: Symbol::intern(name),
+            ty: None,
+        }
+    }
+
+    #[test]
+    fn parse_enum_def() {
+        assert_eq!(
+            EnumDef::new("enum Shape { Circle(r), Rect(w, h) }"),
+            Ok((
+                "",
+                EnumDef {
+                    name: Symbol::intern("Shape"),
+                    type_params: Vec::new(),
+                    variants: vec![
+                        Variant {
+                            name: Symbol::intern("Circle"),
+                            fields: vec![field("r")],
+                            span: Span::new("Circle(r), Rect(w, h) }", ", Rect(w, h) }"),
+                        },
+                        Variant {
+                            name: Symbol::intern("Rect"),
+                            fields: vec![field("w"), field("h")],
+                            span: Span::new("Rect(w, h) }", " }"),
+                        },
+                    ],
+                    span: Span::new("enum Shape { Circle(r), Rect(w, h) }", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_enum_def_with_variant_without_payload() {
+        assert_eq!(
+            EnumDef::new(
+                "enum Option {
+    Some(value),
+    None,
+}",
+            ),
+            Ok((
+                "",
+                EnumDef {
+                    name: Symbol::intern("Option"),
+                    type_params: Vec::new(),
+                    variants: vec![
+                        Variant {
+                            name: Symbol::intern("Some"),
+                            fields: vec![field("value")],
+                            span: Span::new("Some(value),\n    None,\n}", ",\n    None,\n}"),
+                        },
+                        Variant {
+                            name: Symbol::intern("None"),
+                            fields: Vec::new(),
+                            span: Span::new("None,\n}", ",\n}"),
+                        },
+                    ],
+                    span: Span::new("enum Option {\n    Some(value),\n    None,\n}", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_generic_enum_def() {
+        let (s, enum_def) = EnumDef::new("enum Option<T> { Some(value: T), None }").unwrap();
+
+        assert_eq!(s, "");
+        assert_eq!(enum_def.type_params, vec![Symbol::intern("T")]);
+        assert_eq!(
+            enum_def.variants[0].fields,
+            vec![Field {
+                name: Symbol::intern("value"),
+                ty: Some(TypeExpr {
+                    name: Symbol::intern("T"),
+                    args: Vec::new(),
+                    span: Span::new("T), None }", "), None }"),
+                }),
+            }],
+        );
+    }
+
+    #[test]
+    fn cannot_parse_enum_def_without_space_after_enum() {
+        assert_eq!(
+            EnumDef::new("enumShape{}"),
+            Err("Expected whitespace".to_string()),
+        );
+    }
+
+    #[test]
+    fn cannot_parse_enum_def_without_commas() {
+        assert_eq!(
+            EnumDef::new("enum E { A(x y) B }"),
+            Err("Expected \"}\"".to_string()),
+        );
+        assert_eq!(
+            EnumDef::new("enum E { A(x, y) B }"),
+            Err("Expected \"}\"".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_enum_def() {
+        let mut env = Env::default();
+
+        EnumDef {
+            name: Symbol::intern("Shape"),
+            type_params: Vec::new(),
+            variants: vec![Variant {
+                name: Symbol::intern("Circle"),
+                fields: vec![field("r")],
+                span: Span::default(),
+            }],
+            span: Span::default(),
+        }
+        .eval(&mut env)
+        .unwrap();
+
+        assert_eq!(
+            env.get_constructor(Symbol::intern("Circle")),
+            Ok((Symbol::intern("Shape"), vec![Symbol::intern("r")])),
+        );
+        assert_eq!(
+            env.get_binding(Symbol::intern("Circle")),
+            Err("binding with name ‘Circle’ does not exist".to_string()),
+        );
+    }
+}
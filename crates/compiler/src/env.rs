@@ -1,56 +1,156 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::{Rc, Weak};
 
-use crate::{statement::Statement, val::Val};
+use crate::statement::Statement;
+use crate::symbol::Symbol;
+use crate::val::Val;
+
+/// A scope of bindings, functions and constructors. Cloning an `Env` shares the scope rather than
+/// copying it, so a child can keep its parents alive for as long as it is needed rather than only
+/// for as long as they are borrowed.
+///
+/// Scoping is lexical: a function's body runs in a child of the scope the function was defined in,
+/// so what the names in it refer to does not depend on where it is called from.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Env(Rc<RefCell<Scope>>);
 
 #[derive(Debug, PartialEq, Default)]
-pub struct Env<'parent> {
-    named: HashMap<String, NamedInfo>,
-    parent: Option<&'parent Self>,
+struct Scope {
+    named: HashMap<Symbol, NamedInfo>,
+    parent: Option<Env>,
 }
 
-impl<'parent> Env<'parent> {
-    pub(crate) fn create_child(&'parent self) -> Self {
-        Self {
+impl Env {
+    pub(crate) fn create_child(&self) -> Self {
+        Self(Rc::new(RefCell::new(Scope {
             named: HashMap::new(),
-            parent: Some(self),
-        }
+            parent: Some(self.clone()),
+        })))
+    }
+
+    pub(crate) fn store_binding(&mut self, name: Symbol, val: Val) {
+        self.store(name, NamedInfo::Binding(val));
+    }
+
+    /// Stores a function defined in this scope, which its body is run in a child of whenever it
+    /// is called.
+    pub(crate) fn store_func(&mut self, name: Symbol, params: Rc<[Symbol]>, body: Rc<Statement>) {
+        // The function is stored in the scope it is defined in, so holding onto that scope
+        // strongly would keep it alive forever. That scope is only known once room has been made
+        // for the name, so that a redefined function calling itself calls the new definition.
+        self.make_room(name);
+        let env = Rc::downgrade(&self.0);
+        self.0
+            .borrow_mut()
+            .named
+            .insert(name, NamedInfo::Func { params, body, env });
     }
 
-    pub(crate) fn store_binding(&mut self, name: String, val: Val) {
-        self.named.insert(name, NamedInfo::Binding(val));
+    pub(crate) fn store_constructor(
+        &mut self,
+        name: Symbol,
+        enum_name: Symbol,
+        fields: Vec<Symbol>,
+    ) {
+        self.store(name, NamedInfo::Constructor { enum_name, fields });
+    }
+
+    fn store(&mut self, name: Symbol, info: NamedInfo) {
+        self.make_room(name);
+        self.0.borrow_mut().named.insert(name, info);
+    }
+
+    fn make_room(&mut self, name: Symbol) {
+        // Functions defined before a name was redefined still see what it was before, so the new
+        // definition goes in a scope of its own rather than replacing the old one
+        if self.defines(name) {
+            *self = self.create_child();
+        }
     }
 
-    pub(crate) fn store_func(&mut self, name: String, params: Vec<String>, body: Statement) {
-        self.named.insert(name, NamedInfo::Func { params, body });
+    /// Whether something with this name is defined in this scope itself, rather than in one of
+    /// its parents.
+    pub(crate) fn defines(&self, name: Symbol) -> bool {
+        self.0.borrow().named.contains_key(&name)
     }
 
-    pub(crate) fn get_binding(&self, name: &str) -> Result<Val, String> {
+    pub(crate) fn get_binding(&self, name: Symbol) -> Result<Val, String> {
         self.get_named_info(name)
             .and_then(NamedInfo::into_binding)
             .ok_or_else(|| format!("binding with name ‘{}’ does not exist", name))
     }
 
-    pub(crate) fn get_func(&self, name: &str) -> Result<(Vec<String>, Statement), String> {
+    // Functions are shared with the scope they are stored in, so getting one does not copy its
+    // body. The scope the function was defined in comes along with it.
+    pub(crate) fn get_func(&self, name: Symbol) -> Result<Func, String> {
         self.get_named_info(name)
             .and_then(NamedInfo::into_func)
             .ok_or_else(|| format!("function with name ‘{}’ does not exist", name))
     }
 
-    fn get_named_info(&self, name: &str) -> Option<NamedInfo> {
-        self.named
-            .get(name)
+    pub(crate) fn get_constructor(&self, name: Symbol) -> Result<(Symbol, Vec<Symbol>), String> {
+        self.get_named_info(name)
+            .and_then(NamedInfo::into_constructor)
+            .ok_or_else(|| format!("constructor with name ‘{}’ does not exist", name))
+    }
+
+    fn get_named_info(&self, name: Symbol) -> Option<NamedInfo> {
+        let scope = self.0.borrow();
+
+        scope
+            .named
+            .get(&name)
             .cloned()
-            .or_else(|| self.parent.and_then(|parent| parent.get_named_info(name)))
+            .or_else(|| scope.parent.as_ref()?.get_named_info(name))
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// A function found in an `Env`, along with the scope it was defined in.
+#[derive(Debug)]
+pub(crate) struct Func {
+    pub(crate) params: Rc<[Symbol]>,
+    pub(crate) body: Rc<Statement>,
+    pub(crate) env: Env,
+}
+
+#[derive(Debug, Clone)]
 enum NamedInfo {
     Binding(Val),
     Func {
-        params: Vec<String>,
-        body: Statement,
+        params: Rc<[Symbol]>,
+        body: Rc<Statement>,
+        env: Weak<RefCell<Scope>>,
     },
+    Constructor {
+        enum_name: Symbol,
+        fields: Vec<Symbol>,
+    },
+}
+
+// Scopes are compared by identity, since comparing their contents could go round in circles
+impl PartialEq for NamedInfo {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Binding(a), Self::Binding(b)) => a == b,
+            (
+                Self::Func { params, body, env },
+                Self::Func {
+                    params: other_params,
+                    body: other_body,
+                    env: other_env,
+                },
+            ) => params == other_params && body == other_body && env.ptr_eq(other_env),
+            (
+                Self::Constructor { enum_name, fields },
+                Self::Constructor {
+                    enum_name: other_enum_name,
+                    fields: other_fields,
+                },
+            ) => enum_name == other_enum_name && fields == other_fields,
+            _ => false,
+        }
+    }
 }
 
 impl NamedInfo {
@@ -62,11 +162,84 @@ impl NamedInfo {
         }
     }
 
-    fn into_func(self) -> Option<(Vec<String>, Statement)> {
-        if let Self::Func { params, body } = self {
-            Some((params, body))
+    fn into_func(self) -> Option<Func> {
+        if let Self::Func { params, body, env } = self {
+            // Whatever found the function did so through the scope it is stored in, so that scope
+            // is still alive
+            let env = Env(env.upgrade()?);
+            Some(Func { params, body, env })
         } else {
             None
         }
     }
+
+    fn into_constructor(self) -> Option<(Symbol, Vec<Symbol>)> {
+        if let Self::Constructor { enum_name, fields } = self {
+            Some((enum_name, fields))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_func_body_with_every_lookup() {
+        let mut env = Env::default();
+        let body = Rc::new(crate::parse("x").unwrap().0);
+        env.store_func(
+            Symbol::intern("id"),
+            Rc::new([Symbol::intern("x")]),
+            Rc::clone(&body),
+        );
+
+        let child_env = env.create_child();
+        let func = child_env.get_func(Symbol::intern("id")).unwrap();
+
+        assert_eq!(&*func.params, [Symbol::intern("x")]);
+        assert!(Rc::ptr_eq(&body, &func.body));
+        assert!(Rc::ptr_eq(&env.0, &func.env.0));
+    }
+
+    #[test]
+    fn redefined_recursive_func_calls_itself() {
+        let mut env = Env::default();
+        for line in ["fn h x => true", "fn h x => match x { 0 => 7, _ => h 0 }"] {
+            crate::parse(line).unwrap().eval(&mut env).unwrap();
+        }
+
+        assert_eq!(
+            crate::parse("h 5").unwrap().eval(&mut env),
+            Ok(Val::Number(7))
+        );
+    }
+
+    #[test]
+    fn child_env_keeps_parent_alive() {
+        let child_env = {
+            let mut env = Env::default();
+            env.store_binding(Symbol::intern("a"), Val::Number(1));
+            env.create_child()
+        };
+
+        assert_eq!(
+            child_env.get_binding(Symbol::intern("a")),
+            Ok(Val::Number(1))
+        );
+    }
+
+    #[test]
+    fn see_bindings_stored_in_parent_after_child_is_created() {
+        let mut env = Env::default();
+        let child_env = env.create_child();
+        env.store_binding(Symbol::intern("a"), Val::Number(1));
+
+        assert_eq!(
+            child_env.get_binding(Symbol::intern("a")),
+            Ok(Val::Number(1))
+        );
+    }
 }
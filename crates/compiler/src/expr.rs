@@ -1,31 +1,52 @@
 mod binding_usage;
 mod block;
 mod func_call;
+mod list_literal;
+mod map_literal;
+mod match_expr;
 
+use std::fmt;
+
+use crate::checker::Checker;
 use crate::env::Env;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
 use crate::utils;
-use crate::val::Val;
+use crate::val::{MapKey, Val};
 
 pub(crate) use binding_usage::BindingUsage;
 pub(crate) use block::Block;
-use func_call::FuncCall;
+pub(crate) use func_call::FuncCall;
+use list_literal::ListLiteral;
+use map_literal::MapLiteral;
+use match_expr::Match;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct Number(pub i32);
 
 impl Number {
     fn new(s: &str) -> Result<(&str, Self), String> {
-        let (s, number) = utils::extract_digits(s)?;
-        Ok((s, Self(number.parse().unwrap())))
+        let (s, number) = utils::extract_number(s)?;
+        Ok((s, Self(number)))
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum Op {
     Add,
     Sub,
     Mul,
     Div,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
 }
 
 impl Op {
@@ -35,30 +56,214 @@ impl Op {
             .or_else(|_| utils::tag("-", s).map(|s| (s, Self::Sub)))
             .or_else(|_| utils::tag("*", s).map(|s| (s, Self::Mul)))
             .or_else(|_| utils::tag("/", s).map(|s| (s, Self::Div)))
+            .or_else(|_| utils::tag("==", s).map(|s| (s, Self::Eq)))
+            .or_else(|_| utils::tag("!=", s).map(|s| (s, Self::NotEq)))
+            .or_else(|_| utils::tag("<=", s).map(|s| (s, Self::LtEq)))
+            .or_else(|_| utils::tag(">=", s).map(|s| (s, Self::GtEq)))
+            .or_else(|_| utils::tag("<", s).map(|s| (s, Self::Lt)))
+            .or_else(|_| utils::tag(">", s).map(|s| (s, Self::Gt)))
+    }
+}
+
+impl Op {
+    pub(crate) fn apply(&self, lhs: Val, rhs: Val) -> Result<Val, String> {
+        // Equality is defined between any two values, whereas the other operations only make
+        // sense for numbers
+        match self {
+            Self::Eq => return Ok(Val::Bool(lhs == rhs)),
+            Self::NotEq => return Ok(Val::Bool(lhs != rhs)),
+            _ => {}
+        }
+
+        let (Val::Number(lhs), Val::Number(rhs)) = (lhs, rhs) else {
+            return Err(
+                "Cannot evaluate operation whose left-hand side and right-hand side are \
+                        not both numbers"
+                    .to_string(),
+            );
+        };
+
+        let result = match self {
+            // Arithmetic wraps around on overflow, as it does in compiled code
+            Self::Add => Val::Number(lhs.wrapping_add(rhs)),
+            Self::Sub => Val::Number(lhs.wrapping_sub(rhs)),
+            Self::Mul => Val::Number(lhs.wrapping_mul(rhs)),
+            Self::Div => match (lhs.checked_div(rhs), rhs) {
+                (Some(quotient), _) => Val::Number(quotient),
+                (None, 0) => return Err("attempt to divide by zero".to_string()),
+                (None, _) => return Err("attempt to divide with overflow".to_string()),
+            },
+            Self::Lt => Val::Bool(lhs < rhs),
+            Self::LtEq => Val::Bool(lhs <= rhs),
+            Self::Gt => Val::Bool(lhs > rhs),
+            Self::GtEq => Val::Bool(lhs >= rhs),
+            Self::Eq | Self::NotEq => unreachable!(),
+        };
+
+        Ok(result)
+    }
+}
+
+pub(crate) fn index_into(target: Val, index: Val) -> Result<Val, String> {
+    match (target, index) {
+        (Val::Map(map), index) => {
+            let key = MapKey::try_from(index)?;
+
+            map.get(&key)
+                .cloned()
+                .ok_or_else(|| format!("map has no key ‘{}’", key))
+        }
+        (Val::List(items), Val::Number(idx)) => usize::try_from(idx)
+            .ok()
+            .and_then(|idx| items.get(idx))
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "index {} is out of bounds for a list of length {}",
+                    idx,
+                    items.len(),
+                )
+            }),
+        (target, _) => Err(format!("cannot index into ‘{}’", target)),
+    }
+}
+
+pub(crate) fn range(start: Val, end: Val, inclusive: bool) -> Result<Val, String> {
+    match (start, end) {
+        (Val::Number(start), Val::Number(end)) => Ok(Val::Range {
+            start,
+            end,
+            inclusive,
+        }),
+        _ => Err("Cannot evaluate range whose start and end are not both numbers".to_string()),
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+        };
+
+        write!(f, "{}", symbol)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum Expr {
     Number(Number),
+    Bool(bool),
+    Str(String),
     Operation {
         lhs: Box<Self>,
         rhs: Box<Self>,
         op: Op,
+        span: Span,
     },
     BindingUsage(BindingUsage),
     FuncCall(FuncCall),
     Block(Block),
+    Match(Match),
+    ListLiteral(ListLiteral),
+    MapLiteral(MapLiteral),
+    Index {
+        target: Box<Self>,
+        index: Box<Self>,
+        span: Span,
+    },
+    Range {
+        start: Box<Self>,
+        end: Box<Self>,
+        inclusive: bool,
+        span: Span,
+    },
 }
 
 impl Expr {
     pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        Self::new_operation(s).or_else(|_| Self::new_non_operation(s))
+        Self::new_with_block_args(s, true)
+    }
+
+    // Parses an expression that is directly followed by a block, such as the iterable of a `for`
+    // loop, where that block would otherwise be taken as the last argument of a function call
+    pub(crate) fn new_before_block(s: &str) -> Result<(&str, Self), String> {
+        Self::new_with_block_args(s, false)
+    }
+
+    fn new_with_block_args(s: &str, block_args: bool) -> Result<(&str, Self), String> {
+        let before = s;
+
+        // Operations and ranges start the same way as any other expression, so the left-hand side
+        // is only parsed once rather than once per alternative
+        let (s, lhs) = Self::new_non_operation(s, block_args)?;
+
+        if let Ok((s, (op, rhs))) = Self::new_operation_rhs(s, block_args) {
+            return Ok((
+                s,
+                Self::Operation {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                    op,
+                    span: Span::new(before, s),
+                },
+            ));
+        }
+
+        if let Ok((s, (inclusive, end))) = Self::new_range_end(s, block_args) {
+            return Ok((
+                s,
+                Self::Range {
+                    start: Box::new(lhs),
+                    end: Box::new(end),
+                    inclusive,
+                    span: Span::new(before, s),
+                },
+            ));
+        }
+
+        Ok((s, lhs))
     }
 
-    fn new_non_operation(s: &str) -> Result<(&str, Self), String> {
+    fn new_non_operation(s: &str, block_args: bool) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let (s, expr) = Match::new(s)
+            .map(|(s, match_expr)| (s, Self::Match(match_expr)))
+            .or_else(|_| {
+                FuncCall::new_with_block_args(s, block_args)
+                    .map(|(s, func_call)| (s, Self::FuncCall(func_call)))
+            })
+            .or_else(|_| Self::new_atom(s))?;
+
+        Self::new_index(before, s, expr)
+    }
+
+    // Function call arguments are restricted to atoms, so that `add x y` passes both `x` and `y`
+    // to `add` rather than calling `x` with `y`
+    pub(super) fn new_argument(s: &str, block_args: bool) -> Result<(&str, Self), String> {
+        if !block_args && s.starts_with('{') {
+            return Err("Block arguments are not allowed here".to_string());
+        }
+
+        let (after, expr) = Self::new_atom(s)?;
+        Self::new_index(s, after, expr)
+    }
+
+    fn new_atom(s: &str) -> Result<(&str, Self), String> {
         Self::new_number(s)
-            .or_else(|_| FuncCall::new(s).map(|(s, func_call)| (s, Self::FuncCall(func_call))))
+            .or_else(|_| utils::extract_bool(s).map(|(s, b)| (s, Self::Bool(b))))
+            .or_else(|_| utils::extract_string(s).map(|(s, string)| (s, Self::Str(string))))
+            .or_else(|_| ListLiteral::new(s).map(|(s, list)| (s, Self::ListLiteral(list))))
+            .or_else(|_| MapLiteral::new(s).map(|(s, map)| (s, Self::MapLiteral(map))))
             .or_else(|_| {
                 BindingUsage::new(s)
                     .map(|(s, binding_usage)| (s, Self::BindingUsage(binding_usage)))
@@ -66,59 +271,260 @@ impl Expr {
             .or_else(|_| Block::new(s).map(|(s, block)| (s, Self::Block(block))))
     }
 
-    fn new_operation(s: &str) -> Result<(&str, Self), String> {
-        let (s, lhs) = Self::new_non_operation(s)?;
+    // Indexing binds tighter than any operation, and can be chained (`a["b"]["c"]`)
+    fn new_index<'a>(
+        before: &str,
+        mut s: &'a str,
+        mut expr: Self,
+    ) -> Result<(&'a str, Self), String> {
+        while let Ok(new_s) = utils::tag("[", s) {
+            let (new_s, _) = utils::extract_whitespace(new_s);
+            let (new_s, index) = Self::new(new_s)?;
+            let (new_s, _) = utils::extract_whitespace(new_s);
+
+            s = utils::tag("]", new_s)?;
+            expr = Self::Index {
+                target: Box::new(expr),
+                index: Box::new(index),
+                span: Span::new(before, s),
+            };
+        }
+
+        Ok((s, expr))
+    }
+
+    fn new_operation_rhs(s: &str, block_args: bool) -> Result<(&str, (Op, Self)), String> {
         let (s, _) = utils::extract_whitespace(s);
 
         let (s, op) = Op::new(s)?;
         let (s, _) = utils::extract_whitespace(s);
 
-        let (s, rhs) = Self::new_non_operation(s)?;
+        let (s, rhs) = Self::new_non_operation(s, block_args)?;
 
-        Ok((
-            s,
-            Self::Operation {
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-                op,
-            },
-        ))
+        Ok((s, (op, rhs)))
+    }
+
+    fn new_range_end(s: &str, block_args: bool) -> Result<(&str, (bool, Self)), String> {
+        let (s, _) = utils::extract_whitespace(s);
+
+        let s = utils::tag("..", s)?;
+        let (s, inclusive) = match utils::tag("=", s) {
+            Ok(s) => (s, true),
+            Err(_) => (s, false),
+        };
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, end) = Self::new_non_operation(s, block_args)?;
+
+        Ok((s, (inclusive, end)))
     }
 
     fn new_number(s: &str) -> Result<(&str, Self), String> {
         Number::new(s).map(|(s, number)| (s, Self::Number(number)))
     }
 
-    pub(crate) fn eval(&self, env: &Env) -> Result<Val, String> {
+    pub(crate) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
         match self {
             Expr::Number(Number(n)) => Ok(Val::Number(*n)),
-            Expr::Operation { lhs, rhs, op } => {
+            Expr::Bool(b) => Ok(Val::Bool(*b)),
+            Expr::Str(s) => Ok(Val::Str(s.clone())),
+            Expr::Operation { lhs, rhs, op, .. } => {
                 let lhs = lhs.eval(env)?;
                 let rhs = rhs.eval(env)?;
 
-                let (lhs, rhs) = match (lhs,rhs) {
-                    (Val::Number(lhs), Val::Number(rhs)) => (lhs, rhs),
-                    _ => return Err("Cannot evaluate operation whose left-hand side and right-hand side are not both numbers".to_string()),
-                };
-
-                let result = match op {
-                    Op::Add => lhs + rhs,
-                    Op::Sub => lhs - rhs,
-                    Op::Mul => lhs * rhs,
-                    Op::Div => lhs / rhs,
-                };
-
-                Ok(Val::Number(result))
+                Ok(op.apply(lhs, rhs)?)
             }
             Self::FuncCall(func_call) => func_call.eval(env),
             Self::BindingUsage(binding_usage) => binding_usage.eval(env),
             Self::Block(block) => block.eval(env),
+            Self::Match(match_expr) => match_expr.eval(env),
+            Self::ListLiteral(list) => list.eval(env),
+            Self::MapLiteral(map) => map.eval(env),
+            Self::Index { target, index, .. } => {
+                let target = target.eval(env)?;
+                let index = index.eval(env)?;
+
+                Ok(index_into(target, index)?)
+            }
+            Self::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => Ok(range(start.eval(env)?, end.eval(env)?, *inclusive)?),
+        }
+    }
+
+    /// Returns the span the expression was parsed from, which literals do not keep track of.
+    pub(crate) fn span(&self) -> Option<Span> {
+        match self {
+            Self::Number(_) | Self::Bool(_) | Self::Str(_) => None,
+            Self::Operation { span, .. } | Self::Index { span, .. } | Self::Range { span, .. } => {
+                Some(*span)
+            }
+            Self::BindingUsage(binding_usage) => Some(binding_usage.span),
+            Self::FuncCall(func_call) => Some(func_call.span),
+            Self::Block(block) => Some(block.span),
+            Self::Match(match_expr) => Some(match_expr.span),
+            Self::ListLiteral(list) => Some(list.span),
+            Self::MapLiteral(map) => Some(map.span),
+        }
+    }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        match self {
+            Self::Number(_) | Self::Bool(_) | Self::Str(_) => {}
+            Self::Operation { lhs, rhs, .. } => {
+                lhs.resolve(resolver);
+                rhs.resolve(resolver);
+            }
+            Self::FuncCall(func_call) => func_call.resolve(resolver),
+            Self::BindingUsage(binding_usage) => binding_usage.resolve(resolver),
+            Self::Block(block) => block.resolve(resolver),
+            Self::Match(match_expr) => match_expr.resolve(resolver),
+            Self::ListLiteral(list) => list.resolve(resolver),
+            Self::MapLiteral(map) => map.resolve(resolver),
+            Self::Index { target, index, .. } => {
+                target.resolve(resolver);
+                index.resolve(resolver);
+            }
+            Self::Range { start, end, .. } => {
+                start.resolve(resolver);
+                end.resolve(resolver);
+            }
+        }
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let node = match self {
+            Self::Number(Number(n)) => Node::Const(Val::Number(*n)),
+            Self::Bool(b) => Node::Const(Val::Bool(*b)),
+            Self::Str(s) => Node::Const(Val::Str(s.clone())),
+            Self::Operation { lhs, rhs, op, .. } => Node::Operation {
+                lhs: Box::new(lhs.lower(lowerer)?),
+                rhs: Box::new(rhs.lower(lowerer)?),
+                op: *op,
+            },
+            Self::FuncCall(func_call) => func_call.lower(lowerer)?,
+            Self::BindingUsage(binding_usage) => lowerer.lower_usage(binding_usage.name)?,
+            Self::Block(block) => block.lower(lowerer)?,
+            Self::Match(match_expr) => match_expr.lower(lowerer)?,
+            Self::ListLiteral(list) => list.lower(lowerer)?,
+            Self::MapLiteral(map) => map.lower(lowerer)?,
+            Self::Index { target, index, .. } => Node::Index {
+                target: Box::new(target.lower(lowerer)?),
+                index: Box::new(index.lower(lowerer)?),
+            },
+            Self::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => Node::Range {
+                start: Box::new(start.lower(lowerer)?),
+                end: Box::new(end.lower(lowerer)?),
+                inclusive: *inclusive,
+            },
+        };
+
+        Ok(node)
+    }
+
+    pub(crate) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        match self {
+            Self::Number(_) => Type::Int,
+            Self::Bool(_) => Type::Bool,
+            Self::Str(_) => Type::Str,
+            Self::Operation { lhs, rhs, op, span } => {
+                let lhs = lhs.check(env, checker);
+                let rhs = rhs.check(env, checker);
+
+                if let Op::Eq | Op::NotEq = op {
+                    if let Err(conflict) = checker.unify(&lhs, &rhs, *span) {
+                        let [lhs, rhs] = checker.display([&lhs, &rhs]);
+                        checker.conflict(
+                            format!("cannot compare ‘{}’ with ‘{}’ using ‘{}’", lhs, rhs, op),
+                            *span,
+                            conflict,
+                        );
+                    }
+
+                    return Type::Bool;
+                }
+
+                let lhs_result = checker.unify(&Type::Int, &lhs, *span);
+                let rhs_result = checker.unify(&Type::Int, &rhs, *span);
+
+                if let Err(conflict) = lhs_result.and(rhs_result) {
+                    let [lhs, rhs] = checker.display([&lhs, &rhs]);
+                    checker.conflict(
+                        format!(
+                            "‘{}’ requires both sides to be ‘Int’, got ‘{}’ and ‘{}’",
+                            op, lhs, rhs,
+                        ),
+                        *span,
+                        conflict,
+                    );
+                }
+
+                match op {
+                    Op::Add | Op::Sub | Op::Mul | Op::Div => Type::Int,
+                    _ => Type::Bool,
+                }
+            }
+            Self::FuncCall(func_call) => func_call.check(env, checker),
+            Self::BindingUsage(binding_usage) => binding_usage.check(env, checker),
+            Self::Block(block) => block.check(env, checker),
+            Self::Match(match_expr) => match_expr.check(env, checker),
+            Self::ListLiteral(list) => list.check(env, checker),
+            Self::MapLiteral(map) => map.check(env, checker),
+            Self::Index {
+                target,
+                index,
+                span,
+            } => {
+                let target = target.check(env, checker);
+                let index = index.check(env, checker);
+
+                // Lists and maps can both be indexed, so nothing can be inferred about a target
+                // whose type is not yet known
+                match checker.resolve(&target) {
+                    Type::List(item) => {
+                        checker.expect(&Type::Int, &index, *span, "list index");
+                        *item
+                    }
+                    Type::Map(key, val) => {
+                        checker.expect(&key, &index, *span, "map key");
+                        *val
+                    }
+                    Type::Var(_) | Type::Unknown | Type::Never => Type::Unknown,
+                    target => {
+                        let [target] = checker.display([&target]);
+                        checker.error(format!("cannot index into ‘{}’", target), *span);
+                        Type::Unknown
+                    }
+                }
+            }
+            Self::Range {
+                start, end, span, ..
+            } => {
+                let start = start.check(env, checker);
+                let end = end.check(env, checker);
+
+                checker.expect(&Type::Int, &start, *span, "start of range");
+                checker.expect(&Type::Int, &end, *span, "end of range");
+
+                Type::Range
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::symbol::Symbol;
+    use std::rc::Rc;
+
     use super::*;
     use crate::statement::Statement;
 
@@ -147,6 +553,16 @@ mod tests {
         assert_eq!(Op::new("/"), Ok(("", Op::Div)));
     }
 
+    #[test]
+    fn parse_eq_op() {
+        assert_eq!(Op::new("=="), Ok(("", Op::Eq)));
+    }
+
+    #[test]
+    fn parse_lt_eq_op() {
+        assert_eq!(Op::new("<= 1"), Ok((" 1", Op::LtEq)));
+    }
+
     #[test]
     fn parse_one_plus_two() {
         assert_eq!(
@@ -157,6 +573,7 @@ mod tests {
                     lhs: Box::new(Expr::Number(Number(1))),
                     rhs: Box::new(Expr::Number(Number(2))),
                     op: Op::Add,
+                    span: Span::new("1+2", ""),
                 },
             )),
         );
@@ -172,6 +589,7 @@ mod tests {
                     lhs: Box::new(Expr::Number(Number(2))),
                     rhs: Box::new(Expr::Number(Number(2))),
                     op: Op::Mul,
+                    span: Span::new("2 * 2", ""),
                 },
             )),
         );
@@ -184,6 +602,7 @@ mod tests {
                 lhs: Box::new(Expr::Number(Number(10))),
                 rhs: Box::new(Expr::Number(Number(10))),
                 op: Op::Add,
+                span: Span::default(),
             }
             .eval(&Env::default()),
             Ok(Val::Number(20)),
@@ -197,6 +616,7 @@ mod tests {
                 lhs: Box::new(Expr::Number(Number(1))),
                 rhs: Box::new(Expr::Number(Number(5))),
                 op: Op::Sub,
+                span: Span::default(),
             }
             .eval(&Env::default()),
             Ok(Val::Number(-4)),
@@ -210,6 +630,7 @@ mod tests {
                 lhs: Box::new(Expr::Number(Number(5))),
                 rhs: Box::new(Expr::Number(Number(6))),
                 op: Op::Mul,
+                span: Span::default(),
             }
             .eval(&Env::default()),
             Ok(Val::Number(30)),
@@ -223,12 +644,176 @@ mod tests {
                 lhs: Box::new(Expr::Number(Number(200))),
                 rhs: Box::new(Expr::Number(Number(20))),
                 op: Op::Div,
+                span: Span::default(),
             }
             .eval(&Env::default()),
             Ok(Val::Number(10)),
         );
     }
 
+    #[test]
+    fn eval_div_by_zero() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Number(Number(200))),
+                rhs: Box::new(Expr::Number(Number(0))),
+                op: Op::Div,
+                span: Span::default(),
+            }
+            .eval(&Env::default()),
+            Err(Interrupt::Error("attempt to divide by zero".to_string())),
+        );
+    }
+
+    #[test]
+    fn eval_lt() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Number(Number(1))),
+                rhs: Box::new(Expr::Number(Number(2))),
+                op: Op::Lt,
+                span: Span::default(),
+            }
+            .eval(&Env::default()),
+            Ok(Val::Bool(true)),
+        );
+    }
+
+    #[test]
+    fn eval_eq_of_non_numbers() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Bool(true)),
+                rhs: Box::new(Expr::Block(Block {
+                    stmts: Vec::new(),
+                    span: Span::default()
+                })),
+                op: Op::Eq,
+                span: Span::default(),
+            }
+            .eval(&Env::default()),
+            Ok(Val::Bool(false)),
+        );
+    }
+
+    #[test]
+    fn parse_bool_as_expr() {
+        assert_eq!(Expr::new("true"), Ok(("", Expr::Bool(true))));
+    }
+
+    #[test]
+    fn parse_str_as_expr() {
+        assert_eq!(
+            Expr::new(r#""hello""#),
+            Ok(("", Expr::Str("hello".to_string()))),
+        );
+    }
+
+    #[test]
+    fn parse_chained_index() {
+        assert_eq!(
+            Expr::new(r#"m["a"][1]"#),
+            Ok((
+                "",
+                Expr::Index {
+                    target: Box::new(Expr::Index {
+                        target: Box::new(Expr::BindingUsage(BindingUsage {
+                            name: Symbol::intern("m"),
+                            kind: None,
+                            span: Span::new(r#"m["a"][1]"#, r#"["a"][1]"#),
+                        })),
+                        index: Box::new(Expr::Str("a".to_string())),
+                        span: Span::new(r#"m["a"][1]"#, "[1]"),
+                    }),
+                    index: Box::new(Expr::Number(Number(1))),
+                    span: Span::new(r#"m["a"][1]"#, ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_map_index() {
+        let (_, expr) = Expr::new(r#"#{ "a": 1, "b": 2 }["b"]"#).unwrap();
+        assert_eq!(expr.eval(&Env::default()), Ok(Val::Number(2)));
+    }
+
+    #[test]
+    fn eval_list_index() {
+        let (_, expr) = Expr::new("[1, 2, 3][2]").unwrap();
+        assert_eq!(expr.eval(&Env::default()), Ok(Val::Number(3)));
+    }
+
+    #[test]
+    fn eval_list_index_out_of_bounds() {
+        let (_, expr) = Expr::new("[1][1]").unwrap();
+
+        assert_eq!(
+            expr.eval(&Env::default()),
+            Err(Interrupt::Error(
+                "index 1 is out of bounds for a list of length 1".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_inclusive_range() {
+        assert_eq!(
+            Expr::new("1..=n"),
+            Ok((
+                "",
+                Expr::Range {
+                    start: Box::new(Expr::Number(Number(1))),
+                    end: Box::new(Expr::BindingUsage(BindingUsage {
+                        name: Symbol::intern("n"),
+                        kind: None,
+                        span: Span::new("n", "")
+                    })),
+                    inclusive: true,
+                    span: Span::new("1..=n", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_range() {
+        assert_eq!(
+            Expr::Range {
+                start: Box::new(Expr::Number(Number(0))),
+                end: Box::new(Expr::Number(Number(10))),
+                inclusive: false,
+                span: Span::default(),
+            }
+            .eval(&Env::default()),
+            Ok(Val::Range {
+                start: 0,
+                end: 10,
+                inclusive: false,
+            }),
+        );
+    }
+
+    #[test]
+    fn eval_map_index_with_missing_key() {
+        let (_, expr) = Expr::new(r#"#{ "a": 1 }["b"]"#).unwrap();
+
+        assert_eq!(
+            expr.eval(&Env::default()),
+            Err(Interrupt::Error(r#"map has no key ‘"b"’"#.to_string())),
+        );
+    }
+
+    #[test]
+    fn eval_index_into_number() {
+        let (_, expr) = Expr::new("1[0]").unwrap();
+
+        assert_eq!(
+            expr.eval(&Env::default()),
+            Err(Interrupt::Error("cannot index into ‘1’".to_string())),
+        );
+    }
+
     #[test]
     fn parse_number_as_expr() {
         assert_eq!(Expr::new("456"), Ok(("", Expr::Number(Number(456)))));
@@ -242,6 +827,7 @@ mod tests {
                 "",
                 Expr::Block(Block {
                     stmts: vec![Statement::Expr(Expr::Number(Number(200)))],
+                    span: Span::new("{ 200 }", "")
                 }),
             )),
         );
@@ -252,6 +838,7 @@ mod tests {
         assert_eq!(
             Expr::Block(Block {
                 stmts: vec![Statement::Expr(Expr::Number(Number(10)))],
+                span: Span::default()
             })
             .eval(&Env::default()),
             Ok(Val::Number(10)),
@@ -263,11 +850,10 @@ mod tests {
         assert_eq!(
             Expr::Operation {
                 lhs: Box::new(Expr::Number(Number(10))),
-                rhs: Box::new(Expr::Block(Block { stmts: Vec::new() })),
-                op: Op::Add,
-            }
+                rhs: Box::new(Expr::Block(Block { stmts: Vec::new(), span: Span::default()  })),
+                op: Op::Add, span: Span::default(), }
             .eval(&Env::default()),
-            Err("Cannot evaluate operation whose left-hand side and right-hand side are not both numbers".to_string()),
+            Err(Interrupt::Error("Cannot evaluate operation whose left-hand side and right-hand side are not both numbers".to_string())),
         );
     }
 
@@ -278,8 +864,10 @@ mod tests {
             Ok((
                 "",
                 Expr::FuncCall(FuncCall {
-                    callee: "add".to_string(),
+                    callee: Symbol::intern("add"),
                     params: vec![Expr::Number(Number(1)), Expr::Number(Number(2))],
+                    kind: None,
+                    span: Span::new("add 1 2", ""),
                 }),
             )),
         );
@@ -290,23 +878,30 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "add".to_string(),
-            vec!["x".to_string(), "y".to_string()],
-            Statement::Expr(Expr::Operation {
+            Symbol::intern("add"),
+            vec![Symbol::intern("x"), Symbol::intern("y")].into(),
+            Rc::new(Statement::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "x".to_string(),
+                    name: Symbol::intern("x"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "y".to_string(),
+                    name: Symbol::intern("y"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 op: Op::Add,
-            }),
+                span: Span::default(),
+            })),
         );
 
         assert_eq!(
             Expr::FuncCall(FuncCall {
-                callee: "add".to_string(),
+                callee: Symbol::intern("add"),
                 params: vec![Expr::Number(Number(2)), Expr::Number(Number(2))],
+                kind: None,
+                span: Span::default(),
             })
             .eval(&env),
             Ok(Val::Number(4)),
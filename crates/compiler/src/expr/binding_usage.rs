@@ -1,4 +1,11 @@
+use crate::checker::Checker;
 use crate::env::Env;
+use crate::interrupt::Interrupt;
+use crate::resolver::{NameKind, Resolver};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::{TypeEnv, TypeInfo};
+use crate::types::Type;
 use crate::utils;
 use crate::val::Val;
 
@@ -6,33 +13,74 @@ use super::func_call::FuncCall;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct BindingUsage {
-    pub(crate) name: String,
+    pub(crate) name: Symbol,
+    // Filled in once the program has been resolved
+    pub(crate) kind: Option<NameKind>,
+    pub(crate) span: Span,
 }
 
 impl BindingUsage {
     pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
         let (s, name) = utils::extract_ident(s)?;
 
         Ok((
             s,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
+                kind: None,
+                span: Span::new(before, s),
             },
         ))
     }
 
-    pub(super) fn eval(&self, env: &Env) -> Result<Val, String> {
-        env.get_binding(&self.name).or_else(|error_msg| {
-            if env.get_func(&self.name).is_ok() {
-                FuncCall {
-                    callee: self.name.clone(),
-                    params: Vec::new(),
+    pub(super) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        match self.kind {
+            // Using the name of a function or constructor on its own calls it without arguments
+            Some(NameKind::Func | NameKind::Constructor | NameKind::Builtin) => {
+                self.call(self.kind).eval(env)
+            }
+            Some(NameKind::Binding) => Ok(env.get_binding(self.name)?),
+            // Without having been resolved, the name is taken to refer to a binding unless there
+            // is only a function or constructor with it
+            None => match env.get_binding(self.name) {
+                Ok(val) => Ok(val),
+                Err(_)
+                    if env.get_func(self.name).is_ok()
+                        || env.get_constructor(self.name).is_ok() =>
+                {
+                    self.call(None).eval(env)
                 }
-                .eval(env)
-            } else {
-                Err(error_msg)
+                Err(error_msg) => Err(Interrupt::Error(error_msg)),
+            },
+        }
+    }
+
+    fn call(&self, kind: Option<NameKind>) -> FuncCall {
+        FuncCall {
+            callee: self.name,
+            params: Vec::new(),
+            kind,
+            span: self.span,
+        }
+    }
+
+    pub(super) fn resolve(&mut self, resolver: &mut Resolver) {
+        self.kind = resolver.resolve_usage(self.name, self.span);
+    }
+
+    pub(super) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        // Using the name of a function or constructor on its own calls it without arguments.
+        // Undefined names have already been reported by the resolver.
+        match env.get(self.name) {
+            Some(TypeInfo::Binding(ty)) => checker.instantiate(ty),
+            Some(TypeInfo::Func { params, ret }) if params.is_empty() => checker.instantiate(ret),
+            Some(TypeInfo::Constructor { fields, ret }) if fields.is_empty() => {
+                checker.instantiate(ret)
             }
-        })
+            _ => Type::Unknown,
+        }
     }
 }
 
@@ -53,8 +101,10 @@ mod tests {
             Ok((
                 "",
                 Statement::BindingDef(BindingDef {
-                    name: "a".to_string(),
+                    name: Symbol::intern("a"),
+                    ty: None,
                     val: Expr::Number(Number(10)),
+                    span: Span::new("let a = 10", ""),
                 }),
             )),
         );
@@ -63,11 +113,13 @@ mod tests {
     #[test]
     fn eval_existing_binding_usage() {
         let mut env = Env::default();
-        env.store_binding("foo".to_string(), Val::Number(10));
+        env.store_binding(Symbol::intern("foo"), Val::Number(10));
 
         assert_eq!(
             BindingUsage {
-                name: "foo".to_string(),
+                name: Symbol::intern("foo"),
+                kind: None,
+                span: Span::default()
             }
             .eval(&env),
             Ok(Val::Number(10)),
@@ -80,24 +132,63 @@ mod tests {
 
         assert_eq!(
             BindingUsage {
-                name: "i_dont_exist".to_string(),
+                name: Symbol::intern("i_dont_exist"),
+                kind: None,
+                span: Span::default()
             }
             .eval(&empty_env),
-            Err("binding with name ‘i_dont_exist’ does not exist".to_string()),
+            Err(Interrupt::Error(
+                "binding with name ‘i_dont_exist’ does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_binding_usage_of_constructor_without_payload() {
+        let mut env = Env::default();
+        env.store_constructor(Symbol::intern("None"), Symbol::intern("Option"), Vec::new());
+
+        assert_eq!(
+            BindingUsage {
+                name: Symbol::intern("None"),
+                kind: Some(NameKind::Constructor),
+                span: Span::default()
+            }
+            .eval(&env),
+            Ok(Val::Variant {
+                enum_name: Symbol::intern("Option"),
+                variant: Symbol::intern("None"),
+                fields: Vec::new(),
+            }),
         );
     }
 
     #[test]
     fn eval_binding_usage() {
         let mut env = Env::default();
-        env.store_binding("ten".to_string(), Val::Number(10));
+        env.store_binding(Symbol::intern("ten"), Val::Number(10));
 
         assert_eq!(
             Expr::BindingUsage(BindingUsage {
-                name: "ten".to_string(),
+                name: Symbol::intern("ten"),
+                kind: None,
+                span: Span::default()
             })
             .eval(&env),
             Ok(Val::Number(10)),
         );
     }
+
+    #[test]
+    fn eval_unresolved_usage_of_func_without_params() {
+        let parse = crate::parse(
+            "{
+    fn one => 1
+    one
+}",
+        )
+        .unwrap();
+
+        assert_eq!(parse.eval(&mut Env::default()), Ok(Val::Number(1)));
+    }
 }
@@ -1,57 +1,221 @@
+use crate::checker::Checker;
+use crate::diagnostic::Diagnostic;
 use crate::env::Env;
+use crate::expr::Expr;
+use crate::func_def::FuncDef;
+use crate::interrupt::Interrupt;
+use crate::lint::Lint;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
 use crate::statement::Statement;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
 use crate::utils;
 use crate::val::Val;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct Block {
     pub(crate) stmts: Vec<Statement>,
+    pub(crate) span: Span,
 }
 
 impl Block {
-    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("{", s)?;
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("{", before)?;
         let (s, _) = utils::extract_whitespace(s);
 
-        let (s, stmts) = utils::sequence(Statement::new, utils::extract_whitespace, s)?;
+        let (s, stmts) = utils::sequence(Statement::new, |s| Ok(utils::extract_whitespace(s)), s)?;
 
         let (s, _) = utils::extract_whitespace(s);
         let s = utils::tag("}", s)?;
 
-        Ok((s, Block { stmts }))
+        Ok((
+            s,
+            Block {
+                stmts,
+                span: Span::new(before, s),
+            },
+        ))
     }
 
-    pub(crate) fn eval(&self, env: &Env) -> Result<Val, String> {
-        if self.stmts.is_empty() {
-            return Ok(Val::Unit);
+    pub(crate) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        let mut child_env = env.create_child();
+        let mut result = Val::Unit;
+        let mut declared = 0;
+
+        for (idx, stmt) in self.stmts.iter().enumerate() {
+            if idx >= declared {
+                let run = func_run(&self.stmts[idx..]);
+                declared = idx + run.len();
+
+                // Functions that call each other have to be stored in the same scope, which
+                // redefining a name would otherwise move the ones after it out of
+                if run.iter().any(|func_def| child_env.defines(func_def.name)) {
+                    child_env = child_env.create_child();
+                }
+            }
+
+            result = stmt.eval(&mut child_env)?;
         }
 
-        let mut child_env = env.create_child();
+        Ok(result)
+    }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        resolver.in_scope(|resolver| {
+            let mut declared = 0;
+
+            for idx in 0..self.stmts.len() {
+                if idx >= declared {
+                    let run = func_run(&self.stmts[idx..]);
+                    declared = idx + run.len();
+                    run.iter().for_each(|func_def| func_def.declare(resolver));
+                }
+
+                self.stmts[idx].resolve(resolver);
+            }
+        });
+
+        self.lint_discarded_stmts(resolver);
+    }
+
+    // Reports statements whose results are thrown away, and statements that can never be run
+    fn lint_discarded_stmts(&self, resolver: &mut Resolver) {
+        let Some((_, stmts_except_last)) = self.stmts.split_last() else {
+            return;
+        };
 
-        let stmts_except_last = &self.stmts[..self.stmts.len() - 1];
         for stmt in stmts_except_last {
-            stmt.eval(&mut child_env)?;
+            let (keyword, span) = match stmt {
+                Statement::Break(span) => ("break", span),
+                Statement::Continue(span) => ("continue", span),
+                Statement::Return(_, span) => ("return", span),
+                // Blocks and matches may contain `return`, `break` or `continue`, which are run
+                // for their effect rather than their result
+                Statement::Expr(Expr::Block(_) | Expr::Match(_)) => continue,
+                Statement::Expr(expr) => {
+                    resolver.lint(
+                        Lint::UnusedResult,
+                        Diagnostic::warning(
+                            "the result of this expression is never used".to_string(),
+                            expr.span().unwrap_or(self.span),
+                        ),
+                    );
+                    continue;
+                }
+                _ => continue,
+            };
+
+            resolver.lint(
+                Lint::UnreachableCode,
+                Diagnostic::warning(format!("code after this ‘{}’ is never run", keyword), *span),
+            );
+
+            // Everything after this is unreachable, so there is nothing more to report
+            return;
         }
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        lowerer.in_scope(|lowerer| {
+            let mut stmts = Vec::new();
+            let mut declared = 0;
 
-        // We can unwrap safely here because we have already checked whether self.stmts is empty.
-        self.stmts.last().unwrap().eval(&mut child_env)
+            for (idx, stmt) in self.stmts.iter().enumerate() {
+                if idx >= declared {
+                    let run = func_run(&self.stmts[idx..]);
+                    declared = idx + run.len();
+                    run.iter()
+                        .for_each(|func_def| func_def.declare_lowered(lowerer));
+                }
+
+                stmts.push(stmt.lower(lowerer)?);
+            }
+
+            Ok(Node::Block(stmts))
+        })
+    }
+
+    pub(crate) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        let mut child_env = env.create_child();
+        let mut result = Type::Unit;
+        let mut checked = 0;
+
+        for (idx, stmt) in self.stmts.iter().enumerate() {
+            if idx >= checked {
+                let run = func_run(&self.stmts[idx..]);
+                if !run.is_empty() {
+                    checked = idx + run.len();
+                    FuncDef::check_group(&run, &mut child_env, checker);
+                }
+            }
+
+            // Function definitions have already been checked along with the rest of their run
+            result = match stmt {
+                Statement::FuncDef(_) if idx < checked => Type::Unit,
+                stmt => stmt.check(&mut child_env, checker),
+            };
+        }
+
+        result
     }
 }
 
+// The function definitions at the start of `stmts`, which can call each other because nothing
+// else is defined in between. A second definition of the same name ends the run, since it
+// shadows the first.
+fn func_run(stmts: &[Statement]) -> Vec<&FuncDef> {
+    let mut run: Vec<&FuncDef> = Vec::new();
+
+    for stmt in stmts {
+        match stmt {
+            Statement::FuncDef(func_def) if run.iter().all(|other| other.name != func_def.name) => {
+                run.push(func_def)
+            }
+            _ => break,
+        }
+    }
+
+    run
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::{BindingUsage, Expr, Number, Op};
     use super::*;
     use crate::binding_def::BindingDef;
+    use crate::span::Span;
+    use crate::symbol::Symbol;
 
     #[test]
     fn parse_empty_block() {
-        assert_eq!(Block::new("{}"), Ok(("", Block { stmts: Vec::new() })));
+        assert_eq!(
+            Block::new("{}"),
+            Ok((
+                "",
+                Block {
+                    stmts: Vec::new(),
+                    span: Span::new("{}", "")
+                }
+            ))
+        );
     }
 
     #[test]
     fn parse_empty_block_with_whitespace() {
-        assert_eq!(Block::new("{   }"), Ok(("", Block { stmts: Vec::new() })));
+        assert_eq!(
+            Block::new("{   }"),
+            Ok((
+                "",
+                Block {
+                    stmts: Vec::new(),
+                    span: Span::new("{   }", "")
+                }
+            ))
+        );
     }
 
     #[test]
@@ -62,6 +226,7 @@ mod tests {
                 "",
                 Block {
                     stmts: vec![Statement::Expr(Expr::Number(Number(5)))],
+                    span: Span::new("{ 5 }", "")
                 },
             )),
         );
@@ -82,19 +247,31 @@ mod tests {
                 Block {
                     stmts: vec![
                         Statement::BindingDef(BindingDef {
-                            name: "a".to_string(),
+                            name: Symbol::intern("a"),
+                            ty: None,
                             val: Expr::Number(Number(10)),
+                            span: Span::new(
+                                "let a = 10\n    let b = a\n    b\n}",
+                                "\n    let b = a\n    b\n}"
+                            ),
                         }),
                         Statement::BindingDef(BindingDef {
-                            name: "b".to_string(),
+                            name: Symbol::intern("b"),
+                            ty: None,
                             val: Expr::BindingUsage(BindingUsage {
-                                name: "a".to_string(),
+                                name: Symbol::intern("a"),
+                                kind: None,
+                                span: Span::new("a\n    b\n}", "\n    b\n}")
                             }),
+                            span: Span::new("let b = a\n    b\n}", "\n    b\n}"),
                         }),
                         Statement::Expr(Expr::BindingUsage(BindingUsage {
-                            name: "b".to_string(),
+                            name: Symbol::intern("b"),
+                            kind: None,
+                            span: Span::new("b\n}", "\n}")
                         })),
                     ],
+                    span: Span::new("{\n    let a = 10\n    let b = a\n    b\n}", "")
                 },
             )),
         );
@@ -103,7 +280,11 @@ mod tests {
     #[test]
     fn eval_empty_block() {
         assert_eq!(
-            Block { stmts: Vec::new() }.eval(&Env::default()),
+            Block {
+                stmts: Vec::new(),
+                span: Span::default()
+            }
+            .eval(&Env::default()),
             Ok(Val::Unit),
         );
     }
@@ -113,6 +294,7 @@ mod tests {
         assert_eq!(
             Block {
                 stmts: vec![Statement::Expr(Expr::Number(Number(25)))],
+                span: Span::default()
             }
             .eval(&Env::default()),
             Ok(Val::Number(25)),
@@ -125,13 +307,18 @@ mod tests {
             Block {
                 stmts: vec![
                     Statement::BindingDef(BindingDef {
-                        name: "one".to_string(),
+                        name: Symbol::intern("one"),
+                        ty: None,
                         val: Expr::Number(Number(1)),
+                        span: Span::default(),
                     }),
                     Statement::Expr(Expr::BindingUsage(BindingUsage {
-                        name: "one".to_string(),
+                        name: Symbol::intern("one"),
+                        kind: None,
+                        span: Span::default()
                     })),
                 ],
+                span: Span::default()
             }
             .eval(&Env::default()),
             Ok(Val::Number(1)),
@@ -144,18 +331,25 @@ mod tests {
             Block {
                 stmts: vec![
                     Statement::BindingDef(BindingDef {
-                        name: "foo".to_string(),
+                        name: Symbol::intern("foo"),
+                        ty: None,
                         val: Expr::Number(Number(5)),
+                        span: Span::default(),
                     }),
                     Statement::BindingDef(BindingDef {
-                        name: "bar".to_string(),
+                        name: Symbol::intern("bar"),
+                        ty: None,
                         val: Expr::Number(Number(4)),
+                        span: Span::default(),
                     }),
                     Statement::BindingDef(BindingDef {
-                        name: "baz".to_string(),
+                        name: Symbol::intern("baz"),
+                        ty: None,
                         val: Expr::Number(Number(3)),
+                        span: Span::default(),
                     }),
                 ],
+                span: Span::default()
             }
             .eval(&Env::default()),
             Ok(Val::Unit),
@@ -173,8 +367,10 @@ mod tests {
                         lhs: Box::new(Expr::Number(Number(10))),
                         rhs: Box::new(Expr::Number(Number(7))),
                         op: Op::Sub,
+                        span: Span::default(),
                     }),
                 ],
+                span: Span::default()
             }
             .eval(&Env::default()),
             Ok(Val::Number(3)),
@@ -184,21 +380,28 @@ mod tests {
     #[test]
     fn eval_block_using_bindings_from_parent_env() {
         let mut env = Env::default();
-        env.store_binding("foo".to_string(), Val::Number(2));
+        env.store_binding(Symbol::intern("foo"), Val::Number(2));
 
         assert_eq!(
             Block {
                 stmts: vec![
                     Statement::BindingDef(BindingDef {
-                        name: "baz".to_string(),
+                        name: Symbol::intern("baz"),
+                        ty: None,
                         val: Expr::BindingUsage(BindingUsage {
-                            name: "foo".to_string(),
+                            name: Symbol::intern("foo"),
+                            kind: None,
+                            span: Span::default()
                         }),
+                        span: Span::default(),
                     }),
                     Statement::Expr(Expr::BindingUsage(BindingUsage {
-                        name: "baz".to_string(),
+                        name: Symbol::intern("baz"),
+                        kind: None,
+                        span: Span::default()
                     })),
                 ],
+                span: Span::default()
             }
             .eval(&env),
             Ok(Val::Number(2)),
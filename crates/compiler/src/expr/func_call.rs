@@ -1,55 +1,216 @@
 use super::Expr;
+use crate::builtins::{self, Builtin};
+use crate::checker::Checker;
+use crate::env::Func;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::{NameKind, Resolver};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::{TypeEnv, TypeInfo};
+use crate::types::Type;
 use crate::{Env, Val, utils};
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct FuncCall {
-    pub(crate) callee: String,
+    pub(crate) callee: Symbol,
     pub(crate) params: Vec<Expr>,
+    // Filled in once the program has been resolved
+    pub(crate) kind: Option<NameKind>,
+    pub(crate) span: Span,
 }
 
 impl FuncCall {
     pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
+        Self::new_with_block_args(s, true)
+    }
+
+    pub(super) fn new_with_block_args(s: &str, block_args: bool) -> Result<(&str, Self), String> {
+        let before = s;
+
         let (s, callee) = utils::extract_ident(s)?;
-        let (s, _) = utils::take_while(|c| c == ' ', s);
+        let (s, _) = utils::take_while_careful(|c| c == ' ', s, "Expected space".to_string())?;
+
+        let (s, params) = utils::sequence_non_empty(
+            |s| Expr::new_argument(s, block_args),
+            |s| Ok(utils::take_while(|c| c == ' ', s)),
+            s,
+        )?;
 
-        let (s, params) =
-            utils::sequence_non_empty(Expr::new, |s| utils::take_while(|c| c == ' ', s), s)?;
+        // The spaces after the last argument are consumed along with it, but are not part of the call
+        let end = &before[before[..before.len() - s.len()].trim_end_matches(' ').len()..];
 
         Ok((
             s,
             Self {
-                callee: callee.to_string(),
+                callee: Symbol::intern(callee),
                 params,
+                kind: None,
+                span: Span::new(before, end),
             },
         ))
     }
 
-    pub(super) fn eval(&self, env: &Env) -> Result<Val, String> {
-        let mut child_env = env.create_child();
+    pub(super) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        match self.kind {
+            Some(NameKind::Func) => self.eval_func(env, env.get_func(self.callee)?),
+            Some(NameKind::Constructor) => {
+                let (enum_name, fields) = env.get_constructor(self.callee)?;
+                self.eval_constructor(env, enum_name, fields.len())
+            }
+            Some(NameKind::Builtin) => match builtins::get(self.callee.as_str()) {
+                Some(builtin) => self.eval_builtin(env, builtin),
+                None => Err(Interrupt::Error(format!(
+                    "builtin with name ‘{}’ does not exist",
+                    self.callee,
+                ))),
+            },
+            // Calls that have not been resolved are worked out from whatever has the name
+            Some(NameKind::Binding) | None => self.eval_unresolved(env),
+        }
+    }
+
+    pub(super) fn resolve(&mut self, resolver: &mut Resolver) {
+        self.kind = resolver.resolve_callee(self.callee, self.params.len(), self.span);
+
+        for param in &mut self.params {
+            param.resolve(resolver);
+        }
+    }
+
+    pub(super) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let args = self
+            .params
+            .iter()
+            .map(|param_expr| param_expr.lower(lowerer))
+            .collect::<Result<_, _>>()?;
+
+        lowerer.lower_call(self.callee, args)
+    }
+
+    pub(super) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        let args: Vec<_> = self
+            .params
+            .iter()
+            .map(|param_expr| param_expr.check(env, checker))
+            .collect();
+
+        let func_ty = match env.get(self.callee) {
+            Some(info @ (TypeInfo::Constructor { .. } | TypeInfo::Func { .. })) => info.to_type(),
+            _ => match builtins::type_of(self.callee.as_str()) {
+                Some(ty) => ty,
+                None => return Type::Unknown,
+            },
+        };
 
-        let (param_names, body) = env.get_func(&self.callee)?;
+        let Type::Func { params, ret } = checker.instantiate(&func_ty) else {
+            unreachable!()
+        };
 
-        let num_expected_params = param_names.len();
+        // Calls with the wrong number of arguments have already been reported by the resolver
+        for (idx, (param, arg)) in params.iter().zip(&args).enumerate() {
+            let what = format!("argument {} of ‘{}’", idx + 1, self.callee);
+            checker.expect(param, arg, self.span, &what);
+        }
+
+        *ret
+    }
+
+    fn eval_unresolved(&self, env: &Env) -> Result<Val, Interrupt> {
+        if let Ok((enum_name, fields)) = env.get_constructor(self.callee) {
+            return self.eval_constructor(env, enum_name, fields.len());
+        }
+
+        match env.get_func(self.callee) {
+            Ok(func) => self.eval_func(env, func),
+            Err(error_msg) => match builtins::get(self.callee.as_str()) {
+                Some(builtin) => self.eval_builtin(env, builtin),
+                None => Err(Interrupt::Error(error_msg)),
+            },
+        }
+    }
+
+    fn eval_func(&self, env: &Env, func: Func) -> Result<Val, Interrupt> {
+        let num_expected_params = func.params.len();
         let num_actual_params = self.params.len();
 
         if num_expected_params != num_actual_params {
-            return Err(format!(
+            return Err(Interrupt::Error(format!(
                 "expected {} parameters, got {}",
                 num_expected_params, num_actual_params,
-            ));
+            )));
+        }
+
+        // The arguments are worked out where the function is called, but its body only sees
+        // what was in scope where it was defined
+        let args = self
+            .params
+            .iter()
+            .map(|param_expr| param_expr.eval(env))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut child_env = func.env.create_child();
+        for (param_name, arg) in func.params.iter().zip(args) {
+            child_env.store_binding(*param_name, arg);
+        }
+
+        match func.body.eval(&mut child_env) {
+            Err(Interrupt::Return(val)) => Ok(val),
+            // Loop control flow cannot cross function boundaries
+            result => result.map_err(|interrupt| Interrupt::Error(interrupt.into_error())),
+        }
+    }
+
+    fn eval_builtin(&self, env: &Env, builtin: Builtin) -> Result<Val, Interrupt> {
+        if builtin.arity != self.params.len() {
+            return Err(Interrupt::Error(format!(
+                "expected {} parameters, got {}",
+                builtin.arity,
+                self.params.len(),
+            )));
         }
 
-        for (param_name, param_expr) in param_names.into_iter().zip(&self.params) {
-            let param_val = param_expr.eval(&child_env)?;
-            child_env.store_binding(param_name, param_val);
+        let args = self
+            .params
+            .iter()
+            .map(|param_expr| param_expr.eval(env))
+            .collect::<Result<_, _>>()?;
+
+        Ok((builtin.func)(args)?)
+    }
+
+    fn eval_constructor(
+        &self,
+        env: &Env,
+        enum_name: Symbol,
+        num_fields: usize,
+    ) -> Result<Val, Interrupt> {
+        if num_fields != self.params.len() {
+            return Err(Interrupt::Error(format!(
+                "expected {} parameters, got {}",
+                num_fields,
+                self.params.len(),
+            )));
         }
 
-        body.eval(&mut child_env)
+        let fields = self
+            .params
+            .iter()
+            .map(|param_expr| param_expr.eval(env))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Val::Variant {
+            enum_name,
+            variant: self.callee,
+            fields,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Rc;
+
     use crate::{
         expr::{BindingUsage, Number, Op},
         statement::Statement,
@@ -78,8 +239,58 @@ mod tests {
             Ok((
                 "",
                 FuncCall {
-                    callee: "factorial".to_string(),
+                    callee: Symbol::intern("factorial"),
                     params: vec![Expr::Number(Number(10))],
+                    kind: None,
+                    span: Span::new("factorial 10", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_func_call_with_binding_usages_as_parameters() {
+        assert_eq!(
+            FuncCall::new("add x y"),
+            Ok((
+                "",
+                FuncCall {
+                    callee: Symbol::intern("add"),
+                    params: vec![
+                        Expr::BindingUsage(BindingUsage {
+                            name: Symbol::intern("x"),
+                            kind: None,
+                            span: Span::new("x y", " y")
+                        }),
+                        Expr::BindingUsage(BindingUsage {
+                            name: Symbol::intern("y"),
+                            kind: None,
+                            span: Span::new("y", "")
+                        }),
+                    ],
+                    kind: None,
+                    span: Span::new("add x y", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn cannot_parse_func_call_without_space_before_parameters() {
+        assert_eq!(FuncCall::new("xs[0]"), Err("Expected space".to_string()),);
+    }
+
+    #[test]
+    fn parse_func_call_stops_before_operation() {
+        assert_eq!(
+            FuncCall::new("square 2 + 1"),
+            Ok((
+                "+ 1",
+                FuncCall {
+                    callee: Symbol::intern("square"),
+                    params: vec![Expr::Number(Number(2))],
+                    kind: None,
+                    span: Span::new("square 2 + 1", " + 1"),
                 },
             )),
         );
@@ -90,34 +301,192 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "id".to_string(),
-            vec!["x".to_string()],
-            Statement::Expr(Expr::BindingUsage(BindingUsage {
-                name: "x".to_string(),
-            })),
+            Symbol::intern("id"),
+            vec![Symbol::intern("x")].into(),
+            Rc::new(Statement::Expr(Expr::BindingUsage(BindingUsage {
+                name: Symbol::intern("x"),
+                kind: None,
+                span: Span::default(),
+            }))),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "id".to_string(),
+                callee: Symbol::intern("id"),
                 params: vec![Expr::Number(Number(10))],
+                kind: None,
+                span: Span::default(),
             }
             .eval(&env),
             Ok(Val::Number(10)),
         );
     }
 
+    #[test]
+    fn eval_constructor_call() {
+        let mut env = Env::default();
+
+        env.store_constructor(
+            Symbol::intern("Rect"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("w"), Symbol::intern("h")],
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("Rect"),
+                params: vec![Expr::Number(Number(2)), Expr::Number(Number(3))],
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Ok(Val::Variant {
+                enum_name: Symbol::intern("Shape"),
+                variant: Symbol::intern("Rect"),
+                fields: vec![Val::Number(2), Val::Number(3)],
+            }),
+        );
+    }
+
+    #[test]
+    fn eval_constructor_call_with_too_few_parameters() {
+        let mut env = Env::default();
+
+        env.store_constructor(
+            Symbol::intern("Rect"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("w"), Symbol::intern("h")],
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("Rect"),
+                params: vec![Expr::Number(Number(2))],
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Err(Interrupt::Error("expected 2 parameters, got 1".to_string())),
+        );
+    }
+
+    #[test]
+    fn eval_builtin_call() {
+        let mut env = Env::default();
+        env.store_binding(Symbol::intern("m"), Val::Map(Default::default()));
+
+        let (_, func_call) = FuncCall::new(r#"has m "a""#).unwrap();
+        assert_eq!(func_call.eval(&env), Ok(Val::Bool(false)));
+    }
+
+    #[test]
+    fn eval_func_call_shadowing_builtin() {
+        let mut env = Env::default();
+
+        env.store_func(
+            Symbol::intern("keys"),
+            vec![Symbol::intern("x")].into(),
+            Rc::new(Statement::Expr(Expr::BindingUsage(BindingUsage {
+                name: Symbol::intern("x"),
+                kind: None,
+                span: Span::default(),
+            }))),
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("keys"),
+                params: vec![Expr::Number(Number(7))],
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Ok(Val::Number(7)),
+        );
+    }
+
+    #[test]
+    fn eval_func_call_with_break_in_body() {
+        let mut env = Env::default();
+        env.store_func(
+            Symbol::intern("escape"),
+            Rc::new([]),
+            Rc::new(Statement::Break(Span::default())),
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("escape"),
+                params: Vec::new(),
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Err(Interrupt::Error(
+                "cannot break outside of a loop".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_func_call_with_early_return() {
+        let mut env = Env::default();
+
+        let (_, body) = Statement::new(
+            "{
+    for x in 0..10 {
+        match x {
+            n if n == limit => { return n * 10 },
+            _ => {},
+        }
+    }
+    0
+}",
+        )
+        .unwrap();
+        env.store_func(
+            Symbol::intern("find"),
+            vec![Symbol::intern("limit")].into(),
+            Rc::new(body),
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("find"),
+                params: vec![Expr::Number(Number(4))],
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Ok(Val::Number(40)),
+        );
+        assert_eq!(
+            FuncCall {
+                callee: Symbol::intern("find"),
+                params: vec![Expr::Number(Number(20))],
+                kind: None,
+                span: Span::default(),
+            }
+            .eval(&env),
+            Ok(Val::Number(0)),
+        );
+    }
+
     #[test]
     fn eval_non_existent_func_call() {
         let env = Env::default();
 
         assert_eq!(
             FuncCall {
-                callee: "i_dont_exist".to_string(),
+                callee: Symbol::intern("i_dont_exist"),
                 params: vec![Expr::Number(Number(1))],
+                kind: None,
+                span: Span::default(),
             }
             .eval(&env),
-            Err("function with name ‘i_dont_exist’ does not exist".to_string()),
+            Err(Interrupt::Error(
+                "function with name ‘i_dont_exist’ does not exist".to_string()
+            )),
         );
     }
 
@@ -126,26 +495,33 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "mul".to_string(),
-            vec!["a".to_string(), "b".to_string()],
-            Statement::Expr(Expr::Operation {
+            Symbol::intern("mul"),
+            vec![Symbol::intern("a"), Symbol::intern("b")].into(),
+            Rc::new(Statement::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "a".to_string(),
+                    name: Symbol::intern("a"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "b".to_string(),
+                    name: Symbol::intern("b"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 op: Op::Mul,
-            }),
+                span: Span::default(),
+            })),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "mul".to_string(),
+                callee: Symbol::intern("mul"),
                 params: vec![Expr::Number(Number(100))],
+                kind: None,
+                span: Span::default(),
             }
             .eval(&env),
-            Err("expected 2 parameters, got 1".to_string()),
+            Err(Interrupt::Error("expected 2 parameters, got 1".to_string())),
         );
     }
 
@@ -154,26 +530,33 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "square".to_string(),
-            vec!["n".to_string()],
-            Statement::Expr(Expr::Operation {
+            Symbol::intern("square"),
+            vec![Symbol::intern("n")].into(),
+            Rc::new(Statement::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "n".to_string(),
+                    name: Symbol::intern("n"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "n".to_string(),
+                    name: Symbol::intern("n"),
+                    kind: None,
+                    span: Span::default(),
                 })),
                 op: Op::Mul,
-            }),
+                span: Span::default(),
+            })),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "square".to_string(),
+                callee: Symbol::intern("square"),
                 params: vec![Expr::Number(Number(5)), Expr::Number(Number(42))],
+                kind: None,
+                span: Span::default(),
             }
             .eval(&env),
-            Err("expected 1 parameters, got 2".to_string()),
+            Err(Interrupt::Error("expected 1 parameters, got 2".to_string())),
         );
     }
 }
@@ -0,0 +1,120 @@
+use super::Expr;
+use crate::checker::Checker;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::{Env, Val, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct ListLiteral {
+    pub(crate) items: Vec<Expr>,
+    pub(crate) span: Span,
+}
+
+impl ListLiteral {
+    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("[", before)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, items) = utils::sequence(Expr::new, utils::extract_comma, s)?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag("]", s)?;
+
+        Ok((
+            s,
+            Self {
+                items,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    pub(super) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| item.eval(env))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Val::List(items))
+    }
+
+    pub(super) fn resolve(&mut self, resolver: &mut Resolver) {
+        for item in &mut self.items {
+            item.resolve(resolver);
+        }
+    }
+
+    pub(super) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| item.lower(lowerer))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Node::List(items))
+    }
+
+    pub(super) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        let item_ty = checker.fresh_var();
+
+        for item in &self.items {
+            let ty = item.check(env, checker);
+            checker.expect(&item_ty, &ty, self.span, "list item");
+        }
+
+        Type::List(Box::new(item_ty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Number;
+    use super::*;
+
+    #[test]
+    fn parse_list_literal() {
+        assert_eq!(
+            ListLiteral::new("[1, 2,\n 3]"),
+            Ok((
+                "",
+                ListLiteral {
+                    items: vec![
+                        Expr::Number(Number(1)),
+                        Expr::Number(Number(2)),
+                        Expr::Number(Number(3)),
+                    ],
+                    span: Span::new("[1, 2,\n 3]", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn cannot_parse_list_literal_without_commas() {
+        assert_eq!(
+            ListLiteral::new("[1 2 3]"),
+            Err("Expected \"]\"".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_list_literal() {
+        let (_, list) = ListLiteral::new(r#"[1, "a", []]"#).unwrap();
+
+        assert_eq!(
+            list.eval(&Env::default()),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Str("a".to_string()),
+                Val::List(Vec::new()),
+            ])),
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use super::Expr;
+use crate::checker::Checker;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::val::MapKey;
+use crate::{Env, Val, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct MapLiteral {
+    pub(crate) entries: Vec<(Expr, Expr)>,
+    pub(crate) span: Span,
+}
+
+impl MapLiteral {
+    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("#{", before)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, entries) = utils::sequence(Self::new_entry, utils::extract_comma, s)?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag("}", s)?;
+
+        Ok((
+            s,
+            Self {
+                entries,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    fn new_entry(s: &str) -> Result<(&str, (Expr, Expr)), String> {
+        let (s, key) = Expr::new(s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let s = utils::tag(":", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, val) = Expr::new(s)?;
+
+        Ok((s, (key, val)))
+    }
+
+    pub(super) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        let mut map = BTreeMap::new();
+
+        // Later entries win when a key is repeated, just as with `insert`
+        for (key, val) in &self.entries {
+            let key = MapKey::try_from(key.eval(env)?)?;
+            map.insert(key, val.eval(env)?);
+        }
+
+        Ok(Val::Map(map))
+    }
+
+    pub(super) fn resolve(&mut self, resolver: &mut Resolver) {
+        for (key, val) in &mut self.entries {
+            key.resolve(resolver);
+            val.resolve(resolver);
+        }
+    }
+
+    pub(super) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, val)| Ok((key.lower(lowerer)?, val.lower(lowerer)?)))
+            .collect::<Result<_, String>>()?;
+
+        Ok(Node::Map(entries))
+    }
+
+    pub(super) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        let key_ty = checker.fresh_var();
+        let val_ty = checker.fresh_var();
+
+        for (key, val) in &self.entries {
+            let ty = key.check(env, checker);
+
+            match checker.resolve(&ty) {
+                Type::Int | Type::Str | Type::Var(_) | Type::Unknown | Type::Never => {
+                    checker.expect(&key_ty, &ty, self.span, "map key");
+                }
+                ty => {
+                    let [ty] = checker.display([&ty]);
+                    checker.error(
+                        format!("map keys must be ‘Int’ or ‘Str’, got ‘{}’", ty),
+                        self.span,
+                    );
+                }
+            }
+
+            let ty = val.check(env, checker);
+            checker.expect(&val_ty, &ty, self.span, "map value");
+        }
+
+        Type::Map(Box::new(key_ty), Box::new(val_ty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Number;
+    use super::*;
+
+    #[test]
+    fn parse_empty_map_literal() {
+        assert_eq!(
+            MapLiteral::new("#{}"),
+            Ok((
+                "",
+                MapLiteral {
+                    entries: Vec::new(),
+                    span: Span::new("#{}", "")
+                }
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_map_literal() {
+        assert_eq!(
+            MapLiteral::new(r#"#{ "a": 1, 2: 3 }"#),
+            Ok((
+                "",
+                MapLiteral {
+                    entries: vec![
+                        (Expr::Str("a".to_string()), Expr::Number(Number(1))),
+                        (Expr::Number(Number(2)), Expr::Number(Number(3))),
+                    ],
+                    span: Span::new(r#"#{ "a": 1, 2: 3 }"#, ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn cannot_parse_map_literal_without_commas() {
+        assert_eq!(
+            MapLiteral::new("#{ 1: 2 3: 4 }"),
+            Err("Expected \"}\"".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_map_literal_orders_keys() {
+        let (_, map) = MapLiteral::new(r#"#{ "b": 1, "a": 2, 10: 3 }"#).unwrap();
+
+        assert_eq!(
+            map.eval(&Env::default()).map(|map| map.to_string()),
+            Ok(r#"#{10: 3, "a": 2, "b": 1}"#.to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_map_literal_with_invalid_key() {
+        let (_, map) = MapLiteral::new("#{ true: 1 }").unwrap();
+
+        assert_eq!(
+            map.eval(&Env::default()),
+            Err(Interrupt::Error(
+                "map keys must be numbers or strings, but ‘true’ is neither".to_string()
+            )),
+        );
+    }
+}
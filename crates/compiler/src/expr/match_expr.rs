@@ -0,0 +1,575 @@
+use super::Expr;
+use crate::checker::Checker;
+use crate::diagnostic::Diagnostic;
+use crate::interrupt::Interrupt;
+use crate::lower::{Arm, Lowerer, Node};
+use crate::pattern::Pattern;
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::{Env, Val, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Match {
+    pub(crate) scrutinee: Box<Expr>,
+    pub(crate) arms: Vec<MatchArm>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct MatchArm {
+    pub(crate) pattern: Pattern,
+    pub(crate) guard: Option<Expr>,
+    pub(crate) body: Expr,
+}
+
+impl Match {
+    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("match", before)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let (s, scrutinee) = Expr::new_before_block(s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let s = utils::tag("{", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, arms) = utils::sequence(MatchArm::new, utils::extract_comma, s)?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag("}", s)?;
+
+        Ok((
+            s,
+            Self {
+                scrutinee: Box::new(scrutinee),
+                arms,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    pub(super) fn eval(&self, env: &Env) -> Result<Val, Interrupt> {
+        let val = self.scrutinee.eval(env)?;
+
+        for arm in &self.arms {
+            let mut child_env = env.create_child();
+
+            if !arm.pattern.bind(&val, &mut child_env)? {
+                continue;
+            }
+
+            if let Some(guard) = &arm.guard {
+                match guard.eval(&child_env)? {
+                    Val::Bool(true) => {}
+                    Val::Bool(false) => continue,
+                    _ => {
+                        return Err(Interrupt::Error(
+                            "match guard must evaluate to a boolean".to_string(),
+                        ));
+                    }
+                }
+            }
+
+            return arm.body.eval(&child_env);
+        }
+
+        Err(Interrupt::Error(format!(
+            "no arm of match expression matched ‘{}’",
+            val,
+        )))
+    }
+
+    pub(super) fn resolve(&mut self, resolver: &mut Resolver) {
+        self.scrutinee.resolve(resolver);
+
+        for arm in &mut self.arms {
+            resolver.in_scope(|resolver| {
+                arm.pattern.resolve(resolver, self.span);
+
+                if let Some(guard) = &mut arm.guard {
+                    guard.resolve(resolver);
+                }
+
+                arm.body.resolve(resolver);
+            });
+        }
+    }
+
+    pub(super) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let scrutinee = self.scrutinee.lower(lowerer)?;
+
+        let arms = self
+            .arms
+            .iter()
+            .map(|arm| {
+                lowerer.in_scope(|lowerer| {
+                    Ok(Arm {
+                        pattern: arm.pattern.lower(lowerer)?,
+                        guard: arm
+                            .guard
+                            .as_ref()
+                            .map(|guard| guard.lower(lowerer))
+                            .transpose()?,
+                        body: arm.body.lower(lowerer)?,
+                    })
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Node::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+        })
+    }
+
+    pub(super) fn check(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        let scrutinee_ty = self.scrutinee.check(env, checker);
+        let ty = checker.fresh_var();
+
+        for arm in &self.arms {
+            let mut child_env = env.create_child();
+            arm.pattern
+                .check(&scrutinee_ty, &mut child_env, checker, self.span);
+
+            if let Some(guard) = &arm.guard {
+                let guard_ty = guard.check(&child_env, checker);
+                checker.expect(&Type::Bool, &guard_ty, self.span, "match guard");
+            }
+
+            let body_ty = arm.body.check(&child_env, checker);
+
+            if let Err(conflict) = checker.unify(&ty, &body_ty, self.span) {
+                let [ty, body_ty] = checker.display([&ty, &body_ty]);
+                checker.conflict(
+                    format!(
+                        "match arms have incompatible types ‘{}’ and ‘{}’",
+                        ty, body_ty,
+                    ),
+                    self.span,
+                    conflict,
+                );
+            }
+        }
+
+        let scrutinee_ty = checker.resolve(&scrutinee_ty);
+        let missing = self.missing_variants(&scrutinee_ty, env);
+
+        if !missing.is_empty() {
+            let [scrutinee_ty] = checker.display([&scrutinee_ty]);
+            let diagnostic = missing.into_iter().fold(
+                Diagnostic::warning(
+                    format!(
+                        "match expression does not cover every value of type ‘{}’",
+                        scrutinee_ty,
+                    ),
+                    self.span,
+                ),
+                |diagnostic, missing| {
+                    diagnostic.with_note(format!("‘{}’ is not matched", missing), None)
+                },
+            );
+            checker.push(diagnostic);
+        }
+
+        ty
+    }
+
+    // Returns what the arms of the match fail to cover. Coverage is only worked out for booleans,
+    // the variants of enums and the lengths of lists, without looking inside their fields or
+    // items, so anything else must have an irrefutable arm to be considered covered.
+    fn missing_variants(&self, scrutinee_ty: &Type, env: &TypeEnv) -> Vec<String> {
+        let unguarded_patterns: Vec<_> = self
+            .arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| &arm.pattern)
+            .collect();
+
+        if unguarded_patterns
+            .iter()
+            .any(|pattern| pattern.is_irrefutable(env))
+        {
+            return Vec::new();
+        }
+
+        let covers = |name: Symbol| {
+            unguarded_patterns.iter().any(|pattern| match pattern {
+                Pattern::Binding(binding) => *binding == name,
+                Pattern::Variant {
+                    name: variant,
+                    fields,
+                } => *variant == name && fields.iter().all(|field| field.is_irrefutable(env)),
+                _ => false,
+            })
+        };
+
+        match scrutinee_ty {
+            Type::Bool => [true, false]
+                .into_iter()
+                .filter(|b| !unguarded_patterns.contains(&&Pattern::Bool(*b)))
+                .map(|b| b.to_string())
+                .collect(),
+            Type::Enum(enum_name, _) => env
+                .get_enum(*enum_name)
+                .map_or(&[][..], |enum_info| &enum_info.variants)
+                .iter()
+                .filter(|variant| !covers(**variant))
+                .map(ToString::to_string)
+                .collect(),
+            Type::List(_) => missing_lengths(&unguarded_patterns, env),
+            // The type of the scrutinee is unknown, so there is nothing to compare against
+            Type::Var(_) | Type::Unknown | Type::Never => Vec::new(),
+            _ => vec!["_".to_string()],
+        }
+    }
+}
+
+// Works out which lengths of list the list patterns among `patterns` fail to cover, given that
+// none of the patterns are irrefutable
+fn missing_lengths(patterns: &[&Pattern], env: &TypeEnv) -> Vec<String> {
+    let mut exact = Vec::new();
+    let mut min_rest = None;
+
+    for pattern in patterns {
+        let Pattern::List { items, rest } = pattern else {
+            continue;
+        };
+        if !items.iter().all(|item| item.is_irrefutable(env)) {
+            continue;
+        }
+
+        // The rest of a list is itself a list, which only irrefutable patterns cover
+        match rest {
+            None => exact.push(items.len()),
+            Some(rest) if rest.is_irrefutable(env) => {
+                min_rest = Some(min_rest.map_or(items.len(), |min: usize| min.min(items.len())));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let describe = |len: usize, rest: bool| {
+        let mut items = vec!["_"; len];
+        if rest {
+            items.push("..");
+        }
+        format!("[{}]", items.join(", "))
+    };
+
+    match (min_rest, exact.iter().max()) {
+        (Some(min_rest), _) => (0..min_rest)
+            .filter(|len| !exact.contains(len))
+            .map(|len| describe(len, false))
+            .collect(),
+        (None, Some(&max_exact)) => (0..max_exact)
+            .filter(|len| !exact.contains(len))
+            .map(|len| describe(len, false))
+            .chain(std::iter::once(describe(max_exact + 1, true)))
+            .collect(),
+        (None, None) => vec!["_".to_string()],
+    }
+}
+
+impl MatchArm {
+    fn new(s: &str) -> Result<(&str, Self), String> {
+        let (s, pattern) = Pattern::new(s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, guard) = match utils::tag("if", s) {
+            Ok(s) => {
+                let (s, _) = utils::extract_whitespace_non_empty(s)?;
+                let (s, guard) = Expr::new(s)?;
+                let (s, _) = utils::extract_whitespace(s);
+
+                (s, Some(guard))
+            }
+            Err(_) => (s, None),
+        };
+
+        let s = utils::tag("=>", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, body) = Expr::new(s)?;
+
+        Ok((
+            s,
+            Self {
+                pattern,
+                guard,
+                body,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BindingUsage, Number, Op};
+    use super::*;
+
+    fn shape_env() -> Env {
+        let mut env = Env::default();
+
+        env.store_constructor(
+            Symbol::intern("Circle"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("r")],
+        );
+        env.store_constructor(
+            Symbol::intern("Rect"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("w"), Symbol::intern("h")],
+        );
+
+        env
+    }
+
+    fn rect(w: i32, h: i32) -> Val {
+        Val::Variant {
+            enum_name: Symbol::intern("Shape"),
+            variant: Symbol::intern("Rect"),
+            fields: vec![Val::Number(w), Val::Number(h)],
+        }
+    }
+
+    #[test]
+    fn parse_match_with_guard() {
+        assert_eq!(
+            Match::new(
+                "match n {
+    0 => 1,
+    x if x > 5 => x,
+    _ => 2,
+}",
+            ),
+            Ok((
+                "",
+                Match {
+                    scrutinee: Box::new(Expr::BindingUsage(BindingUsage {
+                        name: Symbol::intern("n"),
+                        kind: None,
+                        span: Span::new(
+                            "n {\n    0 => 1,\n    x if x > 5 => x,\n    _ => 2,\n}",
+                            " {\n    0 => 1,\n    x if x > 5 => x,\n    _ => 2,\n}"
+                        )
+                    })),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Number(0),
+                            guard: None,
+                            body: Expr::Number(Number(1)),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Binding(Symbol::intern("x")),
+                            guard: Some(Expr::Operation {
+                                lhs: Box::new(Expr::BindingUsage(BindingUsage {
+                                    name: Symbol::intern("x"),
+                                    kind: None,
+                                    span: Span::new(
+                                        "x > 5 => x,\n    _ => 2,\n}",
+                                        " > 5 => x,\n    _ => 2,\n}"
+                                    )
+                                })),
+                                rhs: Box::new(Expr::Number(Number(5))),
+                                op: Op::Gt,
+                                span: Span::new(
+                                    "x > 5 => x,\n    _ => 2,\n}",
+                                    " => x,\n    _ => 2,\n}"
+                                ),
+                            }),
+                            body: Expr::BindingUsage(BindingUsage {
+                                name: Symbol::intern("x"),
+                                kind: None,
+                                span: Span::new("x,\n    _ => 2,\n}", ",\n    _ => 2,\n}")
+                            }),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Wildcard,
+                            guard: None,
+                            body: Expr::Number(Number(2)),
+                        },
+                    ],
+                    span: Span::new(
+                        "match n {\n    0 => 1,\n    x if x > 5 => x,\n    _ => 2,\n}",
+                        ""
+                    ),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_match_on_variant() {
+        let env = shape_env();
+
+        let (_, match_expr) = Match::new(
+            "match Rect 2 3 {
+    Circle(r) => r,
+    Rect(w, h) => w * h,
+}",
+        )
+        .unwrap();
+
+        assert_eq!(match_expr.eval(&env), Ok(Val::Number(6)));
+    }
+
+    #[test]
+    fn eval_match_skips_arm_with_false_guard() {
+        let mut env = shape_env();
+        env.store_binding(Symbol::intern("shape"), rect(4, 4));
+
+        let (_, match_expr) = Match::new(
+            "match shape {
+    Rect(w, h) if w == 1 => 0,
+    Rect(w, h) if w == h => w,
+    _ => 100,
+}",
+        )
+        .unwrap();
+
+        assert_eq!(match_expr.eval(&env), Ok(Val::Number(4)));
+    }
+
+    #[test]
+    fn eval_match_bindings_do_not_leak_into_parent_env() {
+        let env = shape_env();
+
+        let (_, match_expr) = Match::new("match 5 { x => x }").unwrap();
+
+        assert_eq!(match_expr.eval(&env), Ok(Val::Number(5)));
+        assert_eq!(
+            env.get_binding(Symbol::intern("x")),
+            Err("binding with name ‘x’ does not exist".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_non_exhaustive_match() {
+        let mut env = shape_env();
+        env.store_binding(Symbol::intern("shape"), rect(1, 2));
+
+        let (_, match_expr) = Match::new("match shape { Circle(r) => r }").unwrap();
+
+        assert_eq!(
+            match_expr.eval(&env),
+            Err(Interrupt::Error(
+                "no arm of match expression matched ‘Rect(1, 2)’".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_match_with_non_bool_guard() {
+        let (_, match_expr) = Match::new("match 1 { x if x => x }").unwrap();
+
+        assert_eq!(
+            match_expr.eval(&Env::default()),
+            Err(Interrupt::Error(
+                "match guard must evaluate to a boolean".to_string()
+            )),
+        );
+    }
+
+    fn check(s: &str) -> Vec<Diagnostic> {
+        let mut env = TypeEnv::default();
+        let (_, enum_def) =
+            crate::enum_def::EnumDef::new("enum Shape { Circle(r), Rect(w, h), Empty }").unwrap();
+        let mut checker = Checker::default();
+        enum_def.check(&mut env, &mut checker);
+
+        let (_, match_expr) = Match::new(s).unwrap();
+        match_expr.check(&env, &mut checker);
+
+        checker.diagnostics
+    }
+
+    #[test]
+    fn check_exhaustive_match_over_enum() {
+        assert_eq!(
+            check("match Empty { Circle(_) => 1, Rect(w, h) => w, Empty => 0 }"),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn check_non_exhaustive_match_over_enum() {
+        let source = "match Empty { Circle(_) => 1, Rect(1, h) => h, _ if true => 0 }";
+        let diagnostics = check(source);
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::warning(
+                    "match expression does not cover every value of type ‘Shape’".to_string(),
+                    Span::new(source, ""),
+                )
+                .with_note("‘Rect’ is not matched".to_string(), None)
+                .with_note("‘Empty’ is not matched".to_string(), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn check_non_exhaustive_match_over_bool() {
+        assert_eq!(check("match 1 < 2 { true => 1, false => 0 }"), Vec::new());
+        assert_eq!(check("match 1 < 2 { true => 1 }").len(), 1);
+    }
+
+    #[test]
+    fn check_match_over_list_lengths() {
+        assert_eq!(check("match [1] { [] => 0, [x, ..rest] => x }"), Vec::new());
+
+        assert_eq!(
+            check("match [1] { [_] => 1, [1, 2] => 2, [_, _, _, ..] => 3 }"),
+            vec![
+                Diagnostic::warning(
+                    "match expression does not cover every value of type ‘List<Int>’".to_string(),
+                    Span::new(
+                        "match [1] { [_] => 1, [1, 2] => 2, [_, _, _, ..] => 3 }",
+                        ""
+                    ),
+                )
+                .with_note("‘[]’ is not matched".to_string(), None)
+                .with_note("‘[_, _]’ is not matched".to_string(), None)
+            ],
+        );
+
+        assert_eq!(
+            check("match [1] { [] => 0, [x] => x }"),
+            vec![
+                Diagnostic::warning(
+                    "match expression does not cover every value of type ‘List<Int>’".to_string(),
+                    Span::new("match [1] { [] => 0, [x] => x }", ""),
+                )
+                .with_note("‘[_, _, ..]’ is not matched".to_string(), None)
+            ],
+        );
+    }
+
+    #[test]
+    fn check_match_with_incompatible_arms() {
+        assert_eq!(
+            check("match 1 { 1 => true, x => x }"),
+            vec![Diagnostic::error(
+                "match arms have incompatible types ‘Bool’ and ‘Int’".to_string(),
+                Span::new("match 1 { 1 => true, x => x }", ""),
+            )],
+        );
+    }
+
+    #[test]
+    fn check_pattern_of_wrong_type() {
+        assert_eq!(
+            check("match Empty { true => 1, _ => 0 }"),
+            vec![Diagnostic::error(
+                "expected pattern ‘true’ to be ‘Shape’, got ‘Bool’".to_string(),
+                Span::new("match Empty { true => 1, _ => 0 }", ""),
+            )],
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::checker::Checker;
+use crate::expr::{Block, Expr};
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::{Env, Val, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct ForLoop {
+    pub(crate) binding: Symbol,
+    pub(crate) iterable: Expr,
+    pub(crate) body: Block,
+    pub(crate) span: Span,
+}
+
+impl ForLoop {
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::tag("for", before)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let (s, binding) = utils::extract_ident(s)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let s = utils::tag("in", s)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let (s, iterable) = Expr::new_before_block(s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, body) = Block::new(s)?;
+
+        Ok((
+            s,
+            Self {
+                binding: Symbol::intern(binding),
+                iterable,
+                body,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    pub(crate) fn eval(&self, env: &Env) -> Result<(), Interrupt> {
+        for item in items(self.iterable.eval(env)?)? {
+            let mut child_env = env.create_child();
+            child_env.store_binding(self.binding, item);
+
+            match self.body.eval(&child_env) {
+                Ok(_) | Err(Interrupt::Continue) => {}
+                Err(Interrupt::Break) => break,
+                Err(interrupt) => return Err(interrupt),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        self.iterable.resolve(resolver);
+
+        resolver.in_scope(|resolver| {
+            resolver.define_binding(self.binding, self.span);
+            self.body.resolve(resolver);
+        });
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let iterable = self.iterable.lower(lowerer)?;
+
+        lowerer.in_scope(|lowerer| {
+            let slot = lowerer.define_binding(self.binding);
+
+            Ok(Node::For {
+                slot,
+                iterable: Box::new(iterable),
+                body: Box::new(self.body.lower(lowerer)?),
+            })
+        })
+    }
+
+    pub(crate) fn check(&self, env: &TypeEnv, checker: &mut Checker) {
+        let iterable_ty = self.iterable.check(env, checker);
+
+        // Several types can be iterated over, so nothing can be inferred about an iterable whose
+        // type is not yet known
+        let item_ty = match checker.resolve(&iterable_ty) {
+            Type::Range => Type::Int,
+            Type::List(item) => *item,
+            Type::Str => Type::Str,
+            Type::Map(key, _) => *key,
+            Type::Var(_) | Type::Unknown | Type::Never => Type::Unknown,
+            ty => {
+                let [ty] = checker.display([&ty]);
+                checker.error(format!("cannot iterate over ‘{}’", ty), self.span);
+                Type::Unknown
+            }
+        };
+
+        let mut child_env = env.create_child();
+        child_env.store_binding(self.binding, item_ty);
+
+        checker.check_loop_body(|checker| {
+            self.body.check(&child_env, checker);
+        });
+    }
+}
+
+pub(crate) fn items(val: Val) -> Result<Box<dyn Iterator<Item = Val>>, String> {
+    match val {
+        Val::Range {
+            start,
+            end,
+            inclusive: false,
+        } => Ok(Box::new((start..end).map(Val::Number))),
+        Val::Range {
+            start,
+            end,
+            inclusive: true,
+        } => Ok(Box::new((start..=end).map(Val::Number))),
+        Val::List(items) => Ok(Box::new(items.into_iter())),
+        Val::Str(s) => {
+            let chars: Vec<_> = s.chars().map(|c| Val::Str(c.to_string())).collect();
+            Ok(Box::new(chars.into_iter()))
+        }
+        Val::Map(map) => Ok(Box::new(map.into_keys().map(Val::from))),
+        _ => Err(format!("cannot iterate over ‘{}’", val)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{BindingUsage, FuncCall, Number};
+    use crate::statement::Statement;
+
+    #[test]
+    fn parse_for_loop_over_range() {
+        assert_eq!(
+            ForLoop::new("for i in 0..10 { i }"),
+            Ok((
+                "",
+                ForLoop {
+                    binding: Symbol::intern("i"),
+                    iterable: Expr::Range {
+                        start: Box::new(Expr::Number(Number(0))),
+                        end: Box::new(Expr::Number(Number(10))),
+                        inclusive: false,
+                        span: Span::new("0..10 { i }", " { i }"),
+                    },
+                    body: Block {
+                        stmts: vec![Statement::Expr(Expr::BindingUsage(BindingUsage {
+                            name: Symbol::intern("i"),
+                            kind: None,
+                            span: Span::new("i }", " }")
+                        }))],
+                        span: Span::new("{ i }", "")
+                    },
+                    span: Span::new("for i in 0..10 { i }", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_for_loop_does_not_take_body_as_argument() {
+        assert_eq!(
+            ForLoop::new("for k in keys m {}"),
+            Ok((
+                "",
+                ForLoop {
+                    binding: Symbol::intern("k"),
+                    iterable: Expr::FuncCall(FuncCall {
+                        callee: Symbol::intern("keys"),
+                        params: vec![Expr::BindingUsage(BindingUsage {
+                            name: Symbol::intern("m"),
+                            kind: None,
+                            span: Span::new("m {}", " {}"),
+                        })],
+                        kind: None,
+                        span: Span::new("keys m {}", " {}"),
+                    }),
+                    body: Block {
+                        stmts: Vec::new(),
+                        span: Span::new("{}", ""),
+                    },
+                    span: Span::new("for k in keys m {}", ""),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn items_of_inclusive_range() {
+        assert_eq!(
+            items(Val::Range {
+                start: 1,
+                end: 3,
+                inclusive: true,
+            })
+            .map(Iterator::collect::<Vec<_>>),
+            Ok(vec![Val::Number(1), Val::Number(2), Val::Number(3)]),
+        );
+    }
+
+    #[test]
+    fn items_of_str() {
+        assert_eq!(
+            items(Val::Str("hi".to_string())).map(Iterator::collect::<Vec<_>>),
+            Ok(vec![Val::Str("h".to_string()), Val::Str("i".to_string())]),
+        );
+    }
+
+    #[test]
+    fn items_of_number() {
+        assert_eq!(
+            items(Val::Number(5)).map(|_| ()),
+            Err("cannot iterate over ‘5’".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_for_loop_binds_fresh_env_per_iteration() {
+        let mut env = Env::default();
+        env.store_binding(Symbol::intern("total"), Val::Number(0));
+
+        let (_, for_loop) = ForLoop::new("for x in [1, 2, 3] { let total = total + x }").unwrap();
+
+        assert_eq!(for_loop.eval(&env), Ok(()));
+
+        // Each iteration shadows `total` in its own env, so the outer binding is never updated
+        assert_eq!(env.get_binding(Symbol::intern("total")), Ok(Val::Number(0)));
+        assert_eq!(
+            env.get_binding(Symbol::intern("x")),
+            Err("binding with name ‘x’ does not exist".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_for_loop_with_break() {
+        // The loop would fail at 5, so it only succeeds if it stops at 3
+        let (_, for_loop) =
+            ForLoop::new("for x in 0..10 { match x { 3 => { break }, 5 => x + true, _ => x } }")
+                .unwrap();
+
+        assert_eq!(for_loop.eval(&Env::default()), Ok(()));
+    }
+
+    #[test]
+    fn eval_for_loop_with_continue() {
+        let (_, for_loop) = ForLoop::new(
+            "for x in 0..3 {
+    continue
+    x + true
+}",
+        )
+        .unwrap();
+
+        assert_eq!(for_loop.eval(&Env::default()), Ok(()));
+    }
+
+    #[test]
+    fn eval_for_loop_over_non_iterable() {
+        let (_, for_loop) = ForLoop::new("for x in true {}").unwrap();
+
+        assert_eq!(
+            for_loop.eval(&Env::default()),
+            Err(Interrupt::Error("cannot iterate over ‘true’".to_string())),
+        );
+    }
+
+    #[test]
+    fn eval_for_loop_propagates_errors_from_body() {
+        let (_, for_loop) = ForLoop::new("for x in [1] { x + true }").unwrap();
+
+        assert_eq!(
+            for_loop.eval(&Env::default()),
+            Err(Interrupt::Error(
+                "Cannot evaluate operation whose left-hand side and right-hand side are not both numbers"
+                    .to_string()
+            )),
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use crate::expr;
+use crate::for_loop;
+use crate::interrupt::Interrupt;
+use crate::lower::{Func, Local, Node, Program, SlotPattern};
+use crate::pattern;
+use crate::val::{MapKey, Val};
+
+/// The bindings of a single call, each stored in the slot it was given when the program was
+/// lowered. Frames are linked to the frame of the function they were defined in, rather than to
+/// the frame of their caller, so that names refer to whatever they referred to where they were
+/// written.
+struct Frame<'parent> {
+    slots: Vec<Val>,
+    parent: Option<&'parent Self>,
+}
+
+impl<'parent> Frame<'parent> {
+    fn new(func: &Func, parent: Option<&'parent Self>) -> Self {
+        Self {
+            slots: vec![Val::Unit; func.num_slots],
+            parent,
+        }
+    }
+
+    fn ancestor(&self, depth: usize) -> &Self {
+        match depth {
+            0 => self,
+            // Lowering only refers to functions that are in scope, whose frames are always there
+            _ => self.parent.unwrap().ancestor(depth - 1),
+        }
+    }
+
+    fn get(&self, local: Local) -> Val {
+        self.ancestor(local.depth).slots[local.slot].clone()
+    }
+}
+
+impl Program {
+    pub fn eval(&self) -> Result<Val, String> {
+        let mut frame = Frame::new(&self.main, None);
+
+        self.main
+            .body
+            .eval(&mut frame, self)
+            .map_err(Interrupt::into_error)
+    }
+}
+
+impl Node {
+    fn eval(&self, frame: &mut Frame, program: &Program) -> Result<Val, Interrupt> {
+        match self {
+            Self::Const(val) => Ok(val.clone()),
+            Self::Local(local) => Ok(frame.get(*local)),
+            Self::Define { slot, val } => {
+                frame.slots[*slot] = val.eval(frame, program)?;
+                Ok(Val::Unit)
+            }
+            Self::Operation { lhs, rhs, op } => {
+                let lhs = lhs.eval(frame, program)?;
+                let rhs = rhs.eval(frame, program)?;
+
+                Ok(op.apply(lhs, rhs)?)
+            }
+            Self::Call { func, depth, args } => {
+                let func = &program.funcs[*func];
+                let args = eval_all(args, frame, program)?;
+
+                let mut callee_frame = Frame::new(func, Some(frame.ancestor(*depth)));
+                for (slot, arg) in callee_frame.slots.iter_mut().zip(args) {
+                    *slot = arg;
+                }
+
+                match func.body.eval(&mut callee_frame, program) {
+                    Err(Interrupt::Return(val)) => Ok(val),
+                    // Loop control flow cannot cross function boundaries
+                    result => result.map_err(|interrupt| Interrupt::Error(interrupt.into_error())),
+                }
+            }
+            Self::Construct {
+                enum_name,
+                variant,
+                args,
+            } => Ok(Val::Variant {
+                enum_name: *enum_name,
+                variant: *variant,
+                fields: eval_all(args, frame, program)?,
+            }),
+            Self::Builtin { builtin, args } => Ok((builtin.func)(eval_all(args, frame, program)?)?),
+            Self::Block(nodes) => {
+                let mut result = Val::Unit;
+
+                for node in nodes {
+                    result = node.eval(frame, program)?;
+                }
+
+                Ok(result)
+            }
+            Self::Match { scrutinee, arms } => {
+                let val = scrutinee.eval(frame, program)?;
+
+                for arm in arms {
+                    if !arm.pattern.bind(&val, &mut frame.slots) {
+                        continue;
+                    }
+
+                    if let Some(guard) = &arm.guard {
+                        match guard.eval(frame, program)? {
+                            Val::Bool(true) => {}
+                            Val::Bool(false) => continue,
+                            _ => {
+                                return Err(Interrupt::Error(
+                                    "match guard must evaluate to a boolean".to_string(),
+                                ));
+                            }
+                        }
+                    }
+
+                    return arm.body.eval(frame, program);
+                }
+
+                Err(Interrupt::Error(format!(
+                    "no arm of match expression matched ‘{}’",
+                    val,
+                )))
+            }
+            Self::List(items) => Ok(Val::List(eval_all(items, frame, program)?)),
+            Self::Map(entries) => {
+                let mut map = BTreeMap::new();
+
+                for (key, val) in entries {
+                    let key = MapKey::try_from(key.eval(frame, program)?)?;
+                    map.insert(key, val.eval(frame, program)?);
+                }
+
+                Ok(Val::Map(map))
+            }
+            Self::Index { target, index } => {
+                let target = target.eval(frame, program)?;
+                let index = index.eval(frame, program)?;
+
+                Ok(expr::index_into(target, index)?)
+            }
+            Self::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = start.eval(frame, program)?;
+                let end = end.eval(frame, program)?;
+
+                Ok(expr::range(start, end, *inclusive)?)
+            }
+            Self::For {
+                slot,
+                iterable,
+                body,
+            } => {
+                for item in for_loop::items(iterable.eval(frame, program)?)? {
+                    frame.slots[*slot] = item;
+
+                    match body.eval(frame, program) {
+                        Ok(_) | Err(Interrupt::Continue) => {}
+                        Err(Interrupt::Break) => break,
+                        Err(interrupt) => return Err(interrupt),
+                    }
+                }
+
+                Ok(Val::Unit)
+            }
+            Self::Break => Err(Interrupt::Break),
+            Self::Continue => Err(Interrupt::Continue),
+            Self::Return(val) => Err(Interrupt::Return(val.eval(frame, program)?)),
+            Self::Spanned { node, .. } => node.eval(frame, program),
+        }
+    }
+}
+
+fn eval_all(nodes: &[Node], frame: &mut Frame, program: &Program) -> Result<Vec<Val>, Interrupt> {
+    nodes.iter().map(|node| node.eval(frame, program)).collect()
+}
+
+impl SlotPattern {
+    // Stores every binding introduced by the pattern in its slot, returning whether `val` matched
+    pub(crate) fn bind(&self, val: &Val, slots: &mut [Val]) -> bool {
+        match self {
+            Self::Wildcard => true,
+            Self::Number(n) => *val == Val::Number(*n),
+            Self::Bool(b) => *val == Val::Bool(*b),
+            Self::Binding(slot) => {
+                slots[*slot] = val.clone();
+                true
+            }
+            Self::Variant {
+                enum_name,
+                variant,
+                fields: patterns,
+            } => match val {
+                Val::Variant {
+                    enum_name: val_enum_name,
+                    variant: val_variant,
+                    fields,
+                } if val_enum_name == enum_name && val_variant == variant => patterns
+                    .iter()
+                    .zip(fields)
+                    .all(|(pattern, field)| pattern.bind(field, slots)),
+                _ => false,
+            },
+            Self::List { items, rest } => {
+                let Some(vals) = pattern::list_items(val, items.len(), rest.is_some()) else {
+                    return false;
+                };
+
+                items
+                    .iter()
+                    .zip(vals)
+                    .all(|(pattern, item)| pattern.bind(item, slots))
+                    && rest.as_ref().is_none_or(|rest| {
+                        rest.bind(&Val::List(vals[items.len()..].to_vec()), slots)
+                    })
+            }
+        }
+    }
+}
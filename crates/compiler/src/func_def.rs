@@ -1,25 +1,55 @@
+use std::rc::Rc;
+
+use crate::checker::Checker;
+use crate::lower::Lowerer;
+use crate::resolver::{Definition, NameKind, Resolver};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::type_expr::TypeExpr;
+use crate::types::Type;
 use crate::{Env, statement::Statement, utils};
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct FuncDef {
-    pub(crate) name: String,
-    pub(crate) params: Vec<String>,
-    pub(crate) body: Box<Statement>,
+    pub(crate) name: Symbol,
+    pub(crate) type_params: Vec<Symbol>,
+    pub(crate) params: Vec<Param>,
+    pub(crate) ret: Option<TypeExpr>,
+    pub(crate) body: Rc<Statement>,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Param {
+    pub(crate) name: Symbol,
+    pub(crate) ty: Option<TypeExpr>,
+    pub(crate) span: Span,
 }
 
 impl FuncDef {
     pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("fn", s)?;
+        let before = s;
+
+        let s = utils::tag("fn", before)?;
         let (s, _) = utils::extract_whitespace_non_empty(s)?;
 
         let (s, name) = utils::extract_ident(s)?;
+        let (s, type_params) = TypeExpr::new_params(s)?;
         let (s, _) = utils::extract_whitespace(s);
 
-        let (s, params) = utils::sequence(
-            |s| utils::extract_ident(s).map(|(s, ident)| (s, ident.to_string())),
-            utils::extract_whitespace,
-            s,
-        )?;
+        let (s, params) = utils::sequence(Param::new, |s| Ok(utils::extract_whitespace(s)), s)?;
+
+        let (s, ret) = match utils::tag("->", s) {
+            Ok(s) => {
+                let (s, _) = utils::extract_whitespace(s);
+                let (s, ret) = TypeExpr::new(s)?;
+                let (s, _) = utils::extract_whitespace(s);
+
+                (s, Some(ret))
+            }
+            Err(_) => (s, None),
+        };
 
         let s = utils::tag("=>", s)?;
         let (s, _) = utils::extract_whitespace(s);
@@ -29,24 +59,217 @@ impl FuncDef {
         Ok((
             s,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
+                type_params,
                 params,
-                body: Box::new(body),
+                ret,
+                body: Rc::new(body),
+                span: Span::new(before, s),
             },
         ))
     }
+
     pub(crate) fn eval(&self, env: &mut Env) -> Result<(), String> {
-        env.store_func(self.name.clone(), self.params.clone(), *self.body.clone());
+        let param_names = self.params.iter().map(|param| param.name).collect();
+        env.store_func(self.name, param_names, Rc::clone(&self.body));
         Ok(())
     }
+
+    /// Defines the function without looking at its body, so that functions defined earlier can
+    /// call it.
+    pub(crate) fn declare(&self, resolver: &mut Resolver) {
+        resolver.define(
+            self.name,
+            Definition {
+                kind: NameKind::Func,
+                arity: self.params.len(),
+                span: Some(self.span),
+            },
+        );
+    }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        // Defined before the body so that the function can call itself
+        if !resolver.is_defined_here(self.name, self.span) {
+            self.declare(resolver);
+        }
+
+        resolver.in_scope(|resolver| {
+            for param in &self.params {
+                resolver.define_param(param.name, param.span);
+            }
+
+            // Nothing else holds onto the body until the function is evaluated
+            Rc::make_mut(&mut self.body).resolve(resolver);
+        });
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<(), String> {
+        let param_names: Vec<_> = self.params.iter().map(|param| param.name).collect();
+
+        lowerer.define_func(self.name, &param_names, |lowerer| self.body.lower(lowerer))
+    }
+
+    /// Gives the function its id without lowering its body, so that functions lowered earlier
+    /// can call it.
+    pub(crate) fn declare_lowered(&self, lowerer: &mut Lowerer) {
+        lowerer.declare_func(self.name, self.params.len());
+    }
+
+    pub(crate) fn check(&self, env: &mut TypeEnv, checker: &mut Checker) {
+        Self::check_group(&[self], env, checker);
+    }
+
+    /// Checks functions defined one after another, which can call each other, all at once. Every
+    /// function is given its type before any of the bodies are checked, so calls between them
+    /// are checked like any other.
+    pub(crate) fn check_group(funcs: &[&Self], env: &mut TypeEnv, checker: &mut Checker) {
+        let signatures: Vec<_> = funcs
+            .iter()
+            .map(|func| func.signature(env, checker))
+            .collect();
+
+        // The functions can call each other, but only with the types of their parameters and
+        // return values, which are only generalized once every body has been checked. They are
+        // stored in an environment of their own so that this does not stop them from being
+        // generalized.
+        {
+            let mut group_env = env.create_child();
+            for (func, (_, params, ret)) in funcs.iter().zip(&signatures) {
+                group_env.store_func(func.name, params.clone(), ret.clone());
+            }
+
+            for (func, (type_params, params, ret)) in funcs.iter().zip(&signatures) {
+                let mut func_env = group_env.create_child();
+                for (name, ty) in func.type_params.iter().zip(type_params) {
+                    func_env.store_type_param(*name, ty.clone());
+                }
+
+                let mut child_env = func_env.create_child();
+                for (param, ty) in func.params.iter().zip(params) {
+                    child_env.store_binding(param.name, ty.clone());
+                }
+
+                checker.check_func_body(ret, func.span, |checker| {
+                    func.body.check(&mut child_env, checker)
+                });
+            }
+        }
+
+        for (func, (type_params, params, ret)) in funcs.iter().zip(signatures) {
+            func.check_type_params(&type_params, checker);
+
+            let Type::Func { params, ret } = checker.generalize(
+                &Type::Func {
+                    params,
+                    ret: Box::new(ret),
+                },
+                env,
+            ) else {
+                unreachable!()
+            };
+
+            env.store_func(func.name, params, *ret);
+        }
+    }
+
+    // Works out the types of the function's type parameters, parameters and return value, before
+    // anything is known about them from its body
+    fn signature(&self, env: &TypeEnv, checker: &mut Checker) -> (Vec<Type>, Vec<Type>, Type) {
+        TypeExpr::check_params(&self.type_params, self.span, checker);
+
+        // Type parameters stand in for whatever types the function is called with, so while the
+        // body is checked they are variables that must not be inferred to be anything in particular
+        let mut func_env = env.create_child();
+        let type_params: Vec<_> = self
+            .type_params
+            .iter()
+            .map(|name| {
+                let ty = checker.fresh_var();
+                func_env.store_type_param(*name, ty.clone());
+                ty
+            })
+            .collect();
+
+        // Annotations are optional, with the types of anything left unannotated being inferred
+        let mut annotated_or_fresh = |annotation: &Option<TypeExpr>| match annotation {
+            Some(annotation) => annotation.to_type(&func_env, checker),
+            None => checker.fresh_var(),
+        };
+
+        let params: Vec<_> = self
+            .params
+            .iter()
+            .map(|param| annotated_or_fresh(&param.ty))
+            .collect();
+        let ret = annotated_or_fresh(&self.ret);
+
+        (type_params, params, ret)
+    }
+
+    fn check_type_params(&self, type_params: &[Type], checker: &mut Checker) {
+        let mut seen: Vec<(u32, &Symbol)> = Vec::new();
+
+        for (name, ty) in self.type_params.iter().zip(type_params) {
+            match checker.resolve(ty) {
+                Type::Var(var) => match seen.iter().find(|(seen_var, _)| *seen_var == var) {
+                    Some((_, other)) => checker.error(
+                        format!(
+                            "type parameters ‘{}’ and ‘{}’ were inferred to be the same type",
+                            other, name,
+                        ),
+                        self.span,
+                    ),
+                    None => seen.push((var, name)),
+                },
+                ty => {
+                    let [ty] = checker.display([&ty]);
+                    checker.error(
+                        format!("type parameter ‘{}’ was inferred to be ‘{}’", name, ty),
+                        self.span,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Param {
+    fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let (s, name) = utils::extract_ident(s)?;
+
+        let (s, ty) = match TypeExpr::new_annotation(s) {
+            Ok((s, ty)) => (s, Some(ty)),
+            Err(_) => (s, None),
+        };
+
+        Ok((
+            s,
+            Self {
+                name: Symbol::intern(name),
+                ty,
+                span: Span::new(before, s),
+            },
+        ))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::symbol::Symbol;
+    use std::rc::Rc;
+
     use crate::{
+        checker::Checker,
         expr::{BindingUsage, Expr, Op},
-        func_def::FuncDef,
+        func_def::{FuncDef, Param},
+        span::Span,
         statement::Statement,
+        type_env::TypeEnv,
+        type_expr::TypeExpr,
+        types::Type,
     };
 
     #[test]
@@ -56,19 +279,101 @@ mod tests {
             Ok((
                 "",
                 FuncDef {
-                    name: "add".to_string(),
-                    params: vec!["x".to_string(), "y".to_string()],
-                    body: Box::new(Statement::Expr(Expr::Operation {
+                    name: Symbol::intern("add"),
+                    type_params: Vec::new(),
+                    params: vec![
+                        Param {
+                            name: Symbol::intern("x"),
+                            ty: None,
+                            span: Span::new("x y => x + y", " y => x + y"),
+                        },
+                        Param {
+                            name: Symbol::intern("y"),
+                            ty: None,
+                            span: Span::new("y => x + y", " => x + y"),
+                        }
+                    ],
+                    ret: None,
+                    body: Rc::new(Statement::Expr(Expr::Operation {
                         lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                            name: "x".to_string(),
+                            name: Symbol::intern("x"),
+                            kind: None,
+                            span: Span::new("x + y", " + y")
                         })),
                         rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                            name: "y".to_string(),
+                            name: Symbol::intern("y"),
+                            kind: None,
+                            span: Span::new("y", "")
                         })),
                         op: Op::Add,
+                        span: Span::new("x + y", ""),
                     })),
+                    span: Span::new("fn add x y => x + y", ""),
                 },
             )),
         );
     }
+
+    #[test]
+    fn parse_func_def_with_annotations() {
+        let int = |span| {
+            Some(TypeExpr {
+                name: Symbol::intern("Int"),
+                args: Vec::new(),
+                span,
+            })
+        };
+
+        let (s, func_def) = FuncDef::new("fn add x: Int y: Int -> Int => x + y").unwrap();
+
+        assert_eq!(s, "");
+        assert_eq!(
+            func_def.params,
+            vec![
+                Param {
+                    name: Symbol::intern("x"),
+                    ty: int(Span::new(
+                        "Int y: Int -> Int => x + y",
+                        " y: Int -> Int => x + y",
+                    )),
+                    span: Span::new("x: Int y: Int -> Int => x + y", " y: Int -> Int => x + y"),
+                },
+                Param {
+                    name: Symbol::intern("y"),
+                    ty: int(Span::new("Int -> Int => x + y", " -> Int => x + y")),
+                    span: Span::new("y: Int -> Int => x + y", " -> Int => x + y"),
+                },
+            ],
+        );
+        assert_eq!(func_def.ret, int(Span::new("Int => x + y", " => x + y")));
+    }
+
+    #[test]
+    fn check_func_def_with_annotated_param() {
+        let (_, func_def) = FuncDef::new("fn id x: Str => x").unwrap();
+        let mut env = TypeEnv::default();
+
+        func_def.check(&mut env, &mut Checker::default());
+
+        assert_eq!(
+            env.type_of("id"),
+            Some(Type::Func {
+                params: vec![Type::Str],
+                ret: Box::new(Type::Str),
+            }),
+        );
+    }
+
+    #[test]
+    fn check_func_def_against_return_annotation() {
+        let (_, func_def) = FuncDef::new("fn f x -> Str => x + 1").unwrap();
+        let mut checker = Checker::default();
+
+        func_def.check(&mut TypeEnv::default(), &mut checker);
+
+        assert_eq!(
+            checker.diagnostics[0].message,
+            "function returns ‘Str’ but its body evaluates to ‘Int’",
+        );
+    }
 }
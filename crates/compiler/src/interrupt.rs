@@ -0,0 +1,30 @@
+use crate::val::Val;
+
+/// Anything that stops evaluation before a value has been produced. Besides errors this covers
+/// control flow such as `break` and `return`, which unwinds through every expression enclosing it
+/// until it reaches the construct that handles it.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Interrupt {
+    Error(String),
+    Break,
+    Continue,
+    Return(Val),
+}
+
+impl Interrupt {
+    /// Turns control flow that escaped every construct able to handle it into an error.
+    pub(crate) fn into_error(self) -> String {
+        match self {
+            Self::Error(msg) => msg,
+            Self::Break => "cannot break outside of a loop".to_string(),
+            Self::Continue => "cannot continue outside of a loop".to_string(),
+            Self::Return(_) => "cannot return outside of a function".to_string(),
+        }
+    }
+}
+
+impl From<String> for Interrupt {
+    fn from(msg: String) -> Self {
+        Self::Error(msg)
+    }
+}
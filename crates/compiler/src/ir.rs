@@ -0,0 +1,697 @@
+mod build;
+mod eval;
+mod ssa;
+mod verify;
+
+use std::fmt;
+
+use crate::builtins::Builtin;
+use crate::expr::Op;
+use crate::lower::Program;
+use crate::symbol::Symbol;
+use crate::val::Val;
+
+impl Program {
+    /// Lists the program in the mid-level IR, once it has been converted into SSA form. The module
+    /// is checked to be well formed both before and after converting it, and an error describes
+    /// the first problem found otherwise.
+    pub fn dump_ir(&self) -> Result<String, String> {
+        let module = Module::build(self);
+        module
+            .verify()
+            .map_err(|msg| format!("built invalid IR: {}", msg))?;
+
+        let module = module.into_ssa();
+        module
+            .verify()
+            .map_err(|msg| format!("converted into invalid IR: {}", msg))?;
+
+        Ok(module.to_string())
+    }
+}
+
+/// A program as a control flow graph of basic blocks for each function, which unlike the nested
+/// `Node`s of a `Program` makes every jump explicit.
+///
+/// Values are numbered and defined exactly once. Blocks take parameters rather than starting with
+/// phi nodes, so jumping to a block passes it the values that differ between its predecessors.
+/// Bindings start off stored in the slots of their function's frame, and stay there when
+/// converting into SSA form only if a nested function can read them.
+#[derive(Debug, Clone)]
+pub(crate) struct Module {
+    pub(crate) main: Function,
+    pub(crate) funcs: Vec<Function>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Function {
+    pub(crate) name: Option<Symbol>,
+    pub(crate) num_params: usize,
+    // Besides the slots of bindings, every loop gets a slot to count how far through it is
+    pub(crate) num_slots: usize,
+    // The first block is where the function starts, and takes its arguments as parameters
+    pub(crate) blocks: Vec<Block>,
+    pub(crate) num_values: usize,
+    // The slots that functions defined inside this one read, which have to stay in the frame
+    pub(crate) observed_slots: Vec<usize>,
+    pub(crate) is_ssa: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Value(pub(crate) usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct BlockId(pub(crate) usize);
+
+#[derive(Debug, Clone)]
+pub(crate) struct Block {
+    pub(crate) params: Vec<Value>,
+    pub(crate) insts: Vec<Inst>,
+    pub(crate) terminator: Terminator,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Inst {
+    Const {
+        dest: Value,
+        val: Val,
+    },
+    // Reads a slot of the current frame, which only happens before converting into SSA form
+    Load {
+        dest: Value,
+        slot: usize,
+    },
+    LoadOuter {
+        dest: Value,
+        depth: usize,
+        slot: usize,
+    },
+    Store {
+        slot: usize,
+        src: Value,
+    },
+    Binary {
+        dest: Value,
+        op: Op,
+        lhs: Value,
+        rhs: Value,
+    },
+    Call {
+        dest: Value,
+        func: usize,
+        depth: usize,
+        args: Vec<Value>,
+    },
+    Builtin {
+        dest: Value,
+        builtin: Builtin,
+        args: Vec<Value>,
+    },
+    Construct {
+        dest: Value,
+        enum_name: Symbol,
+        variant: Symbol,
+        args: Vec<Value>,
+    },
+    List {
+        dest: Value,
+        items: Vec<Value>,
+    },
+    Map {
+        dest: Value,
+        entries: Vec<(Value, Value)>,
+    },
+    Index {
+        dest: Value,
+        target: Value,
+        index: Value,
+    },
+    Range {
+        dest: Value,
+        start: Value,
+        end: Value,
+        inclusive: bool,
+    },
+    // Whether a value is the given variant, as a boolean
+    IsVariant {
+        dest: Value,
+        val: Value,
+        enum_name: Symbol,
+        variant: Symbol,
+    },
+    // Whether a value is a list with `len` items, or at least that many if `rest` is set, as a
+    // boolean
+    IsList {
+        dest: Value,
+        val: Value,
+        len: usize,
+        rest: bool,
+    },
+    // A field of a value already known to be a variant with enough fields, or an item of a value
+    // already known to be a list with enough items
+    Field {
+        dest: Value,
+        val: Value,
+        index: usize,
+    },
+    // The items of a value already known to be a list with enough items, from `start` on
+    Rest {
+        dest: Value,
+        val: Value,
+        start: usize,
+    },
+    // The list of items a loop goes through, and how many there are
+    Items {
+        dest: Value,
+        iterable: Value,
+    },
+    Len {
+        dest: Value,
+        list: Value,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Terminator {
+    Jump(Target),
+    // Fails unless the condition is a boolean, which only a match guard could fail to be
+    Branch {
+        cond: Value,
+        then: Target,
+        otherwise: Target,
+    },
+    Return(Value),
+    NoMatch(Value),
+    Fail(String),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Target {
+    pub(crate) block: BlockId,
+    pub(crate) args: Vec<Value>,
+}
+
+impl Inst {
+    pub(crate) fn dest(&self) -> Option<Value> {
+        self.clone().dest_mut().copied()
+    }
+
+    pub(crate) fn dest_mut(&mut self) -> Option<&mut Value> {
+        match self {
+            Self::Store { .. } => None,
+            Self::Const { dest, .. }
+            | Self::Load { dest, .. }
+            | Self::LoadOuter { dest, .. }
+            | Self::Binary { dest, .. }
+            | Self::Call { dest, .. }
+            | Self::Builtin { dest, .. }
+            | Self::Construct { dest, .. }
+            | Self::List { dest, .. }
+            | Self::Map { dest, .. }
+            | Self::Index { dest, .. }
+            | Self::Range { dest, .. }
+            | Self::IsVariant { dest, .. }
+            | Self::IsList { dest, .. }
+            | Self::Field { dest, .. }
+            | Self::Rest { dest, .. }
+            | Self::Items { dest, .. }
+            | Self::Len { dest, .. } => Some(dest),
+        }
+    }
+
+    /// Calls `f` with every value the instruction uses, allowing them to be replaced.
+    pub(crate) fn for_each_use(&mut self, mut f: impl FnMut(&mut Value)) {
+        match self {
+            Self::Const { .. } | Self::Load { .. } | Self::LoadOuter { .. } => {}
+            Self::Store { src: val, .. }
+            | Self::IsVariant { val, .. }
+            | Self::IsList { val, .. }
+            | Self::Field { val, .. }
+            | Self::Rest { val, .. }
+            | Self::Items { iterable: val, .. }
+            | Self::Len { list: val, .. } => f(val),
+            Self::Binary { lhs, rhs, .. } => {
+                f(lhs);
+                f(rhs);
+            }
+            Self::Index { target, index, .. } => {
+                f(target);
+                f(index);
+            }
+            Self::Range { start, end, .. } => {
+                f(start);
+                f(end);
+            }
+            Self::Call { args, .. }
+            | Self::Builtin { args, .. }
+            | Self::Construct { args, .. }
+            | Self::List { items: args, .. } => args.iter_mut().for_each(f),
+            Self::Map { entries, .. } => {
+                for (key, val) in entries {
+                    f(key);
+                    f(val);
+                }
+            }
+        }
+    }
+}
+
+impl Terminator {
+    pub(crate) fn targets(&self) -> Vec<&Target> {
+        match self {
+            Self::Jump(target) => vec![target],
+            Self::Branch {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Self::Return(_) | Self::NoMatch(_) | Self::Fail(_) => Vec::new(),
+        }
+    }
+
+    pub(crate) fn targets_mut(&mut self) -> Vec<&mut Target> {
+        match self {
+            Self::Jump(target) => vec![target],
+            Self::Branch {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Self::Return(_) | Self::NoMatch(_) | Self::Fail(_) => Vec::new(),
+        }
+    }
+
+    /// Calls `f` with every value the terminator uses, including those passed to other blocks.
+    pub(crate) fn for_each_use(&mut self, mut f: impl FnMut(&mut Value)) {
+        match self {
+            Self::Branch { cond: val, .. } | Self::Return(val) | Self::NoMatch(val) => f(val),
+            Self::Jump(_) | Self::Fail(_) => {}
+        }
+
+        for target in self.targets_mut() {
+            target.args.iter_mut().for_each(&mut f);
+        }
+    }
+}
+
+impl Function {
+    pub(crate) fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0]
+    }
+
+    /// The blocks that can jump to each block, each listed once.
+    pub(crate) fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+
+        for (id, block) in self.blocks.iter().enumerate() {
+            for target in block.terminator.targets() {
+                if !preds[target.block.0].contains(&BlockId(id)) {
+                    preds[target.block.0].push(BlockId(id));
+                }
+            }
+        }
+
+        preds
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.main)?;
+
+        for (id, func) in self.funcs.iter().enumerate() {
+            writeln!(f)?;
+            write!(f, "fn #{} ", id)?;
+            write!(f, "{}", func)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {}", what),
+            _ => format!("{} {}s", n, what),
+        };
+
+        match self.name {
+            Some(name) => writeln!(
+                f,
+                "{} ({}, {})",
+                name,
+                plural(self.num_params, "parameter"),
+                plural(self.num_slots, "slot"),
+            )?,
+            None => writeln!(f, "main ({})", plural(self.num_slots, "slot"))?,
+        }
+
+        for (id, block) in self.blocks.iter().enumerate() {
+            write!(f, "{}", BlockId(id))?;
+            if !block.params.is_empty() {
+                write!(f, "({})", list(&block.params))?;
+            }
+            writeln!(f, ":")?;
+
+            for inst in &block.insts {
+                writeln!(f, "  {}", inst)?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.block)?;
+        if !self.args.is_empty() {
+            write!(f, "({})", list(&self.args))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(dest) = self.dest() {
+            write!(f, "{} = ", dest)?;
+        }
+
+        match self {
+            Self::Const { val, .. } => write!(f, "const {}", val),
+            Self::Load { slot, .. } => write!(f, "load {}", slot),
+            Self::LoadOuter { depth, slot, .. } => write!(f, "load_outer {}, {}", depth, slot),
+            Self::Store { slot, src } => write!(f, "store {}, {}", slot, src),
+            Self::Binary { op, lhs, rhs, .. } => {
+                let mnemonic = match op {
+                    Op::Add => "add",
+                    Op::Sub => "sub",
+                    Op::Mul => "mul",
+                    Op::Div => "div",
+                    Op::Eq => "eq",
+                    Op::NotEq => "not_eq",
+                    Op::Lt => "lt",
+                    Op::LtEq => "lt_eq",
+                    Op::Gt => "gt",
+                    Op::GtEq => "gt_eq",
+                };
+                write!(f, "{} {}, {}", mnemonic, lhs, rhs)
+            }
+            Self::Call {
+                func, depth, args, ..
+            } => write!(f, "call #{}, {}({})", func, depth, list(args)),
+            Self::Builtin { builtin, args, .. } => write!(f, "{}({})", builtin.name, list(args)),
+            Self::Construct {
+                enum_name,
+                variant,
+                args,
+                ..
+            } => write!(f, "{}::{}({})", enum_name, variant, list(args)),
+            Self::List { items, .. } => write!(f, "list [{}]", list(items)),
+            Self::Map { entries, .. } => {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, val)| format!("{}: {}", key, val))
+                    .collect();
+                write!(f, "map #{{{}}}", entries.join(", "))
+            }
+            Self::Index { target, index, .. } => write!(f, "index {}, {}", target, index),
+            Self::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let range = if *inclusive {
+                    "range_inclusive"
+                } else {
+                    "range"
+                };
+                write!(f, "{} {}, {}", range, start, end)
+            }
+            Self::IsVariant {
+                val,
+                enum_name,
+                variant,
+                ..
+            } => write!(f, "is {}, {}::{}", val, enum_name, variant),
+            Self::IsList { val, len, rest, .. } => {
+                let rest = if *rest { ".." } else { "" };
+                write!(f, "is_list {}, {}{}", val, len, rest)
+            }
+            Self::Field { val, index, .. } => write!(f, "field {}, {}", val, index),
+            Self::Rest { val, start, .. } => write!(f, "rest {}, {}", val, start),
+            Self::Items { iterable, .. } => write!(f, "items {}", iterable),
+            Self::Len { list, .. } => write!(f, "len {}", list),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Jump(target) => write!(f, "jump {}", target),
+            Self::Branch {
+                cond,
+                then,
+                otherwise,
+            } => write!(f, "branch {}, {}, {}", cond, then, otherwise),
+            Self::Return(val) => write!(f, "return {}", val),
+            Self::NoMatch(val) => write!(f, "no_match {}", val),
+            Self::Fail(msg) => write!(f, "fail {:?}", msg),
+        }
+    }
+}
+
+fn list(vals: &[Value]) -> String {
+    let vals: Vec<_> = vals.iter().map(Value::to_string).collect();
+    vals.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lints, TypeEnv};
+
+    fn lower(s: &str) -> Program {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.lower().unwrap()
+    }
+
+    // Runs the program both before and after converting into SSA form, checking that the module
+    // is well formed and gives the same result as the program it was built from each time
+    fn eval(s: &str) -> Result<Val, String> {
+        let program = lower(s);
+        let expected = program.eval();
+
+        let module = Module::build(&program);
+        assert_eq!(module.verify(), Ok(()));
+        assert_eq!(module.eval(), expected);
+
+        let module = module.into_ssa();
+        assert_eq!(module.verify(), Ok(()));
+        assert_eq!(module.eval(), expected);
+
+        expected
+    }
+
+    #[test]
+    fn eval_recursive_func() {
+        assert_eq!(
+            eval(
+                "{
+    fn fib n => match n {
+        0 => 0,
+        1 => 1,
+        n => {
+            let a = n - 1
+            let b = n - 2
+            fib a + fib b
+        },
+    }
+    fib 15
+}"
+            ),
+            Ok(Val::Number(610)),
+        );
+    }
+
+    #[test]
+    fn eval_list_patterns() {
+        assert_eq!(
+            eval(
+                "{
+    fn sum items => match items {
+        [] => 0,
+        [x, ..rest] => {
+            let total = sum rest
+            x + total
+        },
+    }
+    let a = sum [1, 2, 3]
+    let b = match [4, 5] { [x] => x, [_, y] => y, _ => 0 }
+    a + b
+}"
+            ),
+            Ok(Val::Number(11)),
+        );
+    }
+
+    #[test]
+    fn eval_nested_funcs_using_outer_bindings() {
+        assert_eq!(
+            eval(
+                "{
+    let offset = 1
+    fn scale x => {
+        let factor = 3
+        fn times y => {
+            let scaled = y * factor
+            scaled + offset
+        }
+        times x
+    }
+    let a = scale 5
+    let b = scale 2
+    a + b
+}"
+            ),
+            Ok(Val::Number(23)),
+        );
+    }
+
+    #[test]
+    fn eval_loops_and_early_return() {
+        assert_eq!(
+            eval(
+                "{
+    fn find limit items => {
+        for item in items {
+            match item > limit {
+                true => { return item },
+                false => { continue },
+            }
+        }
+        0
+    }
+    let total = find 2 [1, 2, 3, 4]
+    let missing = find 9 [1, 2]
+    for i in 0..=10 {
+        match i {
+            5 => { break },
+            _ => i,
+        }
+    }
+    [total, missing]
+}"
+            ),
+            Ok(Val::List(vec![Val::Number(3), Val::Number(0)])),
+        );
+    }
+
+    #[test]
+    fn eval_matches_on_variants_with_guards() {
+        assert_eq!(
+            eval(
+                "{
+    enum Shape { Square(side), Rect(width, height), Empty }
+    fn area shape => match shape {
+        Square(side) => side * side,
+        Rect(width, height) if width == height => 0 - 1,
+        Rect(width, height) => width * height,
+        Empty => 0,
+    }
+    let a = Square 3
+    let b = Rect 2 2
+    let c = Rect 2 5
+    [area a, area b, area c, area Empty]
+}"
+            ),
+            Ok(Val::List(vec![
+                Val::Number(9),
+                Val::Number(-1),
+                Val::Number(10),
+                Val::Number(0),
+            ])),
+        );
+    }
+
+    #[test]
+    fn eval_runtime_errors() {
+        assert_eq!(
+            eval("[1, 2][5]"),
+            Err("index 5 is out of bounds for a list of length 2".to_string()),
+        );
+        assert_eq!(
+            eval("{ fn f => break f }"),
+            Err("cannot break outside of a loop".to_string()),
+        );
+        assert_eq!(
+            eval("return 1"),
+            Err("cannot return outside of a function".to_string()),
+        );
+        assert_eq!(
+            eval(
+                "match 3 {
+    1 => 1,
+    2 => 2,
+}"
+            ),
+            Err("no arm of match expression matched ‘3’".to_string()),
+        );
+    }
+
+    #[test]
+    fn pass_values_that_change_in_loop_as_block_params() {
+        assert_eq!(
+            lower(
+                "{
+    let items = [1, 2]
+    for item in items {
+        item + 1
+    }
+}"
+            )
+            .dump_ir(),
+            Ok("main (3 slots)
+b0:
+  %0 = const 1
+  %1 = const 2
+  %2 = list [%0, %1]
+  %3 = const Unit
+  %4 = items %2
+  %5 = len %4
+  %6 = const 0
+  jump b1(%6)
+b1(%7):
+  %8 = lt %7, %5
+  branch %8, b2, b4
+b2:
+  %9 = index %4, %7
+  %10 = const 1
+  %11 = add %9, %10
+  jump b3
+b3:
+  %12 = const 1
+  %13 = add %7, %12
+  jump b1(%13)
+b4:
+  %14 = const Unit
+  return %14
+"
+            .to_string()),
+        );
+    }
+}
@@ -0,0 +1,494 @@
+use super::{Block, BlockId, Function, Inst, Module, Target, Terminator, Value};
+use crate::expr::Op;
+use crate::interrupt::Interrupt;
+use crate::lower::{Func, Local, Node, Program, SlotPattern};
+use crate::val::Val;
+
+impl Module {
+    /// Builds the control flow graph of every function in the program, with bindings stored in
+    /// slots.
+    pub(crate) fn build(program: &Program) -> Self {
+        let captures = program.captures();
+
+        Self {
+            main: Builder::build(&program.main, &captures, true),
+            funcs: program
+                .funcs
+                .iter()
+                .map(|func| Builder::build(func, &captures, false))
+                .collect(),
+        }
+    }
+}
+
+// The functions defined inside a function can only be called from inside it, so everything they
+// read from its frame shows up in what the calls it makes directly to them capture
+fn find_observed_slots(node: &Node, captures: &[Vec<Local>], observed: &mut Vec<usize>) {
+    if let Node::Call { func, depth: 0, .. } = node {
+        for capture in &captures[*func] {
+            if capture.depth == 1 && !observed.contains(&capture.slot) {
+                observed.push(capture.slot);
+            }
+        }
+    }
+
+    node.for_each_child(|child| find_observed_slots(child, captures, observed));
+}
+
+struct Loop {
+    next: BlockId,
+    exit: BlockId,
+}
+
+struct Builder {
+    // Blocks are only given their terminator once they are finished
+    blocks: Vec<(Vec<Value>, Vec<Inst>, Option<Terminator>)>,
+    current: BlockId,
+    num_values: usize,
+    num_slots: usize,
+    loops: Vec<Loop>,
+    is_main: bool,
+}
+
+impl Builder {
+    fn build(func: &Func, captures: &[Vec<Local>], is_main: bool) -> Function {
+        let mut builder = Self {
+            blocks: Vec::new(),
+            current: BlockId(0),
+            num_values: 0,
+            num_slots: func.num_slots,
+            loops: Vec::new(),
+            is_main,
+        };
+
+        let entry = builder.block();
+        for slot in 0..func.num_params {
+            let param = builder.param(entry);
+            builder.push(Inst::Store { slot, src: param });
+        }
+
+        let result = builder.node(&func.body);
+        builder.terminate(Terminator::Return(result));
+
+        let mut function = builder.finish(func);
+        find_observed_slots(&func.body, captures, &mut function.observed_slots);
+        function.observed_slots.sort();
+        function
+    }
+
+    // Drops the blocks nothing can reach, such as those following a `break`, and numbers the rest
+    // in the order they were created
+    fn finish(self, func: &Func) -> Function {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![BlockId(0)];
+        while let Some(id) = stack.pop() {
+            if !reachable[id.0] {
+                reachable[id.0] = true;
+                let terminator = self.blocks[id.0].2.as_ref().unwrap();
+                stack.extend(terminator.targets().iter().map(|target| target.block));
+            }
+        }
+
+        let mut renumbered = vec![None; self.blocks.len()];
+        let mut next = 0;
+        for (id, reachable) in reachable.iter().enumerate() {
+            if *reachable {
+                renumbered[id] = Some(BlockId(next));
+                next += 1;
+            }
+        }
+
+        let blocks = self
+            .blocks
+            .into_iter()
+            .zip(reachable)
+            .filter(|(_, reachable)| *reachable)
+            .map(|((params, insts, terminator), _)| {
+                let mut terminator = terminator.unwrap();
+                for target in terminator.targets_mut() {
+                    target.block = renumbered[target.block.0].unwrap();
+                }
+                Block {
+                    params,
+                    insts,
+                    terminator,
+                }
+            })
+            .collect();
+
+        Function {
+            name: func.name,
+            num_params: func.num_params,
+            num_slots: self.num_slots,
+            blocks,
+            num_values: self.num_values,
+            observed_slots: Vec::new(),
+            is_ssa: false,
+        }
+    }
+
+    fn value(&mut self) -> Value {
+        self.num_values += 1;
+        Value(self.num_values - 1)
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push((Vec::new(), Vec::new(), None));
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn param(&mut self, block: BlockId) -> Value {
+        let param = self.value();
+        self.blocks[block.0].0.push(param);
+        param
+    }
+
+    fn push(&mut self, inst: Inst) {
+        self.blocks[self.current.0].1.push(inst);
+    }
+
+    // Adds an instruction defining a new value, returning it
+    fn define(&mut self, inst: impl FnOnce(Value) -> Inst) -> Value {
+        let dest = self.value();
+        self.push(inst(dest));
+        dest
+    }
+
+    fn constant(&mut self, val: Val) -> Value {
+        self.define(|dest| Inst::Const { dest, val })
+    }
+
+    // Ends the current block. Anything generated afterwards goes in a new block, which nothing
+    // jumps to unless it is switched to first.
+    fn terminate(&mut self, terminator: Terminator) {
+        self.blocks[self.current.0].2 = Some(terminator);
+        self.current = self.block();
+    }
+
+    fn jump(&mut self, block: BlockId, args: Vec<Value>) {
+        self.terminate(Terminator::Jump(Target { block, args }));
+    }
+
+    fn branch(&mut self, cond: Value, then: BlockId, otherwise: BlockId) {
+        self.terminate(Terminator::Branch {
+            cond,
+            then: Target {
+                block: then,
+                args: Vec::new(),
+            },
+            otherwise: Target {
+                block: otherwise,
+                args: Vec::new(),
+            },
+        });
+    }
+
+    // Control flow that has nowhere to go fails the same way it does in the interpreter, leaving
+    // `()` behind for the unreachable code after it
+    fn fail(&mut self, interrupt: Interrupt) -> Value {
+        self.terminate(Terminator::Fail(interrupt.into_error()));
+        self.constant(Val::Unit)
+    }
+
+    fn all(&mut self, nodes: &[Node]) -> Vec<Value> {
+        nodes.iter().map(|node| self.node(node)).collect()
+    }
+
+    fn node(&mut self, node: &Node) -> Value {
+        match node {
+            Node::Const(val) => self.constant(val.clone()),
+            Node::Local(Local { depth: 0, slot }) => {
+                let slot = *slot;
+                self.define(|dest| Inst::Load { dest, slot })
+            }
+            Node::Local(Local { depth, slot }) => {
+                let (depth, slot) = (*depth, *slot);
+                self.define(|dest| Inst::LoadOuter { dest, depth, slot })
+            }
+            Node::Define { slot, val } => {
+                let src = self.node(val);
+                self.push(Inst::Store { slot: *slot, src });
+                self.constant(Val::Unit)
+            }
+            Node::Operation { lhs, rhs, op } => {
+                let lhs = self.node(lhs);
+                let rhs = self.node(rhs);
+                let op = *op;
+                self.define(|dest| Inst::Binary { dest, op, lhs, rhs })
+            }
+            Node::Call { func, depth, args } => {
+                let args = self.all(args);
+                let (func, depth) = (*func, *depth);
+                self.define(|dest| Inst::Call {
+                    dest,
+                    func,
+                    depth,
+                    args,
+                })
+            }
+            Node::Construct {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let args = self.all(args);
+                let (enum_name, variant) = (*enum_name, *variant);
+                self.define(|dest| Inst::Construct {
+                    dest,
+                    enum_name,
+                    variant,
+                    args,
+                })
+            }
+            Node::Builtin { builtin, args } => {
+                let args = self.all(args);
+                let builtin = *builtin;
+                self.define(|dest| Inst::Builtin {
+                    dest,
+                    builtin,
+                    args,
+                })
+            }
+            Node::Block(nodes) => match nodes.split_last() {
+                Some((last, nodes)) => {
+                    self.all(nodes);
+                    self.node(last)
+                }
+                None => self.constant(Val::Unit),
+            },
+            Node::Match { scrutinee, arms } => {
+                let scrutinee = self.node(scrutinee);
+                let end = self.block();
+                let result = self.param(end);
+
+                for arm in arms {
+                    let next = self.block();
+                    self.pattern(&arm.pattern, scrutinee, next);
+
+                    if let Some(guard) = &arm.guard {
+                        let guard = self.node(guard);
+                        let body = self.block();
+                        self.branch(guard, body, next);
+                        self.current = body;
+                    }
+
+                    let body = self.node(&arm.body);
+                    self.jump(end, vec![body]);
+                    self.current = next;
+                }
+
+                self.terminate(Terminator::NoMatch(scrutinee));
+                self.current = end;
+                result
+            }
+            Node::List(items) => {
+                let items = self.all(items);
+                self.define(|dest| Inst::List { dest, items })
+            }
+            Node::Map(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, val)| (self.node(key), self.node(val)))
+                    .collect();
+                self.define(|dest| Inst::Map { dest, entries })
+            }
+            Node::Index { target, index } => {
+                let target = self.node(target);
+                let index = self.node(index);
+                self.define(|dest| Inst::Index {
+                    dest,
+                    target,
+                    index,
+                })
+            }
+            Node::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.node(start);
+                let end = self.node(end);
+                let inclusive = *inclusive;
+                self.define(|dest| Inst::Range {
+                    dest,
+                    start,
+                    end,
+                    inclusive,
+                })
+            }
+            Node::For {
+                slot,
+                iterable,
+                body,
+            } => self.for_loop(*slot, iterable, body),
+            Node::Break => match self.loops.last() {
+                Some(innermost) => {
+                    let exit = innermost.exit;
+                    self.jump(exit, Vec::new());
+                    self.constant(Val::Unit)
+                }
+                None => self.fail(Interrupt::Break),
+            },
+            Node::Continue => match self.loops.last() {
+                Some(innermost) => {
+                    let next = innermost.next;
+                    self.jump(next, Vec::new());
+                    self.constant(Val::Unit)
+                }
+                None => self.fail(Interrupt::Continue),
+            },
+            Node::Return(val) => {
+                let val = self.node(val);
+                if self.is_main {
+                    self.fail(Interrupt::Return(Val::Unit))
+                } else {
+                    self.terminate(Terminator::Return(val));
+                    self.constant(Val::Unit)
+                }
+            }
+            Node::Spanned { node, .. } => self.node(node),
+        }
+    }
+
+    // Loops go through a list of their items by position, which is kept in a slot of its own
+    fn for_loop(&mut self, slot: usize, iterable: &Node, body: &Node) -> Value {
+        let iterable = self.node(iterable);
+        let items = self.define(|dest| Inst::Items { dest, iterable });
+        let len = self.define(|dest| Inst::Len { dest, list: items });
+
+        let position = self.num_slots;
+        self.num_slots += 1;
+        let zero = self.constant(Val::Number(0));
+        self.push(Inst::Store {
+            slot: position,
+            src: zero,
+        });
+
+        let (header, first, next, exit) = (self.block(), self.block(), self.block(), self.block());
+        self.jump(header, Vec::new());
+
+        self.current = header;
+        let idx = self.define(|dest| Inst::Load {
+            dest,
+            slot: position,
+        });
+        let more = self.define(|dest| Inst::Binary {
+            dest,
+            op: Op::Lt,
+            lhs: idx,
+            rhs: len,
+        });
+        self.branch(more, first, exit);
+
+        self.current = first;
+        let item = self.define(|dest| Inst::Index {
+            dest,
+            target: items,
+            index: idx,
+        });
+        self.push(Inst::Store { slot, src: item });
+
+        self.loops.push(Loop { next, exit });
+        self.node(body);
+        self.loops.pop();
+        self.jump(next, Vec::new());
+
+        self.current = next;
+        let idx = self.define(|dest| Inst::Load {
+            dest,
+            slot: position,
+        });
+        let one = self.constant(Val::Number(1));
+        let src = self.define(|dest| Inst::Binary {
+            dest,
+            op: Op::Add,
+            lhs: idx,
+            rhs: one,
+        });
+        self.push(Inst::Store {
+            slot: position,
+            src,
+        });
+        self.jump(header, Vec::new());
+
+        self.current = exit;
+        self.constant(Val::Unit)
+    }
+
+    // Stores whatever `pattern` binds in its slots if `val` matches it, and jumps to `otherwise`
+    // if it does not
+    fn pattern(&mut self, pattern: &SlotPattern, val: Value, otherwise: BlockId) {
+        match pattern {
+            SlotPattern::Wildcard => {}
+            SlotPattern::Number(_) | SlotPattern::Bool(_) => {
+                let expected = match pattern {
+                    SlotPattern::Number(n) => Val::Number(*n),
+                    _ => Val::Bool(matches!(pattern, SlotPattern::Bool(true))),
+                };
+                let expected = self.constant(expected);
+                let matched = self.define(|dest| Inst::Binary {
+                    dest,
+                    op: Op::Eq,
+                    lhs: val,
+                    rhs: expected,
+                });
+
+                let then = self.block();
+                self.branch(matched, then, otherwise);
+                self.current = then;
+            }
+            SlotPattern::Binding(slot) => self.push(Inst::Store {
+                slot: *slot,
+                src: val,
+            }),
+            SlotPattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let (enum_name, variant) = (*enum_name, *variant);
+                let matched = self.define(|dest| Inst::IsVariant {
+                    dest,
+                    val,
+                    enum_name,
+                    variant,
+                });
+
+                let then = self.block();
+                self.branch(matched, then, otherwise);
+                self.current = then;
+
+                for (index, field) in fields.iter().enumerate() {
+                    let field_val = self.define(|dest| Inst::Field { dest, val, index });
+                    self.pattern(field, field_val, otherwise);
+                }
+            }
+            SlotPattern::List { items, rest } => {
+                let (len, has_rest) = (items.len(), rest.is_some());
+                let matched = self.define(|dest| Inst::IsList {
+                    dest,
+                    val,
+                    len,
+                    rest: has_rest,
+                });
+
+                let then = self.block();
+                self.branch(matched, then, otherwise);
+                self.current = then;
+
+                for (index, item) in items.iter().enumerate() {
+                    let item_val = self.define(|dest| Inst::Field { dest, val, index });
+                    self.pattern(item, item_val, otherwise);
+                }
+                if let Some(rest) = rest {
+                    let rest_val = self.define(|dest| Inst::Rest {
+                        dest,
+                        val,
+                        start: len,
+                    });
+                    self.pattern(rest, rest_val, otherwise);
+                }
+            }
+        }
+    }
+}
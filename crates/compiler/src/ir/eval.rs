@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use super::{Function, Inst, Module, Terminator};
+use crate::expr;
+use crate::for_loop;
+use crate::pattern;
+use crate::val::{MapKey, Val};
+
+// The slots of a single call, linked to the frame of the function it was defined in by its
+// position on the stack of frames
+struct Frame {
+    slots: Vec<Val>,
+    parent: Option<usize>,
+}
+
+impl Module {
+    /// Runs the program, which behaves exactly like evaluating the `Program` it was built from.
+    pub(crate) fn eval(&self) -> Result<Val, String> {
+        let mut frames = Vec::new();
+        self.call(&self.main, Vec::new(), None, &mut frames)
+    }
+
+    fn call(
+        &self,
+        func: &Function,
+        args: Vec<Val>,
+        parent: Option<usize>,
+        frames: &mut Vec<Frame>,
+    ) -> Result<Val, String> {
+        frames.push(Frame {
+            slots: vec![Val::Unit; func.num_slots],
+            parent,
+        });
+        let result = self.run(func, args, frames);
+        frames.pop();
+        result
+    }
+
+    fn run(&self, func: &Function, args: Vec<Val>, frames: &mut Vec<Frame>) -> Result<Val, String> {
+        let frame = frames.len() - 1;
+        let ancestor = |frames: &[Frame], depth: usize| {
+            (0..depth).fold(frame, |frame, _| frames[frame].parent.unwrap())
+        };
+
+        let mut values = vec![Val::Unit; func.num_values];
+        let mut block = func.block(super::BlockId(0));
+        let mut args = args;
+
+        loop {
+            for (param, arg) in block.params.iter().zip(args) {
+                values[param.0] = arg;
+            }
+
+            for inst in &block.insts {
+                let get = |val: super::Value| values[val.0].clone();
+                let get_all = |vals: &[super::Value]| vals.iter().map(|val| get(*val)).collect();
+
+                let result = match inst {
+                    Inst::Const { val, .. } => val.clone(),
+                    Inst::Load { slot, .. } => frames[frame].slots[*slot].clone(),
+                    Inst::LoadOuter { depth, slot, .. } => {
+                        frames[ancestor(frames, *depth)].slots[*slot].clone()
+                    }
+                    Inst::Store { slot, src } => {
+                        frames[frame].slots[*slot] = get(*src);
+                        continue;
+                    }
+                    Inst::Binary { op, lhs, rhs, .. } => op.apply(get(*lhs), get(*rhs))?,
+                    Inst::Call {
+                        func: id,
+                        depth,
+                        args,
+                        ..
+                    } => {
+                        let parent = ancestor(frames, *depth);
+                        self.call(&self.funcs[*id], get_all(args), Some(parent), frames)?
+                    }
+                    Inst::Builtin { builtin, args, .. } => (builtin.func)(get_all(args))?,
+                    Inst::Construct {
+                        enum_name,
+                        variant,
+                        args,
+                        ..
+                    } => Val::Variant {
+                        enum_name: *enum_name,
+                        variant: *variant,
+                        fields: get_all(args),
+                    },
+                    Inst::List { items, .. } => Val::List(get_all(items)),
+                    Inst::Map { entries, .. } => {
+                        let mut map = BTreeMap::new();
+                        for (key, val) in entries {
+                            map.insert(MapKey::try_from(get(*key))?, get(*val));
+                        }
+                        Val::Map(map)
+                    }
+                    Inst::Index { target, index, .. } => {
+                        expr::index_into(get(*target), get(*index))?
+                    }
+                    Inst::Range {
+                        start,
+                        end,
+                        inclusive,
+                        ..
+                    } => expr::range(get(*start), get(*end), *inclusive)?,
+                    Inst::IsVariant {
+                        val,
+                        enum_name,
+                        variant,
+                        ..
+                    } => Val::Bool(matches!(
+                        get(*val),
+                        Val::Variant {
+                            enum_name: val_enum_name,
+                            variant: val_variant,
+                            ..
+                        } if val_enum_name == *enum_name && val_variant == *variant
+                    )),
+                    Inst::IsList { val, len, rest, .. } => {
+                        Val::Bool(pattern::list_items(&get(*val), *len, *rest).is_some())
+                    }
+                    Inst::Field { val, index, .. } => match get(*val) {
+                        Val::Variant { fields, .. } | Val::List(fields) => fields[*index].clone(),
+                        _ => unreachable!(),
+                    },
+                    Inst::Rest { val, start, .. } => match get(*val) {
+                        Val::List(items) => Val::List(items[*start..].to_vec()),
+                        _ => unreachable!(),
+                    },
+                    Inst::Items { iterable, .. } => {
+                        Val::List(for_loop::items(get(*iterable))?.collect())
+                    }
+                    Inst::Len { list, .. } => match get(*list) {
+                        Val::List(items) => Val::Number(items.len() as i32),
+                        _ => unreachable!(),
+                    },
+                };
+
+                values[inst.dest().unwrap().0] = result;
+            }
+
+            let target = match &block.terminator {
+                Terminator::Jump(target) => target,
+                Terminator::Branch {
+                    cond,
+                    then,
+                    otherwise,
+                } => match values[cond.0] {
+                    Val::Bool(true) => then,
+                    Val::Bool(false) => otherwise,
+                    _ => return Err("match guard must evaluate to a boolean".to_string()),
+                },
+                Terminator::Return(val) => return Ok(values[val.0].clone()),
+                Terminator::NoMatch(val) => {
+                    return Err(format!(
+                        "no arm of match expression matched ‘{}’",
+                        values[val.0],
+                    ));
+                }
+                Terminator::Fail(msg) => return Err(msg.clone()),
+            };
+
+            args = target
+                .args
+                .iter()
+                .map(|arg| values[arg.0].clone())
+                .collect();
+            block = func.block(target.block);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use super::{BlockId, Function, Inst, Module, Value};
+use crate::val::Val;
+
+impl Module {
+    /// Converts every function into SSA form, where rather than being loaded from slots, bindings
+    /// are referred to by the values they were defined with. Values that differ depending on how a
+    /// block was reached, such as those of bindings in a loop, are passed to it as parameters.
+    pub(crate) fn into_ssa(mut self) -> Self {
+        for func in std::iter::once(&mut self.main).chain(&mut self.funcs) {
+            if !func.is_ssa {
+                Converter::new(func).convert(func);
+            }
+        }
+
+        self
+    }
+}
+
+// Works out what each load of a slot reads by following the control flow graph backwards from it,
+// as described in "Simple and Efficient Construction of Static Single Assignment Form" by Braun
+// et al. Every block is known from the start, which makes it simpler than the original.
+struct Converter {
+    preds: Vec<Vec<BlockId>>,
+    // The last value each block stores in each slot
+    stored: Vec<HashMap<usize, Value>>,
+    // What each slot holds when each block starts, once it has been worked out
+    at_start: HashMap<(BlockId, usize), Value>,
+    // Parameters added to each block, and the arguments to add to the jumps from each predecessor
+    params: Vec<Vec<Value>>,
+    args: HashMap<(BlockId, BlockId), Vec<Value>>,
+    // Slots can be read before anything is stored in them, in which case they hold `()`
+    unit: Option<Value>,
+    num_values: usize,
+}
+
+impl Converter {
+    fn new(func: &Function) -> Self {
+        let stored = func
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut stored = HashMap::new();
+                for inst in &block.insts {
+                    if let Inst::Store { slot, src } = inst {
+                        stored.insert(*slot, *src);
+                    }
+                }
+                stored
+            })
+            .collect();
+
+        Self {
+            preds: func.predecessors(),
+            stored,
+            at_start: HashMap::new(),
+            params: vec![Vec::new(); func.blocks.len()],
+            args: HashMap::new(),
+            unit: None,
+            num_values: func.num_values,
+        }
+    }
+
+    fn value(&mut self) -> Value {
+        self.num_values += 1;
+        Value(self.num_values - 1)
+    }
+
+    fn unit(&mut self) -> Value {
+        match self.unit {
+            Some(unit) => unit,
+            None => {
+                let unit = self.value();
+                self.unit = Some(unit);
+                unit
+            }
+        }
+    }
+
+    fn at_end(&mut self, block: BlockId, slot: usize) -> Value {
+        match self.stored[block.0].get(&slot) {
+            Some(val) => *val,
+            None => self.at_start(block, slot),
+        }
+    }
+
+    fn at_start(&mut self, block: BlockId, slot: usize) -> Value {
+        if let Some(val) = self.at_start.get(&(block, slot)) {
+            return *val;
+        }
+
+        let preds = self.preds[block.0].clone();
+        let val = match preds[..] {
+            [] => self.unit(),
+            [pred] => self.at_end(pred, slot),
+            _ => {
+                // The parameter is recorded before looking at the predecessors, so that a loop
+                // leading back here finds it instead of going round forever
+                let param = self.value();
+                self.params[block.0].push(param);
+                self.at_start.insert((block, slot), param);
+
+                for pred in preds {
+                    let arg = self.at_end(pred, slot);
+                    self.args.entry((pred, block)).or_default().push(arg);
+                }
+                param
+            }
+        };
+
+        self.at_start.insert((block, slot), val);
+        val
+    }
+
+    fn convert(mut self, func: &mut Function) {
+        // Every load is replaced by whatever it would have read
+        let mut replaced = HashMap::new();
+        for id in 0..func.blocks.len() {
+            let mut stored = HashMap::new();
+
+            for inst in &func.blocks[id].insts {
+                match inst {
+                    Inst::Load { dest, slot } => {
+                        let val = match stored.get(slot) {
+                            Some(val) => *val,
+                            None => self.at_start(BlockId(id), *slot),
+                        };
+                        replaced.insert(*dest, val);
+                    }
+                    Inst::Store { slot, src } => {
+                        stored.insert(*slot, *src);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (id, block) in func.blocks.iter_mut().enumerate() {
+            block.params.append(&mut self.params[id]);
+
+            for target in block.terminator.targets_mut() {
+                if let Some(args) = self.args.get(&(BlockId(id), target.block)) {
+                    target.args.extend(args);
+                }
+            }
+
+            block.insts.retain(|inst| match inst {
+                Inst::Load { .. } => false,
+                Inst::Store { slot, .. } => func.observed_slots.contains(slot),
+                _ => true,
+            });
+        }
+
+        if let Some(unit) = self.unit {
+            func.blocks[0].insts.insert(
+                0,
+                Inst::Const {
+                    dest: unit,
+                    val: Val::Unit,
+                },
+            );
+        }
+
+        func.num_values = self.num_values;
+        remove_redundant_params(func, &mut replaced);
+        replace_uses(func, &replaced);
+        renumber(func);
+        func.is_ssa = true;
+    }
+}
+
+// Follows replacements until reaching a value that is not replaced
+fn resolve(val: Value, replaced: &HashMap<Value, Value>) -> Value {
+    match replaced.get(&val) {
+        Some(replacement) => resolve(*replacement, replaced),
+        None => val,
+    }
+}
+
+// Removes the parameters that are always passed the same value, or themselves, which are added
+// whenever a slot is read in a block with more than one predecessor
+fn remove_redundant_params(func: &mut Function, replaced: &mut HashMap<Value, Value>) {
+    let preds = func.predecessors();
+
+    loop {
+        let mut changed = false;
+
+        for (id, block_preds) in preds.iter().enumerate().skip(1) {
+            let mut idx = 0;
+
+            while idx < func.blocks[id].params.len() {
+                let param = func.blocks[id].params[idx];
+
+                let mut incoming = Vec::new();
+                for pred in block_preds {
+                    for target in func.blocks[pred.0].terminator.targets() {
+                        if target.block.0 == id {
+                            let arg = resolve(target.args[idx], replaced);
+                            if arg != param && !incoming.contains(&arg) {
+                                incoming.push(arg);
+                            }
+                        }
+                    }
+                }
+
+                let [only] = incoming[..] else {
+                    idx += 1;
+                    continue;
+                };
+
+                replaced.insert(param, only);
+                func.blocks[id].params.remove(idx);
+                for pred in block_preds {
+                    for target in func.blocks[pred.0].terminator.targets_mut() {
+                        if target.block.0 == id {
+                            target.args.remove(idx);
+                        }
+                    }
+                }
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return;
+        }
+    }
+}
+
+fn replace_uses(func: &mut Function, replaced: &HashMap<Value, Value>) {
+    for block in &mut func.blocks {
+        for inst in &mut block.insts {
+            inst.for_each_use(|val| *val = resolve(*val, replaced));
+        }
+        block
+            .terminator
+            .for_each_use(|val| *val = resolve(*val, replaced));
+    }
+}
+
+// Numbers the values that are left in the order they are defined
+fn renumber(func: &mut Function) {
+    let mut numbers = HashMap::new();
+    let mut next = 0;
+    let mut number = |val: &mut Value| {
+        *val = *numbers.entry(*val).or_insert_with(|| {
+            next += 1;
+            Value(next - 1)
+        });
+    };
+
+    for block in &mut func.blocks {
+        block.params.iter_mut().for_each(&mut number);
+
+        for inst in &mut block.insts {
+            if let Some(dest) = inst.dest_mut() {
+                number(dest);
+            }
+            inst.for_each_use(&mut number);
+        }
+        block.terminator.for_each_use(&mut number);
+    }
+
+    func.num_values = next;
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use super::{BlockId, Function, Inst, Module, Value};
+
+impl Module {
+    /// Checks that every function is well formed: that each value is defined exactly once before
+    /// anything uses it, that every jump passes the block it jumps to the parameters it takes,
+    /// and that every call and slot refers to something that exists.
+    pub(crate) fn verify(&self) -> Result<(), String> {
+        self.verify_function(&self.main)
+            .map_err(|msg| format!("main: {}", msg))?;
+
+        for (id, func) in self.funcs.iter().enumerate() {
+            self.verify_function(func)
+                .map_err(|msg| format!("fn #{}: {}", id, msg))?;
+        }
+
+        Ok(())
+    }
+
+    fn verify_function(&self, func: &Function) -> Result<(), String> {
+        if func.blocks.is_empty() {
+            return Err("there are no blocks".to_string());
+        }
+        if func.blocks[0].params.len() != func.num_params {
+            return Err(format!(
+                "b0 takes {} parameters but the function takes {}",
+                func.blocks[0].params.len(),
+                func.num_params,
+            ));
+        }
+
+        // Where each value is defined, as its block and the position in it, with parameters
+        // coming before the first instruction
+        let mut defs = HashMap::new();
+        let mut define = |val: Value, block: usize, position: usize| {
+            if val.0 >= func.num_values {
+                return Err(format!("{} is out of range", val));
+            }
+            match defs.insert(val, (block, position)) {
+                Some(_) => Err(format!("{} is defined more than once", val)),
+                None => Ok(()),
+            }
+        };
+
+        for (id, block) in func.blocks.iter().enumerate() {
+            for param in &block.params {
+                define(*param, id, 0)?;
+            }
+            for (position, inst) in block.insts.iter().enumerate() {
+                if let Some(dest) = inst.dest() {
+                    define(dest, id, position + 1)?;
+                }
+            }
+        }
+
+        for block in &func.blocks {
+            for target in block.terminator.targets() {
+                if target.block.0 >= func.blocks.len() {
+                    return Err(format!("{} does not exist", target.block));
+                }
+            }
+        }
+
+        let preds = func.predecessors();
+        if !preds[0].is_empty() {
+            return Err("b0 is jumped to".to_string());
+        }
+        let dominators = dominators(func, &preds)?;
+
+        for (id, block) in func.blocks.iter().enumerate() {
+            // Anything used must be defined earlier in the same block, or in a block that every
+            // path to this one goes through
+            let check_use = |val: Value, position: usize| match defs.get(&val) {
+                Some((def_block, def_position)) if *def_block == id => {
+                    if *def_position < position {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "{} is used in {} before it is defined",
+                            val,
+                            BlockId(id)
+                        ))
+                    }
+                }
+                Some((def_block, _)) if dominators[id][*def_block] => Ok(()),
+                Some(_) => Err(format!(
+                    "{} is used in {} where it may not be defined",
+                    val,
+                    BlockId(id),
+                )),
+                None => Err(format!("{} is never defined", val)),
+            };
+
+            for (position, inst) in block.insts.iter().enumerate() {
+                let mut result = Ok(());
+                inst.clone().for_each_use(|val| {
+                    if result.is_ok() {
+                        result = check_use(*val, position + 1);
+                    }
+                });
+                result?;
+
+                self.verify_inst(func, inst)?;
+            }
+
+            let mut result = Ok(());
+            block.terminator.clone().for_each_use(|val| {
+                if result.is_ok() {
+                    result = check_use(*val, block.insts.len() + 1);
+                }
+            });
+            result?;
+
+            for target in block.terminator.targets() {
+                let params = func.blocks[target.block.0].params.len();
+                if target.args.len() != params {
+                    return Err(format!(
+                        "{} passes {} arguments to {}, which takes {}",
+                        BlockId(id),
+                        target.args.len(),
+                        target.block,
+                        params,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify_inst(&self, func: &Function, inst: &Inst) -> Result<(), String> {
+        match inst {
+            Inst::Load { .. } if func.is_ssa => {
+                Err("slots are loaded from after converting into SSA form".to_string())
+            }
+            Inst::Load { slot, .. } | Inst::Store { slot, .. } if *slot >= func.num_slots => {
+                Err(format!("slot {} is out of range", slot))
+            }
+            Inst::LoadOuter { depth: 0, .. } => {
+                Err("slots of the current frame are loaded from as outer slots".to_string())
+            }
+            Inst::Call { func: id, args, .. } => match self.funcs.get(*id) {
+                Some(callee) if callee.num_params == args.len() => Ok(()),
+                Some(callee) => Err(format!(
+                    "fn #{} is called with {} arguments but takes {}",
+                    id,
+                    args.len(),
+                    callee.num_params,
+                )),
+                None => Err(format!("fn #{} does not exist", id)),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+// Works out which blocks each block is dominated by, meaning that every path from the start of the
+// function to it goes through them
+fn dominators(func: &Function, preds: &[Vec<BlockId>]) -> Result<Vec<Vec<bool>>, String> {
+    let num_blocks = func.blocks.len();
+
+    let mut reachable = vec![false; num_blocks];
+    let mut stack = vec![0];
+    while let Some(id) = stack.pop() {
+        if !reachable[id] {
+            reachable[id] = true;
+            for target in func.blocks[id].terminator.targets() {
+                stack.push(target.block.0);
+            }
+        }
+    }
+    if let Some(id) = reachable.iter().position(|reachable| !reachable) {
+        return Err(format!("{} is unreachable", BlockId(id)));
+    }
+
+    let mut dominators = vec![vec![true; num_blocks]; num_blocks];
+    dominators[0] = (0..num_blocks).map(|id| id == 0).collect();
+
+    loop {
+        let mut changed = false;
+
+        for id in 1..num_blocks {
+            let mut dominated_by: Vec<_> = (0..num_blocks)
+                .map(|other| preds[id].iter().all(|pred| dominators[pred.0][other]))
+                .collect();
+            dominated_by[id] = true;
+
+            if dominated_by != dominators[id] {
+                dominators[id] = dominated_by;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return Ok(dominators);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Block, Target, Terminator};
+    use crate::val::Val;
+
+    fn module(blocks: Vec<Block>, num_values: usize) -> Module {
+        Module {
+            main: Function {
+                name: None,
+                num_params: 0,
+                num_slots: 1,
+                blocks,
+                num_values,
+                observed_slots: Vec::new(),
+                is_ssa: true,
+            },
+            funcs: Vec::new(),
+        }
+    }
+
+    fn constant(dest: usize) -> Inst {
+        Inst::Const {
+            dest: Value(dest),
+            val: Val::Number(1),
+        }
+    }
+
+    fn jump(block: usize, args: &[usize]) -> Terminator {
+        Terminator::Jump(Target {
+            block: BlockId(block),
+            args: args.iter().map(|arg| Value(*arg)).collect(),
+        })
+    }
+
+    #[test]
+    fn accept_values_defined_in_dominating_block() {
+        let module = module(
+            vec![
+                Block {
+                    params: Vec::new(),
+                    insts: vec![constant(0)],
+                    terminator: jump(1, &[0]),
+                },
+                Block {
+                    params: vec![Value(1)],
+                    insts: Vec::new(),
+                    terminator: Terminator::Return(Value(0)),
+                },
+            ],
+            2,
+        );
+
+        assert_eq!(module.verify(), Ok(()));
+    }
+
+    #[test]
+    fn reject_use_not_dominated_by_def() {
+        let cond = Inst::Const {
+            dest: Value(0),
+            val: Val::Bool(true),
+        };
+        let module = module(
+            vec![
+                Block {
+                    params: Vec::new(),
+                    insts: vec![cond],
+                    terminator: Terminator::Branch {
+                        cond: Value(0),
+                        then: Target {
+                            block: BlockId(1),
+                            args: Vec::new(),
+                        },
+                        otherwise: Target {
+                            block: BlockId(2),
+                            args: Vec::new(),
+                        },
+                    },
+                },
+                Block {
+                    params: Vec::new(),
+                    insts: vec![constant(1)],
+                    terminator: jump(2, &[]),
+                },
+                Block {
+                    params: Vec::new(),
+                    insts: Vec::new(),
+                    terminator: Terminator::Return(Value(1)),
+                },
+            ],
+            2,
+        );
+
+        assert_eq!(
+            module.verify(),
+            Err("main: %1 is used in b2 where it may not be defined".to_string()),
+        );
+    }
+
+    #[test]
+    fn reject_malformed_functions() {
+        let use_before_def = module(
+            vec![Block {
+                params: Vec::new(),
+                insts: vec![
+                    Inst::Store {
+                        slot: 0,
+                        src: Value(0),
+                    },
+                    constant(0),
+                ],
+                terminator: Terminator::Return(Value(0)),
+            }],
+            1,
+        );
+        assert_eq!(
+            use_before_def.verify(),
+            Err("main: %0 is used in b0 before it is defined".to_string()),
+        );
+
+        let wrong_args = module(
+            vec![
+                Block {
+                    params: Vec::new(),
+                    insts: vec![constant(0)],
+                    terminator: jump(1, &[]),
+                },
+                Block {
+                    params: vec![Value(1)],
+                    insts: Vec::new(),
+                    terminator: Terminator::Return(Value(1)),
+                },
+            ],
+            2,
+        );
+        assert_eq!(
+            wrong_args.verify(),
+            Err("main: b0 passes 0 arguments to b1, which takes 1".to_string()),
+        );
+
+        let defined_twice = module(
+            vec![Block {
+                params: Vec::new(),
+                insts: vec![constant(0), constant(0)],
+                terminator: Terminator::Return(Value(0)),
+            }],
+            1,
+        );
+        assert_eq!(
+            defined_twice.verify(),
+            Err("main: %0 is defined more than once".to_string()),
+        );
+
+        let unreachable = module(
+            vec![
+                Block {
+                    params: Vec::new(),
+                    insts: vec![constant(0)],
+                    terminator: Terminator::Return(Value(0)),
+                },
+                Block {
+                    params: Vec::new(),
+                    insts: Vec::new(),
+                    terminator: Terminator::Return(Value(0)),
+                },
+            ],
+            1,
+        );
+        assert_eq!(
+            unreachable.verify(),
+            Err("main: b1 is unreachable".to_string()),
+        );
+
+        let load_in_ssa = module(
+            vec![Block {
+                params: Vec::new(),
+                insts: vec![Inst::Load {
+                    dest: Value(0),
+                    slot: 0,
+                }],
+                terminator: Terminator::Return(Value(0)),
+            }],
+            1,
+        );
+        assert_eq!(
+            load_in_ssa.verify(),
+            Err("main: slots are loaded from after converting into SSA form".to_string()),
+        );
+    }
+}
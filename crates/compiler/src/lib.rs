@@ -1,22 +1,91 @@
 #![allow(dead_code)]
 
 mod binding_def;
+mod builtins;
+mod bytecode;
+mod c;
+mod checker;
+mod codegen;
+mod diagnostic;
+mod enum_def;
 mod env;
 mod expr;
+mod for_loop;
+mod frame;
 mod func_def;
+mod interrupt;
+mod ir;
+mod lint;
+mod llvm;
+mod lower;
+mod optimize;
+mod pattern;
+mod resolver;
+mod span;
 mod statement;
+mod symbol;
+mod type_env;
+mod type_expr;
+mod types;
 mod utils;
 mod val;
+mod vm;
+mod wasm;
+mod x86_64;
 
+pub use bytecode::Module;
+pub use diagnostic::{Diagnostic, Note, Severity};
 pub use env::Env;
-pub use val::Val;
+pub use lint::{Level, Lint, Lints};
+pub use lower::Program;
+pub use optimize::{OptLevel, Report};
+pub use span::Span;
+pub use symbol::Symbol;
+pub use type_env::TypeEnv;
+pub use types::Type;
+pub use val::{MapKey, Val};
 
 #[derive(Debug)]
 pub struct Parse(statement::Statement);
 
 impl Parse {
     pub fn eval(&self, env: &mut Env) -> Result<Val, String> {
-        self.0.eval(env)
+        self.0.eval(env).map_err(interrupt::Interrupt::into_error)
+    }
+
+    /// Checks the program for undefined names and type errors without running it, storing the
+    /// types of anything it defines in `env`. The diagnostics include warnings as well as errors,
+    /// so the program should only be run if none of them `is_error`.
+    ///
+    /// This also works out what each name in the program refers to, which evaluation relies on,
+    /// and reports anything `lints` asks for.
+    pub fn check(&mut self, env: &mut TypeEnv, lints: &Lints) -> Vec<Diagnostic> {
+        let mut resolver = resolver::Resolver::new(env, lints);
+        self.0.resolve(&mut resolver);
+        let mut diagnostics = resolver.diagnostics;
+
+        let mut checker = checker::Checker::default();
+        self.0.check(env, &mut checker);
+        diagnostics.extend(checker.diagnostics);
+
+        diagnostics
+    }
+
+    /// Works out where every name in the program is stored ahead of time, so that it can be run
+    /// without looking anything up by name. Unlike `eval`, this only works for programs that
+    /// define everything they use themselves.
+    pub fn lower(&self) -> Result<Program, String> {
+        let mut lowerer = lower::Lowerer::new();
+        let main = self.0.lower(&mut lowerer)?;
+
+        Ok(lowerer.finish(main))
+    }
+
+    /// Compiles the program to bytecode, which can then be `run` many times over. As with
+    /// `lower`, the program has to define everything it uses itself. `source` must be the input
+    /// the program was parsed from, and is used to record which line each instruction came from.
+    pub fn compile(&self, source: &str) -> Result<Module, String> {
+        Ok(Module::compile(&self.lower()?, source))
     }
 }
 
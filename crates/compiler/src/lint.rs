@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// A kind of problem that does not stop a program from running, but is likely to be a mistake.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Lint {
+    UnusedBinding,
+    UnusedParam,
+    Shadowing,
+    NameCollision,
+    UnreachableCode,
+    UnusedResult,
+}
+
+impl Lint {
+    pub const ALL: [Self; 6] = [
+        Self::UnusedBinding,
+        Self::UnusedParam,
+        Self::Shadowing,
+        Self::NameCollision,
+        Self::UnreachableCode,
+        Self::UnusedResult,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnusedBinding => "unused-binding",
+            Self::UnusedParam => "unused-param",
+            Self::Shadowing => "shadowing",
+            Self::NameCollision => "name-collision",
+            Self::UnreachableCode => "unreachable-code",
+            Self::UnusedResult => "unused-result",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// How seriously a lint is taken.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level of every lint, with lints warning unless configured otherwise.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Lints {
+    levels: HashMap<Lint, Level>,
+}
+
+impl Lints {
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn set_all(&mut self, level: Level) {
+        for lint in Lint::ALL {
+            self.set(lint, level);
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+
+    // Turns a warning found by `lint` into whatever its level calls for, if anything
+    pub(crate) fn apply(&self, lint: Lint, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        diagnostic.severity = match self.level(lint) {
+            Level::Allow => return None,
+            Level::Warn => Severity::Warning,
+            Level::Deny => Severity::Error,
+        };
+
+        Some(diagnostic.with_note(format!("reported by the ‘{}’ lint", lint), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+    use crate::type_env::TypeEnv;
+
+    // Checks each line in turn against the same environment, returning the messages of the
+    // diagnostics together with whether they are errors
+    fn lint(lines: &[&str], lints: &Lints) -> Vec<(bool, String)> {
+        let mut env = TypeEnv::default();
+
+        lines
+            .iter()
+            .flat_map(|line| crate::parse(line).unwrap().check(&mut env, lints))
+            .map(|diagnostic| (diagnostic.is_error(), diagnostic.message))
+            .collect()
+    }
+
+    fn warnings(messages: &[&str]) -> Vec<(bool, String)> {
+        messages
+            .iter()
+            .map(|message| (false, message.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn lint_names_round_trip() {
+        for lint in Lint::ALL {
+            assert_eq!(Lint::from_name(lint.name()), Some(lint));
+        }
+
+        assert_eq!(Lint::from_name("unused"), None);
+    }
+
+    #[test]
+    fn apply_lint_levels() {
+        let warning = || Diagnostic::warning("‘a’ is never used".to_string(), Span::default());
+
+        let mut lints = Lints::default();
+        lints.set(Lint::UnusedParam, Level::Allow);
+        lints.set(Lint::UnusedBinding, Level::Deny);
+
+        assert_eq!(lints.apply(Lint::UnusedParam, warning()), None);
+        assert_eq!(
+            lints
+                .apply(Lint::UnusedBinding, warning())
+                .unwrap()
+                .severity,
+            Severity::Error,
+        );
+        assert_eq!(
+            lints.apply(Lint::Shadowing, warning()).unwrap().severity,
+            Severity::Warning,
+        );
+    }
+
+    #[test]
+    fn lint_unused_bindings_and_params() {
+        assert_eq!(
+            lint(&["fn f x y => { let a = 1 x }"], &Lints::default()),
+            warnings(&["‘a’ is never used", "‘y’ is never used"]),
+        );
+    }
+
+    #[test]
+    fn do_not_lint_unused_globals() {
+        assert_eq!(
+            lint(&["let a = 1", "fn f => 2"], &Lints::default()),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn lint_shadowing() {
+        assert_eq!(
+            lint(&["fn f x => { let x = x + 1 x }"], &Lints::default()),
+            warnings(&["‘x’ shadows an earlier binding"]),
+        );
+    }
+
+    #[test]
+    fn lint_name_collision_across_inputs() {
+        assert_eq!(
+            lint(&["fn a => 1", "let a = 2"], &Lints::default()),
+            warnings(&["‘a’ is defined as a binding, but already names a function"]),
+        );
+    }
+
+    #[test]
+    fn lint_unreachable_code() {
+        assert_eq!(
+            lint(
+                &["fn f x => {
+    return x
+    x + 1
+}"],
+                &Lints::default(),
+            ),
+            warnings(&["code after this ‘return’ is never run"]),
+        );
+    }
+
+    #[test]
+    fn lint_unused_block_result() {
+        assert_eq!(
+            lint(
+                &["{
+    let a = 1
+    a + 1
+    [a]
+}"],
+                &Lints::default(),
+            ),
+            warnings(&["the result of this expression is never used"]),
+        );
+    }
+
+    #[test]
+    fn deny_and_allow_lints() {
+        let mut lints = Lints::default();
+        lints.set(Lint::UnusedParam, Level::Deny);
+        lints.set(Lint::UnusedBinding, Level::Allow);
+
+        assert_eq!(
+            lint(&["fn f x y => { let a = 1 x }"], &lints),
+            vec![(true, "‘y’ is never used".to_string())],
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::fmt::Write;
+
+use crate::codegen::{Funcs, Kind};
+use crate::expr::Op;
+use crate::lower::{Func, Local, Node, Program};
+use crate::val::Val;
+
+// Division by zero, or of the most negative number by -1, is undefined behaviour in LLVM, so
+// divisions go through a function that traps instead, which the optimizer can inline
+const DIVIDE: &str = "
+define internal i32 @divide(i32 %lhs, i32 %rhs) {
+entry:
+  %zero = icmp eq i32 %rhs, 0
+  %min = icmp eq i32 %lhs, -2147483648
+  %minus_one = icmp eq i32 %rhs, -1
+  %overflow = and i1 %min, %minus_one
+  %invalid = or i1 %zero, %overflow
+  br i1 %invalid, label %trap, label %divide
+trap:
+  call void @llvm.trap()
+  unreachable
+divide:
+  %quotient = sdiv i32 %lhs, %rhs
+  ret i32 %quotient
+}
+
+declare void @llvm.trap()
+";
+
+const PRINTF: &str = "
+@format = private unnamed_addr constant [4 x i8] c\"%d\\0A\\00\"
+
+declare i32 @printf(ptr, ...)
+";
+
+impl Program {
+    /// Generates LLVM IR in the textual form `clang` and `llc` accept, with a `main` function that
+    /// prints the value the program evaluates to unless it is `()`. The output only depends on the
+    /// program, so the same program always gives the same IR.
+    ///
+    /// Only programs made of numbers, bindings, arithmetic, blocks and functions are supported.
+    pub fn to_llvm_ir(&self) -> Result<String, String> {
+        let mut generator = Generator {
+            program: self,
+            captures: self.captures(),
+            funcs: Funcs::new(self.funcs.len()),
+            divides: false,
+        };
+
+        let (body, result) = generator.function(&self.main, &[])?;
+
+        let mut ir = String::new();
+        for (_, func) in generator.funcs.into_generated() {
+            ir.push_str(&func);
+        }
+
+        ir.push_str("\ndefine i32 @main() {\nentry:\n");
+        ir.push_str(&body);
+        if let Some(result) = &result {
+            let _ = writeln!(
+                ir,
+                "  %printed = call i32 (ptr, ...) @printf(ptr @format, i32 {})",
+                result,
+            );
+        }
+        ir.push_str("  ret i32 0\n}\n");
+
+        if generator.divides {
+            ir.push_str(DIVIDE);
+        }
+        if result.is_some() {
+            ir.push_str(PRINTF);
+        }
+
+        Ok(ir)
+    }
+}
+
+struct Generator<'p> {
+    program: &'p Program,
+    // LLVM functions cannot see the values of the function they were defined in, so the
+    // bindings of enclosing functions they use are passed to them as extra parameters instead
+    captures: Vec<Vec<Local>>,
+    funcs: Funcs<String>,
+    divides: bool,
+}
+
+impl Generator<'_> {
+    // Generates the body of a function, returning it along with the value it returns, which is
+    // `None` if it is `()`
+    fn function(
+        &mut self,
+        func: &Func,
+        captures: &[Local],
+    ) -> Result<(String, Option<String>), String> {
+        let mut body = Body {
+            generator: self,
+            captures,
+            // Bindings are never changed once defined, so rather than being stored anywhere,
+            // each slot just refers to the value it was defined with
+            slots: (0..func.num_slots)
+                .map(|slot| (slot < func.num_params).then(|| format!("%a{}", slot)))
+                .collect(),
+            temps: 0,
+            ir: String::new(),
+        };
+
+        let result = body.node(&func.body)?;
+        Ok((body.ir, result))
+    }
+
+    fn call(&mut self, id: usize) -> Result<Kind, String> {
+        if let Some(kind) = self.funcs.start(id) {
+            return Ok(kind);
+        }
+
+        let program = self.program;
+        let func = &program.funcs[id];
+        let captures = self.captures[id].clone();
+        let (body, result) = self.function(func, &captures)?;
+
+        let params: Vec<_> = (0..func.num_params)
+            .map(|param| format!("i32 %a{}", param))
+            .chain((0..captures.len()).map(|capture| format!("i32 %c{}", capture)))
+            .collect();
+
+        let mut ir = String::new();
+        let _ = writeln!(ir, "\n; fn {}", func.name.map_or("", |name| name.as_str()));
+        let _ = writeln!(
+            ir,
+            "define internal {} @{}({}) {{\nentry:",
+            if result.is_some() { "i32" } else { "void" },
+            func_name(program, id),
+            params.join(", "),
+        );
+        ir.push_str(&body);
+        match &result {
+            Some(result) => {
+                let _ = writeln!(ir, "  ret i32 {}", result);
+            }
+            None => ir.push_str("  ret void\n"),
+        }
+        ir.push_str("}\n");
+
+        let kind = match result {
+            Some(_) => Kind::Number,
+            None => Kind::Unit,
+        };
+        self.funcs.finish(id, ir, kind).map_err(unsupported)?;
+
+        Ok(kind)
+    }
+}
+
+// The body of a single function, which every node in it is generated into
+struct Body<'g, 'p> {
+    generator: &'g mut Generator<'p>,
+    captures: &'g [Local],
+    slots: Vec<Option<String>>,
+    temps: usize,
+    ir: String,
+}
+
+impl Body<'_, '_> {
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps - 1)
+    }
+
+    fn local(&self, local: Local) -> String {
+        match local {
+            Local { depth: 0, slot } => self.slots[slot].clone().unwrap(),
+            _ => {
+                let capture = self.captures.iter().position(|c| *c == local).unwrap();
+                format!("%c{}", capture)
+            }
+        }
+    }
+
+    // Generates the instructions for a node, returning the value it evaluates to, which is
+    // `None` if it is `()`
+    fn node(&mut self, node: &Node) -> Result<Option<String>, String> {
+        match node {
+            Node::Const(Val::Number(n)) => Ok(Some(n.to_string())),
+            Node::Const(Val::Unit) => Ok(None),
+            Node::Local(local) => Ok(Some(self.local(*local))),
+            Node::Define { slot, val } => {
+                let val = self
+                    .node(val)?
+                    .ok_or_else(|| unsupported("bindings to ‘()’"))?;
+                self.slots[*slot] = Some(val);
+                Ok(None)
+            }
+            Node::Operation { lhs, rhs, op } => {
+                let instr = match op {
+                    Op::Add => "add",
+                    Op::Sub => "sub",
+                    Op::Mul => "mul",
+                    Op::Div => "sdiv",
+                    _ => return Err(unsupported("comparisons")),
+                };
+
+                let (Some(lhs), Some(rhs)) = (self.node(lhs)?, self.node(rhs)?) else {
+                    return Err(unsupported("arithmetic on ‘()’"));
+                };
+
+                let temp = self.temp();
+                if *op == Op::Div {
+                    self.generator.divides = true;
+                    let _ = writeln!(
+                        self.ir,
+                        "  {} = call i32 @divide(i32 {}, i32 {})",
+                        temp, lhs, rhs,
+                    );
+                } else {
+                    // Numbers are 32-bit and wrap around, just like `i32`s do without `nsw`
+                    let _ = writeln!(self.ir, "  {} = {} i32 {}, {}", temp, instr, lhs, rhs);
+                }
+                Ok(Some(temp))
+            }
+            Node::Call { func, depth, args } => {
+                let mut operands = Vec::new();
+                for arg in args {
+                    let arg = self
+                        .node(arg)?
+                        .ok_or_else(|| unsupported("passing ‘()’ to functions"))?;
+                    operands.push(format!("i32 {}", arg));
+                }
+
+                // The callee's enclosing functions start with the one `depth` functions out from
+                // here, which may be this function itself
+                for capture in self.generator.captures[*func].clone() {
+                    let val = self.local(Local {
+                        depth: depth + capture.depth - 1,
+                        slot: capture.slot,
+                    });
+                    operands.push(format!("i32 {}", val));
+                }
+
+                let kind = self.generator.call(*func)?;
+                let name = func_name(self.generator.program, *func);
+
+                if kind == Kind::Number {
+                    let temp = self.temp();
+                    let _ = writeln!(
+                        self.ir,
+                        "  {} = call i32 @{}({})",
+                        temp,
+                        name,
+                        operands.join(", "),
+                    );
+                    Ok(Some(temp))
+                } else {
+                    let _ = writeln!(self.ir, "  call void @{}({})", name, operands.join(", "));
+                    Ok(None)
+                }
+            }
+            Node::Block(nodes) => {
+                let mut result = None;
+                for node in nodes {
+                    result = self.node(node)?;
+                }
+                Ok(result)
+            }
+            Node::Spanned { node, .. } => self.node(node),
+            Node::Const(_) => Err(unsupported("values other than numbers")),
+            Node::Construct { .. } => Err(unsupported("enums")),
+            Node::Builtin { .. } | Node::List(_) | Node::Map(_) | Node::Index { .. } => {
+                Err(unsupported("lists and maps"))
+            }
+            Node::Range { .. } | Node::For { .. } | Node::Break | Node::Continue => {
+                Err(unsupported("loops"))
+            }
+            Node::Match { .. } => Err(unsupported("match expressions")),
+            Node::Return(_) => Err(unsupported("early returns")),
+        }
+    }
+}
+
+fn func_name(program: &Program, id: usize) -> String {
+    match program.funcs[id].name {
+        Some(name) => format!("fn{}_{}", id, name),
+        None => format!("fn{}", id),
+    }
+}
+
+fn unsupported(what: &str) -> String {
+    format!("the LLVM backend does not support {}", what)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::{Env, Lints, TypeEnv};
+
+    fn generate(s: &str) -> Result<String, String> {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.lower().unwrap().to_llvm_ir()
+    }
+
+    // Runs the IR with LLVM's interpreter if it is available, checking that it prints the same
+    // as the interpreter
+    fn generate_and_run(s: &str) -> String {
+        let ir = generate(s).unwrap();
+
+        let path = crate::codegen::scratch_path("llvm-test").with_extension("ll");
+        std::fs::write(&path, &ir).unwrap();
+
+        // Older versions only understand `ptr` when asked to, and newer ones no longer have the
+        // option
+        let mut output = Command::new("lli")
+            .arg("-opaque-pointers")
+            .arg(&path)
+            .output();
+        if let Ok(failed) = &output
+            && String::from_utf8_lossy(&failed.stderr).contains("Unknown command line argument")
+        {
+            output = Command::new("lli").arg(&path).output();
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        if let Ok(output) = output {
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+
+            let mut parse = crate::parse(s).unwrap();
+            parse.check(&mut TypeEnv::default(), &Lints::default());
+            let expected = match parse.eval(&mut Env::default()).unwrap() {
+                Val::Unit => String::new(),
+                val => format!("{}\n", val),
+            };
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+        }
+
+        ir
+    }
+
+    #[test]
+    fn generate_arithmetic() {
+        assert_eq!(
+            generate_and_run(
+                "{
+    let a = 7 * 6
+    let b = a / 4
+    b - 20
+}"
+            ),
+            "
+define i32 @main() {
+entry:
+  %t0 = mul i32 7, 6
+  %t1 = call i32 @divide(i32 %t0, i32 4)
+  %t2 = sub i32 %t1, 20
+  %printed = call i32 (ptr, ...) @printf(ptr @format, i32 %t2)
+  ret i32 0
+}
+"
+            .to_string()
+                + DIVIDE
+                + PRINTF,
+        );
+    }
+
+    #[test]
+    fn pass_captured_bindings_as_params() {
+        assert_eq!(
+            generate_and_run(
+                "{
+    let base = 100
+    fn outer x => {
+        let doubled = x * 2
+        fn inner y => {
+            let sum = base + doubled
+            sum + y
+        }
+        inner 3
+    }
+    fn sub a b => a - b
+    let result = outer 5
+    sub result 1
+}"
+            ),
+            "
+; fn outer
+define internal i32 @fn0_outer(i32 %a0, i32 %c0) {
+entry:
+  %t0 = mul i32 %a0, 2
+  %t1 = call i32 @fn2_inner(i32 3, i32 %t0, i32 %c0)
+  ret i32 %t1
+}
+
+; fn sub
+define internal i32 @fn1_sub(i32 %a0, i32 %a1) {
+entry:
+  %t0 = sub i32 %a0, %a1
+  ret i32 %t0
+}
+
+; fn inner
+define internal i32 @fn2_inner(i32 %a0, i32 %c0, i32 %c1) {
+entry:
+  %t0 = add i32 %c1, %c0
+  %t1 = add i32 %t0, %a0
+  ret i32 %t1
+}
+
+define i32 @main() {
+entry:
+  %t0 = call i32 @fn0_outer(i32 5, i32 100)
+  %t1 = call i32 @fn1_sub(i32 %t0, i32 1)
+  %printed = call i32 (ptr, ...) @printf(ptr @format, i32 %t1)
+  ret i32 0
+}
+"
+            .to_string()
+                + PRINTF,
+        );
+    }
+
+    #[test]
+    fn generate_funcs_evaluating_to_unit() {
+        assert_eq!(
+            generate_and_run(
+                "{
+    fn nothing x => {
+        let y = x
+    }
+    nothing 1
+}"
+            ),
+            "
+; fn nothing
+define internal void @fn0_nothing(i32 %a0) {
+entry:
+  ret void
+}
+
+define i32 @main() {
+entry:
+  call void @fn0_nothing(i32 1)
+  ret i32 0
+}
+",
+        );
+    }
+
+    #[test]
+    fn reject_unsupported_program() {
+        assert_eq!(
+            generate("[1, 2]"),
+            Err("the LLVM backend does not support lists and maps".to_string()),
+        );
+        assert_eq!(
+            generate("1 < 2"),
+            Err("the LLVM backend does not support comparisons".to_string()),
+        );
+    }
+}
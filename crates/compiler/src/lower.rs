@@ -0,0 +1,829 @@
+use std::collections::BTreeSet;
+
+use crate::builtins::{self, Builtin};
+use crate::expr::Op;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::val::Val;
+
+/// A program whose names have all been worked out ahead of time, so that it can be run without
+/// looking anything up by name. Every binding is stored in a numbered slot of the frame of the
+/// function it belongs to, and every call refers directly to the function it calls.
+#[derive(Debug)]
+pub struct Program {
+    pub(crate) main: Func,
+    pub(crate) funcs: Vec<Func>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Func {
+    // The name the function was defined with, which the program as a whole does not have
+    pub(crate) name: Option<Symbol>,
+    pub(crate) num_params: usize,
+    // Parameters take up the first slots of the frame, followed by every other binding
+    pub(crate) num_slots: usize,
+    pub(crate) body: Node,
+}
+
+/// Refers to the slot of a binding, in the frame `depth` functions out from the current one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Local {
+    pub(crate) depth: usize,
+    pub(crate) slot: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Node {
+    Const(Val),
+    Local(Local),
+    Define {
+        slot: usize,
+        val: Box<Self>,
+    },
+    Operation {
+        lhs: Box<Self>,
+        rhs: Box<Self>,
+        op: Op,
+    },
+    // Functions can only be called where they are in scope, so the frame they were defined in is
+    // always `depth` functions out from the caller's
+    Call {
+        func: usize,
+        depth: usize,
+        args: Vec<Self>,
+    },
+    Construct {
+        enum_name: Symbol,
+        variant: Symbol,
+        args: Vec<Self>,
+    },
+    Builtin {
+        builtin: Builtin,
+        args: Vec<Self>,
+    },
+    Block(Vec<Self>),
+    Match {
+        scrutinee: Box<Self>,
+        arms: Vec<Arm>,
+    },
+    List(Vec<Self>),
+    Map(Vec<(Self, Self)>),
+    Index {
+        target: Box<Self>,
+        index: Box<Self>,
+    },
+    Range {
+        start: Box<Self>,
+        end: Box<Self>,
+        inclusive: bool,
+    },
+    For {
+        slot: usize,
+        iterable: Box<Self>,
+        body: Box<Self>,
+    },
+    Break,
+    Continue,
+    Return(Box<Self>),
+    Spanned {
+        span: Span,
+        node: Box<Self>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Arm {
+    pub(crate) pattern: SlotPattern,
+    pub(crate) guard: Option<Node>,
+    pub(crate) body: Node,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SlotPattern {
+    Wildcard,
+    Number(i32),
+    Bool(bool),
+    Binding(usize),
+    Variant {
+        enum_name: Symbol,
+        variant: Symbol,
+        fields: Vec<Self>,
+    },
+    List {
+        items: Vec<Self>,
+        rest: Option<Box<Self>>,
+    },
+}
+
+impl Program {
+    /// Works out which bindings of enclosing functions each function uses, whether itself or
+    /// through the functions it calls, relative to the function's own frame. Backends whose
+    /// functions cannot reach the frames of enclosing functions pass these along as extra
+    /// parameters instead, which works because bindings never change once defined.
+    pub(crate) fn captures(&self) -> Vec<Vec<Local>> {
+        let mut captures = vec![Vec::new(); self.funcs.len()];
+
+        // Functions can call each other, so what they capture has to be worked out until it
+        // stops changing
+        loop {
+            let mut changed = false;
+
+            for id in 0..self.funcs.len() {
+                let mut found = BTreeSet::new();
+                find_captures(&self.funcs[id].body, &captures, &mut found);
+
+                // They are sorted, so that they are always passed in the same order
+                let found: Vec<_> = found
+                    .into_iter()
+                    .map(|(depth, slot)| Local { depth, slot })
+                    .collect();
+                if found != captures[id] {
+                    captures[id] = found;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return captures;
+            }
+        }
+    }
+}
+
+fn find_captures(node: &Node, captures: &[Vec<Local>], found: &mut BTreeSet<(usize, usize)>) {
+    match node {
+        Node::Local(Local { depth, slot }) if *depth > 0 => {
+            found.insert((*depth, *slot));
+        }
+        Node::Call { func, depth, .. } => {
+            // The callee's enclosing functions start with the one `depth` functions out from
+            // here, which may be this function itself
+            for capture in &captures[*func] {
+                let depth = depth + capture.depth - 1;
+                if depth > 0 {
+                    found.insert((depth, capture.slot));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    node.for_each_child(|child| find_captures(child, captures, found));
+}
+
+impl Node {
+    /// Calls `f` with every node directly inside this one, in the order they are evaluated.
+    pub(crate) fn for_each_child<'n>(&'n self, mut f: impl FnMut(&'n Self)) {
+        match self {
+            Self::Const(_) | Self::Local(_) | Self::Break | Self::Continue => {}
+            Self::Define { val: node, .. } | Self::Return(node) | Self::Spanned { node, .. } => {
+                f(node)
+            }
+            Self::Operation { lhs, rhs, .. } => {
+                f(lhs);
+                f(rhs);
+            }
+            Self::Call { args, .. }
+            | Self::Construct { args, .. }
+            | Self::Builtin { args, .. }
+            | Self::Block(args)
+            | Self::List(args) => args.iter().for_each(f),
+            Self::Match { scrutinee, arms } => {
+                f(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        f(guard);
+                    }
+                    f(&arm.body);
+                }
+            }
+            Self::Map(entries) => {
+                for (key, val) in entries {
+                    f(key);
+                    f(val);
+                }
+            }
+            Self::Index { target, index } => {
+                f(target);
+                f(index);
+            }
+            Self::Range { start, end, .. } => {
+                f(start);
+                f(end);
+            }
+            Self::For { iterable, body, .. } => {
+                f(iterable);
+                f(body);
+            }
+        }
+    }
+
+    /// Like `for_each_child`, but allows the children to be changed.
+    pub(crate) fn for_each_child_mut(&mut self, mut f: impl FnMut(&mut Self)) {
+        match self {
+            Self::Const(_) | Self::Local(_) | Self::Break | Self::Continue => {}
+            Self::Define { val: node, .. } | Self::Return(node) | Self::Spanned { node, .. } => {
+                f(node)
+            }
+            Self::Operation { lhs, rhs, .. } => {
+                f(lhs);
+                f(rhs);
+            }
+            Self::Call { args, .. }
+            | Self::Construct { args, .. }
+            | Self::Builtin { args, .. }
+            | Self::Block(args)
+            | Self::List(args) => args.iter_mut().for_each(f),
+            Self::Match { scrutinee, arms } => {
+                f(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &mut arm.guard {
+                        f(guard);
+                    }
+                    f(&mut arm.body);
+                }
+            }
+            Self::Map(entries) => {
+                for (key, val) in entries {
+                    f(key);
+                    f(val);
+                }
+            }
+            Self::Index { target, index } => {
+                f(target);
+                f(index);
+            }
+            Self::Range { start, end, .. } => {
+                f(start);
+                f(end);
+            }
+            Self::For { iterable, body, .. } => {
+                f(iterable);
+                f(body);
+            }
+        }
+    }
+}
+
+// What a name in scope refers to while lowering
+#[derive(Debug, Clone)]
+enum Name {
+    Local(usize),
+    Func {
+        id: usize,
+        num_params: usize,
+    },
+    Constructor {
+        enum_name: Symbol,
+        num_fields: usize,
+    },
+}
+
+struct Scope {
+    // The function whose frame the scope's bindings are stored in, as an index into `frames`
+    frame: usize,
+    names: Vec<(Symbol, Name)>,
+}
+
+/// Turns a program into a `Program`, keeping track of which names are in scope and which slots
+/// are in use as it goes.
+pub(crate) struct Lowerer {
+    // Functions are given their ids before their bodies are lowered, so that they can call
+    // themselves
+    funcs: Vec<Option<Func>>,
+    scopes: Vec<Scope>,
+    // The number of slots used so far by each function being lowered, innermost last
+    frames: Vec<usize>,
+}
+
+impl Lowerer {
+    pub(crate) fn new() -> Self {
+        Self {
+            funcs: Vec::new(),
+            scopes: vec![Scope {
+                frame: 0,
+                names: Vec::new(),
+            }],
+            frames: vec![0],
+        }
+    }
+
+    pub(crate) fn finish(self, main: Node) -> Program {
+        Program {
+            main: Func {
+                name: None,
+                num_params: 0,
+                num_slots: self.frames[0],
+                body: main,
+            },
+            funcs: self.funcs.into_iter().map(Option::unwrap).collect(),
+        }
+    }
+
+    /// Defines a binding in the current scope, returning the slot it is stored in.
+    pub(crate) fn define_binding(&mut self, name: Symbol) -> usize {
+        let num_slots = self.frames.last_mut().unwrap();
+        let slot = *num_slots;
+        *num_slots += 1;
+
+        self.define(name, Name::Local(slot));
+        slot
+    }
+
+    pub(crate) fn define_constructor(
+        &mut self,
+        name: Symbol,
+        enum_name: Symbol,
+        num_fields: usize,
+    ) {
+        self.define(
+            name,
+            Name::Constructor {
+                enum_name,
+                num_fields,
+            },
+        );
+    }
+
+    fn define(&mut self, name: Symbol, definition: Name) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .names
+            .push((name, definition));
+    }
+
+    /// Runs `f` in a scope of its own, forgetting everything it defines afterwards.
+    pub(crate) fn in_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(Scope {
+            frame: self.frames.len() - 1,
+            names: Vec::new(),
+        });
+        let result = f(self);
+        self.scopes.pop();
+
+        result
+    }
+
+    /// Lowers a function with the given parameters, whose body is lowered by `lower_body` in a
+    /// frame of its own.
+    pub(crate) fn define_func(
+        &mut self,
+        name: Symbol,
+        params: &[Symbol],
+        lower_body: impl FnOnce(&mut Self) -> Result<Node, String>,
+    ) -> Result<(), String> {
+        let id = match self.declared_func(name) {
+            Some(id) => id,
+            None => self.declare_func(name, params.len()),
+        };
+
+        self.frames.push(0);
+        let body = self.in_scope(|lowerer| {
+            for param in params {
+                lowerer.define_binding(*param);
+            }
+
+            lower_body(lowerer)
+        });
+        let num_slots = self.frames.pop().unwrap();
+
+        self.funcs[id] = Some(Func {
+            name: Some(name),
+            num_params: params.len(),
+            num_slots,
+            body: body?,
+        });
+
+        Ok(())
+    }
+
+    /// Gives a function its id ahead of its definition, so that functions defined before it can
+    /// call it.
+    pub(crate) fn declare_func(&mut self, name: Symbol, num_params: usize) -> usize {
+        let id = self.funcs.len();
+        self.funcs.push(None);
+        self.define(name, Name::Func { id, num_params });
+
+        id
+    }
+
+    // The id of the function with this name that was declared in the current scope but has not
+    // been lowered yet
+    fn declared_func(&self, name: Symbol) -> Option<usize> {
+        match self
+            .scopes
+            .last()?
+            .names
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)?
+        {
+            (_, Name::Func { id, .. }) if self.funcs[*id].is_none() => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<(usize, &Name)> {
+        self.scopes.iter().rev().find_map(|scope| {
+            let (_, definition) = scope.names.iter().rev().find(|(n, _)| *n == name)?;
+            Some((self.frames.len() - 1 - scope.frame, definition))
+        })
+    }
+
+    pub(crate) fn lower_usage(&self, name: Symbol) -> Result<Node, String> {
+        match self.lookup(name) {
+            Some((depth, Name::Local(slot))) => Ok(Node::Local(Local { depth, slot: *slot })),
+            // Using the name of a function or constructor on its own calls it without arguments
+            _ => self.lower_call(name, Vec::new()),
+        }
+    }
+
+    pub(crate) fn lower_call(&self, name: Symbol, args: Vec<Node>) -> Result<Node, String> {
+        let check_arity = |num_params: usize| {
+            if num_params == args.len() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected {} parameters, got {}",
+                    num_params,
+                    args.len(),
+                ))
+            }
+        };
+
+        match self.lookup(name) {
+            Some((depth, Name::Func { id, num_params })) => {
+                check_arity(*num_params)?;
+                Ok(Node::Call {
+                    func: *id,
+                    depth,
+                    args,
+                })
+            }
+            Some((
+                _,
+                Name::Constructor {
+                    enum_name,
+                    num_fields,
+                },
+            )) => {
+                check_arity(*num_fields)?;
+                Ok(Node::Construct {
+                    enum_name: *enum_name,
+                    variant: name,
+                    args,
+                })
+            }
+            Some((_, Name::Local(_))) => {
+                Err(format!("cannot call ‘{}’, which is not a function", name))
+            }
+            None => match builtins::get(name.as_str()) {
+                Some(builtin) => {
+                    check_arity(builtin.arity)?;
+                    Ok(Node::Builtin { builtin, args })
+                }
+                None => Err(format!("‘{}’ is not defined", name)),
+            },
+        }
+    }
+
+    /// Returns the enum that `name` is a variant of, if it names a constructor.
+    pub(crate) fn lookup_constructor(&self, name: Symbol) -> Option<(Symbol, usize)> {
+        match self.lookup(name) {
+            Some((
+                _,
+                Name::Constructor {
+                    enum_name,
+                    num_fields,
+                },
+            )) => Some((*enum_name, *num_fields)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Env, Lints, TypeEnv};
+
+    fn lower(s: &str) -> Result<Program, String> {
+        crate::parse(s).unwrap().lower()
+    }
+
+    // Runs the program with every evaluator, checking that they agree before returning the result
+    fn eval(s: &str) -> Result<Val, String> {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+
+        let result = parse.lower()?.eval();
+        assert_eq!(result, parse.eval(&mut Env::default()));
+        assert_eq!(result, parse.compile(s)?.run());
+
+        result
+    }
+
+    #[test]
+    fn give_each_binding_its_own_slot() {
+        let program = lower(
+            "{
+    let a = 1
+    let b = {
+        let c = a
+        c
+    }
+    let a = b
+    a
+}",
+        )
+        .unwrap();
+
+        assert_eq!(program.main.num_slots, 4);
+        assert!(program.funcs.is_empty());
+    }
+
+    #[test]
+    fn store_params_in_first_slots_of_frame() {
+        let program = lower(
+            "{
+    fn add x y => {
+        let sum = x + y
+        sum
+    }
+    add 1 2
+}",
+        )
+        .unwrap();
+
+        assert_eq!(program.main.num_slots, 0);
+        assert_eq!(program.funcs[0].num_params, 2);
+        assert_eq!(program.funcs[0].num_slots, 3);
+    }
+
+    #[test]
+    fn refer_to_bindings_of_enclosing_functions_by_depth() {
+        let mut lowerer = Lowerer::new();
+        lowerer.define_binding(Symbol::intern("a"));
+
+        lowerer
+            .define_func(Symbol::intern("f"), &[Symbol::intern("b")], |lowerer| {
+                assert_eq!(
+                    lowerer.lookup(Symbol::intern("a")).map(|(depth, _)| depth),
+                    Some(1),
+                );
+                lowerer.lower_usage(Symbol::intern("b"))
+            })
+            .unwrap();
+
+        assert!(matches!(
+            lowerer.funcs[0].as_ref().unwrap().body,
+            Node::Local(Local { depth: 0, slot: 0 }),
+        ));
+    }
+
+    #[test]
+    fn eval_recursive_func() {
+        assert_eq!(
+            eval(
+                "{
+    fn fib n => match n {
+        0 => 0,
+        1 => 1,
+        n => {
+            let a = n - 1
+            let b = n - 2
+            fib a + fib b
+        },
+    }
+    fib 15
+}"
+            ),
+            Ok(Val::Number(610)),
+        );
+    }
+
+    #[test]
+    fn eval_nested_func_using_outer_binding() {
+        assert_eq!(
+            eval(
+                "{
+    fn scale x => {
+        let factor = 3
+        fn times y => y * factor
+        times x
+    }
+    scale 5
+}"
+            ),
+            Ok(Val::Number(15)),
+        );
+    }
+
+    #[test]
+    fn eval_free_names_where_func_was_defined() {
+        // The caller's own binding of the same name is not what the function sees
+        assert_eq!(
+            eval(
+                "{
+    let x = 1
+    fn f => x
+    fn g x => f
+    g 2
+}"
+            ),
+            Ok(Val::Number(1)),
+        );
+        // Nor is a binding that shadows it after the function was defined
+        assert_eq!(
+            eval(
+                "{
+    let x = 1
+    fn f => x
+    let x = 2
+    f
+}"
+            ),
+            Ok(Val::Number(1)),
+        );
+        assert_eq!(
+            eval(
+                "{
+    fn f => 1
+    fn h => f
+    fn g f => h
+    g 5
+}"
+            ),
+            Ok(Val::Number(1)),
+        );
+    }
+
+    #[test]
+    fn eval_list_patterns() {
+        eval(
+            "{
+    fn sum items => match items {
+        [] => 0,
+        [x, ..rest] => {
+            let total = sum rest
+            x + total
+        },
+    }
+    fn second items => match items {
+        [_, y, ..] => y,
+        _ => 0,
+    }
+    let a = sum [1, 2, 3]
+    let b = second [4, 5]
+    let c = second [6]
+    let d = a + b
+    [d, c]
+}",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn eval_funcs_calling_each_other() {
+        assert_eq!(
+            eval(
+                "{
+    fn even n => match n { 0 => true, _ => odd { n - 1 } }
+    fn odd n => match n { 0 => false, _ => even { n - 1 } }
+    [even 4, odd 4]
+}"
+            ),
+            Ok(Val::List(vec![Val::Bool(true), Val::Bool(false)])),
+        );
+        // Redefining a name does not separate the function from the ones it calls
+        assert_eq!(
+            eval(
+                "{
+    fn odd n => 0
+    let unused = 1
+    fn even n => match n { 0 => true, _ => odd { n - 1 } }
+    fn odd n => match n { 0 => false, _ => even { n - 1 } }
+    even 3
+}"
+            ),
+            Ok(Val::Bool(false)),
+        );
+    }
+
+    #[test]
+    fn eval_args_where_func_is_called() {
+        assert_eq!(
+            eval(
+                "{
+    fn f a b => b
+    let a = 5
+    f 1 a
+}"
+            ),
+            Ok(Val::Number(5)),
+        );
+    }
+
+    #[test]
+    fn eval_loops_and_early_return() {
+        assert_eq!(
+            eval(
+                "{
+    fn find limit items => {
+        for item in items {
+            match item > limit {
+                true => { return item },
+                false => { continue },
+            }
+        }
+        0
+    }
+    let total = find 2 [1, 2, 3, 4]
+    for i in 0..=10 {
+        match i {
+            5 => { break },
+            _ => i,
+        }
+    }
+    total
+}"
+            ),
+            Ok(Val::Number(3)),
+        );
+    }
+
+    #[test]
+    fn eval_enums_and_builtins() {
+        assert_eq!(
+            eval(
+                "{
+    enum Option { Some(value), None }
+    fn lookup map key => match has map key {
+        true => Some map[key],
+        false => None,
+    }
+    let map = insert #{\"a\": 1} \"b\" 2
+    [lookup map \"b\", lookup map \"c\", keys map]
+}"
+            ),
+            Ok(Val::List(vec![
+                Val::Variant {
+                    enum_name: Symbol::intern("Option"),
+                    variant: Symbol::intern("Some"),
+                    fields: vec![Val::Number(2)],
+                },
+                Val::Variant {
+                    enum_name: Symbol::intern("Option"),
+                    variant: Symbol::intern("None"),
+                    fields: Vec::new(),
+                },
+                Val::List(vec![Val::Str("a".to_string()), Val::Str("b".to_string())]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn eval_runtime_errors() {
+        assert_eq!(
+            eval("[1, 2][5]"),
+            Err("index 5 is out of bounds for a list of length 2".to_string()),
+        );
+        assert_eq!(
+            eval("{ fn f => break f }"),
+            Err("cannot break outside of a loop".to_string()),
+        );
+    }
+
+    #[test]
+    fn cannot_lower_undefined_names() {
+        assert_eq!(
+            lower("a + 1").unwrap_err(),
+            "‘a’ is not defined".to_string()
+        );
+        assert_eq!(
+            lower(
+                "{
+    let a = 1
+    a 2
+}"
+            )
+            .unwrap_err(),
+            "cannot call ‘a’, which is not a function".to_string(),
+        );
+    }
+
+    #[test]
+    fn cannot_lower_calls_with_wrong_number_of_arguments() {
+        assert_eq!(
+            lower(
+                "{
+    fn f x => x
+    f 1 2
+}"
+            )
+            .unwrap_err(),
+            "expected 1 parameters, got 2".to_string(),
+        );
+    }
+}
@@ -0,0 +1,275 @@
+mod dead_code;
+mod fold;
+mod inline;
+
+use std::fmt;
+
+use crate::lower::{Func, Node, Program};
+use crate::val::Val;
+
+/// How much work to put into optimizing a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    /// Folds constants and removes dead code, going over the program once.
+    O1,
+    /// Also inlines small functions, going over the program again for as long as that finds
+    /// more to do.
+    O2,
+}
+
+impl OptLevel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "1" => Some(Self::O1),
+            "2" => Some(Self::O2),
+            _ => None,
+        }
+    }
+}
+
+// Each pass can leave more for the others to do, but going round forever is never worth it
+const MAX_ROUNDS: usize = 4;
+
+/// How many changes each optimization pass made, in the order the passes first ran, along with
+/// what was removed from each function.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    passes: Vec<(&'static str, usize)>,
+    removed: Vec<(Removed, String, usize)>,
+}
+
+/// Something dead code elimination can remove from a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Removed {
+    Func,
+    Binding,
+    Statement,
+    Slot,
+}
+
+impl Report {
+    fn record(&mut self, pass: &'static str, changes: usize) {
+        match self.passes.iter_mut().find(|(name, _)| *name == pass) {
+            Some((_, total)) => *total += changes,
+            None => self.passes.push((pass, changes)),
+        }
+    }
+
+    // Notes that `count` of something were removed from `func`, or that `func` itself was
+    pub(crate) fn record_removed(&mut self, removed: Removed, func: &Func, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        let place = match func.name {
+            Some(name) => format!("‘{}’", name),
+            None => "main".to_string(),
+        };
+        match self
+            .removed
+            .iter_mut()
+            .find(|(other, other_place, _)| *other == removed && *other_place == place)
+        {
+            Some((_, _, total)) => *total += count,
+            None => self.removed.push((removed, place, count)),
+        }
+    }
+
+    pub fn changes(&self) -> usize {
+        self.passes.iter().map(|(_, changes)| changes).sum()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (pass, changes) in &self.passes {
+            match changes {
+                1 => writeln!(f, "{}: 1 change", pass)?,
+                _ => writeln!(f, "{}: {} changes", pass, changes)?,
+            }
+        }
+
+        let plural = |count: usize, what: &str| match count {
+            1 => format!("1 {}", what),
+            _ => format!("{} {}s", count, what),
+        };
+        for (removed, place, count) in &self.removed {
+            match removed {
+                Removed::Func => writeln!(f, "  removed unused function {}", place)?,
+                Removed::Binding => writeln!(
+                    f,
+                    "  removed {} from {}",
+                    plural(*count, "unused binding"),
+                    place,
+                )?,
+                Removed::Statement => writeln!(
+                    f,
+                    "  removed {} from {}",
+                    plural(*count, "discarded statement"),
+                    place,
+                )?,
+                Removed::Slot => writeln!(f, "  freed {} in {}", plural(*count, "slot"), place)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+type Pass = (&'static str, fn(&mut Program, &mut Report) -> usize);
+
+const FOLD: Pass = ("constant folding", |program, _| program.fold_constants());
+const INLINE: Pass = ("inlining", |program, _| program.inline_functions());
+const DEAD_CODE: Pass = ("dead code elimination", Program::remove_dead_code);
+
+impl Program {
+    /// Runs the optimization passes `level` asks for, reporting what each of them changed. This
+    /// assumes that the program has been checked, so that operations are only ever applied to
+    /// values they work on.
+    pub fn optimize(&mut self, level: OptLevel) -> Report {
+        let (passes, rounds): (&[Pass], _) = match level {
+            OptLevel::O1 => (&[FOLD, DEAD_CODE], 1),
+            OptLevel::O2 => (&[FOLD, INLINE, DEAD_CODE], MAX_ROUNDS),
+        };
+
+        let mut report = Report::default();
+        for _ in 0..rounds {
+            let before = report.changes();
+            for (name, pass) in passes {
+                let changes = pass(self, &mut report);
+                report.record(name, changes);
+            }
+
+            if report.changes() == before {
+                break;
+            }
+        }
+
+        report
+    }
+}
+
+// Merges blocks nested inside a block into it and drops the nodes whose values are discarded
+// that `removable` allows, returning what is left along with how many blocks were merged and the
+// nodes that were dropped
+fn simplify_block(nodes: Vec<Node>, removable: impl Fn(&Node) -> bool) -> (Node, usize, Vec<Node>) {
+    let mut merged = 0;
+    let mut simplified = Vec::new();
+
+    for node in nodes {
+        match node {
+            Node::Block(nested) => {
+                simplified.extend(nested);
+                merged += 1;
+            }
+            Node::Spanned { node, .. } if matches!(*node, Node::Block(_)) => {
+                let Node::Block(nested) = *node else {
+                    unreachable!()
+                };
+                simplified.extend(nested);
+                merged += 1;
+            }
+            node => simplified.push(node),
+        }
+    }
+
+    let last = simplified.pop();
+    let (dropped, mut simplified): (Vec<_>, Vec<_>) =
+        simplified.into_iter().partition(|node| removable(node));
+
+    let node = match last {
+        Some(last) if simplified.is_empty() => last,
+        Some(last) => {
+            simplified.push(last);
+            Node::Block(simplified)
+        }
+        None => Node::Const(Val::Unit),
+    };
+    (node, merged, dropped)
+}
+
+// Lowers the program `s` parses to and runs `pass` on it, checking that this does not change
+// what the program does, whether it is interpreted or compiled to bytecode. Returns the optimized
+// program along with whatever the pass returned.
+#[cfg(test)]
+fn run_pass<T>(s: &str, pass: impl FnOnce(&mut Program) -> T) -> (Program, T) {
+    use crate::{Env, Lints, TypeEnv};
+
+    let mut parse = crate::parse(s).unwrap();
+    parse.check(&mut TypeEnv::default(), &Lints::default());
+
+    let mut program = parse.lower().unwrap();
+    let result = pass(&mut program);
+
+    let expected = parse.eval(&mut Env::default());
+    assert_eq!(program.eval(), expected);
+    assert_eq!(crate::Module::compile(&program, s).run(), expected);
+
+    (program, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimize(s: &str, level: OptLevel) -> (Program, Report) {
+        run_pass(s, |program| program.optimize(level))
+    }
+
+    const PROGRAM: &str = "{
+    fn square x => x * x
+    fn unused y => y + 1
+    let a = 3
+    let b = square a
+    let c = b + 1
+    b
+}";
+
+    #[test]
+    fn run_passes_for_level() {
+        let (program, report) = optimize(PROGRAM, OptLevel::O1);
+
+        // Without inlining, the call stays and so does the function it calls
+        assert_eq!(program.funcs.len(), 1);
+        assert_eq!(
+            report.to_string(),
+            "constant folding: 3 changes
+dead code elimination: 7 changes
+  removed unused function ‘unused’
+  removed 2 unused bindings from main
+  freed 2 slots in main
+",
+        );
+
+        let (program, report) = optimize(PROGRAM, OptLevel::O2);
+
+        assert!(program.funcs.is_empty());
+        assert!(matches!(program.main.body, Node::Const(Val::Number(9))));
+        assert_eq!(program.main.num_slots, 0);
+        assert_eq!(
+            report.to_string(),
+            "constant folding: 7 changes
+inlining: 1 change
+dead code elimination: 14 changes
+  removed unused function ‘square’
+  removed unused function ‘unused’
+  removed 4 unused bindings from main
+  freed 4 slots in main
+",
+        );
+    }
+
+    #[test]
+    fn report_nothing_changing() {
+        let (_, report) = optimize("[1, 2][1]", OptLevel::O2);
+
+        assert_eq!(report.changes(), 0);
+        assert_eq!(
+            report.to_string(),
+            "constant folding: 0 changes
+inlining: 0 changes
+dead code elimination: 0 changes
+",
+        );
+    }
+}
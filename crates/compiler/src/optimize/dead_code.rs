@@ -0,0 +1,441 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::{Removed, Report};
+use crate::expr::Op;
+use crate::lower::{Func, Local, Node, Program, SlotPattern};
+use crate::val::{MapKey, Val};
+
+impl Program {
+    /// Removes functions that are never called and bindings that are never used, along with
+    /// anything whose value is discarded when working it out has no effect, then gives the
+    /// bindings left the lowest slots they can have. Returns how many changes were made, noting
+    /// in `report` what was removed from where.
+    pub fn remove_dead_code(&mut self, report: &mut Report) -> usize {
+        let mut changes = self.remove_unused_funcs(report);
+
+        // Whether a nested function reads a binding from an enclosing one is only worked out by
+        // its slot, which is enough to keep every binding that might be
+        let mut captured = HashSet::new();
+        for func in std::iter::once(&self.main).chain(&self.funcs) {
+            find_locals(&func.body, &mut |local| {
+                if local.depth > 0 {
+                    captured.insert(local.slot);
+                }
+            });
+        }
+
+        for func in std::iter::once(&mut self.main).chain(&mut self.funcs) {
+            let mut used = captured.clone();
+            find_locals(&func.body, &mut |local| {
+                if local.depth == 0 {
+                    used.insert(local.slot);
+                }
+            });
+
+            let mut dead = Dead::default();
+            remove_dead_nodes(&mut func.body, &used, &mut dead);
+
+            report.record_removed(Removed::Binding, func, dead.bindings);
+            report.record_removed(Removed::Statement, func, dead.statements);
+            changes += dead.changes;
+        }
+
+        changes + self.compact_slots(report)
+    }
+
+    fn remove_unused_funcs(&mut self, report: &mut Report) -> usize {
+        let mut called = vec![false; self.funcs.len()];
+        let mut stack = Vec::new();
+        find_calls(&self.main.body, &mut stack);
+
+        while let Some(id) = stack.pop() {
+            if !called[id] {
+                called[id] = true;
+                find_calls(&self.funcs[id].body, &mut stack);
+            }
+        }
+
+        // Functions are referred to by their position, which changes for those after any that
+        // are removed
+        let mut ids = Vec::new();
+        let mut next = 0;
+        for called in &called {
+            ids.push(next);
+            next += usize::from(*called);
+        }
+
+        let funcs = std::mem::take(&mut self.funcs);
+        let num_funcs = funcs.len();
+        for (func, called) in funcs.into_iter().zip(&called) {
+            match called {
+                true => self.funcs.push(func),
+                false => report.record_removed(Removed::Func, &func, 1),
+            }
+        }
+
+        for func in std::iter::once(&mut self.main).chain(&mut self.funcs) {
+            renumber_calls(&mut func.body, &ids);
+        }
+
+        num_funcs - self.funcs.len()
+    }
+
+    // Renumbers the slots of every function so that the ones no longer used are not set aside,
+    // returning how many slots were freed. Slots are read by the functions nested inside the one
+    // they belong to as well, so those are renumbered along with it.
+    fn compact_slots(&mut self, report: &mut Report) -> usize {
+        // Here `main` is function zero and the others are numbered from one. Only functions that
+        // are called are left, so each of them is reached from `main`.
+        let num_funcs = self.funcs.len() + 1;
+        let mut parents = vec![None; num_funcs];
+        let mut reached = vec![false; num_funcs];
+        reached[0] = true;
+        let mut unchecked = vec![0];
+
+        while let Some(idx) = unchecked.pop() {
+            let mut calls = Vec::new();
+            find_call_depths(&self.func(idx).body, &mut calls);
+
+            for (callee, depth) in calls {
+                if !reached[callee + 1] {
+                    reached[callee + 1] = true;
+                    parents[callee + 1] = ancestor(&parents, idx, depth);
+                    unchecked.push(callee + 1);
+                }
+            }
+        }
+
+        let mut used: Vec<BTreeSet<usize>> = (0..num_funcs)
+            .map(|idx| (0..self.func(idx).num_params).collect())
+            .collect();
+        for idx in 0..num_funcs {
+            for_each_slot(&mut self.func_mut(idx).body, &mut |depth, slot| {
+                if let Some(owner) = ancestor(&parents, idx, depth) {
+                    used[owner].insert(*slot);
+                }
+            });
+        }
+
+        // The slots left keep their order, so parameters stay where the arguments are put
+        let slots: Vec<Vec<usize>> = (0..num_funcs)
+            .map(|idx| {
+                let mut slots = vec![0; self.func(idx).num_slots];
+                for (new, old) in used[idx].iter().enumerate() {
+                    slots[*old] = new;
+                }
+                slots
+            })
+            .collect();
+
+        let mut changes = 0;
+        for (idx, used) in used.iter().enumerate() {
+            let func = self.func_mut(idx);
+            for_each_slot(&mut func.body, &mut |depth, slot| {
+                if let Some(owner) = ancestor(&parents, idx, depth) {
+                    *slot = slots[owner][*slot];
+                }
+            });
+
+            let freed = func.num_slots - used.len();
+            func.num_slots = used.len();
+            report.record_removed(Removed::Slot, func, freed);
+            changes += freed;
+        }
+
+        changes
+    }
+
+    fn func(&self, idx: usize) -> &Func {
+        match idx {
+            0 => &self.main,
+            idx => &self.funcs[idx - 1],
+        }
+    }
+
+    fn func_mut(&mut self, idx: usize) -> &mut Func {
+        match idx {
+            0 => &mut self.main,
+            idx => &mut self.funcs[idx - 1],
+        }
+    }
+}
+
+// The function `depth` functions out from the one numbered `idx`, counting `main` as zero
+fn ancestor(parents: &[Option<usize>], idx: usize, depth: usize) -> Option<usize> {
+    (0..depth).try_fold(idx, |idx, _| parents[idx])
+}
+
+fn find_calls(node: &Node, calls: &mut Vec<usize>) {
+    if let Node::Call { func, .. } = node {
+        calls.push(*func);
+    }
+    node.for_each_child(|child| find_calls(child, calls));
+}
+
+fn find_call_depths(node: &Node, calls: &mut Vec<(usize, usize)>) {
+    if let Node::Call { func, depth, .. } = node {
+        calls.push((*func, *depth));
+    }
+    node.for_each_child(|child| find_call_depths(child, calls));
+}
+
+fn renumber_calls(node: &mut Node, ids: &[usize]) {
+    if let Node::Call { func, .. } = node {
+        *func = ids[*func];
+    }
+    node.for_each_child_mut(|child| renumber_calls(child, ids));
+}
+
+fn find_locals(node: &Node, f: &mut impl FnMut(Local)) {
+    if let Node::Local(local) = node {
+        f(*local);
+    }
+    node.for_each_child(|child| find_locals(child, f));
+}
+
+// Calls `f` with every slot a node reads or defines, along with how many functions out from the
+// current one the slot belongs to
+fn for_each_slot(node: &mut Node, f: &mut impl FnMut(usize, &mut usize)) {
+    match node {
+        Node::Local(Local { depth, slot }) => f(*depth, slot),
+        Node::Define { slot, .. } | Node::For { slot, .. } => f(0, slot),
+        Node::Match { arms, .. } => {
+            for arm in arms {
+                for_each_pattern_slot(&mut arm.pattern, f);
+            }
+        }
+        _ => {}
+    }
+    node.for_each_child_mut(|child| for_each_slot(child, f));
+}
+
+fn for_each_pattern_slot(pattern: &mut SlotPattern, f: &mut impl FnMut(usize, &mut usize)) {
+    match pattern {
+        SlotPattern::Binding(slot) => f(0, slot),
+        SlotPattern::Variant { fields, .. } => {
+            for field in fields {
+                for_each_pattern_slot(field, f);
+            }
+        }
+        SlotPattern::List { items, rest } => {
+            for pattern in items.iter_mut().chain(rest.as_deref_mut()) {
+                for_each_pattern_slot(pattern, f);
+            }
+        }
+        SlotPattern::Wildcard | SlotPattern::Number(_) | SlotPattern::Bool(_) => {}
+    }
+}
+
+// What was removed from a function, along with how many changes that took in all, including
+// those not worth reporting such as merging blocks
+#[derive(Default)]
+struct Dead {
+    bindings: usize,
+    statements: usize,
+    changes: usize,
+}
+
+fn remove_dead_nodes(node: &mut Node, used: &HashSet<usize>, dead: &mut Dead) {
+    node.for_each_child_mut(|child| remove_dead_nodes(child, used, dead));
+
+    match node {
+        // The value still has to be worked out if that might fail, but a definition always
+        // evaluates to `()`
+        Node::Define { slot, val } if !used.contains(slot) => {
+            let val = std::mem::replace(&mut **val, Node::Const(Val::Unit));
+            *node = match is_pure(&val) {
+                true => Node::Const(Val::Unit),
+                false => Node::Block(vec![val, Node::Const(Val::Unit)]),
+            };
+            dead.bindings += 1;
+            dead.changes += 1;
+        }
+        Node::Block(nodes) => {
+            let (simplified, merged, dropped) =
+                super::simplify_block(std::mem::take(nodes), is_pure);
+            *node = simplified;
+
+            // What is left of a definition that was removed has already been reported
+            dead.statements += dropped.iter().filter(|node| !is_unit(node)).count();
+            dead.changes += merged + dropped.len();
+        }
+        // The value of the body of a loop is thrown away each time round
+        Node::For { body, .. } if !is_unit(body) && is_pure(body) => {
+            **body = Node::Const(Val::Unit);
+            dead.statements += 1;
+            dead.changes += 1;
+        }
+        _ => {}
+    }
+}
+
+fn is_unit(node: &Node) -> bool {
+    match node {
+        Node::Spanned { node, .. } => is_unit(node),
+        node => matches!(node, Node::Const(Val::Unit)),
+    }
+}
+
+// Whether working out the value of a node has no effect other than giving the value, which means
+// that it cannot fail either
+fn is_pure(node: &Node) -> bool {
+    let children_are_pure = || {
+        let mut pure = true;
+        node.for_each_child(|child| pure &= is_pure(child));
+        pure
+    };
+
+    match node {
+        Node::Const(_) | Node::Local(_) => true,
+        // Checked programs only apply operations to numbers, so only division can fail
+        Node::Operation {
+            op: Op::Div, rhs, ..
+        } if !matches!(**rhs, Node::Const(Val::Number(n)) if n != 0 && n != -1) => false,
+        Node::Map(entries)
+            if !entries.iter().all(
+                |(key, _)| matches!(key, Node::Const(key) if MapKey::try_from(key.clone()).is_ok()),
+            ) =>
+        {
+            false
+        }
+        Node::Operation { .. }
+        | Node::Construct { .. }
+        | Node::Block(_)
+        | Node::List(_)
+        | Node::Map(_)
+        | Node::Spanned { .. } => children_are_pure(),
+        // Anything else could fail, loop forever or jump elsewhere, or, for definitions, be
+        // relied on later
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimize::run_pass;
+
+    fn remove_dead_code(s: &str) -> (Program, usize, Report) {
+        let mut report = Report::default();
+        let (program, changes) = run_pass(s, |program| program.remove_dead_code(&mut report));
+
+        (program, changes, report)
+    }
+
+    #[test]
+    fn remove_unused_bindings_and_funcs() {
+        let (program, _, _) = remove_dead_code(
+            "{
+    fn unused x => x
+    fn used x => x + 1
+    let a = [1, 2]
+    let b = used 1
+    b
+}",
+        );
+
+        assert_eq!(program.funcs.len(), 1);
+        assert_eq!(program.funcs[0].name.unwrap().as_str(), "used");
+        assert_eq!(program.main.num_slots, 1);
+
+        assert!(matches!(
+            &program.main.body,
+            Node::Spanned { node, .. } if matches!(&**node, Node::Block(nodes) if nodes.len() == 2),
+        ));
+    }
+
+    #[test]
+    fn report_what_was_removed() {
+        let (_, _, report) = remove_dead_code(
+            "{
+    fn unused x => x
+    fn used x => {
+        let y = x * 2
+        x + 1
+    }
+    let a = [1, 2]
+    let b = used 1
+    b + 1
+    b
+}",
+        );
+
+        assert_eq!(
+            report.to_string(),
+            "  removed unused function ‘unused’
+  removed 1 unused binding from main
+  removed 1 discarded statement from main
+  removed 1 unused binding from ‘used’
+  freed 1 slot in main
+  freed 1 slot in ‘used’
+",
+        );
+    }
+
+    #[test]
+    fn compact_slots_read_by_nested_funcs() {
+        let (program, _, _) = remove_dead_code(
+            "{
+    let a = 1
+    let b = 2
+    fn outer x => {
+        let c = 3
+        let d = x + b
+        fn inner y => y + d
+        inner c
+    }
+    outer 1
+}",
+        );
+
+        // Only `a` goes from `main`, and `c` is still used by `outer` itself
+        assert_eq!(program.main.num_slots, 1);
+        assert_eq!(program.funcs[0].num_slots, 3);
+    }
+
+    #[test]
+    fn keep_bindings_whose_values_might_fail() {
+        let (program, _, _) = remove_dead_code(
+            "{
+    let zero = 0
+    let a = 1 / zero
+    let b = 1 / 2
+    2
+}",
+        );
+
+        assert_eq!(program.eval(), Err("attempt to divide by zero".to_string()),);
+    }
+
+    #[test]
+    fn keep_bindings_used_by_nested_funcs() {
+        let (program, changes, _) = remove_dead_code(
+            "{
+    let factor = 3
+    fn scale x => x * factor
+    scale 2
+}",
+        );
+
+        assert_eq!(changes, 1);
+        assert_eq!(program.eval(), Ok(Val::Number(6)));
+    }
+
+    #[test]
+    fn drop_discarded_pure_statements() {
+        let (program, changes, _) = remove_dead_code(
+            "{
+    let a = 1
+    a + 1
+    [a, 2]
+    a
+}",
+        );
+
+        assert_eq!(changes, 2);
+        assert!(matches!(
+            &program.main.body,
+            Node::Spanned { node, .. } if matches!(&**node, Node::Block(nodes) if nodes.len() == 2),
+        ));
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use crate::lower::{Arm, Local, Node, Program, SlotPattern};
+use crate::val::Val;
+
+impl Program {
+    /// Works out ahead of time the results of operations whose operands are constants, replacing
+    /// uses of bindings defined as constants with their values, and simplifies the blocks and
+    /// matches this leaves behind. Operations that fail, such as dividing by zero, are left to
+    /// fail when the program is run. Returns how many changes were made.
+    pub fn fold_constants(&mut self) -> usize {
+        std::iter::once(&mut self.main)
+            .chain(&mut self.funcs)
+            .map(|func| {
+                let mut folder = Folder::default();
+                folder.node(&mut func.body);
+                folder.changes
+            })
+            .sum()
+    }
+}
+
+#[derive(Default)]
+struct Folder {
+    // The values of the bindings of the current function that are defined as constants, which
+    // never change because every binding has a slot of its own
+    known: HashMap<usize, Val>,
+    changes: usize,
+}
+
+impl Folder {
+    fn node(&mut self, node: &mut Node) {
+        match node {
+            Node::Local(Local { depth: 0, slot }) => {
+                if let Some(val) = self.known.get(slot) {
+                    *node = Node::Const(val.clone());
+                    self.changes += 1;
+                }
+            }
+            Node::Define { slot, val } => {
+                self.node(val);
+                if let Some(val) = constant(val) {
+                    self.known.insert(*slot, val.clone());
+                }
+            }
+            Node::Operation { lhs, rhs, op } => {
+                self.node(lhs);
+                self.node(rhs);
+
+                let result = match (&**lhs, &**rhs) {
+                    (Node::Const(lhs), Node::Const(rhs)) => op.apply(lhs.clone(), rhs.clone()).ok(),
+                    _ => None,
+                };
+                if let Some(result) = result {
+                    *node = Node::Const(result);
+                    self.changes += 1;
+                }
+            }
+            Node::Block(nodes) => {
+                let nodes = std::mem::take(nodes);
+                *node = self.block(nodes);
+            }
+            Node::Match { .. } => self.fold_match(node),
+            Node::Spanned { node: inner, .. } => {
+                self.node(inner);
+
+                // Constants cannot fail, so there is nothing to trace back to the statement
+                if let Node::Const(val) = &**inner {
+                    *node = Node::Const(val.clone());
+                }
+            }
+            _ => node.for_each_child_mut(|child| self.node(child)),
+        }
+    }
+
+    fn block(&mut self, mut nodes: Vec<Node>) -> Node {
+        nodes.iter_mut().for_each(|node| self.node(node));
+
+        let (node, merged, dropped) =
+            super::simplify_block(nodes, |node| matches!(node, Node::Const(_)));
+        self.changes += merged + dropped.len();
+        node
+    }
+
+    // Replaces a match on a constant with the arm it takes, if that can be worked out
+    fn fold_match(&mut self, node: &mut Node) {
+        let Node::Match { scrutinee, arms } = node else {
+            unreachable!()
+        };
+
+        self.node(scrutinee);
+        let val = match &**scrutinee {
+            Node::Const(val) => Some(val.clone()),
+            _ => None,
+        };
+
+        for arm in arms.iter_mut() {
+            if let (Some(val), SlotPattern::Binding(slot)) = (&val, &arm.pattern) {
+                self.known.insert(*slot, val.clone());
+            }
+            if let Some(guard) = &mut arm.guard {
+                self.node(guard);
+            }
+            self.node(&mut arm.body);
+        }
+
+        let Some(val) = val else {
+            return;
+        };
+        let Some(idx) = taken_arm(arms, &val) else {
+            return;
+        };
+
+        let arm = arms.swap_remove(idx);
+        self.changes += 1;
+
+        // The binding stays defined in case a function inside the arm uses it
+        *node = match arm.pattern {
+            SlotPattern::Binding(slot) => self.block(vec![
+                Node::Define {
+                    slot,
+                    val: Box::new(Node::Const(val)),
+                },
+                arm.body,
+            ]),
+            _ => arm.body,
+        };
+    }
+}
+
+// The value a node evaluates to, if it is known ahead of time, in which case anything else it
+// does is only for its side effects
+fn constant(node: &Node) -> Option<&Val> {
+    match node {
+        Node::Const(val) => Some(val),
+        Node::Block(nodes) => constant(nodes.last()?),
+        _ => None,
+    }
+}
+
+// The index of the arm a match on `val` takes, if it is certain that no arm before it matches
+fn taken_arm(arms: &[Arm], val: &Val) -> Option<usize> {
+    for (idx, arm) in arms.iter().enumerate() {
+        let matches = match (&arm.pattern, val) {
+            (SlotPattern::Wildcard | SlotPattern::Binding(_), _) => true,
+            (SlotPattern::Number(n), Val::Number(m)) => n == m,
+            (SlotPattern::Bool(b), Val::Bool(c)) => b == c,
+            _ => return None,
+        };
+        if !matches {
+            continue;
+        }
+
+        match &arm.guard {
+            None | Some(Node::Const(Val::Bool(true))) => return Some(idx),
+            Some(Node::Const(Val::Bool(false))) => {}
+            Some(_) => return None,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Op;
+    use crate::optimize::run_pass;
+
+    fn fold(s: &str) -> (Program, usize) {
+        run_pass(s, Program::fold_constants)
+    }
+
+    fn unspanned(node: &Node) -> &Node {
+        match node {
+            Node::Spanned { node, .. } => unspanned(node),
+            node => node,
+        }
+    }
+
+    fn statements(node: &Node) -> Vec<&Node> {
+        match unspanned(node) {
+            Node::Block(nodes) => nodes.iter().map(unspanned).collect(),
+            node => panic!("expected a block, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn fold_operations_on_constants() {
+        let (program, changes) = fold("10 / 2");
+
+        assert!(matches!(program.main.body, Node::Const(Val::Number(5))));
+        assert_eq!(changes, 1);
+    }
+
+    #[test]
+    fn propagate_constant_bindings() {
+        let (program, _) = fold(
+            "{
+    let x = 3
+    x * 4
+}",
+        );
+
+        assert!(matches!(
+            statements(&program.main.body)[..],
+            [Node::Define { .. }, Node::Const(Val::Number(12))],
+        ));
+    }
+
+    #[test]
+    fn leave_operations_that_fail() {
+        let (program, _) = fold(
+            "{
+    let zero = 1 - 1
+    10 / zero
+}",
+        );
+
+        assert!(matches!(
+            statements(&program.main.body)[..],
+            [Node::Define { .. }, Node::Operation { op: Op::Div, .. }],
+        ));
+        assert_eq!(program.eval(), Err("attempt to divide by zero".to_string()),);
+    }
+
+    #[test]
+    fn propagate_constant_bindings_in_funcs() {
+        let (program, changes) = fold(
+            "{
+    fn double x => {
+        let two = 2
+        x * two
+    }
+    double 4
+}",
+        );
+
+        let Node::Operation { lhs, rhs, .. } = statements(&program.funcs[0].body)[1] else {
+            panic!("expected an operation");
+        };
+        assert!(matches!(**lhs, Node::Local(_)));
+        assert!(matches!(**rhs, Node::Const(Val::Number(2))));
+
+        // The use of `two` is replaced, and the definition of `double` is dropped from the block
+        // it left a constant in
+        assert_eq!(changes, 2);
+    }
+
+    #[test]
+    fn take_arm_of_match_on_constant() {
+        let (program, _) = fold(
+            "{
+    let flag = 2 > 1
+    let n = match flag {
+        true => 10,
+        false => 20,
+    }
+    let m = match n {
+        1 => 0,
+        k if k > 5 => k + 1,
+        _ => 0,
+    }
+    m
+}",
+        );
+
+        assert!(matches!(
+            statements(&program.main.body).last(),
+            Some(Node::Const(Val::Number(11))),
+        ));
+    }
+
+    #[test]
+    fn keep_match_whose_arm_depends_on_runtime_values() {
+        let (program, _) = fold(
+            "{
+    fn pick n => match n {
+        0 => 1,
+        _ => 2,
+    }
+    pick 0
+}",
+        );
+
+        assert!(matches!(
+            unspanned(&program.funcs[0].body),
+            Node::Match { .. },
+        ));
+    }
+}
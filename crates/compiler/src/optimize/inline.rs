@@ -0,0 +1,250 @@
+use crate::lower::{Func, Local, Node, Program, SlotPattern};
+
+// Functions whose bodies have more nodes than this are left alone, so that inlining them does not
+// make programs much bigger
+const MAX_INLINE_SIZE: usize = 16;
+
+impl Program {
+    /// Replaces calls to small functions that never end up calling themselves with the bodies of
+    /// those functions, with their parameters defined as bindings of the caller. Returns how many
+    /// calls were replaced.
+    pub fn inline_functions(&mut self) -> usize {
+        let recursive = self.recursive();
+
+        // The bodies are copied out first, since the functions they are inlined into may be
+        // among them
+        let inlinable: Vec<Option<Func>> = self
+            .funcs
+            .iter()
+            .enumerate()
+            .map(|(id, func)| {
+                let inlinable = !recursive[id]
+                    && size(&func.body) <= MAX_INLINE_SIZE
+                    && can_move(&func.body, 0);
+                inlinable.then(|| func.clone())
+            })
+            .collect();
+
+        std::iter::once(&mut self.main)
+            .chain(&mut self.funcs)
+            .map(|func| {
+                let mut num_slots = func.num_slots;
+                let changes = inline_calls(&mut func.body, &inlinable, &mut num_slots);
+                func.num_slots = num_slots;
+                changes
+            })
+            .sum()
+    }
+
+    // Works out which functions can call themselves, whether directly or through other functions
+    fn recursive(&self) -> Vec<bool> {
+        let calls: Vec<Vec<usize>> = self
+            .funcs
+            .iter()
+            .map(|func| {
+                let mut calls = Vec::new();
+                find_calls(&func.body, &mut calls);
+                calls
+            })
+            .collect();
+
+        (0..self.funcs.len())
+            .map(|id| {
+                let mut seen = vec![false; self.funcs.len()];
+                let mut stack = calls[id].clone();
+
+                while let Some(callee) = stack.pop() {
+                    if callee == id {
+                        return true;
+                    }
+                    if !seen[callee] {
+                        seen[callee] = true;
+                        stack.extend(&calls[callee]);
+                    }
+                }
+                false
+            })
+            .collect()
+    }
+}
+
+fn find_calls(node: &Node, calls: &mut Vec<usize>) {
+    if let Node::Call { func, .. } = node {
+        calls.push(*func);
+    }
+    node.for_each_child(|child| find_calls(child, calls));
+}
+
+fn size(node: &Node) -> usize {
+    let mut size = match node {
+        // Spans do not end up in the code generated for the node
+        Node::Spanned { .. } => 0,
+        _ => 1,
+    };
+    node.for_each_child(|child| size += self::size(child));
+    size
+}
+
+// Whether a function body behaves the same when moved into the body of its caller. Returning
+// would return from the caller instead, as would breaking out of a loop the body is not inside,
+// and functions defined inside the body rely on it having a frame of its own.
+fn can_move(node: &Node, loop_depth: usize) -> bool {
+    match node {
+        Node::Return(_) | Node::Call { depth: 0, .. } => false,
+        Node::Break | Node::Continue => loop_depth > 0,
+        Node::For { iterable, body, .. } => {
+            can_move(iterable, loop_depth) && can_move(body, loop_depth + 1)
+        }
+        _ => {
+            let mut can = true;
+            node.for_each_child(|child| can &= can_move(child, loop_depth));
+            can
+        }
+    }
+}
+
+fn inline_calls(node: &mut Node, inlinable: &[Option<Func>], num_slots: &mut usize) -> usize {
+    let mut changes = 0;
+    node.for_each_child_mut(|child| changes += inline_calls(child, inlinable, num_slots));
+
+    let Node::Call { func, depth, args } = node else {
+        return changes;
+    };
+    let Some(callee) = &inlinable[*func] else {
+        return changes;
+    };
+
+    // The callee's bindings are given slots after the caller's own
+    let base = *num_slots;
+    *num_slots += callee.num_slots;
+
+    let mut body = callee.body.clone();
+    relocate(&mut body, base, *depth);
+
+    let mut nodes: Vec<_> = std::mem::take(args)
+        .into_iter()
+        .enumerate()
+        .map(|(param, arg)| Node::Define {
+            slot: base + param,
+            val: Box::new(arg),
+        })
+        .collect();
+    nodes.push(body);
+
+    *node = Node::Block(nodes);
+    changes + 1
+}
+
+// Changes a node from the body of a function called `depth` functions out from the caller to
+// refer to the callee's bindings by the slots given to them in the caller's frame, starting at
+// `base`, and to the frames of enclosing functions relative to the caller's
+fn relocate(node: &mut Node, base: usize, depth: usize) {
+    match node {
+        Node::Local(Local { depth: 0, slot })
+        | Node::Define { slot, .. }
+        | Node::For { slot, .. } => *slot += base,
+        Node::Local(Local {
+            depth: outer_depth, ..
+        })
+        | Node::Call {
+            depth: outer_depth, ..
+        } => *outer_depth = depth + *outer_depth - 1,
+        Node::Match { arms, .. } => {
+            for arm in arms {
+                relocate_pattern(&mut arm.pattern, base);
+            }
+        }
+        _ => {}
+    }
+
+    node.for_each_child_mut(|child| relocate(child, base, depth));
+}
+
+fn relocate_pattern(pattern: &mut SlotPattern, base: usize) {
+    match pattern {
+        SlotPattern::Binding(slot) => *slot += base,
+        SlotPattern::Variant { fields, .. } => {
+            for field in fields {
+                relocate_pattern(field, base);
+            }
+        }
+        SlotPattern::List { items, rest } => {
+            for pattern in items.iter_mut().chain(rest.as_deref_mut()) {
+                relocate_pattern(pattern, base);
+            }
+        }
+        SlotPattern::Wildcard | SlotPattern::Number(_) | SlotPattern::Bool(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimize::run_pass;
+
+    fn inline(s: &str) -> (Program, usize) {
+        run_pass(s, Program::inline_functions)
+    }
+
+    #[test]
+    fn inline_small_funcs() {
+        let (program, changes) = inline(
+            "{
+    let offset = 10
+    fn add x y => {
+        let sum = x + y
+        sum + offset
+    }
+    fn twice x => add x x
+    let a = add 1 2
+    twice a
+}",
+        );
+
+        // The call to `add` inside `twice` is inlined too, though the copy of `twice` inlined
+        // into the program still calls it until the next time round
+        assert_eq!(changes, 3);
+        assert_eq!(program.main.num_slots, 2 + 3 + 1);
+    }
+
+    #[test]
+    fn leave_recursive_funcs() {
+        let (_, changes) = inline(
+            "{
+    fn countdown n => match n {
+        0 => 0,
+        _ => {
+            let m = n - 1
+            countdown m
+        },
+    }
+    countdown 3
+}",
+        );
+
+        assert_eq!(changes, 0);
+    }
+
+    #[test]
+    fn leave_funcs_that_cannot_be_moved() {
+        let (_, changes) = inline(
+            "{
+    fn first items => {
+        for item in items {
+            return item
+        }
+        0
+    }
+    fn scale x => {
+        fn times y => y * 2
+        times x
+    }
+    let a = first [1, 2]
+    scale a
+}",
+        );
+
+        // Only `times` is inlined, into `scale`
+        assert_eq!(changes, 1);
+    }
+}
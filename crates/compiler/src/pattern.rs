@@ -0,0 +1,513 @@
+use crate::checker::Checker;
+use crate::lower::{Lowerer, SlotPattern};
+use crate::resolver::{NameKind, Resolver};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::{TypeEnv, TypeInfo};
+use crate::types::Type;
+use crate::{Env, Val, utils};
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Pattern {
+    Wildcard,
+    Number(i32),
+    Bool(bool),
+    // A bare identifier either binds the matched value or, if it names a constructor without a
+    // payload, matches that variant
+    Binding(Symbol),
+    Variant {
+        name: Symbol,
+        fields: Vec<Self>,
+    },
+    // Matches lists with exactly as many items as it has patterns for, or at least that many if
+    // it has a pattern for the rest of the list, which is either a binding or a wildcard
+    List {
+        items: Vec<Self>,
+        rest: Option<Box<Self>>,
+    },
+}
+
+impl Pattern {
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        Self::new_wildcard(s)
+            .or_else(|_| Self::new_number(s))
+            .or_else(|_| Self::new_bool(s))
+            .or_else(|_| Self::new_variant(s))
+            .or_else(|_| Self::new_binding(s))
+            .or_else(|_| Self::new_list(s))
+    }
+
+    fn new_wildcard(s: &str) -> Result<(&str, Self), String> {
+        let s = utils::tag("_", s)?;
+        Ok((s, Self::Wildcard))
+    }
+
+    fn new_number(s: &str) -> Result<(&str, Self), String> {
+        let (s, number) = utils::extract_number(s)?;
+        Ok((s, Self::Number(number)))
+    }
+
+    fn new_bool(s: &str) -> Result<(&str, Self), String> {
+        utils::extract_bool(s).map(|(s, b)| (s, Self::Bool(b)))
+    }
+
+    fn new_variant(s: &str) -> Result<(&str, Self), String> {
+        let (s, name) = utils::extract_ident(s)?;
+
+        let s = utils::tag("(", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, fields) = utils::sequence(Self::new, utils::extract_comma, s)?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag(")", s)?;
+
+        Ok((
+            s,
+            Self::Variant {
+                name: Symbol::intern(name),
+                fields,
+            },
+        ))
+    }
+
+    fn new_binding(s: &str) -> Result<(&str, Self), String> {
+        let (s, name) = utils::extract_ident(s)?;
+        Ok((s, Self::Binding(Symbol::intern(name))))
+    }
+
+    fn new_list(s: &str) -> Result<(&str, Self), String> {
+        let s = utils::tag("[", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, items) = utils::sequence(Self::new, utils::extract_comma, s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, rest) = match utils::tag("..", s) {
+            Ok(s) => {
+                let (s, rest) = Self::new_wildcard(s)
+                    .or_else(|_| Self::new_binding(s))
+                    .unwrap_or((s, Self::Wildcard));
+                let (s, _) = utils::extract_whitespace(s);
+
+                (s, Some(Box::new(rest)))
+            }
+            Err(_) => (s, None),
+        };
+
+        let s = utils::tag("]", s)?;
+
+        Ok((s, Self::List { items, rest }))
+    }
+
+    /// Stores every binding introduced by the pattern in `env`, returning whether `val` matched.
+    /// Bindings may have been stored even when the pattern does not match overall, so `env` should
+    /// be discarded in that case.
+    pub(crate) fn bind(&self, val: &Val, env: &mut Env) -> Result<bool, String> {
+        match self {
+            Self::Wildcard => Ok(true),
+            Self::Number(n) => Ok(*val == Val::Number(*n)),
+            Self::Bool(b) => Ok(*val == Val::Bool(*b)),
+            Self::Binding(name) => {
+                if env.get_constructor(*name).is_ok() {
+                    return Self::bind_variant(*name, &[], val, env);
+                }
+
+                env.store_binding(*name, val.clone());
+                Ok(true)
+            }
+            Self::Variant { name, fields } => Self::bind_variant(*name, fields, val, env),
+            Self::List { items, rest } => {
+                let Some(vals) = list_items(val, items.len(), rest.is_some()) else {
+                    return Ok(false);
+                };
+
+                for (pattern, item) in items.iter().zip(vals) {
+                    if !pattern.bind(item, env)? {
+                        return Ok(false);
+                    }
+                }
+
+                match rest {
+                    Some(rest) => rest.bind(&Val::List(vals[items.len()..].to_vec()), env),
+                    None => Ok(true),
+                }
+            }
+        }
+    }
+
+    /// Defines every binding introduced by the pattern.
+    pub(crate) fn resolve(&self, resolver: &mut Resolver, span: Span) {
+        match self {
+            Self::Wildcard | Self::Number(_) | Self::Bool(_) => {}
+            Self::Binding(name) => {
+                let is_constructor = resolver
+                    .lookup(*name)
+                    .is_some_and(|definition| definition.kind == NameKind::Constructor);
+
+                if !is_constructor {
+                    resolver.define_binding(*name, span);
+                }
+            }
+            Self::Variant { name, fields } => {
+                resolver.resolve_constructor(*name, span);
+
+                for field in fields {
+                    field.resolve(resolver, span);
+                }
+            }
+            Self::List { items, rest } => {
+                for pattern in items.iter().chain(rest.as_deref()) {
+                    pattern.resolve(resolver, span);
+                }
+            }
+        }
+    }
+
+    /// Gives every binding introduced by the pattern a slot of its own.
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<SlotPattern, String> {
+        match self {
+            Self::Wildcard => Ok(SlotPattern::Wildcard),
+            Self::Number(n) => Ok(SlotPattern::Number(*n)),
+            Self::Bool(b) => Ok(SlotPattern::Bool(*b)),
+            Self::Binding(name) => {
+                if lowerer.lookup_constructor(*name).is_some() {
+                    return Self::lower_variant(*name, &[], lowerer);
+                }
+
+                Ok(SlotPattern::Binding(lowerer.define_binding(*name)))
+            }
+            Self::Variant { name, fields } => Self::lower_variant(*name, fields, lowerer),
+            Self::List { items, rest } => Ok(SlotPattern::List {
+                items: items
+                    .iter()
+                    .map(|pattern| pattern.lower(lowerer))
+                    .collect::<Result<_, _>>()?,
+                rest: rest
+                    .as_ref()
+                    .map(|rest| rest.lower(lowerer).map(Box::new))
+                    .transpose()?,
+            }),
+        }
+    }
+
+    fn lower_variant(
+        name: Symbol,
+        patterns: &[Self],
+        lowerer: &mut Lowerer,
+    ) -> Result<SlotPattern, String> {
+        let (enum_name, num_fields) = match lowerer.lookup_constructor(name) {
+            Some((enum_name, num_fields)) => (enum_name, num_fields),
+            None => return Err(format!("constructor with name ‘{}’ does not exist", name)),
+        };
+
+        if num_fields != patterns.len() {
+            return Err(format!(
+                "expected {} fields in pattern for ‘{}’, got {}",
+                num_fields,
+                name,
+                patterns.len(),
+            ));
+        }
+
+        Ok(SlotPattern::Variant {
+            enum_name,
+            variant: name,
+            fields: patterns
+                .iter()
+                .map(|pattern| pattern.lower(lowerer))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Stores the types of every binding introduced by the pattern in `env`, reporting an error if
+    /// it could never match a value of type `ty`.
+    pub(crate) fn check(&self, ty: &Type, env: &mut TypeEnv, checker: &mut Checker, span: Span) {
+        match self {
+            Self::Wildcard => {}
+            Self::Number(n) => {
+                checker.expect(ty, &Type::Int, span, &format!("pattern ‘{}’", n));
+            }
+            Self::Bool(b) => {
+                checker.expect(ty, &Type::Bool, span, &format!("pattern ‘{}’", b));
+            }
+            Self::Binding(name) => {
+                if let Some(TypeInfo::Constructor { .. }) = env.get(*name) {
+                    return Self::check_variant(*name, &[], ty, env, checker, span);
+                }
+
+                env.store_binding(*name, ty.clone());
+            }
+            Self::Variant { name, fields } => {
+                Self::check_variant(*name, fields, ty, env, checker, span)
+            }
+            Self::List { items, rest } => {
+                let item_ty = checker.fresh_var();
+                let list_ty = Type::List(Box::new(item_ty.clone()));
+                checker.expect(ty, &list_ty, span, "list pattern");
+
+                for pattern in items {
+                    pattern.check(&item_ty, env, checker, span);
+                }
+                if let Some(rest) = rest {
+                    rest.check(&list_ty, env, checker, span);
+                }
+            }
+        }
+    }
+
+    fn check_variant(
+        name: Symbol,
+        patterns: &[Self],
+        ty: &Type,
+        env: &mut TypeEnv,
+        checker: &mut Checker,
+        span: Span,
+    ) {
+        // Patterns naming unknown constructors are left for evaluation to report, but still bind
+        // their fields
+        let field_tys = match env.get(name) {
+            Some(info @ TypeInfo::Constructor { .. }) => {
+                let Type::Func { params, ret } = checker.instantiate(&info.to_type()) else {
+                    unreachable!()
+                };
+
+                let what = format!("pattern for ‘{}’", name);
+                checker.expect(ty, &ret, span, &what);
+
+                if params.len() != patterns.len() {
+                    checker.error(
+                        format!(
+                            "expected {} fields in pattern for ‘{}’, got {}",
+                            params.len(),
+                            name,
+                            patterns.len(),
+                        ),
+                        span,
+                    );
+                }
+
+                params
+            }
+            _ => Vec::new(),
+        };
+
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let field_ty = field_tys.get(idx).cloned().unwrap_or(Type::Unknown);
+            pattern.check(&field_ty, env, checker, span);
+        }
+    }
+
+    /// Returns whether the pattern matches every value it could be given.
+    pub(crate) fn is_irrefutable(&self, env: &TypeEnv) -> bool {
+        match self {
+            Self::Wildcard => true,
+            Self::Binding(name) => !matches!(env.get(*name), Some(TypeInfo::Constructor { .. })),
+            Self::Number(_) | Self::Bool(_) | Self::Variant { .. } | Self::List { .. } => false,
+        }
+    }
+
+    fn bind_variant(
+        name: Symbol,
+        patterns: &[Self],
+        val: &Val,
+        env: &mut Env,
+    ) -> Result<bool, String> {
+        let (enum_name, field_names) = env.get_constructor(name)?;
+
+        if field_names.len() != patterns.len() {
+            return Err(format!(
+                "expected {} fields in pattern for ‘{}’, got {}",
+                field_names.len(),
+                name,
+                patterns.len(),
+            ));
+        }
+
+        let fields = match val {
+            Val::Variant {
+                enum_name: val_enum_name,
+                variant,
+                fields,
+            } if *val_enum_name == enum_name && *variant == name => fields,
+            _ => return Ok(false),
+        };
+
+        for (pattern, field) in patterns.iter().zip(fields) {
+            if !pattern.bind(field, env)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// The items of `val` if it is a list with `len` items, or with at least that many if `rest` is
+/// set.
+pub(crate) fn list_items(val: &Val, len: usize, rest: bool) -> Option<&[Val]> {
+    match val {
+        Val::List(items) if items.len() == len || (rest && items.len() > len) => Some(items),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wildcard_pattern() {
+        assert_eq!(Pattern::new("_ =>"), Ok((" =>", Pattern::Wildcard)));
+    }
+
+    #[test]
+    fn parse_variant_pattern_with_nested_patterns() {
+        assert_eq!(
+            Pattern::new("Rect(w, 1, _)"),
+            Ok((
+                "",
+                Pattern::Variant {
+                    name: Symbol::intern("Rect"),
+                    fields: vec![
+                        Pattern::Binding(Symbol::intern("w")),
+                        Pattern::Number(1),
+                        Pattern::Wildcard,
+                    ],
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_bool_pattern() {
+        assert_eq!(Pattern::new("false"), Ok(("", Pattern::Bool(false))));
+    }
+
+    #[test]
+    fn cannot_parse_number_pattern_that_is_too_large() {
+        assert!(Pattern::new("99999999999 => 1").is_err());
+        assert!(crate::parse("match 1 { 99999999999 => 1, _ => 0 }").is_err());
+    }
+
+    #[test]
+    fn parse_list_patterns() {
+        assert_eq!(
+            Pattern::new("[] =>"),
+            Ok((
+                " =>",
+                Pattern::List {
+                    items: Vec::new(),
+                    rest: None,
+                },
+            )),
+        );
+        assert_eq!(
+            Pattern::new("[x, 1, ..rest]"),
+            Ok((
+                "",
+                Pattern::List {
+                    items: vec![Pattern::Binding(Symbol::intern("x")), Pattern::Number(1)],
+                    rest: Some(Box::new(Pattern::Binding(Symbol::intern("rest")))),
+                },
+            )),
+        );
+        assert_eq!(
+            Pattern::new("[_, ..]"),
+            Ok((
+                "",
+                Pattern::List {
+                    items: vec![Pattern::Wildcard],
+                    rest: Some(Box::new(Pattern::Wildcard)),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn bind_list_pattern() {
+        let mut env = Env::default();
+        let list = Val::List(vec![Val::Number(1), Val::Number(2), Val::Number(3)]);
+
+        let (_, pattern) = Pattern::new("[x, ..rest]").unwrap();
+        assert_eq!(pattern.bind(&list, &mut env), Ok(true));
+        assert_eq!(env.get_binding(Symbol::intern("x")), Ok(Val::Number(1)));
+        assert_eq!(
+            env.get_binding(Symbol::intern("rest")),
+            Ok(Val::List(vec![Val::Number(2), Val::Number(3)])),
+        );
+
+        let (_, pattern) = Pattern::new("[x, y]").unwrap();
+        assert_eq!(pattern.bind(&list, &mut env), Ok(false));
+    }
+
+    #[test]
+    fn bind_binding_pattern() {
+        let mut env = Env::default();
+
+        assert_eq!(
+            Pattern::Binding(Symbol::intern("x")).bind(&Val::Number(5), &mut env),
+            Ok(true),
+        );
+        assert_eq!(env.get_binding(Symbol::intern("x")), Ok(Val::Number(5)));
+    }
+
+    #[test]
+    fn bind_variant_pattern() {
+        let mut env = Env::default();
+        env.store_constructor(
+            Symbol::intern("Circle"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("r")],
+        );
+
+        let circle = Val::Variant {
+            enum_name: Symbol::intern("Shape"),
+            variant: Symbol::intern("Circle"),
+            fields: vec![Val::Number(3)],
+        };
+
+        assert_eq!(
+            Pattern::Variant {
+                name: Symbol::intern("Circle"),
+                fields: vec![Pattern::Binding(Symbol::intern("radius"))],
+            }
+            .bind(&circle, &mut env),
+            Ok(true),
+        );
+        assert_eq!(
+            env.get_binding(Symbol::intern("radius")),
+            Ok(Val::Number(3))
+        );
+    }
+
+    #[test]
+    fn bind_constructor_without_payload_as_variant_pattern() {
+        let mut env = Env::default();
+        env.store_constructor(Symbol::intern("None"), Symbol::intern("Option"), Vec::new());
+
+        assert_eq!(
+            Pattern::Binding(Symbol::intern("None")).bind(&Val::Number(1), &mut env),
+            Ok(false),
+        );
+    }
+
+    #[test]
+    fn bind_variant_pattern_with_wrong_number_of_fields() {
+        let mut env = Env::default();
+        env.store_constructor(
+            Symbol::intern("Rect"),
+            Symbol::intern("Shape"),
+            vec![Symbol::intern("w"), Symbol::intern("h")],
+        );
+
+        assert_eq!(
+            Pattern::Variant {
+                name: Symbol::intern("Rect"),
+                fields: vec![Pattern::Wildcard],
+            }
+            .bind(&Val::Unit, &mut env),
+            Err("expected 2 fields in pattern for ‘Rect’, got 1".to_string()),
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use crate::builtins;
+use crate::diagnostic::Diagnostic;
+use crate::lint::{Lint, Lints};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::{TypeEnv, TypeInfo};
+
+/// What a name refers to, as worked out before the program is run.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum NameKind {
+    Binding,
+    Func,
+    Constructor,
+    Builtin,
+}
+
+impl NameKind {
+    fn is_callable(self) -> bool {
+        self != Self::Binding
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Binding => "binding",
+            Self::Func => "function",
+            Self::Constructor => "constructor",
+            Self::Builtin => "builtin",
+        }
+    }
+}
+
+/// Everything the resolver knows about the definition of a name.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Definition {
+    pub(crate) kind: NameKind,
+    // The number of arguments a function or constructor is called with
+    pub(crate) arity: usize,
+    // Names defined by earlier inputs or built into the language have no location to point to
+    pub(crate) span: Option<Span>,
+}
+
+impl Definition {
+    fn binding(span: Option<Span>) -> Self {
+        Self {
+            kind: NameKind::Binding,
+            arity: 0,
+            span,
+        }
+    }
+}
+
+// A name defined in one of the scopes the resolver is in
+struct Entry {
+    name: Symbol,
+    definition: Definition,
+    used: bool,
+    // What to report if the name goes out of scope without having been used
+    unused_lint: Option<Lint>,
+}
+
+/// Links every use of a name to the definition it refers to, keeping track of which names are
+/// in scope at each point of the program.
+pub(crate) struct Resolver<'env> {
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    // Names defined by earlier inputs, which are only known through the types they were given
+    globals: &'env TypeEnv<'env>,
+    lints: &'env Lints,
+    // Entries are kept in the order they were defined in, so that later ones shadow earlier ones
+    scopes: Vec<Vec<Entry>>,
+}
+
+impl<'env> Resolver<'env> {
+    pub(crate) fn new(globals: &'env TypeEnv<'env>, lints: &'env Lints) -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            globals,
+            lints,
+            scopes: vec![Vec::new()],
+        }
+    }
+
+    /// Defines a function or constructor.
+    pub(crate) fn define(&mut self, name: Symbol, definition: Definition) {
+        self.insert(name, definition, None);
+    }
+
+    pub(crate) fn define_binding(&mut self, name: Symbol, span: Span) {
+        self.insert(
+            name,
+            Definition::binding(Some(span)),
+            Some(Lint::UnusedBinding),
+        );
+    }
+
+    pub(crate) fn define_param(&mut self, name: Symbol, span: Span) {
+        self.insert(
+            name,
+            Definition::binding(Some(span)),
+            Some(Lint::UnusedParam),
+        );
+    }
+
+    fn insert(&mut self, name: Symbol, definition: Definition, unused_lint: Option<Lint>) {
+        if let Some(previous) = self.lookup(name) {
+            self.check_redefinition(name, previous, definition);
+        }
+
+        self.scopes.last_mut().unwrap().push(Entry {
+            name,
+            definition,
+            used: false,
+            unused_lint,
+        });
+    }
+
+    /// Whether the name is defined in the innermost scope by the definition at `span`, which is
+    /// the case for functions that were declared ahead of their definition.
+    pub(crate) fn is_defined_here(&self, name: Symbol, span: Span) -> bool {
+        self.scopes
+            .last()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|entry| entry.name == name)
+            .is_some_and(|entry| entry.definition.span.is_some_and(|defined| defined == span))
+    }
+
+    fn check_redefinition(&mut self, name: Symbol, previous: Definition, definition: Definition) {
+        // Builtins are only used when nothing else has their name, so defining one is expected
+        if previous.kind == NameKind::Builtin {
+            return;
+        }
+
+        let (lint, message) = if previous.kind == definition.kind {
+            (
+                Lint::Shadowing,
+                format!("‘{}’ shadows an earlier {}", name, previous.kind.describe()),
+            )
+        } else {
+            (
+                Lint::NameCollision,
+                format!(
+                    "‘{}’ is defined as a {}, but already names a {}",
+                    name,
+                    definition.kind.describe(),
+                    previous.kind.describe(),
+                ),
+            )
+        };
+
+        let mut diagnostic = Diagnostic::warning(message, definition.span.unwrap_or_default());
+
+        if let Some(previous_span) = previous.span {
+            diagnostic =
+                diagnostic.with_note("previously defined here".to_string(), Some(previous_span));
+        }
+
+        self.lint(lint, diagnostic);
+    }
+
+    /// Runs `f` in a scope of its own, forgetting everything it defines afterwards.
+    pub(crate) fn in_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(Vec::new());
+        let result = f(self);
+
+        for entry in self.scopes.pop().unwrap() {
+            if let (false, Some(lint), Some(span)) =
+                (entry.used, entry.unused_lint, entry.definition.span)
+            {
+                let message = format!("‘{}’ is never used", entry.name);
+                self.lint(lint, Diagnostic::warning(message, span));
+            }
+        }
+
+        result
+    }
+
+    /// Reports a problem found by `lint`, unless it is allowed.
+    pub(crate) fn lint(&mut self, lint: Lint, diagnostic: Diagnostic) {
+        self.diagnostics.extend(self.lints.apply(lint, diagnostic));
+    }
+
+    pub(crate) fn lookup(&self, name: Symbol) -> Option<Definition> {
+        if let Some(entry) = self.find_entry(name) {
+            return Some(entry.definition);
+        }
+
+        let (kind, arity) = match self.globals.get(name) {
+            Some(TypeInfo::Binding(_)) => return Some(Definition::binding(None)),
+            Some(TypeInfo::Func { params, .. }) => (NameKind::Func, params.len()),
+            Some(TypeInfo::Constructor { fields, .. }) => (NameKind::Constructor, fields.len()),
+            None => (NameKind::Builtin, builtins::get(name.as_str())?.arity),
+        };
+
+        Some(Definition {
+            kind,
+            arity,
+            span: None,
+        })
+    }
+
+    fn find_entry(&self, name: Symbol) -> Option<&Entry> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|entry| entry.name == name))
+    }
+
+    fn mark_used(&mut self, name: Symbol) {
+        let entry = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|entry| entry.name == name));
+
+        if let Some(entry) = entry {
+            entry.used = true;
+        }
+    }
+
+    /// Returns what `name` refers to where it is used as a value, reporting an error if it is
+    /// not defined. Functions and constructors used this way are called without arguments.
+    pub(crate) fn resolve_usage(&mut self, name: Symbol, span: Span) -> Option<NameKind> {
+        self.mark_used(name);
+
+        let Some(definition) = self.lookup(name) else {
+            self.undefined(name, span, |_| true);
+            return None;
+        };
+
+        if definition.kind.is_callable() {
+            self.check_arity(name, definition, 0, span);
+        }
+
+        Some(definition.kind)
+    }
+
+    /// Returns what `name` refers to where it is called with `num_args` arguments, reporting an
+    /// error if it cannot be.
+    pub(crate) fn resolve_callee(
+        &mut self,
+        name: Symbol,
+        num_args: usize,
+        span: Span,
+    ) -> Option<NameKind> {
+        self.mark_used(name);
+
+        match self.lookup(name) {
+            Some(Definition {
+                kind: NameKind::Binding,
+                ..
+            }) => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("cannot call ‘{}’, which is not a function", name),
+                    span,
+                ));
+                None
+            }
+            Some(definition) => {
+                self.check_arity(name, definition, num_args, span);
+                Some(definition.kind)
+            }
+            None => {
+                self.undefined(name, span, NameKind::is_callable);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn resolve_constructor(&mut self, name: Symbol, span: Span) {
+        match self.lookup(name).map(|definition| definition.kind) {
+            Some(NameKind::Constructor) => {}
+            Some(_) => self.diagnostics.push(Diagnostic::error(
+                format!("‘{}’ is not a constructor", name),
+                span,
+            )),
+            None => self.undefined(name, span, |kind| kind == NameKind::Constructor),
+        }
+    }
+
+    fn check_arity(&mut self, name: Symbol, definition: Definition, num_args: usize, span: Span) {
+        if definition.arity == num_args {
+            return;
+        }
+
+        let takes = match definition.arity {
+            1 => "1 argument".to_string(),
+            arity => format!("{} arguments", arity),
+        };
+        let mut diagnostic = Diagnostic::error(
+            format!("‘{}’ takes {}, got {}", name, takes, num_args),
+            span,
+        );
+
+        if let Some(definition_span) = definition.span {
+            diagnostic =
+                diagnostic.with_note(format!("‘{}’ is defined here", name), Some(definition_span));
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn undefined(&mut self, name: Symbol, span: Span, is_candidate: impl Fn(NameKind) -> bool) {
+        let mut diagnostic = Diagnostic::error(format!("‘{}’ is not defined", name), span);
+
+        if let Some(suggestion) = self.suggest(name, is_candidate) {
+            diagnostic = diagnostic.with_note(format!("did you mean ‘{}’?", suggestion), None);
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    // Finds the name in scope that is closest to `name`, if any is close enough to have been
+    // what was meant
+    fn suggest(&self, name: Symbol, is_candidate: impl Fn(NameKind) -> bool) -> Option<Symbol> {
+        let mut candidates: Vec<Symbol> = self
+            .scopes
+            .iter()
+            .flatten()
+            .map(|entry| entry.name)
+            .chain(self.globals.names())
+            .chain(builtins::NAMES.map(Symbol::intern))
+            .filter(|candidate| {
+                self.lookup(*candidate)
+                    .is_some_and(|definition| is_candidate(definition.kind))
+            })
+            .collect();
+
+        // Sorted so that ties are broken the same way every time
+        candidates.sort_unstable_by_key(|candidate| candidate.as_str());
+        candidates.dedup();
+
+        let name = name.as_str();
+        let max_distance = (name.chars().count() / 3).max(1);
+
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(name, candidate.as_str()), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+}
+
+// The number of characters that have to be inserted, removed, replaced or swapped with their
+// neighbour to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // distances[i][j] is the distance between the first i characters of `a` and the first j
+    // characters of `b`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            distances[i][j] = if i == 0 || j == 0 {
+                i + j
+            } else {
+                let replaced = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+                let mut distance = replaced
+                    .min(distances[i - 1][j] + 1)
+                    .min(distances[i][j - 1] + 1);
+
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    distance = distance.min(distances[i - 2][j - 2] + 1);
+                }
+
+                distance
+            };
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::Level;
+
+    fn resolve(s: &str) -> Vec<Diagnostic> {
+        let mut parse = crate::parse(s).unwrap();
+        let env = TypeEnv::default();
+        let mut lints = Lints::default();
+        lints.set_all(Level::Allow);
+        let mut resolver = Resolver::new(&env, &lints);
+        parse.0.resolve(&mut resolver);
+
+        resolver.diagnostics
+    }
+
+    fn messages(diagnostics: Vec<Diagnostic>) -> Vec<String> {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn edit_distance_between_names() {
+        assert_eq!(edit_distance("length", "length"), 0);
+        assert_eq!(edit_distance("lenght", "length"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn resolve_defined_names() {
+        assert_eq!(
+            resolve(
+                "{
+    enum Shape { Circle(r), Empty }
+    fn area shape => match shape { Circle(r) => r * r, Empty => 0 }
+    let xs = [Circle 1, Empty]
+    for x in xs { area x }
+    keys #{}
+}",
+            ),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn report_every_undefined_name() {
+        assert_eq!(
+            messages(resolve(
+                "{
+    let a = b + c
+    fn f x => y
+}",
+            )),
+            vec![
+                "‘b’ is not defined".to_string(),
+                "‘c’ is not defined".to_string(),
+                "‘y’ is not defined".to_string(),
+            ],
+        );
+    }
+
+    #[test]
+    fn suggest_similar_name() {
+        assert_eq!(
+            resolve("{ let count = 1 cuont + 1 }"),
+            vec![
+                Diagnostic::error(
+                    "‘cuont’ is not defined".to_string(),
+                    Span::new("cuont + 1 }", " + 1 }")
+                )
+                .with_note("did you mean ‘count’?".to_string(), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn suggest_only_callable_names_for_calls() {
+        assert_eq!(
+            resolve("{ let key = 1 kes #{} }")[0].notes[0].message,
+            "did you mean ‘keys’?",
+        );
+    }
+
+    #[test]
+    fn names_do_not_leak_out_of_scopes() {
+        assert_eq!(
+            messages(resolve(
+                "{
+    { let a = 1 }
+    for i in 0..3 { i }
+    match 1 { n => n }
+    [a, i, n]
+}",
+            )),
+            vec![
+                "‘a’ is not defined".to_string(),
+                "‘i’ is not defined".to_string(),
+                "‘n’ is not defined".to_string(),
+            ],
+        );
+    }
+
+    #[test]
+    fn binding_is_not_in_scope_in_its_own_value() {
+        assert_eq!(
+            messages(resolve("let a = a")),
+            vec!["‘a’ is not defined".to_string()],
+        );
+    }
+
+    #[test]
+    fn resolve_funcs_calling_each_other() {
+        assert_eq!(
+            resolve(
+                "{
+    fn even n => match n { 0 => true, _ => odd { n - 1 } }
+    fn odd n => match n { 0 => false, _ => even { n - 1 } }
+    even 4
+}",
+            ),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn func_is_not_in_scope_before_its_definition() {
+        assert_eq!(
+            messages(resolve(
+                "{
+    let a = f
+    fn f => 1
+    a
+}",
+            )),
+            vec!["‘f’ is not defined".to_string()],
+        );
+    }
+
+    #[test]
+    fn report_call_of_binding() {
+        assert_eq!(
+            messages(resolve("{ let f = 1 f 2 }")),
+            vec!["cannot call ‘f’, which is not a function".to_string()],
+        );
+    }
+
+    #[test]
+    fn report_unknown_constructor_in_pattern() {
+        assert_eq!(
+            messages(resolve("match 1 { Some(x) => x }")),
+            vec!["‘Some’ is not defined".to_string()],
+        );
+    }
+
+    #[test]
+    fn report_every_arity_mismatch() {
+        assert_eq!(
+            messages(resolve(
+                "{
+    enum Shape { Rect(w, h) }
+    fn add x y => x + y
+    [add 1, Rect 1 2 3, has #{}, add]
+}",
+            )),
+            vec![
+                "‘add’ takes 2 arguments, got 1".to_string(),
+                "‘Rect’ takes 2 arguments, got 3".to_string(),
+                "‘has’ takes 2 arguments, got 1".to_string(),
+                "‘add’ takes 2 arguments, got 0".to_string(),
+            ],
+        );
+    }
+
+    #[test]
+    fn point_at_definition_of_func_called_with_wrong_arity() {
+        let source = "{
+    fn add x y => x + y
+    add 1
+}";
+        let rendered: String = resolve(source)
+            .iter()
+            .map(|diagnostic| diagnostic.render(source))
+            .collect();
+
+        assert_eq!(
+            rendered,
+            "error: ‘add’ takes 2 arguments, got 1
+  --> 3:5
+  |
+3 |     add 1
+  |     ^^^^^
+note: ‘add’ is defined here
+  --> 2:5
+  |
+2 |     fn add x y => x + y
+  |     ^^^^^^^^^^^^^^^^^^^
+",
+        );
+    }
+
+    #[test]
+    fn check_arity_of_funcs_from_earlier_inputs() {
+        let mut env = TypeEnv::default();
+        let lints = Lints::default();
+        crate::parse("fn double x => x * 2")
+            .unwrap()
+            .check(&mut env, &lints);
+
+        let mut parse = crate::parse("double 1 2").unwrap();
+        let mut resolver = Resolver::new(&env, &lints);
+        parse.0.resolve(&mut resolver);
+
+        assert_eq!(
+            resolver.diagnostics,
+            vec![Diagnostic::error(
+                "‘double’ takes 1 argument, got 2".to_string(),
+                Span::new("double 1 2", ""),
+            )],
+        );
+    }
+}
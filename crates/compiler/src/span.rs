@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+/// A region of the source code that a syntax tree node was parsed from.
+///
+/// Parsers only ever see the input that is left to parse, so rather than byte offsets a span
+/// records how far from the end of the input it starts and ends. `range` turns this back into
+/// offsets once the complete source is known.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    start_from_end: usize,
+    end_from_end: usize,
+}
+
+impl Span {
+    /// Creates a span covering everything consumed between `before` and `after`, which must both
+    /// be suffixes of the same input.
+    pub(crate) fn new(before: &str, after: &str) -> Self {
+        Self {
+            start_from_end: before.len(),
+            end_from_end: after.len(),
+        }
+    }
+
+    /// Creates a span covering both `self` and `other`, and everything in between.
+    pub(crate) fn to(self, other: Self) -> Self {
+        Self {
+            start_from_end: self.start_from_end.max(other.start_from_end),
+            end_from_end: self.end_from_end.min(other.end_from_end),
+        }
+    }
+
+    /// Returns the byte offsets covered by the span in `source`, which must be the complete input
+    /// the span was parsed from.
+    pub fn range(&self, source: &str) -> Range<usize> {
+        source.len() - self.start_from_end..source.len() - self.end_from_end
+    }
+}
+
+/// Finds which line of a source spans in it start on.
+pub(crate) struct Lines {
+    len: usize,
+    // The offset at which each line starts
+    starts: Vec<usize>,
+}
+
+impl Lines {
+    pub(crate) fn new(source: &str) -> Self {
+        Self {
+            len: source.len(),
+            starts: std::iter::once(0)
+                .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+                .collect(),
+        }
+    }
+
+    /// Returns the line `span` starts on, counting from one.
+    pub(crate) fn line_of(&self, span: Span) -> usize {
+        let start = self.len - span.start_from_end;
+        self.starts
+            .partition_point(|line_start| *line_start <= start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_of_span() {
+        let source = "let a = 10";
+        let span = Span::new(&source[8..], &source[10..]);
+
+        assert_eq!(span.range(source), 8..10);
+    }
+
+    #[test]
+    fn range_of_joined_spans() {
+        let source = "1 + 2 + 3";
+        let first = Span::new(&source[0..], &source[1..]);
+        let last = Span::new(&source[8..], &source[9..]);
+
+        assert_eq!(first.to(last).range(source), 0..9);
+        assert_eq!(last.to(first).range(source), 0..9);
+    }
+
+    #[test]
+    fn line_of_span() {
+        let source = "let a = 1\n\nlet b = a";
+        let lines = Lines::new(source);
+
+        assert_eq!(lines.line_of(Span::new(source, &source[3..])), 1);
+        assert_eq!(lines.line_of(Span::new(&source[11..], "")), 3);
+    }
+}
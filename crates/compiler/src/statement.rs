@@ -1,13 +1,28 @@
 use crate::binding_def::BindingDef;
+use crate::checker::Checker;
+use crate::enum_def::EnumDef;
 use crate::env::Env;
 use crate::expr::Expr;
+use crate::for_loop::ForLoop;
 use crate::func_def::FuncDef;
+use crate::interrupt::Interrupt;
+use crate::lower::{Lowerer, Node};
+use crate::resolver::Resolver;
+use crate::span::Span;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::utils;
 use crate::val::Val;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum Statement {
     BindingDef(BindingDef),
     FuncDef(FuncDef),
+    EnumDef(EnumDef),
+    ForLoop(ForLoop),
+    Break(Span),
+    Continue(Span),
+    Return(Expr, Span),
     Expr(Expr),
 }
 
@@ -16,10 +31,31 @@ impl Statement {
         BindingDef::new(s)
             .map(|(s, binding_def)| (s, Self::BindingDef(binding_def)))
             .or_else(|_| FuncDef::new(s).map(|(s, func_def)| (s, Self::FuncDef(func_def))))
+            .or_else(|_| EnumDef::new(s).map(|(s, enum_def)| (s, Self::EnumDef(enum_def))))
+            .or_else(|_| ForLoop::new(s).map(|(s, for_loop)| (s, Self::ForLoop(for_loop))))
+            .or_else(|_| {
+                utils::keyword("break", s).map(|after| (after, Self::Break(Span::new(s, after))))
+            })
+            .or_else(|_| {
+                utils::keyword("continue", s)
+                    .map(|after| (after, Self::Continue(Span::new(s, after))))
+            })
+            .or_else(|_| Self::new_return(s))
             .or_else(|_| Expr::new(s).map(|(s, expr)| (s, Self::Expr(expr))))
     }
 
-    pub(crate) fn eval(&self, env: &mut Env) -> Result<Val, String> {
+    fn new_return(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let s = utils::keyword("return", before)?;
+        let (s, _) = utils::extract_whitespace_non_empty(s)?;
+
+        let (s, expr) = Expr::new(s)?;
+
+        Ok((s, Self::Return(expr, Span::new(before, s))))
+    }
+
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<Val, Interrupt> {
         match self {
             Self::BindingDef(binding_def) => {
                 binding_def.eval(env)?;
@@ -29,15 +65,116 @@ impl Statement {
                 func_def.eval(env)?;
                 Ok(Val::Unit)
             }
+            Self::EnumDef(enum_def) => {
+                enum_def.eval(env)?;
+                Ok(Val::Unit)
+            }
+            Self::ForLoop(for_loop) => {
+                for_loop.eval(env)?;
+                Ok(Val::Unit)
+            }
+            Self::Break(_) => Err(Interrupt::Break),
+            Self::Continue(_) => Err(Interrupt::Continue),
+            Self::Return(expr, _) => Err(Interrupt::Return(expr.eval(env)?)),
             Self::Expr(expr) => expr.eval(env),
         }
     }
+
+    pub(crate) fn resolve(&mut self, resolver: &mut Resolver) {
+        match self {
+            Self::BindingDef(binding_def) => binding_def.resolve(resolver),
+            Self::FuncDef(func_def) => func_def.resolve(resolver),
+            Self::EnumDef(enum_def) => enum_def.resolve(resolver),
+            Self::ForLoop(for_loop) => for_loop.resolve(resolver),
+            Self::Break(_) | Self::Continue(_) => {}
+            Self::Return(expr, _) | Self::Expr(expr) => expr.resolve(resolver),
+        }
+    }
+
+    pub(crate) fn lower(&self, lowerer: &mut Lowerer) -> Result<Node, String> {
+        let node = match self {
+            Self::BindingDef(binding_def) => binding_def.lower(lowerer),
+            Self::FuncDef(func_def) => {
+                func_def.lower(lowerer)?;
+                Ok(Node::Const(Val::Unit))
+            }
+            Self::EnumDef(enum_def) => {
+                enum_def.lower(lowerer);
+                Ok(Node::Const(Val::Unit))
+            }
+            Self::ForLoop(for_loop) => for_loop.lower(lowerer),
+            Self::Break(_) => Ok(Node::Break),
+            Self::Continue(_) => Ok(Node::Continue),
+            Self::Return(expr, _) => Ok(Node::Return(Box::new(expr.lower(lowerer)?))),
+            Self::Expr(expr) => expr.lower(lowerer),
+        }?;
+
+        // Where each statement came from is kept so that compiled code can be traced back to it
+        Ok(match self.span() {
+            Some(span) => Node::Spanned {
+                span,
+                node: Box::new(node),
+            },
+            None => node,
+        })
+    }
+
+    pub(crate) fn span(&self) -> Option<Span> {
+        match self {
+            Self::BindingDef(binding_def) => Some(binding_def.span),
+            Self::FuncDef(func_def) => Some(func_def.span),
+            Self::EnumDef(_) => None,
+            Self::ForLoop(for_loop) => Some(for_loop.span),
+            Self::Break(span) | Self::Continue(span) | Self::Return(_, span) => Some(*span),
+            Self::Expr(expr) => expr.span(),
+        }
+    }
+
+    pub(crate) fn check(&self, env: &mut TypeEnv, checker: &mut Checker) -> Type {
+        match self {
+            Self::BindingDef(binding_def) => {
+                binding_def.check(env, checker);
+                Type::Unit
+            }
+            Self::FuncDef(func_def) => {
+                func_def.check(env, checker);
+                Type::Unit
+            }
+            Self::EnumDef(enum_def) => {
+                enum_def.check(env, checker);
+                Type::Unit
+            }
+            Self::ForLoop(for_loop) => {
+                for_loop.check(env, checker);
+                Type::Unit
+            }
+            Self::Break(span) => {
+                checker.check_loop_control("break", *span);
+                Type::Never
+            }
+            Self::Continue(span) => {
+                checker.check_loop_control("continue", *span);
+                Type::Never
+            }
+            Self::Return(expr, span) => {
+                let ty = expr.check(env, checker);
+                checker.check_return(ty, *span);
+                Type::Never
+            }
+            Self::Expr(expr) => expr.check(env, checker),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::symbol::Symbol;
+    use std::rc::Rc;
+
     use super::*;
     use crate::expr::{BindingUsage, Block, Expr, Number, Op};
+    use crate::func_def::Param;
+    use crate::span::Span;
 
     #[test]
     fn parse_expr() {
@@ -49,6 +186,7 @@ mod tests {
                     lhs: Box::new(Expr::Number(Number(1))),
                     rhs: Box::new(Expr::Number(Number(1))),
                     op: Op::Add,
+                    span: Span::new("1+1", ""),
                 }),
             )),
         );
@@ -62,6 +200,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_break() {
+        assert_eq!(
+            Statement::new("break }"),
+            Ok((" }", Statement::Break(Span::new("break }", " }"))))
+        );
+    }
+
+    #[test]
+    fn parse_binding_usage_starting_with_continue() {
+        assert_eq!(
+            Statement::new("continued"),
+            Ok((
+                "",
+                Statement::Expr(Expr::BindingUsage(BindingUsage {
+                    name: Symbol::intern("continued"),
+                    kind: None,
+                    span: Span::new("continued", "")
+                })),
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_continue() {
+        assert_eq!(
+            Statement::Continue(Span::default()).eval(&mut Env::default()),
+            Err(Interrupt::Continue),
+        );
+    }
+
+    #[test]
+    fn parse_return() {
+        assert_eq!(
+            Statement::new("return 1 + 1"),
+            Ok((
+                "",
+                Statement::Return(
+                    Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(1))),
+                        rhs: Box::new(Expr::Number(Number(1))),
+                        op: Op::Add,
+                        span: Span::new("1 + 1", ""),
+                    },
+                    Span::new("return 1 + 1", ""),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_return() {
+        assert_eq!(
+            Statement::Return(Expr::Number(Number(3)), Span::default()).eval(&mut Env::default()),
+            Err(Interrupt::Return(Val::Number(3))),
+        );
+    }
+
     #[test]
     fn parse_func_def() {
         assert_eq!(
@@ -69,11 +265,20 @@ mod tests {
             Ok((
                 "",
                 Statement::FuncDef(FuncDef {
-                    name: "identity".to_string(),
-                    params: vec!["x".to_string()],
-                    body: Box::new(Statement::Expr(Expr::BindingUsage(BindingUsage {
-                        name: "x".to_string(),
+                    name: Symbol::intern("identity"),
+                    type_params: Vec::new(),
+                    params: vec![Param {
+                        name: Symbol::intern("x"),
+                        ty: None,
+                        span: Span::new("x => x", " => x"),
+                    }],
+                    ret: None,
+                    body: Rc::new(Statement::Expr(Expr::BindingUsage(BindingUsage {
+                        name: Symbol::intern("x"),
+                        kind: None,
+                        span: Span::new("x", "")
                     }))),
+                    span: Span::new("fn identity x => x", ""),
                 }),
             )),
         );
@@ -86,9 +291,15 @@ mod tests {
             Ok((
                 "",
                 FuncDef {
-                    name: "nothing".to_string(),
+                    name: Symbol::intern("nothing"),
+                    type_params: Vec::new(),
                     params: Vec::new(),
-                    body: Box::new(Statement::Expr(Expr::Block(Block { stmts: Vec::new() }))),
+                    ret: None,
+                    body: Rc::new(Statement::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::new("{}", "")
+                    }))),
+                    span: Span::new("fn nothing => {}", ""),
                 },
             )),
         );
@@ -101,9 +312,19 @@ mod tests {
             Ok((
                 "",
                 FuncDef {
-                    name: "greet".to_string(),
-                    params: vec!["name".to_string()],
-                    body: Box::new(Statement::Expr(Expr::Block(Block { stmts: Vec::new() }))),
+                    name: Symbol::intern("greet"),
+                    type_params: Vec::new(),
+                    params: vec![Param {
+                        name: Symbol::intern("name"),
+                        ty: None,
+                        span: Span::new("name => {}", " => {}"),
+                    }],
+                    ret: None,
+                    body: Rc::new(Statement::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::new("{}", "")
+                    }))),
+                    span: Span::new("fn greet name => {}", ""),
                 },
             )),
         );
@@ -113,9 +334,12 @@ mod tests {
     fn eval_func_def() {
         assert_eq!(
             Statement::FuncDef(FuncDef {
-                name: "always_return_one".to_string(),
+                name: Symbol::intern("always_return_one"),
+                type_params: Vec::new(),
                 params: Vec::new(),
-                body: Box::new(Statement::Expr(Expr::Number(Number(1)))),
+                ret: None,
+                body: Rc::new(Statement::Expr(Expr::Number(Number(1)))),
+                span: Span::default(),
             })
             .eval(&mut Env::default()),
             Ok(Val::Unit),
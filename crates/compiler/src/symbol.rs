@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+/// An interned identifier. Symbols are cheap to copy and compare, and are only turned back into
+/// text when they need to be shown.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+// Identifiers are few and live for as long as the program they appear in, which is usually the
+// whole session, so they are never freed
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Mutex::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = INTERNER.lock().unwrap();
+
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Self(interner.names.len() as u32);
+        let name: &'static str = Box::leak(name.into());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_same_name_once() {
+        assert_eq!(Symbol::intern("fib"), Symbol::intern("fib"));
+        assert_ne!(Symbol::intern("fib"), Symbol::intern("fob"));
+    }
+
+    #[test]
+    fn map_symbol_back_to_name() {
+        let symbol = Symbol::intern("counter");
+
+        assert_eq!(symbol.as_str(), "counter");
+        assert_eq!(symbol.to_string(), "counter");
+        assert_eq!(format!("{:?}", symbol), "\"counter\"");
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use crate::symbol::Symbol;
+use crate::types::Type;
+
+/// The static counterpart of `Env`, recording the types of names rather than their values.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TypeEnv<'parent> {
+    named: HashMap<Symbol, TypeInfo>,
+    enums: HashMap<Symbol, EnumInfo>,
+    type_params: HashMap<Symbol, Type>,
+    parent: Option<&'parent Self>,
+}
+
+impl<'parent> TypeEnv<'parent> {
+    pub(crate) fn create_child(&'parent self) -> Self {
+        Self {
+            named: HashMap::new(),
+            enums: HashMap::new(),
+            type_params: HashMap::new(),
+            parent: Some(self),
+        }
+    }
+
+    pub(crate) fn store_binding(&mut self, name: Symbol, ty: Type) {
+        self.named.insert(name, TypeInfo::Binding(ty));
+    }
+
+    pub(crate) fn store_func(&mut self, name: Symbol, params: Vec<Type>, ret: Type) {
+        self.named.insert(name, TypeInfo::Func { params, ret });
+    }
+
+    pub(crate) fn store_constructor(&mut self, name: Symbol, fields: Vec<Type>, ret: Type) {
+        self.named
+            .insert(name, TypeInfo::Constructor { fields, ret });
+    }
+
+    pub(crate) fn store_enum(&mut self, name: Symbol, num_params: usize, variants: Vec<Symbol>) {
+        self.enums.insert(
+            name,
+            EnumInfo {
+                num_params,
+                variants,
+            },
+        );
+    }
+
+    pub(crate) fn store_type_param(&mut self, name: Symbol, ty: Type) {
+        self.type_params.insert(name, ty);
+    }
+
+    pub(crate) fn get(&self, name: Symbol) -> Option<&TypeInfo> {
+        self.named
+            .get(&name)
+            .or_else(|| self.parent.and_then(|parent| parent.get(name)))
+    }
+
+    pub(crate) fn get_enum(&self, name: Symbol) -> Option<&EnumInfo> {
+        self.enums
+            .get(&name)
+            .or_else(|| self.parent.and_then(|parent| parent.get_enum(name)))
+    }
+
+    pub(crate) fn get_type_param(&self, name: Symbol) -> Option<&Type> {
+        self.type_params
+            .get(&name)
+            .or_else(|| self.parent.and_then(|parent| parent.get_type_param(name)))
+    }
+
+    /// Returns the names of everything in scope, including anything that is shadowed.
+    pub(crate) fn names(&self) -> Vec<Symbol> {
+        let mut names = self.parent.map_or_else(Vec::new, |parent| parent.names());
+        names.extend(self.named.keys().copied());
+
+        names
+    }
+
+    /// Returns the types of everything in scope, including anything that is shadowed.
+    pub(crate) fn types(&self) -> Vec<&Type> {
+        let mut types = self.parent.map_or_else(Vec::new, |parent| parent.types());
+
+        for info in self.named.values() {
+            match info {
+                TypeInfo::Binding(ty) => types.push(ty),
+                TypeInfo::Func { params, ret } => {
+                    types.extend(params);
+                    types.push(ret);
+                }
+                TypeInfo::Constructor { fields, ret } => {
+                    types.extend(fields);
+                    types.push(ret);
+                }
+            }
+        }
+
+        types.extend(self.type_params.values());
+
+        types
+    }
+
+    /// Returns the type of whatever `name` refers to, with functions and constructors given a
+    /// function type.
+    pub fn type_of(&self, name: &str) -> Option<Type> {
+        self.get(Symbol::intern(name)).map(TypeInfo::to_type)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum TypeInfo {
+    Binding(Type),
+    Func { params: Vec<Type>, ret: Type },
+    Constructor { fields: Vec<Type>, ret: Type },
+}
+
+impl TypeInfo {
+    pub(crate) fn to_type(&self) -> Type {
+        match self {
+            Self::Binding(ty) => ty.clone(),
+            Self::Func { params, ret }
+            | Self::Constructor {
+                fields: params,
+                ret,
+            } => Type::Func {
+                params: params.clone(),
+                ret: Box::new(ret.clone()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct EnumInfo {
+    pub(crate) num_params: usize,
+    pub(crate) variants: Vec<Symbol>,
+}
@@ -0,0 +1,213 @@
+use crate::checker::Checker;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::type_env::TypeEnv;
+use crate::types::Type;
+use crate::utils;
+
+/// A type written out in the source code, such as `Int` or `Map<Str, List<Int>>`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct TypeExpr {
+    pub(crate) name: Symbol,
+    pub(crate) args: Vec<Self>,
+    pub(crate) span: Span,
+}
+
+impl TypeExpr {
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let before = s;
+
+        let (s, name) = utils::extract_ident(s)?;
+
+        let (s, args) = match utils::tag("<", s) {
+            Ok(s) => {
+                let (s, _) = utils::extract_whitespace(s);
+                let (s, args) = utils::sequence_non_empty(Self::new, utils::extract_comma, s)?;
+                let (s, _) = utils::extract_whitespace(s);
+                let s = utils::tag(">", s)?;
+
+                (s, args)
+            }
+            Err(_) => (s, Vec::new()),
+        };
+
+        Ok((
+            s,
+            Self {
+                name: Symbol::intern(name),
+                args,
+                span: Span::new(before, s),
+            },
+        ))
+    }
+
+    // Parses the annotation following a binding or parameter, as in `x: Int`
+    pub(crate) fn new_annotation(s: &str) -> Result<(&str, Self), String> {
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag(":", s)?;
+        let (s, _) = utils::extract_whitespace(s);
+
+        Self::new(s)
+    }
+
+    // Parses the type parameters following the name of a function or enum, as in `<K, V>`, which
+    // may be left off entirely
+    pub(crate) fn new_params(s: &str) -> Result<(&str, Vec<Symbol>), String> {
+        let Ok(s) = utils::tag("<", s) else {
+            return Ok((s, Vec::new()));
+        };
+        let (s, _) = utils::extract_whitespace(s);
+
+        let (s, params) = utils::sequence_non_empty(
+            |s| utils::extract_ident(s).map(|(s, ident)| (s, Symbol::intern(ident))),
+            utils::extract_comma,
+            s,
+        )?;
+
+        let (s, _) = utils::extract_whitespace(s);
+        let s = utils::tag(">", s)?;
+
+        Ok((s, params))
+    }
+
+    /// Reports every type parameter in a list parsed with `new_params` that has the same name as
+    /// one before it, since uses of it could only ever refer to one of them.
+    pub(crate) fn check_params(params: &[Symbol], span: Span, checker: &mut Checker) {
+        for (idx, name) in params.iter().enumerate() {
+            if params[..idx].contains(name) {
+                checker.error(
+                    format!("type parameter ‘{}’ is declared more than once", name),
+                    span,
+                );
+            }
+        }
+    }
+
+    /// Works out which type is written out, reporting an error and falling back to `Unknown` if
+    /// it does not name one.
+    pub(crate) fn to_type(&self, env: &TypeEnv, checker: &mut Checker) -> Type {
+        if let Some(ty) = env.get_type_param(self.name) {
+            if !self.args.is_empty() {
+                checker.error(
+                    format!("type parameter ‘{}’ cannot take type arguments", self.name),
+                    self.span,
+                );
+            }
+
+            return ty.clone();
+        }
+
+        let num_params = match self.name.as_str() {
+            "Int" | "Bool" | "Str" | "Unit" | "Range" => 0,
+            "List" => 1,
+            "Map" => 2,
+            _ => match env.get_enum(self.name) {
+                Some(enum_info) => enum_info.num_params,
+                None => {
+                    checker.error(format!("unknown type ‘{}’", self.name), self.span);
+                    return Type::Unknown;
+                }
+            },
+        };
+
+        if self.args.len() != num_params {
+            checker.error(
+                format!(
+                    "expected {} type arguments for ‘{}’, got {}",
+                    num_params,
+                    self.name,
+                    self.args.len(),
+                ),
+                self.span,
+            );
+            return Type::Unknown;
+        }
+
+        let mut args = self.args.iter().map(|arg| arg.to_type(env, checker));
+
+        match self.name.as_str() {
+            "Int" => Type::Int,
+            "Bool" => Type::Bool,
+            "Str" => Type::Str,
+            "Unit" => Type::Unit,
+            "Range" => Type::Range,
+            "List" => Type::List(Box::new(args.next().unwrap())),
+            "Map" => Type::Map(
+                Box::new(args.next().unwrap()),
+                Box::new(args.next().unwrap()),
+            ),
+            _ => Type::Enum(self.name, args.collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str, args: Vec<TypeExpr>, span: Span) -> TypeExpr {
+        TypeExpr {
+            name: Symbol::intern(name),
+            args,
+            span,
+        }
+    }
+
+    #[test]
+    fn parse_nested_type() {
+        assert_eq!(
+            TypeExpr::new("Map<Str, List<Int>> =>"),
+            Ok((
+                " =>",
+                named(
+                    "Map",
+                    vec![
+                        named(
+                            "Str",
+                            Vec::new(),
+                            Span::new("Str, List<Int>> =>", ", List<Int>> =>"),
+                        ),
+                        named(
+                            "List",
+                            vec![named("Int", Vec::new(), Span::new("Int>> =>", ">> =>"),)],
+                            Span::new("List<Int>> =>", "> =>"),
+                        ),
+                    ],
+                    Span::new("Map<Str, List<Int>> =>", " =>"),
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_annotation() {
+        assert_eq!(
+            TypeExpr::new_annotation(": Bool = true"),
+            Ok((
+                " = true",
+                named("Bool", Vec::new(), Span::new("Bool = true", " = true")),
+            )),
+        );
+    }
+
+    #[test]
+    fn type_of_unknown_name() {
+        let mut checker = Checker::default();
+        let ty =
+            named("Shape", Vec::new(), Span::default()).to_type(&TypeEnv::default(), &mut checker);
+
+        assert_eq!(ty, Type::Unknown);
+        assert_eq!(checker.diagnostics[0].message, "unknown type ‘Shape’");
+    }
+
+    #[test]
+    fn type_with_wrong_number_of_args() {
+        let mut checker = Checker::default();
+        named("List", Vec::new(), Span::default()).to_type(&TypeEnv::default(), &mut checker);
+
+        assert_eq!(
+            checker.diagnostics[0].message,
+            "expected 1 type arguments for ‘List’, got 0",
+        );
+    }
+}
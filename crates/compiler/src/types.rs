@@ -0,0 +1,153 @@
+use std::fmt;
+
+use crate::symbol::Symbol;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Int,
+    Bool,
+    Str,
+    Unit,
+    Range,
+    List(Box<Self>),
+    Map(Box<Self>, Box<Self>),
+    Enum(Symbol, Vec<Self>),
+    Func { params: Vec<Self>, ret: Box<Self> },
+    // A type that is yet to be inferred
+    Var(u32),
+    // A type parameter of a polymorphic function or binding, which stands for a different type
+    // variable wherever it is used
+    Generic(u32),
+    // The type of expressions that never produce a value, such as `break`
+    Never,
+    // Stands in for types that cannot be worked out statically, and is compatible with every type
+    Unknown,
+}
+
+impl Type {
+    /// Creates a copy of the type with `f` applied to each of the types it is immediately made
+    /// up of.
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&Self) -> Self) -> Self {
+        match self {
+            Self::List(item) => Self::List(Box::new(f(item))),
+            Self::Map(key, val) => Self::Map(Box::new(f(key)), Box::new(f(val))),
+            Self::Enum(name, args) => Self::Enum(*name, args.iter().map(f).collect()),
+            Self::Func { params, ret } => Self::Func {
+                params: params.iter().map(&mut f).collect(),
+                ret: Box::new(f(ret)),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Appends the type variables occurring in the type to `vars` in the order they first appear,
+    /// skipping any that are already present.
+    pub(crate) fn vars(&self, vars: &mut Vec<u32>) {
+        match self {
+            Self::Var(var) if !vars.contains(var) => vars.push(*var),
+            Self::List(item) => item.vars(vars),
+            Self::Map(key, val) => {
+                key.vars(vars);
+                val.vars(vars);
+            }
+            Self::Enum(_, args) => {
+                for arg in args {
+                    arg.vars(vars);
+                }
+            }
+            Self::Func { params, ret } => {
+                for param in params {
+                    param.vars(vars);
+                }
+                ret.vars(vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "Int"),
+            Self::Bool => write!(f, "Bool"),
+            Self::Str => write!(f, "Str"),
+            Self::Unit => write!(f, "Unit"),
+            Self::Range => write!(f, "Range"),
+            Self::List(item) => write!(f, "List<{}>", item),
+            Self::Map(key, val) => write!(f, "Map<{}, {}>", key, val),
+            Self::Enum(name, args) => {
+                write!(f, "{}", name)?;
+
+                if let Some((first, rest)) = args.split_first() {
+                    write!(f, "<{}", first)?;
+                    for arg in rest {
+                        write!(f, ", {}", arg)?;
+                    }
+                    write!(f, ">")?;
+                }
+
+                Ok(())
+            }
+            Self::Func { params, ret } => {
+                for param in params {
+                    write!(f, "{} -> ", param)?;
+                }
+
+                write!(f, "{}", ret)
+            }
+            Self::Var(var) => write!(f, "?{}", var),
+            Self::Generic(idx) => match u8::try_from(*idx) {
+                Ok(idx @ 0..26) => write!(f, "{}", (b'a' + idx) as char),
+                _ => write!(f, "t{}", idx),
+            },
+            Self::Never => write!(f, "!"),
+            Self::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vars_in_order_of_appearance() {
+        let mut vars = Vec::new();
+
+        Type::Func {
+            params: vec![Type::Var(3), Type::List(Box::new(Type::Var(1)))],
+            ret: Box::new(Type::Var(3)),
+        }
+        .vars(&mut vars);
+
+        assert_eq!(vars, vec![3, 1]);
+    }
+
+    #[test]
+    fn display_func_type() {
+        assert_eq!(
+            Type::Func {
+                params: vec![Type::Int, Type::List(Box::new(Type::Int))],
+                ret: Box::new(Type::Bool),
+            }
+            .to_string(),
+            "Int -> List<Int> -> Bool",
+        );
+    }
+
+    #[test]
+    fn display_generic_types() {
+        assert_eq!(
+            Type::Func {
+                params: vec![Type::Generic(0), Type::Generic(1)],
+                ret: Box::new(Type::Map(
+                    Box::new(Type::Generic(0)),
+                    Box::new(Type::Generic(30)),
+                )),
+            }
+            .to_string(),
+            "a -> b -> Map<a, t30>",
+        );
+    }
+}
@@ -15,10 +15,49 @@ pub(crate) fn extract_ident(s: &str) -> Result<(&str, &str), String> {
     }
 }
 
+pub(crate) fn extract_bool(s: &str) -> Result<(&str, bool), String> {
+    match extract_ident(s) {
+        Ok((s, "true")) => Ok((s, true)),
+        Ok((s, "false")) => Ok((s, false)),
+        _ => Err("Expected boolean".to_string()),
+    }
+}
+
+pub(crate) fn extract_string(s: &str) -> Result<(&str, String), String> {
+    let s = tag("\"", s)?;
+
+    let mut string = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&s[idx + 1..], string)),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, c @ ('"' | '\\'))) => string.push(c),
+                _ => return Err("Invalid escape sequence in string".to_string()),
+            },
+            c => string.push(c),
+        }
+    }
+
+    Err("Expected closing \"".to_string())
+}
+
 pub(crate) fn extract_digits(s: &str) -> Result<(&str, &str), String> {
     take_while_careful(|c| c.is_ascii_digit(), s, "Expected digits".to_string())
 }
 
+pub(crate) fn extract_number(s: &str) -> Result<(&str, i32), String> {
+    let (s, digits) = extract_digits(s)?;
+    let number = digits
+        .parse()
+        .map_err(|_| format!("Number {} is too large", digits))?;
+
+    Ok((s, number))
+}
+
 pub(crate) fn extract_whitespace(s: &str) -> (&str, &str) {
     take_while(|c| WHITESPACE.contains(&c), s)
 }
@@ -31,6 +70,14 @@ pub(crate) fn extract_whitespace_non_empty(s: &str) -> Result<(&str, &str), Stri
     )
 }
 
+pub(crate) fn extract_comma(s: &str) -> Result<(&str, &str), String> {
+    let (after_whitespace, _) = extract_whitespace(s);
+    let after_comma = tag(",", after_whitespace)?;
+    let (remainder, _) = extract_whitespace(after_comma);
+
+    Ok((remainder, &s[..s.len() - remainder.len()]))
+}
+
 pub(crate) fn take_while(accept: impl Fn(char) -> bool, s: &str) -> (&str, &str) {
     let extracted_end = s
         .char_indices()
@@ -42,7 +89,7 @@ pub(crate) fn take_while(accept: impl Fn(char) -> bool, s: &str) -> (&str, &str)
     (remainder, extracted)
 }
 
-fn take_while_careful(
+pub(crate) fn take_while_careful(
     accept: impl Fn(char) -> bool,
     s: &str,
     error_message: String,
@@ -64,9 +111,17 @@ pub(crate) fn tag<'a>(starting_text: &str, s: &'a str) -> Result<&'a str, String
     }
 }
 
+// Unlike `tag`, this only matches whole words, so `keyword("break", "breakfast")` fails
+pub(crate) fn keyword<'a>(word: &str, s: &'a str) -> Result<&'a str, String> {
+    match extract_ident(s) {
+        Ok((s, ident)) if ident == word => Ok(s),
+        _ => Err(format!("Expected \"{word}\"")),
+    }
+}
+
 pub(crate) fn sequence<T>(
     parser: impl Fn(&str) -> Result<(&str, T), String>,
-    separator_parser: impl Fn(&str) -> (&str, &str),
+    separator_parser: impl Fn(&str) -> Result<(&str, &str), String>,
     mut s: &str,
 ) -> Result<(&str, Vec<T>), String> {
     let mut items = Vec::new();
@@ -75,8 +130,11 @@ pub(crate) fn sequence<T>(
         s = new_s;
         items.push(item);
 
-        let (new_s, _) = separator_parser(s);
-        s = new_s;
+        // Without a separator after it, an item is the last one
+        match separator_parser(s) {
+            Ok((new_s, _)) => s = new_s,
+            Err(_) => break,
+        }
     }
 
     Ok((s, items))
@@ -84,7 +142,7 @@ pub(crate) fn sequence<T>(
 
 pub(crate) fn sequence_non_empty<T>(
     parser: impl Fn(&str) -> Result<(&str, T), String>,
-    separator_parser: impl Fn(&str) -> (&str, &str),
+    separator_parser: impl Fn(&str) -> Result<(&str, &str), String>,
     s: &str,
 ) -> Result<(&str, Vec<T>), String> {
     let (s, sequence) = sequence(parser, separator_parser, s)?;
@@ -109,6 +167,15 @@ mod tests {
         assert_eq!(extract_digits("10-20"), Ok(("-20", "10")));
     }
 
+    #[test]
+    fn extract_number_that_is_too_large() {
+        assert_eq!(extract_number("2147483647"), Ok(("", 2147483647)));
+        assert_eq!(
+            extract_number("2147483648"),
+            Err("Number 2147483648 is too large".to_string()),
+        );
+    }
+
     #[test]
     fn extract_digits_with_no_remainder() {
         assert_eq!(extract_digits("100"), Ok(("", "100")));
@@ -119,6 +186,37 @@ mod tests {
         assert_eq!(extract_digits("abcd"), Err("Expected digits".to_string()));
     }
 
+    #[test]
+    fn extract_bool_literal() {
+        assert_eq!(extract_bool("true }"), Ok((" }", true)));
+    }
+
+    #[test]
+    fn do_not_extract_bool_from_longer_ident() {
+        assert_eq!(extract_bool("falsey"), Err("Expected boolean".to_string()));
+    }
+
+    #[test]
+    fn extract_string_literal() {
+        assert_eq!(extract_string("\"abc\": 1"), Ok((": 1", "abc".to_string())));
+    }
+
+    #[test]
+    fn extract_string_literal_with_escapes() {
+        assert_eq!(
+            extract_string(r#""say \"hi\"\n""#),
+            Ok(("", "say \"hi\"\n".to_string())),
+        );
+    }
+
+    #[test]
+    fn do_not_extract_unterminated_string_literal() {
+        assert_eq!(
+            extract_string("\"abc"),
+            Err("Expected closing \"".to_string()),
+        );
+    }
+
     #[test]
     fn extract_spaces() {
         assert_eq!(extract_whitespace("    1"), ("1", "    "));
@@ -155,6 +253,24 @@ mod tests {
         assert_eq!(tag("let", "let a"), Ok(" a"));
     }
 
+    #[test]
+    fn keyword_followed_by_non_ident_char() {
+        assert_eq!(keyword("break", "break}"), Ok("}"));
+    }
+
+    #[test]
+    fn keyword_does_not_match_prefix_of_ident() {
+        assert_eq!(
+            keyword("break", "breakfast"),
+            Err("Expected \"break\"".to_string()),
+        );
+    }
+
+    #[test]
+    fn extract_comma_with_surrounding_whitespace() {
+        assert_eq!(extract_comma(" , b)"), Ok(("b)", " , ")));
+    }
+
     #[test]
     fn extract_newlines_or_spaces() {
         assert_eq!(extract_whitespace(" \n   \n\nabc"), ("abc", " \n   \n\n"));
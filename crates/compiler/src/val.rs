@@ -1,16 +1,103 @@
+use std::collections::BTreeMap;
 use std::fmt;
 
+use crate::symbol::Symbol;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Val {
     Number(i32),
+    Bool(bool),
+    Str(String),
+    List(Vec<Self>),
+    // Maps are ordered by key so that iterating over and displaying them is deterministic
+    Map(BTreeMap<MapKey, Self>),
+    Range {
+        start: i32,
+        end: i32,
+        inclusive: bool,
+    },
     Unit,
+    Variant {
+        enum_name: Symbol,
+        variant: Symbol,
+        fields: Vec<Self>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum MapKey {
+    Number(i32),
+    Str(String),
+}
+
+impl TryFrom<Val> for MapKey {
+    type Error = String;
+
+    fn try_from(val: Val) -> Result<Self, Self::Error> {
+        match val {
+            Val::Number(n) => Ok(Self::Number(n)),
+            Val::Str(s) => Ok(Self::Str(s)),
+            _ => Err(format!(
+                "map keys must be numbers or strings, but ‘{}’ is neither",
+                val,
+            )),
+        }
+    }
+}
+
+impl From<MapKey> for Val {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Number(n) => Self::Number(n),
+            MapKey::Str(s) => Self::Str(s),
+        }
+    }
 }
 
 impl fmt::Display for Val {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Number(n) => write!(f, "{}", n),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Str(s) => write!(f, "{:?}", s),
+            Self::List(items) => {
+                let items: Vec<_> = items.iter().map(ToString::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Self::Map(entries) => {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, val)| format!("{}: {}", key, val))
+                    .collect();
+                write!(f, "#{{{}}}", entries.join(", "))
+            }
+            Self::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{}..{}{}", start, if *inclusive { "=" } else { "" }, end),
             Self::Unit => write!(f, "Unit"),
+            Self::Variant {
+                variant, fields, ..
+            } => {
+                write!(f, "{}", variant)?;
+
+                if !fields.is_empty() {
+                    let fields: Vec<_> = fields.iter().map(ToString::to_string).collect();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Str(s) => write!(f, "{:?}", s),
         }
     }
 }
@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+
+use crate::builtins;
+use crate::bytecode::{Function, Instr, Module};
+use crate::expr;
+use crate::for_loop;
+use crate::val::{MapKey, Val};
+
+struct CallFrame<'m> {
+    func: &'m Function,
+    ip: usize,
+    // Where the frame's slots start in `Vm::slots`
+    slots: usize,
+    // The frame of the function this one was defined in, as an index into `Vm::frames`
+    parent: Option<usize>,
+    // The heights of the stack and of `Vm::loops` when the call was made
+    stack: usize,
+    loops: usize,
+}
+
+struct Loop {
+    items: Box<dyn Iterator<Item = Val>>,
+    stack: usize,
+}
+
+struct Vm<'m> {
+    module: &'m Module,
+    stack: Vec<Val>,
+    slots: Vec<Val>,
+    frames: Vec<CallFrame<'m>>,
+    loops: Vec<Loop>,
+}
+
+impl Module {
+    pub fn run(&self) -> Result<Val, String> {
+        let mut vm = Vm {
+            module: self,
+            stack: Vec::new(),
+            slots: Vec::new(),
+            frames: Vec::new(),
+            loops: Vec::new(),
+        };
+        vm.push_frame(&self.main, None, Vec::new());
+
+        vm.run()
+    }
+}
+
+impl<'m> Vm<'m> {
+    fn push_frame(&mut self, func: &'m Function, parent: Option<usize>, args: Vec<Val>) {
+        let slots = self.slots.len();
+        self.slots.extend(args);
+        self.slots.resize(slots + func.num_slots, Val::Unit);
+
+        self.frames.push(CallFrame {
+            func,
+            ip: 0,
+            slots,
+            parent,
+            stack: self.stack.len(),
+            loops: self.loops.len(),
+        });
+    }
+
+    // The index of the frame `depth` functions out from the current one
+    fn ancestor(&self, depth: u32) -> Result<usize, String> {
+        // Modules are checked when they are loaded, so the frame is only ever missing if the
+        // module was put together some other way
+        (0..depth).try_fold(self.frames.len() - 1, |frame, _| {
+            self.frames[frame]
+                .parent
+                .ok_or_else(|| "enclosing function has no frame".to_string())
+        })
+    }
+
+    fn pop(&mut self) -> Val {
+        self.stack.pop().unwrap()
+    }
+
+    fn pop_n(&mut self, n: u32) -> Vec<Val> {
+        self.stack.split_off(self.stack.len() - n as usize)
+    }
+
+    fn run(&mut self) -> Result<Val, String> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instr = frame.func.code[frame.ip];
+            frame.ip += 1;
+            let slots = frame.slots;
+
+            match instr {
+                Instr::Const(constant) => {
+                    self.stack
+                        .push(self.module.constants[constant as usize].clone());
+                }
+                Instr::Load(slot) => self.stack.push(self.slots[slots + slot as usize].clone()),
+                Instr::LoadOuter { depth, slot } => {
+                    let frame = &self.frames[self.ancestor(depth)?];
+                    self.stack
+                        .push(self.slots[frame.slots + slot as usize].clone());
+                }
+                Instr::Store(slot) => self.slots[slots + slot as usize] = self.pop(),
+                Instr::Pop => {
+                    self.pop();
+                }
+                Instr::Binary(op) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(op.apply(lhs, rhs)?);
+                }
+                Instr::Index => {
+                    let index = self.pop();
+                    let target = self.pop();
+                    self.stack.push(expr::index_into(target, index)?);
+                }
+                Instr::Range { inclusive } => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    self.stack.push(expr::range(start, end, inclusive)?);
+                }
+                Instr::List(len) => {
+                    let items = self.pop_n(len);
+                    self.stack.push(Val::List(items));
+                }
+                Instr::Map(len) => {
+                    let mut entries = self.pop_n(len * 2).into_iter();
+                    let mut map = BTreeMap::new();
+
+                    while let (Some(key), Some(val)) = (entries.next(), entries.next()) {
+                        map.insert(MapKey::try_from(key)?, val);
+                    }
+
+                    self.stack.push(Val::Map(map));
+                }
+                Instr::Construct { variant, num_args } => {
+                    let mut val = self.module.constants[variant as usize].clone();
+                    if let Val::Variant { fields, .. } = &mut val {
+                        *fields = self.pop_n(num_args);
+                    }
+                    self.stack.push(val);
+                }
+                Instr::CallBuiltin { builtin, num_args } => {
+                    let builtin = builtins::get(builtins::NAMES[builtin as usize]).unwrap();
+                    let args = self.pop_n(num_args);
+                    self.stack.push((builtin.func)(args)?);
+                }
+                Instr::Call {
+                    func,
+                    depth,
+                    num_args,
+                } => {
+                    let parent = self.ancestor(depth)?;
+                    let args = self.pop_n(num_args);
+                    self.push_frame(&self.module.funcs[func as usize], Some(parent), args);
+                }
+                Instr::Return => {
+                    let val = self.pop();
+                    let frame = self.frames.pop().unwrap();
+
+                    if self.frames.is_empty() {
+                        return Ok(val);
+                    }
+
+                    self.stack.truncate(frame.stack);
+                    self.slots.truncate(frame.slots);
+                    self.loops.truncate(frame.loops);
+                    self.stack.push(val);
+                }
+                Instr::Jump(target) => self.jump(target),
+                Instr::Guard(target) => match self.pop() {
+                    Val::Bool(true) => {}
+                    Val::Bool(false) => self.jump(target),
+                    _ => return Err("match guard must evaluate to a boolean".to_string()),
+                },
+                Instr::Match {
+                    pattern,
+                    slot,
+                    otherwise,
+                } => {
+                    let func = self.frames.last().unwrap().func;
+                    let val = self.slots[slots + slot as usize].clone();
+
+                    if !func.patterns[pattern as usize].bind(&val, &mut self.slots[slots..]) {
+                        self.jump(otherwise);
+                    }
+                }
+                Instr::NoMatch(slot) => {
+                    return Err(format!(
+                        "no arm of match expression matched ‘{}’",
+                        self.slots[slots + slot as usize],
+                    ));
+                }
+                Instr::IterStart => {
+                    let items = for_loop::items(self.pop())?;
+                    self.loops.push(Loop {
+                        items,
+                        stack: self.stack.len(),
+                    });
+                }
+                Instr::IterNext { slot, exit } => match self.loops.last_mut().unwrap().items.next()
+                {
+                    Some(item) => self.slots[slots + slot as usize] = item,
+                    None => {
+                        self.loops.pop();
+                        self.jump(exit);
+                    }
+                },
+                Instr::LoopExit(target) => {
+                    let innermost = self.loops.pop().unwrap();
+                    self.stack.truncate(innermost.stack);
+                    self.jump(target);
+                }
+                Instr::LoopContinue(target) => {
+                    let innermost = self.loops.last().unwrap();
+                    self.stack.truncate(innermost.stack);
+                    self.jump(target);
+                }
+                Instr::Fail(msg) => match &self.module.constants[msg as usize] {
+                    Val::Str(msg) => return Err(msg.clone()),
+                    constant => unreachable!("failed with {:?}", constant),
+                },
+            }
+        }
+    }
+
+    fn jump(&mut self, target: u32) {
+        self.frames.last_mut().unwrap().ip = target as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Env, Lints, TypeEnv, Val};
+
+    // Runs the program on the virtual machine, checking that it agrees with the interpreter
+    fn run(s: &str) -> Result<Val, String> {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+
+        let result = parse.compile(s)?.run();
+        assert_eq!(result, parse.eval(&mut Env::default()));
+
+        result
+    }
+
+    #[test]
+    fn run_arithmetic() {
+        assert_eq!(
+            run("{
+    let a = 2 * 3
+    a - 1
+}"),
+            Ok(Val::Number(5)),
+        );
+    }
+
+    #[test]
+    fn run_func_using_outer_binding() {
+        assert_eq!(
+            run("{
+    let base = 10
+    fn add n => {
+        fn inner m => base + m
+        inner n
+    }
+    add 5
+}"),
+            Ok(Val::Number(15)),
+        );
+    }
+
+    #[test]
+    fn run_funcs_in_scope_they_were_defined_in() {
+        assert_eq!(
+            run("{
+    let x = 1
+    fn f => x
+    fn g x => f
+    g 2
+}"),
+            Ok(Val::Number(1)),
+        );
+        assert_eq!(
+            run("{
+    fn f a b => b
+    let a = 5
+    f 1 a
+}"),
+            Ok(Val::Number(5)),
+        );
+    }
+
+    #[test]
+    fn run_collections() {
+        assert_eq!(
+            run("{
+    let m = insert #{ 1: [1, 2] } 2 [3]
+    let k = keys m
+    let xs = m[k[1]]
+    xs[0]
+}"),
+            Ok(Val::Number(3)),
+        );
+    }
+
+    #[test]
+    fn discard_values_on_stack_when_breaking() {
+        assert_eq!(
+            run(
+                "[1, { for x in 0..3 { 1 + { break } } }, { for x in 0..3 { 1 + { continue } } }, 2]"
+            ),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Unit,
+                Val::Unit,
+                Val::Number(2),
+            ])),
+        );
+    }
+
+    #[test]
+    fn return_from_inside_loop() {
+        assert_eq!(
+            run("{
+    fn first xs => {
+        for x in xs {
+            let y = 1 + { return x }
+        }
+        0
+    }
+    first [7, 8]
+}"),
+            Ok(Val::Number(7)),
+        );
+    }
+
+    #[test]
+    fn match_with_guards() {
+        assert_eq!(
+            run("{
+    enum Shape { Square(side), Dot }
+    let s = Square 4
+    match s {
+        Square(side) if side > 5 => 1,
+        Square(side) => side,
+        Dot => 0,
+    }
+}"),
+            Ok(Val::Number(4)),
+        );
+    }
+
+    #[test]
+    fn fail_like_interpreter() {
+        assert_eq!(
+            run("{ break }"),
+            Err("cannot break outside of a loop".to_string()),
+        );
+        assert_eq!(
+            run("{ return 1 }"),
+            Err("cannot return outside of a function".to_string()),
+        );
+        assert_eq!(
+            run("{
+    fn f => { continue }
+    for x in [1] { f }
+}"),
+            Err("cannot continue outside of a loop".to_string()),
+        );
+        assert_eq!(
+            run("match 3 { 1 => 1 }"),
+            Err("no arm of match expression matched ‘3’".to_string()),
+        );
+        assert_eq!(
+            run("match 3 { n if 1 => 1 }"),
+            Err("match guard must evaluate to a boolean".to_string()),
+        );
+        assert_eq!(run("1 / 0"), Err("attempt to divide by zero".to_string()));
+        assert_eq!(
+            run("{
+    let low = 0 - 2147483647
+    let min = low - 1
+    let m = 0 - 1
+    min / m
+}"),
+            Err("attempt to divide with overflow".to_string()),
+        );
+    }
+}
@@ -0,0 +1,496 @@
+#[cfg(test)]
+mod decode;
+mod encode;
+
+use crate::codegen::{Funcs, Kind};
+use crate::expr::Op;
+use crate::lower::{Local, Node, Program};
+use crate::val::Val;
+
+impl Program {
+    /// Generates a WebAssembly module in the text format. The module exports the program as a
+    /// function named `main`, which returns the `i64` the program evaluates to, or nothing if it
+    /// evaluates to `()`. Where the interpreter would fail to divide, the module traps instead.
+    ///
+    /// Only programs made of numbers, bindings, arithmetic, blocks and functions are supported.
+    pub fn to_wat(&self) -> Result<String, String> {
+        Ok(Generator::new(self).module()?.to_text())
+    }
+
+    /// Like `to_wat`, but in the binary format.
+    pub fn to_wasm(&self) -> Result<Vec<u8>, String> {
+        Ok(Generator::new(self).module()?.to_binary())
+    }
+}
+
+// The subset of WebAssembly the generated code needs, which can be written out in either format
+#[derive(Debug, Clone, PartialEq)]
+struct Module {
+    // The program itself comes first, and is the only function that is exported
+    funcs: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Function {
+    name: String,
+    // Every parameter and local is an `i64`
+    num_params: u32,
+    num_locals: u32,
+    returns: bool,
+    body: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instr {
+    I64Const(i64),
+    LocalGet(u32),
+    LocalSet(u32),
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I32DivS,
+    I32WrapI64,
+    I64ExtendI32S,
+    Call(u32),
+    Drop,
+}
+
+struct Generator<'p> {
+    program: &'p Program,
+    // WebAssembly functions cannot see the locals of the function they were defined in, so the
+    // bindings of enclosing functions they use are passed to them as extra parameters instead
+    captures: Vec<Vec<Local>>,
+    // Functions are given the next index as soon as something calls them, so that recursive calls
+    // know what to call before the function has been generated
+    indices: Vec<Option<u32>>,
+    next_index: u32,
+    funcs: Funcs<Function>,
+}
+
+impl<'p> Generator<'p> {
+    fn new(program: &'p Program) -> Self {
+        let num_funcs = program.funcs.len();
+
+        Self {
+            program,
+            captures: program.captures(),
+            indices: vec![None; num_funcs],
+            // The program itself comes first
+            next_index: 1,
+            funcs: Funcs::new(num_funcs),
+        }
+    }
+
+    fn module(mut self) -> Result<Module, String> {
+        let program = self.program;
+
+        let mut body = Body {
+            generator: &mut self,
+            captures: &[],
+            num_params: 0,
+            code: Vec::new(),
+        };
+        let kind = body.node(&program.main.body)?;
+        let main = Function {
+            name: "main".to_string(),
+            num_params: 0,
+            num_locals: program.main.num_slots as u32,
+            returns: kind == Kind::Number,
+            body: body.code,
+        };
+
+        // Functions are listed in the order they were first called in, which is that of their
+        // indices
+        let mut funcs: Vec<_> = self
+            .funcs
+            .into_generated()
+            .map(|(id, func)| (self.indices[id], func))
+            .collect();
+        funcs.sort_by_key(|(index, _)| *index);
+
+        Ok(Module {
+            funcs: std::iter::once(main)
+                .chain(funcs.into_iter().map(|(_, func)| func))
+                .collect(),
+        })
+    }
+
+    fn call(&mut self, id: usize) -> Result<(u32, Kind), String> {
+        if let Some(kind) = self.funcs.start(id) {
+            return Ok((self.indices[id].unwrap(), kind));
+        }
+
+        let index = self.next_index;
+        self.indices[id] = Some(index);
+        self.next_index += 1;
+
+        let program = self.program;
+        let func = &program.funcs[id];
+        let captures = self.captures[id].clone();
+
+        let mut body = Body {
+            generator: self,
+            captures: &captures,
+            num_params: func.num_params,
+            code: Vec::new(),
+        };
+        let kind = body.node(&func.body)?;
+        let code = body.code;
+
+        let num_params = func.num_params + captures.len();
+        let name = match func.name {
+            Some(name) => format!("fn{}_{}", id, name),
+            None => format!("fn{}", id),
+        };
+        let generated = Function {
+            name,
+            num_params: num_params as u32,
+            num_locals: (func.num_slots - func.num_params) as u32,
+            returns: kind == Kind::Number,
+            body: code,
+        };
+        self.funcs
+            .finish(id, generated, kind)
+            .map_err(unsupported)?;
+
+        Ok((index, kind))
+    }
+}
+
+// The body of a single function, which every node in it is generated into
+struct Body<'g, 'p> {
+    generator: &'g mut Generator<'p>,
+    captures: &'g [Local],
+    num_params: usize,
+    code: Vec<Instr>,
+}
+
+impl Body<'_, '_> {
+    // Parameters come first, followed by the captured bindings and then every other slot
+    fn local(&self, local: Local) -> u32 {
+        let index = match local {
+            Local { depth: 0, slot } if slot < self.num_params => slot,
+            Local { depth: 0, slot } => slot + self.captures.len(),
+            _ => {
+                let capture = self.captures.iter().position(|c| *c == local).unwrap();
+                self.num_params + capture
+            }
+        };
+
+        index as u32
+    }
+
+    fn node(&mut self, node: &Node) -> Result<Kind, String> {
+        match node {
+            Node::Const(Val::Number(n)) => {
+                self.code.push(Instr::I64Const(i64::from(*n)));
+                Ok(Kind::Number)
+            }
+            Node::Const(Val::Unit) => Ok(Kind::Unit),
+            Node::Local(local) => {
+                let index = self.local(*local);
+                self.code.push(Instr::LocalGet(index));
+                Ok(Kind::Number)
+            }
+            Node::Define { slot, val } => {
+                if self.node(val)? == Kind::Unit {
+                    return Err(unsupported("bindings to ‘()’"));
+                }
+                let index = self.local(Local {
+                    depth: 0,
+                    slot: *slot,
+                });
+                self.code.push(Instr::LocalSet(index));
+                Ok(Kind::Unit)
+            }
+            Node::Operation {
+                lhs,
+                rhs,
+                op: Op::Div,
+            } => {
+                // Division is done on 32-bit numbers, where dividing by zero or overflowing traps
+                // rather than giving a result the interpreter would not
+                for operand in [lhs, rhs] {
+                    if self.node(operand)? == Kind::Unit {
+                        return Err(unsupported("arithmetic on ‘()’"));
+                    }
+                    self.code.push(Instr::I32WrapI64);
+                }
+
+                self.code.extend([Instr::I32DivS, Instr::I64ExtendI32S]);
+                Ok(Kind::Number)
+            }
+            Node::Operation { lhs, rhs, op } => {
+                let instr = match op {
+                    Op::Add => Instr::I64Add,
+                    Op::Sub => Instr::I64Sub,
+                    Op::Mul => Instr::I64Mul,
+                    _ => return Err(unsupported("comparisons")),
+                };
+
+                let lhs = self.node(lhs)?;
+                let rhs = self.node(rhs)?;
+                if lhs == Kind::Unit || rhs == Kind::Unit {
+                    return Err(unsupported("arithmetic on ‘()’"));
+                }
+
+                // Numbers are 32-bit, so the result wraps around like it does in the interpreter
+                self.code
+                    .extend([instr, Instr::I32WrapI64, Instr::I64ExtendI32S]);
+                Ok(Kind::Number)
+            }
+            Node::Call { func, depth, args } => {
+                for arg in args {
+                    if self.node(arg)? == Kind::Unit {
+                        return Err(unsupported("passing ‘()’ to functions"));
+                    }
+                }
+
+                // The callee's enclosing functions start with the one `depth` functions out from
+                // here, which may be this function itself
+                for capture in self.generator.captures[*func].clone() {
+                    let index = self.local(Local {
+                        depth: depth + capture.depth - 1,
+                        slot: capture.slot,
+                    });
+                    self.code.push(Instr::LocalGet(index));
+                }
+
+                let (index, kind) = self.generator.call(*func)?;
+                self.code.push(Instr::Call(index));
+                Ok(kind)
+            }
+            Node::Block(nodes) => {
+                let mut kind = Kind::Unit;
+                for (idx, node) in nodes.iter().enumerate() {
+                    kind = self.node(node)?;
+                    if kind == Kind::Number && idx + 1 < nodes.len() {
+                        self.code.push(Instr::Drop);
+                    }
+                }
+                Ok(kind)
+            }
+            Node::Spanned { node, .. } => self.node(node),
+            Node::Const(_) => Err(unsupported("values other than numbers")),
+            Node::Construct { .. } => Err(unsupported("enums")),
+            Node::Builtin { .. } | Node::List(_) | Node::Map(_) | Node::Index { .. } => {
+                Err(unsupported("lists and maps"))
+            }
+            Node::Range { .. } | Node::For { .. } | Node::Break | Node::Continue => {
+                Err(unsupported("loops"))
+            }
+            Node::Match { .. } => Err(unsupported("match expressions")),
+            Node::Return(_) => Err(unsupported("early returns")),
+        }
+    }
+}
+
+fn unsupported(what: &str) -> String {
+    format!("the WebAssembly backend does not support {}", what)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::{Env, Lints, TypeEnv};
+
+    fn lower(s: &str) -> Program {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        parse.lower().unwrap()
+    }
+
+    // Generates the module, checking that its binary decodes back to it
+    fn generate(s: &str) -> Module {
+        let module = Generator::new(&lower(s)).module().unwrap();
+        assert_eq!(decode::decode(&module.to_binary()), Ok(module.clone()));
+        module
+    }
+
+    // Runs the module with Node if it is available, checking that it prints the same as the
+    // interpreter
+    fn generate_and_run(s: &str) -> Module {
+        let module = generate(s);
+        let binary = module.to_binary();
+
+        let path = crate::codegen::scratch_path("wasm-test").with_extension("wasm");
+        std::fs::write(&path, &binary).unwrap();
+
+        let script = "
+const fs = require('fs');
+const module = new WebAssembly.Module(fs.readFileSync(process.argv[1]));
+const result = new WebAssembly.Instance(module).exports.main();
+if (result !== undefined) console.log(String(result));
+";
+        let output = Command::new("node")
+            .arg("-e")
+            .arg(script)
+            .arg(&path)
+            .output();
+        std::fs::remove_file(&path).unwrap();
+
+        if let Ok(output) = output {
+            let mut parse = crate::parse(s).unwrap();
+            parse.check(&mut TypeEnv::default(), &Lints::default());
+            let expected = match parse.eval(&mut Env::default()) {
+                Ok(Val::Unit) => String::new(),
+                Ok(val) => format!("{}\n", val),
+                // Trapping is the only way the module can fail
+                Err(_) => {
+                    assert!(!output.status.success());
+                    return module;
+                }
+            };
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+        }
+
+        module
+    }
+
+    #[test]
+    fn generate_arithmetic() {
+        let module = generate_and_run(
+            "{
+    let a = 7 * 6
+    a / 4
+}",
+        );
+
+        assert_eq!(
+            module.funcs[0].body,
+            [
+                Instr::I64Const(7),
+                Instr::I64Const(6),
+                Instr::I64Mul,
+                Instr::I32WrapI64,
+                Instr::I64ExtendI32S,
+                Instr::LocalSet(0),
+                Instr::LocalGet(0),
+                Instr::I32WrapI64,
+                Instr::I64Const(4),
+                Instr::I32WrapI64,
+                Instr::I32DivS,
+                Instr::I64ExtendI32S,
+            ],
+        );
+    }
+
+    #[test]
+    fn trap_on_failing_division() {
+        generate_and_run("1 / 0");
+        generate_and_run(
+            "{
+    let low = 0 - 2147483647
+    let min = low - 1
+    let m = 0 - 1
+    min / m
+}",
+        );
+    }
+
+    #[test]
+    fn pass_captured_bindings_as_params() {
+        let module = generate_and_run(
+            "{
+    let base = 100
+    fn outer x => {
+        let doubled = x * 2
+        fn inner y => {
+            let sum = base + doubled
+            sum + y
+        }
+        inner 3
+    }
+    fn twice a => {
+        let once = outer a
+        let again = outer once
+        again
+    }
+    twice 5
+}",
+        );
+
+        // `outer` only uses `base` through `inner`, and `twice` only through `outer`
+        let params: Vec<_> = module.funcs.iter().map(|func| func.num_params).collect();
+        assert_eq!(params, [0, 2, 2, 3]);
+    }
+
+    #[test]
+    fn generate_recursive_func() {
+        // Without conditionals, calling it would never return, so it is only generated
+        let module = generate(
+            "{
+    fn down n => {
+        let m = n - 1
+        down m
+    }
+    down 4
+}",
+        );
+
+        assert!(module.funcs[1].returns);
+        assert_eq!(module.funcs[1].body[7], Instr::Call(1));
+    }
+
+    #[test]
+    fn drop_discarded_values() {
+        let module = generate_and_run("{ 1 2 let a = 3 }");
+
+        assert!(!module.funcs[0].returns);
+        assert_eq!(
+            module.funcs[0].body,
+            [
+                Instr::I64Const(1),
+                Instr::Drop,
+                Instr::I64Const(2),
+                Instr::Drop,
+                Instr::I64Const(3),
+                Instr::LocalSet(0),
+            ],
+        );
+    }
+
+    #[test]
+    fn generate_text() {
+        assert_eq!(
+            lower(
+                "{
+    fn sub a b => a - b
+    sub 5 7
+}"
+            )
+            .to_wat(),
+            Ok("(module
+  (func $main (export \"main\") (result i64)
+    i64.const 5
+    i64.const 7
+    call $fn0_sub
+  )
+  (func $fn0_sub (param i64 i64) (result i64)
+    local.get 0
+    local.get 1
+    i64.sub
+    i32.wrap_i64
+    i64.extend_i32_s
+  )
+)
+"
+            .to_string()),
+        );
+    }
+
+    #[test]
+    fn reject_unsupported_program() {
+        assert_eq!(
+            lower("[1, 2]").to_wasm(),
+            Err("the WebAssembly backend does not support lists and maps".to_string()),
+        );
+        assert_eq!(
+            lower("1 < 2").to_wat(),
+            Err("the WebAssembly backend does not support comparisons".to_string()),
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// A decoder for the binary format, which validates everything the generated modules rely on so
+// that tests can check them without anything outside the crate. It only understands the subset
+// of WebAssembly the generator produces.
+
+use super::encode::*;
+use super::{Function, Instr, Module};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValType {
+    I32,
+    I64,
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| "unexpected end of module".to_string())?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8], String> {
+        if self.bytes.len() - self.pos < len {
+            return Err("unexpected end of module".to_string());
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn expect(&mut self, expected: u8, what: &str) -> Result<(), String> {
+        match self.byte()? {
+            byte if byte == expected => Ok(()),
+            byte => Err(format!("expected {}, found {:#04x}", what, byte)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut n = 0u64;
+
+        // A `u32` takes up at most five bytes
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return u32::try_from(n).map_err(|_| "integer too large".to_string());
+            }
+        }
+
+        Err("integer too long".to_string())
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        let mut n = 0i64;
+
+        // An `i64` takes up at most ten bytes
+        for shift in (0..70).step_by(7) {
+            let byte = self.byte()?;
+            n |= i64::from(byte & 0x7f).wrapping_shl(shift);
+
+            if byte & 0x80 == 0 {
+                if shift < 57 && byte & 0x40 != 0 {
+                    n |= -1 << (shift + 7);
+                }
+                return Ok(n);
+            }
+        }
+
+        Err("integer too long".to_string())
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "invalid name".to_string())
+    }
+}
+
+// Decodes and validates a module, returning it if it is one the generator could have produced
+pub(super) fn decode(bytes: &[u8]) -> Result<Module, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != MAGIC || reader.take(4)? != VERSION {
+        return Err("not a WebAssembly module".to_string());
+    }
+
+    let mut types = Vec::new();
+    let mut func_types = Vec::new();
+    let mut exports = Vec::new();
+    let mut bodies = Vec::new();
+    let mut names = Vec::new();
+    let mut last_section = 0;
+
+    while !reader.is_empty() {
+        let id = reader.byte()?;
+        let len = reader.len()?;
+        let mut section = Reader {
+            bytes: reader.take(len)?,
+            pos: 0,
+        };
+
+        if id != CUSTOM_SECTION {
+            if id <= last_section {
+                return Err(format!("section {} is out of order", id));
+            }
+            last_section = id;
+        }
+
+        match id {
+            CUSTOM_SECTION => {
+                if section.name()? == "name" {
+                    names = decode_names(&mut section)?;
+                }
+                // Anything else in a custom section is left alone
+                section.pos = section.bytes.len();
+            }
+            TYPE_SECTION => {
+                for _ in 0..section.len()? {
+                    section.expect(FUNC_TYPE, "a function type")?;
+                    let num_params = section.u32()?;
+                    for _ in 0..num_params {
+                        section.expect(I64, "an i64 parameter")?;
+                    }
+                    let returns = match section.u32()? {
+                        0 => false,
+                        1 => {
+                            section.expect(I64, "an i64 result")?;
+                            true
+                        }
+                        _ => return Err("functions return at most one value".to_string()),
+                    };
+                    types.push((num_params, returns));
+                }
+            }
+            FUNCTION_SECTION => {
+                for _ in 0..section.len()? {
+                    let index = section.len()?;
+                    let ty = *types
+                        .get(index)
+                        .ok_or_else(|| format!("type {} does not exist", index))?;
+                    func_types.push(ty);
+                }
+            }
+            EXPORT_SECTION => {
+                for _ in 0..section.len()? {
+                    let name = section.name()?;
+                    section.expect(EXPORT_FUNC, "a function export")?;
+                    exports.push((name, section.u32()?));
+                }
+            }
+            CODE_SECTION => {
+                for _ in 0..section.len()? {
+                    let len = section.len()?;
+                    bodies.push(section.take(len)?);
+                }
+            }
+            _ => return Err(format!("unexpected section {}", id)),
+        }
+
+        if !section.is_empty() {
+            return Err(format!("section {} is longer than its contents", id));
+        }
+    }
+
+    if bodies.len() != func_types.len() {
+        return Err("every function needs exactly one body".to_string());
+    }
+    if exports != [("main".to_string(), 0)] {
+        return Err("only the first function should be exported, as ‘main’".to_string());
+    }
+
+    let mut funcs = Vec::new();
+    for (index, (body, (num_params, returns))) in bodies.into_iter().zip(&func_types).enumerate() {
+        let mut reader = Reader {
+            bytes: body,
+            pos: 0,
+        };
+
+        let mut num_locals = 0;
+        for _ in 0..reader.len()? {
+            num_locals += reader.u32()?;
+            reader.expect(I64, "i64 locals")?;
+        }
+
+        let body = decode_body(&mut reader, &func_types, num_params + num_locals, *returns)
+            .map_err(|msg| format!("function {}: {}", index, msg))?;
+
+        let name = names
+            .iter()
+            .find(|(named, _)| *named == index as u32)
+            .map_or(String::new(), |(_, name)| name.clone());
+
+        funcs.push(Function {
+            name,
+            num_params: *num_params,
+            num_locals,
+            returns: *returns,
+            body,
+        });
+    }
+
+    Ok(Module { funcs })
+}
+
+fn decode_names(section: &mut Reader) -> Result<Vec<(u32, String)>, String> {
+    let mut names = Vec::new();
+
+    while !section.is_empty() {
+        let id = section.byte()?;
+        let len = section.len()?;
+        let mut subsection = Reader {
+            bytes: section.take(len)?,
+            pos: 0,
+        };
+
+        if id == FUNCTION_NAMES {
+            for _ in 0..subsection.len()? {
+                let index = subsection.u32()?;
+                names.push((index, subsection.name()?));
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+// Decodes the instructions of a function body, checking that each has the operands it needs and
+// that the function leaves exactly what it returns
+fn decode_body(
+    reader: &mut Reader,
+    func_types: &[(u32, bool)],
+    num_locals: u32,
+    returns: bool,
+) -> Result<Vec<Instr>, String> {
+    let mut body = Vec::new();
+    let mut stack = Vec::new();
+
+    let pop = |stack: &mut Vec<ValType>, expected: ValType| match stack.pop() {
+        Some(ty) if ty == expected => Ok(()),
+        Some(ty) => Err(format!("expected {:?} but found {:?}", expected, ty)),
+        None => Err("the stack is empty".to_string()),
+    };
+    let local = |reader: &mut Reader| match reader.u32()? {
+        index if index < num_locals => Ok(index),
+        index => Err(format!("local {} does not exist", index)),
+    };
+
+    loop {
+        let opcode = reader.byte()?;
+
+        let instr = match opcode {
+            END => break,
+            0x42 => Instr::I64Const(reader.i64()?),
+            0x20 => Instr::LocalGet(local(reader)?),
+            0x21 => Instr::LocalSet(local(reader)?),
+            0x10 => Instr::Call(reader.u32()?),
+            _ => [
+                Instr::I64Add,
+                Instr::I64Sub,
+                Instr::I64Mul,
+                Instr::I32DivS,
+                Instr::I32WrapI64,
+                Instr::I64ExtendI32S,
+                Instr::Drop,
+            ]
+            .into_iter()
+            .find(|instr| instr.opcode() == opcode)
+            .ok_or_else(|| format!("unknown opcode {:#04x}", opcode))?,
+        };
+
+        match instr {
+            Instr::I64Const(_) | Instr::LocalGet(_) => stack.push(ValType::I64),
+            Instr::LocalSet(_) => pop(&mut stack, ValType::I64)?,
+            Instr::I64Add | Instr::I64Sub | Instr::I64Mul => {
+                pop(&mut stack, ValType::I64)?;
+                pop(&mut stack, ValType::I64)?;
+                stack.push(ValType::I64);
+            }
+            Instr::I32DivS => {
+                pop(&mut stack, ValType::I32)?;
+                pop(&mut stack, ValType::I32)?;
+                stack.push(ValType::I32);
+            }
+            Instr::I32WrapI64 => {
+                pop(&mut stack, ValType::I64)?;
+                stack.push(ValType::I32);
+            }
+            Instr::I64ExtendI32S => {
+                pop(&mut stack, ValType::I32)?;
+                stack.push(ValType::I64);
+            }
+            Instr::Call(index) => {
+                let (num_params, returns) = *func_types
+                    .get(index as usize)
+                    .ok_or_else(|| format!("function {} does not exist", index))?;
+                for _ in 0..num_params {
+                    pop(&mut stack, ValType::I64)?;
+                }
+                if returns {
+                    stack.push(ValType::I64);
+                }
+            }
+            Instr::Drop => {
+                stack
+                    .pop()
+                    .ok_or_else(|| "the stack is empty".to_string())?;
+            }
+        }
+
+        body.push(instr);
+    }
+
+    if !reader.is_empty() {
+        return Err("the body continues after its end".to_string());
+    }
+
+    let expected: &[ValType] = if returns { &[ValType::I64] } else { &[] };
+    if stack != expected {
+        return Err(format!(
+            "expected to leave {:?} but left {:?}",
+            expected, stack
+        ));
+    }
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn main_with(body: Vec<Instr>, returns: bool) -> Module {
+        Module {
+            funcs: vec![Function {
+                name: "main".to_string(),
+                num_params: 0,
+                num_locals: 1,
+                returns,
+                body,
+            }],
+        }
+    }
+
+    #[test]
+    fn decode_large_constants() {
+        let module = main_with(
+            vec![
+                Instr::I64Const(i64::MIN),
+                Instr::I64Const(-64),
+                Instr::Drop,
+                Instr::Drop,
+                Instr::I64Const(i64::MAX),
+            ],
+            true,
+        );
+
+        assert_eq!(decode(&module.to_binary()), Ok(module));
+    }
+
+    #[test]
+    fn reject_mismatched_stack() {
+        assert_eq!(
+            decode(&main_with(vec![Instr::I64Const(1)], false).to_binary()),
+            Err("function 0: expected to leave [] but left [I64]".to_string()),
+        );
+        assert_eq!(
+            decode(&main_with(vec![Instr::I64Const(1), Instr::I64Add], true).to_binary()),
+            Err("function 0: the stack is empty".to_string()),
+        );
+        assert_eq!(
+            decode(&main_with(vec![Instr::I64Const(1), Instr::I64ExtendI32S], true).to_binary()),
+            Err("function 0: expected I32 but found I64".to_string()),
+        );
+    }
+
+    #[test]
+    fn reject_missing_locals_and_functions() {
+        assert_eq!(
+            decode(&main_with(vec![Instr::LocalGet(1)], true).to_binary()),
+            Err("function 0: local 1 does not exist".to_string()),
+        );
+        assert_eq!(
+            decode(&main_with(vec![Instr::Call(1)], false).to_binary()),
+            Err("function 0: function 1 does not exist".to_string()),
+        );
+    }
+
+    #[test]
+    fn reject_truncated_module() {
+        let binary = main_with(Vec::new(), false).to_binary();
+
+        assert_eq!(
+            decode(&binary[..binary.len() - 20]),
+            Err("unexpected end of module".to_string()),
+        );
+        assert_eq!(
+            decode(b"\0asm\x02\0\0\0"),
+            Err("not a WebAssembly module".to_string()),
+        );
+    }
+}
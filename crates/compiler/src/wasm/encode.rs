@@ -0,0 +1,303 @@
+use std::fmt::Write;
+
+use super::{Function, Instr, Module};
+
+pub(super) const MAGIC: [u8; 4] = *b"\0asm";
+pub(super) const VERSION: [u8; 4] = [1, 0, 0, 0];
+
+pub(super) const I64: u8 = 0x7e;
+pub(super) const FUNC_TYPE: u8 = 0x60;
+pub(super) const EXPORT_FUNC: u8 = 0x00;
+pub(super) const END: u8 = 0x0b;
+
+// Section ids, in the order sections have to appear in
+pub(super) const CUSTOM_SECTION: u8 = 0;
+pub(super) const TYPE_SECTION: u8 = 1;
+pub(super) const FUNCTION_SECTION: u8 = 3;
+pub(super) const EXPORT_SECTION: u8 = 7;
+pub(super) const CODE_SECTION: u8 = 10;
+
+// The subsection of the `name` custom section that names functions
+pub(super) const FUNCTION_NAMES: u8 = 1;
+
+impl Module {
+    pub(super) fn to_text(&self) -> String {
+        let mut wat = String::from("(module\n");
+
+        for (index, func) in self.funcs.iter().enumerate() {
+            let _ = write!(wat, "  (func ${}", func.name);
+            // Only the program itself is exported
+            if index == 0 {
+                wat.push_str(" (export \"main\")");
+            }
+            if func.num_params > 0 {
+                let _ = write!(wat, " (param{})", " i64".repeat(func.num_params as usize));
+            }
+            if func.returns {
+                wat.push_str(" (result i64)");
+            }
+            wat.push('\n');
+
+            if func.num_locals > 0 {
+                let _ = writeln!(
+                    wat,
+                    "    (local{})",
+                    " i64".repeat(func.num_locals as usize)
+                );
+            }
+
+            for instr in &func.body {
+                let _ = match instr {
+                    Instr::I64Const(n) => writeln!(wat, "    i64.const {}", n),
+                    Instr::LocalGet(index) => writeln!(wat, "    local.get {}", index),
+                    Instr::LocalSet(index) => writeln!(wat, "    local.set {}", index),
+                    Instr::Call(index) => {
+                        writeln!(wat, "    call ${}", self.funcs[*index as usize].name)
+                    }
+                    _ => writeln!(wat, "    {}", instr.mnemonic()),
+                };
+            }
+
+            wat.push_str("  )\n");
+        }
+
+        wat.push_str(")\n");
+        wat
+    }
+
+    pub(super) fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION);
+
+        // Functions with the same parameters and result share a type
+        let mut types = Vec::new();
+        let type_indices: Vec<_> = self
+            .funcs
+            .iter()
+            .map(|func| {
+                let ty = (func.num_params, func.returns);
+                match types.iter().position(|other| *other == ty) {
+                    Some(index) => index,
+                    None => {
+                        types.push(ty);
+                        types.len() - 1
+                    }
+                }
+            })
+            .collect();
+
+        let mut section = Vec::new();
+        unsigned(types.len() as u64, &mut section);
+        for (num_params, returns) in types {
+            section.push(FUNC_TYPE);
+            unsigned(u64::from(num_params), &mut section);
+            section.extend(std::iter::repeat_n(I64, num_params as usize));
+            unsigned(u64::from(returns), &mut section);
+            if returns {
+                section.push(I64);
+            }
+        }
+        push_section(TYPE_SECTION, &section, &mut bytes);
+
+        let mut section = Vec::new();
+        unsigned(type_indices.len() as u64, &mut section);
+        for index in type_indices {
+            unsigned(index as u64, &mut section);
+        }
+        push_section(FUNCTION_SECTION, &section, &mut bytes);
+
+        let mut section = Vec::new();
+        unsigned(1, &mut section);
+        name("main", &mut section);
+        section.push(EXPORT_FUNC);
+        unsigned(0, &mut section);
+        push_section(EXPORT_SECTION, &section, &mut bytes);
+
+        let mut section = Vec::new();
+        unsigned(self.funcs.len() as u64, &mut section);
+        for func in &self.funcs {
+            let body = func.to_binary();
+            unsigned(body.len() as u64, &mut section);
+            section.extend(body);
+        }
+        push_section(CODE_SECTION, &section, &mut bytes);
+
+        // Names are optional, but make the module easier to debug
+        let mut names = Vec::new();
+        unsigned(self.funcs.len() as u64, &mut names);
+        for (index, func) in self.funcs.iter().enumerate() {
+            unsigned(index as u64, &mut names);
+            name(&func.name, &mut names);
+        }
+        let mut section = Vec::new();
+        name("name", &mut section);
+        section.push(FUNCTION_NAMES);
+        unsigned(names.len() as u64, &mut section);
+        section.extend(names);
+        push_section(CUSTOM_SECTION, &section, &mut bytes);
+
+        bytes
+    }
+}
+
+impl Function {
+    fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        // Locals are declared in runs of the same type, and they are all the same
+        if self.num_locals > 0 {
+            unsigned(1, &mut bytes);
+            unsigned(u64::from(self.num_locals), &mut bytes);
+            bytes.push(I64);
+        } else {
+            unsigned(0, &mut bytes);
+        }
+
+        for instr in &self.body {
+            bytes.push(instr.opcode());
+            match instr {
+                Instr::I64Const(n) => signed(*n, &mut bytes),
+                Instr::LocalGet(index) | Instr::LocalSet(index) | Instr::Call(index) => {
+                    unsigned(u64::from(*index), &mut bytes)
+                }
+                _ => {}
+            }
+        }
+        bytes.push(END);
+
+        bytes
+    }
+}
+
+impl Instr {
+    pub(super) fn opcode(self) -> u8 {
+        match self {
+            Self::I64Const(_) => 0x42,
+            Self::LocalGet(_) => 0x20,
+            Self::LocalSet(_) => 0x21,
+            Self::I64Add => 0x7c,
+            Self::I64Sub => 0x7d,
+            Self::I64Mul => 0x7e,
+            Self::I32DivS => 0x6d,
+            Self::I32WrapI64 => 0xa7,
+            Self::I64ExtendI32S => 0xac,
+            Self::Call(_) => 0x10,
+            Self::Drop => 0x1a,
+        }
+    }
+
+    fn mnemonic(self) -> &'static str {
+        match self {
+            Self::I64Const(_) => "i64.const",
+            Self::LocalGet(_) => "local.get",
+            Self::LocalSet(_) => "local.set",
+            Self::I64Add => "i64.add",
+            Self::I64Sub => "i64.sub",
+            Self::I64Mul => "i64.mul",
+            Self::I32DivS => "i32.div_s",
+            Self::I32WrapI64 => "i32.wrap_i64",
+            Self::I64ExtendI32S => "i64.extend_i32_s",
+            Self::Call(_) => "call",
+            Self::Drop => "drop",
+        }
+    }
+}
+
+fn push_section(id: u8, contents: &[u8], bytes: &mut Vec<u8>) {
+    bytes.push(id);
+    unsigned(contents.len() as u64, bytes);
+    bytes.extend(contents);
+}
+
+fn name(name: &str, bytes: &mut Vec<u8>) {
+    unsigned(name.len() as u64, bytes);
+    bytes.extend(name.as_bytes());
+}
+
+// Integers are encoded in LEB128, seven bits at a time starting with the least significant, with
+// the top bit of each byte set if there are more to come
+fn unsigned(mut n: u64, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+
+        if n == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+// Signed integers stop once the rest of the bits are all the same as the sign bit of the last byte
+fn signed(mut n: i64, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+
+        let sign_bit = byte & 0x40 != 0;
+        if (n == 0 && !sign_bit) || (n == -1 && sign_bit) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leb(f: impl Fn(&mut Vec<u8>)) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        f(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn encode_unsigned_leb128() {
+        assert_eq!(leb(|bytes| unsigned(0, bytes)), [0x00]);
+        assert_eq!(leb(|bytes| unsigned(127, bytes)), [0x7f]);
+        assert_eq!(leb(|bytes| unsigned(624485, bytes)), [0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn encode_signed_leb128() {
+        assert_eq!(leb(|bytes| signed(63, bytes)), [0x3f]);
+        assert_eq!(leb(|bytes| signed(64, bytes)), [0xc0, 0x00]);
+        assert_eq!(leb(|bytes| signed(-1, bytes)), [0x7f]);
+        assert_eq!(leb(|bytes| signed(-123456, bytes)), [0xc0, 0xbb, 0x78]);
+    }
+
+    #[test]
+    fn encode_empty_program() {
+        let module = Module {
+            funcs: vec![Function {
+                name: "main".to_string(),
+                num_params: 0,
+                num_locals: 0,
+                returns: false,
+                body: Vec::new(),
+            }],
+        };
+
+        assert_eq!(
+            module.to_binary(),
+            [
+                &b"\0asm\x01\0\0\0"[..],
+                // One type, taking and returning nothing
+                &[1, 4, 1, 0x60, 0, 0],
+                // One function of that type
+                &[3, 2, 1, 0],
+                // Exported as `main`
+                &[7, 8, 1, 4, b'm', b'a', b'i', b'n', 0, 0],
+                // With no locals and an empty body
+                &[10, 4, 1, 2, 0, 0x0b],
+                &[0, 14, 4, b'n', b'a', b'm', b'e', 1, 7, 1, 0, 4],
+                b"main",
+            ]
+            .concat(),
+        );
+    }
+}
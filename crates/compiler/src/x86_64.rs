@@ -0,0 +1,412 @@
+use std::fmt::Write;
+
+use crate::codegen::{Funcs, Kind};
+use crate::expr::Op;
+use crate::lower::{Func, Local, Node, Program};
+use crate::val::Val;
+
+// Dividing by zero, or the most negative number by -1, raises an exception that kills the
+// program, so divisions go through a routine that fails like the interpreter instead. It takes
+// the operands in %eax and %ecx, and leaves the quotient in %eax.
+const DIVIDE: &str = "
+divide:
+    testl %ecx, %ecx
+    je .Ldivide_by_zero
+    cmpl $-1, %ecx
+    jne .Ldivide
+    cmpl $-2147483648, %eax
+    je .Ldivide_with_overflow
+.Ldivide:
+    cltd
+    idivl %ecx
+    ret
+.Ldivide_by_zero:
+    leaq .Ldivide_by_zero_message(%rip), %rdi
+    jmp .Lfail
+.Ldivide_with_overflow:
+    leaq .Ldivide_with_overflow_message(%rip), %rdi
+.Lfail:
+    andq $-16, %rsp
+    movq stderr@GOTPCREL(%rip), %rsi
+    movq (%rsi), %rsi
+    call fputs@PLT
+    movl $1, %edi
+    call exit@PLT
+";
+
+const DIVIDE_MESSAGES: &str = ".Ldivide_by_zero_message:
+    .string \"Evaluation error: attempt to divide by zero\\n\"
+.Ldivide_with_overflow_message:
+    .string \"Evaluation error: attempt to divide with overflow\\n\"
+";
+
+impl Program {
+    /// Generates x86-64 assembly for the System V ABI, in the syntax GNU `as` expects by default.
+    /// The program becomes a `main` function that prints the value the program evaluates to,
+    /// unless it is `()`, so linking it with the C library gives an executable that behaves like
+    /// the interpreter, including failing with the same message when it cannot divide.
+    ///
+    /// Only programs made of numbers, bindings, arithmetic, blocks and functions are supported.
+    pub fn to_x86_64(&self) -> Result<String, String> {
+        let mut generator = Generator {
+            program: self,
+            funcs: Funcs::new(self.funcs.len()),
+            divides: false,
+        };
+
+        let (main, kind) = generator.function(&self.main, "main")?;
+
+        let mut asm = String::from("    .text\n");
+        for (_, func) in generator.funcs.into_generated() {
+            asm.push_str(&func);
+        }
+        if generator.divides {
+            asm.push_str(DIVIDE);
+        }
+
+        asm.push_str("\n    .globl main\n");
+        asm.push_str(&main);
+        if kind == Kind::Number {
+            asm.push_str(
+                "    movl %eax, %esi
+    leaq .Lformat(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+",
+            );
+        }
+        asm.push_str(
+            "    xorl %eax, %eax
+    leave
+    ret
+
+    .section .rodata
+.Lformat:
+    .string \"%d\\n\"
+",
+        );
+        if generator.divides {
+            asm.push_str(DIVIDE_MESSAGES);
+        }
+        asm.push_str("    .section .note.GNU-stack,\"\",@progbits\n");
+
+        Ok(asm)
+    }
+}
+
+struct Generator<'p> {
+    program: &'p Program,
+    funcs: Funcs<String>,
+    divides: bool,
+}
+
+// Every frame starts with the frame pointer of the function the frame's function was defined in,
+// followed by its slots, each of which takes up eight bytes
+fn slot_offset(slot: usize) -> usize {
+    16 + 8 * slot
+}
+
+impl Generator<'_> {
+    // Generates the label, prologue and body of a function, leaving its value in %eax
+    fn function(&mut self, func: &Func, label: &str) -> Result<(String, Kind), String> {
+        let mut asm = String::new();
+
+        // The frame is a multiple of 16 bytes, so that the stack stays aligned for calls into C
+        let frame_size = (8 * (func.num_slots + 1)).next_multiple_of(16);
+
+        let _ = writeln!(asm, "{}:", label);
+        asm.push_str("    pushq %rbp\n    movq %rsp, %rbp\n");
+        let _ = writeln!(asm, "    subq ${}, %rsp", frame_size);
+
+        if label == "main" {
+            asm.push_str("    movq $0, -8(%rbp)\n");
+        } else {
+            // Callers push the arguments in order, followed by the static link
+            asm.push_str("    movq 16(%rbp), %rax\n    movq %rax, -8(%rbp)\n");
+
+            for param in 0..func.num_params {
+                let _ = writeln!(
+                    asm,
+                    "    movl {}(%rbp), %eax\n    movl %eax, -{}(%rbp)",
+                    16 + 8 * (func.num_params - param),
+                    slot_offset(param),
+                );
+            }
+        }
+
+        let kind = self.node(&func.body, &mut asm)?;
+
+        Ok((asm, kind))
+    }
+
+    fn call(&mut self, id: usize) -> Result<Kind, String> {
+        if let Some(kind) = self.funcs.start(id) {
+            return Ok(kind);
+        }
+
+        let func = &self.program.funcs[id];
+        let label = format!("func{}", id);
+        let (mut asm, kind) = self.function(func, &label)?;
+        asm.push_str("    leave\n    ret\n");
+
+        let name = func.name.map_or(String::new(), |name| name.to_string());
+        self.funcs
+            .finish(id, format!("\n# fn {}\n{}", name, asm), kind)
+            .map_err(unsupported)?;
+
+        Ok(kind)
+    }
+
+    fn node(&mut self, node: &Node, asm: &mut String) -> Result<Kind, String> {
+        match node {
+            Node::Const(Val::Number(n)) => {
+                let _ = writeln!(asm, "    movl ${}, %eax", n);
+                Ok(Kind::Number)
+            }
+            Node::Const(Val::Unit) => Ok(Kind::Unit),
+            Node::Local(Local { depth: 0, slot }) => {
+                let _ = writeln!(asm, "    movl -{}(%rbp), %eax", slot_offset(*slot));
+                Ok(Kind::Number)
+            }
+            Node::Local(Local { depth, slot }) => {
+                frame_of_ancestor(*depth, asm);
+                let _ = writeln!(asm, "    movl -{}(%rax), %eax", slot_offset(*slot));
+                Ok(Kind::Number)
+            }
+            Node::Define { slot, val } => {
+                if self.node(val, asm)? == Kind::Unit {
+                    return Err(unsupported("bindings to ‘()’"));
+                }
+                let _ = writeln!(asm, "    movl %eax, -{}(%rbp)", slot_offset(*slot));
+                Ok(Kind::Unit)
+            }
+            Node::Operation { lhs, rhs, op } => {
+                let instr = match op {
+                    Op::Add => "addl %ecx, %eax",
+                    Op::Sub => "subl %ecx, %eax",
+                    Op::Mul => "imull %ecx, %eax",
+                    Op::Div => {
+                        self.divides = true;
+                        "call divide"
+                    }
+                    _ => return Err(unsupported("comparisons")),
+                };
+
+                let lhs = self.node(lhs, asm)?;
+                asm.push_str("    pushq %rax\n");
+                let rhs = self.node(rhs, asm)?;
+                asm.push_str("    movl %eax, %ecx\n    popq %rax\n");
+                let _ = writeln!(asm, "    {}", instr);
+
+                if lhs == Kind::Unit || rhs == Kind::Unit {
+                    return Err(unsupported("arithmetic on ‘()’"));
+                }
+                Ok(Kind::Number)
+            }
+            Node::Call { func, depth, args } => {
+                for arg in args {
+                    if self.node(arg, asm)? == Kind::Unit {
+                        return Err(unsupported("passing ‘()’ to functions"));
+                    }
+                    asm.push_str("    pushq %rax\n");
+                }
+
+                // The callee was defined `depth` functions out from here, so that frame is the
+                // one its own references to enclosing functions start from
+                frame_of_ancestor(*depth, asm);
+                asm.push_str("    pushq %rax\n");
+
+                let _ = writeln!(asm, "    call func{}", func);
+                let _ = writeln!(asm, "    addq ${}, %rsp", 8 * (args.len() + 1));
+
+                self.call(*func)
+            }
+            Node::Block(nodes) => {
+                let mut kind = Kind::Unit;
+                for node in nodes {
+                    kind = self.node(node, asm)?;
+                }
+                Ok(kind)
+            }
+            Node::Spanned { node, .. } => self.node(node, asm),
+            Node::Const(_) => Err(unsupported("values other than numbers")),
+            Node::Construct { .. } => Err(unsupported("enums")),
+            Node::Builtin { .. } | Node::List(_) | Node::Map(_) | Node::Index { .. } => {
+                Err(unsupported("lists and maps"))
+            }
+            Node::Range { .. } | Node::For { .. } | Node::Break | Node::Continue => {
+                Err(unsupported("loops"))
+            }
+            Node::Match { .. } => Err(unsupported("match expressions")),
+            Node::Return(_) => Err(unsupported("early returns")),
+        }
+    }
+}
+
+// Leaves the frame pointer of the function `depth` functions out from the current one in %rax
+fn frame_of_ancestor(depth: usize, asm: &mut String) {
+    asm.push_str("    movq %rbp, %rax\n");
+    for _ in 0..depth {
+        asm.push_str("    movq -8(%rax), %rax\n");
+    }
+}
+
+fn unsupported(what: &str) -> String {
+    format!("the x86-64 backend does not support {}", what)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::{Env, Lints, TypeEnv};
+
+    // Builds the program with the system's C compiler and runs it, checking that it prints the
+    // same as the interpreter. Returns `None` without checking anything if there is no compiler.
+    fn build_and_run(s: &str) -> Option<String> {
+        let mut parse = crate::parse(s).unwrap();
+        parse.check(&mut TypeEnv::default(), &Lints::default());
+        let asm = parse.lower().unwrap().to_x86_64().unwrap();
+
+        let dir = crate::codegen::scratch_path("x86_64-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, exe) = (dir.join("prog.s"), dir.join("prog"));
+        std::fs::write(&source, &asm).unwrap();
+
+        let built = Command::new("cc").arg(&source).arg("-o").arg(&exe).output();
+        let output = match built {
+            Ok(built) if built.status.success() => Command::new(&exe).output(),
+            Ok(built) => {
+                std::fs::remove_dir_all(&dir).unwrap();
+                panic!("{}", String::from_utf8_lossy(&built.stderr));
+            }
+            Err(_) => {
+                std::fs::remove_dir_all(&dir).unwrap();
+                return None;
+            }
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+        let output = output.unwrap();
+
+        let printed = match parse.eval(&mut Env::default()) {
+            Ok(val) => {
+                let stdout = String::from_utf8(output.stdout).unwrap();
+                let expected = match val {
+                    Val::Unit => String::new(),
+                    val => format!("{}\n", val),
+                };
+                assert_eq!(stdout, expected);
+                assert!(output.status.success());
+                stdout
+            }
+            Err(msg) => {
+                let stderr = String::from_utf8(output.stderr).unwrap();
+                assert_eq!(stderr, format!("Evaluation error: {}\n", msg));
+                assert_eq!(output.status.code(), Some(1));
+                stderr
+            }
+        };
+
+        Some(printed)
+    }
+
+    #[test]
+    fn fail_to_divide_like_interpreter() {
+        if let Some(stderr) = build_and_run(
+            "{
+    fn sub a b => a - b
+    let zero = 1 - 1
+    sub 1 { 10 / zero }
+}",
+        ) {
+            assert_eq!(stderr, "Evaluation error: attempt to divide by zero\n");
+        }
+        if let Some(stderr) = build_and_run(
+            "{
+    let low = 0 - 2147483647
+    let min = low - 1
+    let m = 0 - 1
+    min / m
+}",
+        ) {
+            assert_eq!(
+                stderr,
+                "Evaluation error: attempt to divide with overflow\n"
+            );
+        }
+    }
+
+    #[test]
+    fn build_arithmetic() {
+        if let Some(stdout) = build_and_run(
+            "{
+    let a = 7 * 6
+    let b = a / 4
+    b - 20
+}",
+        ) {
+            assert_eq!(stdout, "-10\n");
+        }
+    }
+
+    #[test]
+    fn build_nested_funcs() {
+        if let Some(stdout) = build_and_run(
+            "{
+    let base = 100
+    fn outer x => {
+        let doubled = x * 2
+        fn inner y => {
+            let sum = base + doubled
+            sum + y
+        }
+        inner 3
+    }
+    fn sub a b => a - b
+    let result = outer 5
+    sub result 1
+}",
+        ) {
+            assert_eq!(stdout, "112\n");
+        }
+    }
+
+    #[test]
+    fn build_program_evaluating_to_unit() {
+        if let Some(stdout) = build_and_run("let a = 1") {
+            assert_eq!(stdout, "");
+        }
+    }
+
+    #[test]
+    fn wrap_like_interpreter() {
+        // Numbers are 32-bit, so the arithmetic has to be too
+        if let Some(stdout) = build_and_run("{ let big = 2147483647  big + 1 }") {
+            assert_eq!(stdout, "-2147483648\n");
+        }
+    }
+
+    #[test]
+    fn reject_unsupported_program() {
+        assert_eq!(
+            crate::parse("[1, 2]").unwrap().lower().unwrap().to_x86_64(),
+            Err("the x86-64 backend does not support lists and maps".to_string()),
+        );
+        assert_eq!(
+            crate::parse("1 < 2").unwrap().lower().unwrap().to_x86_64(),
+            Err("the x86-64 backend does not support comparisons".to_string()),
+        );
+        assert_eq!(
+            crate::parse("{ fn f n => { let m = f n } f 1 }")
+                .unwrap()
+                .lower()
+                .unwrap()
+                .to_x86_64(),
+            Err(
+                "the x86-64 backend does not support recursive functions evaluating to ‘()’"
+                    .to_string()
+            ),
+        );
+    }
+}